@@ -17,6 +17,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .containers()
         .get(&id)
         .copy_from(path::Path::new(&path))
+        .map_ok(Vec::from)
         .try_concat()
         .await?;
 
@@ -0,0 +1,21 @@
+use shiplift::Docker;
+use std::env;
+
+#[tokio::main]
+async fn main() {
+    let docker = Docker::new();
+    let usage =
+        "Usage: cargo run --example containerdownloaddir -- <container> <remote dir> <local dir>";
+    let id = env::args().nth(1).expect(usage);
+    let remote_dir = env::args().nth(2).expect(usage);
+    let local_dir = env::args().nth(3).expect(usage);
+
+    if let Err(e) = docker
+        .containers()
+        .get(&id)
+        .download_dir(remote_dir, local_dir)
+        .await
+    {
+        eprintln!("Error: {}", e)
+    }
+}
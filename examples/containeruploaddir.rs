@@ -0,0 +1,21 @@
+use shiplift::Docker;
+use std::env;
+
+#[tokio::main]
+async fn main() {
+    let docker = Docker::new();
+    let usage =
+        "Usage: cargo run --example containeruploaddir -- <local dir> <container> <remote dir>";
+    let local_dir = env::args().nth(1).expect(usage);
+    let id = env::args().nth(2).expect(usage);
+    let remote_dir = env::args().nth(3).expect(usage);
+
+    if let Err(e) = docker
+        .containers()
+        .get(&id)
+        .upload_dir(local_dir, remote_dir)
+        .await
+    {
+        eprintln!("Error: {}", e)
+    }
+}
@@ -0,0 +1,40 @@
+use futures::{AsyncWriteExt, StreamExt};
+use shiplift::{tty::TtyChunk, Docker, Exec, ExecContainerOptions};
+use std::env;
+
+#[tokio::main]
+async fn main() {
+    let docker = Docker::new();
+    let id = env::args()
+        .nth(1)
+        .expect("You need to specify a container id");
+
+    let options = ExecContainerOptions::builder()
+        .cmd(vec!["cat"])
+        .attach_stdin(true)
+        .attach_stdout(true)
+        .attach_stderr(true)
+        .build();
+
+    let exec = match Exec::create(&docker, &id, &options).await {
+        Ok(exec) => exec,
+        Err(e) => return eprintln!("Error: {}", e),
+    };
+    let mut multiplexer = match exec.attach().await {
+        Ok(multiplexer) => multiplexer,
+        Err(e) => return eprintln!("Error: {}", e),
+    };
+
+    // `cat` only exits once it sees the end of its input
+    multiplexer.write_all(b"hello from stdin\n").await.unwrap();
+    multiplexer.close_stdin().await.unwrap();
+
+    while let Some(chunk) = multiplexer.next().await {
+        match chunk {
+            Ok(TtyChunk::StdOut(bytes)) => print!("{}", String::from_utf8_lossy(&bytes)),
+            Ok(TtyChunk::StdErr(bytes)) => eprint!("{}", String::from_utf8_lossy(&bytes)),
+            Ok(TtyChunk::StdIn(_)) => unreachable!(),
+            Err(e) => eprintln!("Error: {}", e),
+        }
+    }
+}
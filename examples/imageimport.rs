@@ -0,0 +1,28 @@
+use futures::StreamExt;
+use shiplift::{Docker, ImportOptions};
+use std::{env, fs};
+
+#[tokio::main]
+async fn main() {
+    let docker = Docker::new();
+    let path = env::args()
+        .nth(1)
+        .expect("You need to specify a rootfs tarball path");
+    let repo = env::args()
+        .nth(2)
+        .expect("You need to specify a repository name");
+
+    let bytes = fs::read(path).expect("Unable to read file");
+    let rootfs = futures::stream::once(async move { Ok::<_, std::io::Error>(bytes) });
+
+    let mut stream = docker
+        .images()
+        .import_rootfs(rootfs, &ImportOptions::builder().repo(repo).build());
+
+    while let Some(import_result) = stream.next().await {
+        match import_result {
+            Ok(output) => println!("{:?}", output),
+            Err(e) => eprintln!("Error: {}", e),
+        }
+    }
+}
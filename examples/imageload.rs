@@ -0,0 +1,28 @@
+use futures::StreamExt;
+use shiplift::{Docker, StreamedBody};
+use std::{env, fs::File};
+
+#[tokio::main]
+async fn main() {
+    let docker = Docker::new();
+    let path = env::args()
+        .nth(1)
+        .expect("You need to specify an image path");
+    let f = File::open(path).expect("Unable to open file");
+    let length = f.metadata().ok().map(|metadata| metadata.len());
+
+    let body = StreamedBody::from_reader(f, length).on_progress(|progress| {
+        if let Some(total) = progress.total {
+            eprint!("\ruploaded {}/{} bytes", progress.sent, total);
+        }
+    });
+
+    let mut stream = docker.images().load_body(body);
+
+    while let Some(load_result) = stream.next().await {
+        match load_result {
+            Ok(output) => println!("{:?}", output),
+            Err(e) => eprintln!("Error: {}", e),
+        }
+    }
+}
@@ -0,0 +1,31 @@
+// cargo run --example imagepullall busybox alpine nginx
+
+use futures::StreamExt;
+use shiplift::{Docker, PullOptions};
+use std::env;
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+    let docker = Docker::new();
+    let pulls = env::args()
+        .skip(1)
+        .map(|img| PullOptions::builder().image(img).build());
+
+    let mut stream = docker.images().pull_all(pulls, 2);
+
+    while let Some((image, progress)) = stream.next().await {
+        match progress {
+            Ok(progress) if progress.done => println!("{}: done", image),
+            Ok(progress) => println!(
+                "{}: {}/{} layers, {}/{} bytes",
+                image,
+                progress.layers_done,
+                progress.layers,
+                progress.downloaded,
+                progress.download_total
+            ),
+            Err(e) => eprintln!("{}: Error: {}", image, e),
+        }
+    }
+}
@@ -0,0 +1,26 @@
+//  cargo run --example statssampler -- <container>...
+use futures::StreamExt;
+use shiplift::Docker;
+use std::{env, time::Duration};
+
+#[tokio::main]
+async fn main() {
+    let docker = Docker::new();
+    let ids: Vec<String> = env::args().skip(1).collect();
+    if ids.is_empty() {
+        eprintln!("Usage: cargo run --example statssampler -- <container>...");
+        return;
+    }
+
+    let mut snapshots = docker
+        .containers()
+        .stats_sampler(ids, Duration::from_secs(5), 8);
+    while let Some(snapshot) = snapshots.next().await {
+        for (id, stats) in snapshot.stats {
+            println!("{} memory {}", id, stats.memory_stats.usage);
+        }
+        for (id, e) in snapshot.errors {
+            eprintln!("{} Error: {}", id, e);
+        }
+    }
+}
@@ -0,0 +1,22 @@
+use shiplift::{Docker, SwarmUpdateOptions};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init();
+    let docker = Docker::new();
+    let swarm = docker.swarm();
+
+    let info = swarm.inspect().await?;
+    let mut ca_config = info.spec.ca_config.clone().unwrap_or_default();
+    ca_config.force_rotation();
+
+    swarm
+        .update(
+            &SwarmUpdateOptions::builder(info.version.index, info.spec)
+                .ca_config(ca_config)
+                .build(),
+        )
+        .await?;
+    println!("root CA rotation started");
+    Ok(())
+}
@@ -0,0 +1,24 @@
+use futures::io::AllowStdIo;
+use shiplift::Docker;
+use std::{env, fs::File};
+
+#[tokio::main]
+async fn main() {
+    let docker = Docker::new();
+    let mut args = env::args().skip(1);
+    let (name, path) = match (args.next(), args.next()) {
+        (Some(name), Some(path)) => (name, path),
+        _ => return eprintln!("Usage: volumebackup <volume> <tarball>"),
+    };
+
+    let file = File::create(&path).expect("Failed to create the tarball");
+    match docker
+        .volumes()
+        .get(&name)
+        .backup_to(AllowStdIo::new(file))
+        .await
+    {
+        Ok(()) => println!("backed up {} to {}", name, path),
+        Err(e) => eprintln!("Error: {}", e),
+    }
+}
@@ -0,0 +1,275 @@
+//! What the connected docker daemon supports.
+//!
+//! Options that only some daemons understand are checked against the daemon's API version and
+//! experimental flag before a request is made, so using them against an older daemon fails with
+//! a descriptive [Error::UnsupportedFeature](crate::Error::UnsupportedFeature) instead of an
+//! opaque 400 from the daemon.
+
+use std::{fmt, str::FromStr};
+
+use crate::{
+    docker::{Info, Version},
+    errors::{Error, Result},
+};
+
+/// A docker engine API version, such as `1.41`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ApiVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl ApiVersion {
+    pub const fn new(
+        major: u32,
+        minor: u32,
+    ) -> Self {
+        ApiVersion { major, minor }
+    }
+}
+
+impl fmt::Display for ApiVersion {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+impl FromStr for ApiVersion {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::InvalidResponse(format!("invalid api version {:?}", s));
+        let (major, minor) = s.split_once('.').ok_or_else(invalid)?;
+        Ok(ApiVersion {
+            major: major.parse().map_err(|_| invalid())?,
+            minor: minor.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// An option that not every daemon supports
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Feature {
+    /// Squashing the layers of a built image, `BuildOptionsBuilder::squash`
+    BuildSquash,
+    /// The cgroup namespace mode of a container, `ContainerOptionsBuilder::cgroupns_mode`
+    CgroupnsMode,
+    /// Swarm cluster volumes backed by CSI plugins, `VolumeCreateOptionsBuilder::cluster_volume_spec`
+    ClusterVolume,
+    /// The manifests of an image in the containerd image store, `Image::inspect_manifests`
+    ImageManifests,
+    /// Waiting for a container to exit next or be removed, `Container::wait_for`
+    WaitCondition,
+}
+
+impl Feature {
+    /// The first API version supporting the feature
+    pub fn min_api_version(&self) -> ApiVersion {
+        match self {
+            Feature::BuildSquash => ApiVersion::new(1, 25),
+            Feature::CgroupnsMode => ApiVersion::new(1, 41),
+            Feature::ClusterVolume => ApiVersion::new(1, 42),
+            Feature::ImageManifests => ApiVersion::new(1, 48),
+            Feature::WaitCondition => ApiVersion::new(1, 30),
+        }
+    }
+
+    /// Whether the feature needs the daemon to run with experimental features enabled
+    pub fn requires_experimental(&self) -> bool {
+        matches!(self, Feature::BuildSquash)
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        match self {
+            Feature::BuildSquash => write!(f, "squash"),
+            Feature::CgroupnsMode => write!(f, "cgroupns mode"),
+            Feature::ClusterVolume => write!(f, "cluster volumes"),
+            Feature::ImageManifests => write!(f, "image manifests"),
+            Feature::WaitCondition => write!(f, "wait conditions"),
+        }
+    }
+}
+
+/// What the connected daemon supports, as returned by
+/// [Docker::capabilities](crate::Docker::capabilities)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    pub api_version: ApiVersion,
+    pub experimental: bool,
+    /// Whether the daemon builds images with BuildKit, as it tells by reporting builder version
+    /// 2 in the `Builder-Version` header of its pings
+    pub buildkit_available: bool,
+    /// Whether the daemon is part of an active swarm
+    pub swarm_active: bool,
+    /// Whether the daemon's host uses cgroup v2
+    pub cgroup_v2: bool,
+    /// Whether the daemon runs as an unprivileged user
+    pub rootless: bool,
+}
+
+/// The builder version of daemons that build with BuildKit
+const BUILDKIT_BUILDER_VERSION: &str = "2";
+
+impl Capabilities {
+    /// The capabilities of a daemon supporting `versions`, reporting `info` and the builder
+    /// version of its pings
+    pub(crate) fn from_daemon(
+        versions: VersionSupport,
+        info: &Info,
+        builder_version: Option<&str>,
+    ) -> Capabilities {
+        Capabilities {
+            api_version: versions.api_version,
+            experimental: versions.experimental,
+            buildkit_available: builder_version == Some(BUILDKIT_BUILDER_VERSION),
+            swarm_active: info
+                .swarm
+                .as_ref()
+                .is_some_and(|swarm| swarm.local_node_state == "active"),
+            cgroup_v2: info.cgroup_version.as_deref() == Some("2"),
+            rootless: info
+                .security_options
+                .iter()
+                .any(|option| option.split(',').any(|part| part == "name=rootless")),
+        }
+    }
+
+    /// Returns [Error::UnsupportedFeature](crate::Error::UnsupportedFeature) if the daemon
+    /// doesn't support `feature`
+    pub fn check(
+        &self,
+        feature: Feature,
+    ) -> Result<()> {
+        VersionSupport {
+            api_version: self.api_version,
+            experimental: self.experimental,
+        }
+        .check(feature)
+    }
+}
+
+/// The part of a daemon's [Capabilities] that options are checked against, which only takes
+/// its version to know
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct VersionSupport {
+    pub(crate) api_version: ApiVersion,
+    pub(crate) experimental: bool,
+}
+
+impl VersionSupport {
+    /// What a daemon reporting `version` supports
+    pub(crate) fn from_version(version: &Version) -> Result<VersionSupport> {
+        Ok(VersionSupport {
+            api_version: version.api_version.parse()?,
+            experimental: version.experimental.unwrap_or_default(),
+        })
+    }
+
+    /// Returns [Error::UnsupportedFeature](crate::Error::UnsupportedFeature) if the daemon
+    /// doesn't support `feature`
+    pub(crate) fn check(
+        &self,
+        feature: Feature,
+    ) -> Result<()> {
+        let min_api_version = feature.min_api_version();
+        if self.api_version < min_api_version {
+            return Err(Error::UnsupportedFeature {
+                feature: feature.to_string(),
+                reason: format!(
+                    "requires API version {}, the daemon supports {}",
+                    min_api_version, self.api_version
+                ),
+            });
+        }
+        if feature.requires_experimental() && !self.experimental {
+            return Err(Error::UnsupportedFeature {
+                feature: feature.to_string(),
+                reason: "requires the daemon to run with experimental features enabled".to_owned(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn api_version_ordering() {
+        let version: ApiVersion = "1.41".parse().unwrap();
+        assert_eq!(version, ApiVersion::new(1, 41));
+        assert!(version > "1.9".parse().unwrap());
+        assert!("1.4x".parse::<ApiVersion>().is_err());
+    }
+
+    #[test]
+    fn capabilities_check() {
+        let capabilities = Capabilities {
+            api_version: ApiVersion::new(1, 40),
+            experimental: false,
+            buildkit_available: true,
+            swarm_active: false,
+            cgroup_v2: false,
+            rootless: false,
+        };
+
+        match capabilities.check(Feature::CgroupnsMode) {
+            Err(Error::UnsupportedFeature { feature, reason }) => {
+                assert_eq!(feature, "cgroupns mode");
+                assert_eq!(
+                    reason,
+                    "requires API version 1.41, the daemon supports 1.40"
+                );
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(capabilities.check(Feature::BuildSquash).is_err());
+        assert!(Capabilities {
+            experimental: true,
+            ..capabilities
+        }
+        .check(Feature::BuildSquash)
+        .is_ok());
+    }
+
+    #[test]
+    fn capabilities_from_daemon() {
+        let version: Version = serde_json::from_str(
+            r#"{"Version":"24.0.7","ApiVersion":"1.43","GitCommit":"311b9ff","GoVersion":"go1.20.10",
+                "Os":"linux","Arch":"amd64","KernelVersion":"6.5.0","BuildTime":"2023-10-26T09:08:02Z"}"#,
+        )
+        .unwrap();
+        let info: Info = serde_json::from_str(
+            r#"{"Containers":0,"Images":0,"Driver":"overlay2","DockerRootDir":"/var/lib/docker",
+                "DriverStatus":[],"ID":"abc","KernelVersion":"6.5.0","MemTotal":1,"MemoryLimit":true,
+                "NCPU":1,"NEventsListener":0,"NGoroutines":1,"Name":"host","OperatingSystem":"Ubuntu",
+                "OSType":"linux","SwapLimit":true,"CgroupVersion":"2",
+                "SecurityOptions":["name=seccomp,profile=builtin","name=rootless","name=cgroupns"],
+                "Swarm":{"NodeID":"","LocalNodeState":"inactive"}}"#,
+        )
+        .unwrap();
+
+        let versions = VersionSupport::from_version(&version).unwrap();
+        let capabilities = Capabilities::from_daemon(versions, &info, Some("2"));
+        assert_eq!(
+            capabilities,
+            Capabilities {
+                api_version: ApiVersion::new(1, 43),
+                experimental: false,
+                buildkit_available: true,
+                swarm_active: false,
+                cgroup_v2: true,
+                rootless: true,
+            }
+        );
+    }
+}
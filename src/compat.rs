@@ -0,0 +1,548 @@
+//! Reading responses of daemons that implement the docker engine API with small deviations,
+//! such as podman or balenaEngine.
+//!
+//! A client reads responses strictly by default. Pick the profile of the daemon with
+//! [Docker::with_compatibility](crate::Docker::with_compatibility) to have it read them leniently
+//! instead:
+//!
+//! ```no_run
+//! use shiplift::{Compatibility, Docker};
+//!
+//! let podman = Docker::unix("/run/podman/podman.sock").with_compatibility(Compatibility::Podman);
+//! ```
+
+use std::{slice, vec};
+
+use serde::{
+    de::{self, value::BorrowedStrDeserializer, DeserializeOwned, DeserializeSeed, Visitor},
+    forward_to_deserialize_any,
+};
+use serde_json::{Map, Value};
+
+use crate::errors::Result;
+
+/// How leniently a client reads the responses of the daemon it talks to
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Compatibility {
+    /// Responses must match the docker engine API
+    #[default]
+    Docker,
+    /// Podman's docker-compatible API, which reports `null` for empty lists and maps and
+    /// capitalizes some field names differently, such as `Id` for `ID`
+    Podman,
+    /// balenaEngine and older docker releases such as docker EE, which leave out fields added by
+    /// later API versions
+    Legacy,
+    /// Every strategy at once, for clients talking to a mixed fleet of daemons
+    Lenient,
+}
+
+impl Compatibility {
+    fn leniency(self) -> Option<Leniency> {
+        match self {
+            Compatibility::Docker => None,
+            Compatibility::Podman => Some(Leniency {
+                nulls: true,
+                missing: false,
+                fold_case: true,
+            }),
+            Compatibility::Legacy => Some(Leniency {
+                nulls: true,
+                missing: true,
+                fold_case: false,
+            }),
+            Compatibility::Lenient => Some(Leniency {
+                nulls: true,
+                missing: true,
+                fold_case: true,
+            }),
+        }
+    }
+
+    /// Deserializes a response body
+    pub(crate) fn decode<T>(
+        self,
+        body: &str,
+    ) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        let leniency = match self.leniency() {
+            Some(leniency) => leniency,
+            None => return Ok(serde_json::from_str(body)?),
+        };
+        let value: Value = serde_json::from_str(body)?;
+        Ok(T::deserialize(Lenient::new(&value, leniency))?)
+    }
+}
+
+/// Names the fields of the crate's types are also read under through `#[serde(alias)]`, each
+/// group read as a single field. Serde lists aliases among the names of a struct's fields
+/// without telling which field they stand for, so a missing field is only filled in when none
+/// of its group is given, or it would be read twice.
+const FIELD_ALIASES: &[&[&str]] = &[&["Warnings", "Warning"]];
+
+/// Whether `field` is among the `entries` already, under its own name or an alias
+fn has_entry(
+    entries: &[(&str, Option<&Value>)],
+    field: &str,
+) -> bool {
+    let group = FIELD_ALIASES
+        .iter()
+        .copied()
+        .find(|group| group.contains(&field))
+        .unwrap_or_else(|| slice::from_ref(&field));
+    entries.iter().any(|(key, _)| group.contains(key))
+}
+
+/// The deserialization strategies of a [Compatibility] profile
+#[derive(Copy, Clone, Debug)]
+struct Leniency {
+    /// `null` takes the default of the type expected, such as an empty list
+    nulls: bool,
+    /// Fields that are left out take the default of their type
+    missing: bool,
+    /// Fields are matched ignoring case when there's no exact match
+    fold_case: bool,
+}
+
+/// Deserializes a JSON value with the strategies of a [Leniency]
+#[derive(Copy, Clone)]
+struct Lenient<'de> {
+    /// `None` for a field that was left out
+    value: Option<&'de Value>,
+    leniency: Leniency,
+}
+
+static NULL: Value = Value::Null;
+
+impl<'de> Lenient<'de> {
+    fn new(
+        value: &'de Value,
+        leniency: Leniency,
+    ) -> Self {
+        Lenient {
+            value: Some(value),
+            leniency,
+        }
+    }
+
+    fn nested(
+        self,
+        value: Option<&'de Value>,
+    ) -> Self {
+        Lenient { value, ..self }
+    }
+
+    /// The value to read, or `None` when the default of the type expected should be read
+    /// instead
+    fn given(self) -> Option<&'de Value> {
+        match self.value {
+            Some(Value::Null) if self.leniency.nulls => None,
+            value => value,
+        }
+    }
+}
+
+/// Reads primitives as given, or as their default when the value is left out or `null`
+macro_rules! lenient_primitives {
+    ($($method:ident => $visit:ident($default:expr)),* $(,)?) => {
+        $(
+            fn $method<V>(
+                self,
+                visitor: V,
+            ) -> std::result::Result<V::Value, Self::Error>
+            where
+                V: Visitor<'de>,
+            {
+                match self.given() {
+                    Some(value) => value.$method(visitor),
+                    None => visitor.$visit($default),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Lenient<'de> {
+    type Error = serde_json::Error;
+
+    lenient_primitives! {
+        deserialize_bool => visit_bool(false),
+        deserialize_i8 => visit_i64(0),
+        deserialize_i16 => visit_i64(0),
+        deserialize_i32 => visit_i64(0),
+        deserialize_i64 => visit_i64(0),
+        deserialize_u8 => visit_u64(0),
+        deserialize_u16 => visit_u64(0),
+        deserialize_u32 => visit_u64(0),
+        deserialize_u64 => visit_u64(0),
+        deserialize_f32 => visit_f64(0.0),
+        deserialize_f64 => visit_f64(0.0),
+        deserialize_str => visit_borrowed_str(""),
+        deserialize_string => visit_borrowed_str(""),
+        deserialize_bytes => visit_borrowed_bytes(&[]),
+        deserialize_byte_buf => visit_borrowed_bytes(&[]),
+    }
+
+    fn deserialize_unit<V>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.given() {
+            Some(value) => value.deserialize_unit(visitor),
+            None => visitor.visit_unit(),
+        }
+    }
+
+    fn deserialize_any<V>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            None | Some(Value::Null) => visitor.visit_unit(),
+            Some(Value::Array(values)) => visitor.visit_seq(LenientSeq::new(self, values)),
+            Some(Value::Object(map)) => visitor.visit_map(LenientMap::new(self, Some(map), None)),
+            Some(value) => value.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            None | Some(Value::Null) => visitor.visit_none(),
+            Some(_) => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.given() {
+            None => visitor.visit_seq(LenientSeq::new(self, &[])),
+            Some(Value::Array(values)) => visitor.visit_seq(LenientSeq::new(self, values)),
+            Some(value) => value.deserialize_seq(visitor),
+        }
+    }
+
+    fn deserialize_tuple<V>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.given() {
+            None => visitor.visit_map(LenientMap::new(self, None, None)),
+            Some(Value::Object(map)) => visitor.visit_map(LenientMap::new(self, Some(map), None)),
+            Some(value) => value.deserialize_map(visitor),
+        }
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.given() {
+            None => visitor.visit_map(LenientMap::new(self, None, Some(fields))),
+            Some(Value::Object(map)) => {
+                visitor.visit_map(LenientMap::new(self, Some(map), Some(fields)))
+            }
+            Some(value) => value.deserialize_struct(name, fields, visitor),
+        }
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.value
+            .unwrap_or(&NULL)
+            .deserialize_enum(name, variants, visitor)
+    }
+
+    fn deserialize_ignored_any<V>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        char identifier
+    }
+}
+
+struct LenientSeq<'de> {
+    parent: Lenient<'de>,
+    values: slice::Iter<'de, Value>,
+}
+
+impl<'de> LenientSeq<'de> {
+    fn new(
+        parent: Lenient<'de>,
+        values: &'de [Value],
+    ) -> Self {
+        LenientSeq {
+            parent,
+            values: values.iter(),
+        }
+    }
+}
+
+impl<'de> de::SeqAccess<'de> for LenientSeq<'de> {
+    type Error = serde_json::Error;
+
+    fn next_element_seed<T>(
+        &mut self,
+        seed: T,
+    ) -> std::result::Result<Option<T::Value>, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        self.values
+            .next()
+            .map(|value| seed.deserialize(self.parent.nested(Some(value))))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.values.len())
+    }
+}
+
+struct LenientMap<'de> {
+    parent: Lenient<'de>,
+    entries: vec::IntoIter<(&'de str, Option<&'de Value>)>,
+    value: Option<Option<&'de Value>>,
+}
+
+impl<'de> LenientMap<'de> {
+    /// Entries of `map`, or none if it's left out, read as the struct with `fields` if it's one
+    fn new(
+        parent: Lenient<'de>,
+        map: Option<&'de Map<String, Value>>,
+        fields: Option<&'static [&'static str]>,
+    ) -> Self {
+        let fields = fields.unwrap_or_default();
+        let mut entries: Vec<(&'de str, Option<&'de Value>)> = match map {
+            Some(map) => map
+                .iter()
+                .map(|(key, value)| (field_name(parent.leniency, map, fields, key), Some(value)))
+                .collect(),
+            None => Vec::new(),
+        };
+        if parent.leniency.missing {
+            for field in fields {
+                if !has_entry(&entries, field) {
+                    entries.push((field, None));
+                }
+            }
+        }
+        LenientMap {
+            parent,
+            entries: entries.into_iter(),
+            value: None,
+        }
+    }
+}
+
+/// The field `key` is read as: itself, or a field of the struct differing from it only in case
+fn field_name<'de>(
+    leniency: Leniency,
+    map: &Map<String, Value>,
+    fields: &'static [&'static str],
+    key: &'de str,
+) -> &'de str {
+    if !leniency.fold_case || fields.contains(&key) {
+        return key;
+    }
+    fields
+        .iter()
+        .find(|field| field.eq_ignore_ascii_case(key) && !map.contains_key(**field))
+        .copied()
+        .unwrap_or(key)
+}
+
+impl<'de> de::MapAccess<'de> for LenientMap<'de> {
+    type Error = serde_json::Error;
+
+    fn next_key_seed<K>(
+        &mut self,
+        seed: K,
+    ) -> std::result::Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(BorrowedStrDeserializer::new(key))
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(
+        &mut self,
+        seed: V,
+    ) -> std::result::Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let value = self.value.take().flatten();
+        seed.deserialize(self.parent.nested(value))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "PascalCase")]
+    struct Summary {
+        #[serde(rename = "ID")]
+        id: String,
+        names: Vec<String>,
+        labels: std::collections::HashMap<String, String>,
+        size: u64,
+        state: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "PascalCase")]
+    struct Aliased {
+        #[serde(default, alias = "Warning")]
+        warnings: Option<String>,
+    }
+
+    #[test]
+    fn docker_profile_is_strict() {
+        let body = r#"{"Id":"abc","Names":null,"Labels":{},"Size":1}"#;
+        assert!(Compatibility::Docker.decode::<Summary>(body).is_err());
+    }
+
+    #[test]
+    fn podman_profile_reads_nulls_and_case() {
+        let body = r#"{"Id":"abc","Names":null,"Labels":null,"Size":1,"State":null}"#;
+        assert_eq!(
+            Compatibility::Podman.decode::<Summary>(body).unwrap(),
+            Summary {
+                id: "abc".into(),
+                names: Vec::new(),
+                labels: Default::default(),
+                size: 1,
+                state: None,
+            }
+        );
+        assert!(Compatibility::Podman
+            .decode::<Summary>(r#"{"Id":"abc","Names":[],"Labels":{}}"#)
+            .is_err());
+    }
+
+    #[test]
+    fn legacy_profile_fills_in_missing_fields() {
+        let summary: Summary = Compatibility::Legacy
+            .decode(r#"{"ID":"abc","Names":["/web"]}"#)
+            .unwrap();
+        assert_eq!(summary.names, vec!["/web".to_string()]);
+        assert_eq!((summary.size, summary.state), (0, None));
+        let summary: Summary = Compatibility::Legacy.decode(r#"{"Id":"abc"}"#).unwrap();
+        assert_eq!(summary.id, "");
+    }
+
+    #[test]
+    fn lenient_profile_reads_aliased_fields() {
+        let aliased: Aliased = Compatibility::Lenient
+            .decode(r#"{"Warning":"careful"}"#)
+            .unwrap();
+        assert_eq!(aliased.warnings.as_deref(), Some("careful"));
+        let aliased: Aliased = Compatibility::Lenient.decode("{}").unwrap();
+        assert_eq!(aliased.warnings, None);
+        let nested: Vec<Summary> = Compatibility::Lenient
+            .decode(r#"[{"id":"abc","names":null}]"#)
+            .unwrap();
+        assert_eq!(nested[0].id, "abc");
+    }
+}
@@ -0,0 +1,566 @@
+//! Where a client connects to, resolved from overrides, the environment and the docker CLI's
+//! configuration and contexts the way the docker CLI does it, and the registry credentials
+//! `docker login` stored.
+//!
+//! See [Docker::from_env_and_config](crate::Docker::from_env_and_config).
+
+use std::{
+    collections::HashMap,
+    env, fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::{
+    errors::{Error, Result},
+    image::RegistryAuth,
+};
+
+/// The host a client connects to when nothing else is configured
+pub const DEFAULT_HOST: &str = "unix:///var/run/docker.sock";
+
+/// Client certificates for a daemon reached over TLS
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TlsConfig {
+    /// Directory holding `ca.pem`, `cert.pem` and `key.pem`
+    pub cert_path: PathBuf,
+    /// Whether the daemon's certificate is verified against `ca.pem`
+    pub verify: bool,
+}
+
+impl TlsConfig {
+    /// TLS configured through `DOCKER_CERT_PATH` and `DOCKER_TLS_VERIFY` like
+    /// [ClientConfig::resolve] takes it, if the `tls` feature is enabled
+    pub(crate) fn from_env() -> Option<TlsConfig> {
+        if cfg!(feature = "tls") {
+            let env = |name: &str| env::var(name).ok();
+            tls_from(
+                &env,
+                ClientConfig::default().config_dir_with(&env).as_deref(),
+            )
+        } else {
+            None
+        }
+    }
+}
+
+/// TLS configured through `DOCKER_CERT_PATH` and `DOCKER_TLS_VERIFY`. Like the docker CLI,
+/// verifying turns TLS on by itself, with the certificates of the `config_dir`.
+fn tls_from(
+    env: &dyn Fn(&str) -> Option<String>,
+    config_dir: Option<&Path>,
+) -> Option<TlsConfig> {
+    let verify = env("DOCKER_TLS_VERIFY").is_some_and(|verify| !verify.is_empty());
+    let cert_path = match env("DOCKER_CERT_PATH").filter(|path| !path.is_empty()) {
+        Some(path) => PathBuf::from(path),
+        None if verify => config_dir?.to_owned(),
+        None => return None,
+    };
+    Some(TlsConfig { cert_path, verify })
+}
+
+/// Settings overriding the environment and the docker CLI's configuration, all unset by default
+#[derive(Clone, Debug, Default)]
+pub struct ClientConfig {
+    /// The docker host url, taking precedence over everything else
+    pub host: Option<String>,
+    /// The docker context to use, taking precedence over `DOCKER_HOST`
+    pub context: Option<String>,
+    /// Client certificates, taking precedence over those of the environment or a context
+    pub tls: Option<TlsConfig>,
+    /// The docker CLI's configuration directory, instead of `DOCKER_CONFIG` or `~/.docker`
+    pub config_dir: Option<PathBuf>,
+}
+
+/// Which setting decided the host a client connects to
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// [ClientConfig::host]
+    HostOverride,
+    /// The `DOCKER_HOST` environment variable
+    HostEnv,
+    /// The context named by [ClientConfig::context]
+    ContextOverride(String),
+    /// The context named by the `DOCKER_CONTEXT` environment variable
+    ContextEnv(String),
+    /// The `currentContext` of the docker CLI's `config.json`
+    ContextConfig(String),
+    /// Nothing was configured, so the client uses [DEFAULT_HOST]
+    Default,
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        match self {
+            ConfigSource::HostOverride => write!(f, "host override"),
+            ConfigSource::HostEnv => write!(f, "DOCKER_HOST"),
+            ConfigSource::ContextOverride(name) => write!(f, "context {} (override)", name),
+            ConfigSource::ContextEnv(name) => write!(f, "context {} (DOCKER_CONTEXT)", name),
+            ConfigSource::ContextConfig(name) => write!(f, "context {} (config.json)", name),
+            ConfigSource::Default => write!(f, "default host"),
+        }
+    }
+}
+
+/// Where a client connects to, as resolved by [ClientConfig::resolve]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResolvedConfig {
+    pub host: String,
+    pub tls: Option<TlsConfig>,
+    /// The setting the host was taken from
+    pub source: ConfigSource,
+}
+
+impl ClientConfig {
+    /// Resolves where to connect to, with the docker CLI's precedence:
+    ///
+    /// 1. [host](ClientConfig::host)
+    /// 2. [context](ClientConfig::context)
+    /// 3. `DOCKER_HOST`
+    /// 4. `DOCKER_CONTEXT`
+    /// 5. the `currentContext` of `config.json` in the configuration directory
+    /// 6. [DEFAULT_HOST]
+    ///
+    /// A context named `default` stands for `DOCKER_HOST` and the default host, as it does for
+    /// the docker CLI. TLS settings are taken from [tls](ClientConfig::tls), then from the
+    /// context, then from `DOCKER_CERT_PATH` and `DOCKER_TLS_VERIFY`, the latter using the
+    /// certificates of the configuration directory when `DOCKER_CERT_PATH` isn't set. TLS
+    /// settings for a host other than a unix socket are an [InvalidConfig](Error::InvalidConfig)
+    /// error when the `tls` feature isn't enabled.
+    pub fn resolve(&self) -> Result<ResolvedConfig> {
+        self.resolve_with(&|name| env::var(name).ok())
+    }
+
+    fn resolve_with(
+        &self,
+        env: &dyn Fn(&str) -> Option<String>,
+    ) -> Result<ResolvedConfig> {
+        let resolved = self.resolve_host_with(env)?;
+        if resolved.tls.is_some() && !cfg!(feature = "tls") && !resolved.host.starts_with("unix://")
+        {
+            return Err(Error::InvalidConfig(format!(
+                "connecting to {} over TLS needs the tls feature",
+                resolved.host
+            )));
+        }
+        Ok(resolved)
+    }
+
+    fn resolve_host_with(
+        &self,
+        env: &dyn Fn(&str) -> Option<String>,
+    ) -> Result<ResolvedConfig> {
+        let config_dir = self.config_dir_with(env);
+        let env_tls = || {
+            self.tls
+                .clone()
+                .or_else(|| tls_from(env, config_dir.as_deref()))
+        };
+
+        if let Some(ref host) = self.host {
+            return Ok(ResolvedConfig {
+                host: host.clone(),
+                tls: env_tls(),
+                source: ConfigSource::HostOverride,
+            });
+        }
+
+        let context = match self.context {
+            Some(ref name) => Some(ConfigSource::ContextOverride(name.clone())),
+            None if env("DOCKER_HOST").is_some() => None,
+            None => match env("DOCKER_CONTEXT") {
+                Some(name) => Some(ConfigSource::ContextEnv(name)),
+                None => config_dir
+                    .as_deref()
+                    .and_then(current_context)
+                    .map(ConfigSource::ContextConfig),
+            },
+        };
+
+        match context {
+            Some(ConfigSource::ContextOverride(ref name))
+            | Some(ConfigSource::ContextEnv(ref name))
+            | Some(ConfigSource::ContextConfig(ref name))
+                if name != "default" =>
+            {
+                let config_dir = config_dir.ok_or_else(|| {
+                    Error::InvalidConfig(format!(
+                        "context {} needs a docker config directory",
+                        name
+                    ))
+                })?;
+                let (host, tls) = context_endpoint(&config_dir, name)?;
+                Ok(ResolvedConfig {
+                    host,
+                    tls: self.tls.clone().or(tls),
+                    source: context.unwrap(),
+                })
+            }
+            _ => match env("DOCKER_HOST") {
+                Some(host) => Ok(ResolvedConfig {
+                    host,
+                    tls: env_tls(),
+                    source: ConfigSource::HostEnv,
+                }),
+                None => Ok(ResolvedConfig {
+                    host: DEFAULT_HOST.to_owned(),
+                    tls: env_tls(),
+                    source: ConfigSource::Default,
+                }),
+            },
+        }
+    }
+
+    /// Credentials for `registry`, such as `docker.io` or `registry.example.com:5000`, stored
+    /// by `docker login` in `config.json` of the configuration directory. Credentials kept by
+    /// a credential helper or store can't be read, and are reported as `None` like missing
+    /// ones.
+    pub fn registry_auth(
+        &self,
+        registry: &str,
+    ) -> Result<Option<RegistryAuth>> {
+        self.registry_auth_with(registry, &|name| env::var(name).ok())
+    }
+
+    fn registry_auth_with(
+        &self,
+        registry: &str,
+        env: &dyn Fn(&str) -> Option<String>,
+    ) -> Result<Option<RegistryAuth>> {
+        let config = match self
+            .config_dir_with(env)
+            .and_then(|dir| fs::read(dir.join("config.json")).ok())
+        {
+            Some(config) => config,
+            None => return Ok(None),
+        };
+        let config: CliConfig = serde_json::from_slice(&config)
+            .map_err(|e| Error::InvalidConfig(format!("malformed config.json: {}", e)))?;
+
+        let registry = registry_host(registry);
+        let entry = config
+            .auths
+            .iter()
+            .find(|(address, _)| registry_host(address) == registry);
+        let (address, entry) = match entry {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+
+        if let Some(token) = entry.identity_token.as_ref().filter(|t| !t.is_empty()) {
+            return Ok(Some(RegistryAuth::token(token.clone())));
+        }
+        let auth = match entry.auth.as_ref().filter(|auth| !auth.is_empty()) {
+            Some(auth) => auth,
+            None => return Ok(None),
+        };
+        let invalid = || {
+            Error::InvalidConfig(format!(
+                "malformed credentials for {} in config.json",
+                address
+            ))
+        };
+        let credentials = base64::decode(auth).map_err(|_| invalid())?;
+        let credentials = String::from_utf8(credentials).map_err(|_| invalid())?;
+        let (username, password) = credentials.split_once(':').ok_or_else(invalid)?;
+        Ok(Some(
+            RegistryAuth::builder()
+                .username(username)
+                .password(password)
+                .server_address(address.clone())
+                .build(),
+        ))
+    }
+
+    /// [config_dir](ClientConfig::config_dir), then `DOCKER_CONFIG`, then `~/.docker`
+    fn config_dir_with(
+        &self,
+        env: &dyn Fn(&str) -> Option<String>,
+    ) -> Option<PathBuf> {
+        self.config_dir.clone().or_else(|| {
+            env("DOCKER_CONFIG")
+                .map(PathBuf::from)
+                .or_else(|| env("HOME").map(|home| Path::new(&home).join(".docker")))
+        })
+    }
+}
+
+/// The host a registry address stands for, so that `https://index.docker.io/v1/`, the key
+/// `docker login` stores Docker Hub's credentials under, matches `docker.io`
+pub(crate) fn registry_host(address: &str) -> &str {
+    let host = address
+        .trim_start_matches("https://")
+        .trim_start_matches("http://");
+    let host = host.split('/').next().unwrap_or(host);
+    match host {
+        "index.docker.io" | "registry-1.docker.io" | "registry.hub.docker.com" => "docker.io",
+        host => host,
+    }
+}
+
+#[derive(Deserialize)]
+struct CliConfig {
+    #[serde(rename = "currentContext", default)]
+    current_context: Option<String>,
+    #[serde(default)]
+    auths: HashMap<String, AuthEntry>,
+}
+
+#[derive(Deserialize)]
+struct AuthEntry {
+    #[serde(default)]
+    auth: Option<String>,
+    #[serde(rename = "identitytoken", default)]
+    identity_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ContextMeta {
+    name: String,
+    endpoints: ContextEndpoints,
+}
+
+#[derive(Deserialize)]
+struct ContextEndpoints {
+    docker: Option<ContextEndpoint>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ContextEndpoint {
+    host: String,
+    #[serde(rename = "SkipTLSVerify", default)]
+    skip_tls_verify: bool,
+}
+
+/// The context `config.json` selects, if any
+fn current_context(config_dir: &Path) -> Option<String> {
+    let config = fs::read(config_dir.join("config.json")).ok()?;
+    serde_json::from_slice::<CliConfig>(&config)
+        .ok()?
+        .current_context
+        .filter(|name| !name.is_empty())
+}
+
+/// The docker endpoint of the context `name`. Contexts are stored in directories named after
+/// the digest of their name, which are searched for the one with a matching name.
+fn context_endpoint(
+    config_dir: &Path,
+    name: &str,
+) -> Result<(String, Option<TlsConfig>)> {
+    let contexts = config_dir.join("contexts");
+    let not_found = || Error::InvalidConfig(format!("context {} not found", name));
+    let entries = fs::read_dir(contexts.join("meta")).map_err(|_| not_found())?;
+
+    for entry in entries.flatten() {
+        let meta = match fs::read(entry.path().join("meta.json")) {
+            Ok(meta) => meta,
+            Err(_) => continue,
+        };
+        let meta: ContextMeta = match serde_json::from_slice(&meta) {
+            Ok(meta) => meta,
+            Err(_) => continue,
+        };
+        if meta.name != name {
+            continue;
+        }
+
+        let endpoint = meta.endpoints.docker.ok_or_else(|| {
+            Error::InvalidConfig(format!("context {} has no docker endpoint", name))
+        })?;
+        let cert_path = contexts.join("tls").join(entry.file_name()).join("docker");
+        let tls = if cert_path.is_dir() {
+            Some(TlsConfig {
+                cert_path,
+                verify: !endpoint.skip_tls_verify,
+            })
+        } else {
+            None
+        };
+        return Ok((endpoint.host, tls));
+    }
+
+    Err(not_found())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(
+        config: &ClientConfig,
+        vars: &[(&str, &str)],
+    ) -> Result<ResolvedConfig> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        config.resolve_with(&|name| vars.get(name).cloned())
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn resolve_precedence() {
+        let dir = env::temp_dir().join(format!("shiplift-config-{}", std::process::id()));
+        let meta = dir.join("contexts/meta/0123abcd");
+        fs::create_dir_all(&meta).unwrap();
+        fs::create_dir_all(dir.join("contexts/tls/0123abcd/docker")).unwrap();
+        fs::write(
+            meta.join("meta.json"),
+            r#"{"Name":"remote","Metadata":{},"Endpoints":{"docker":{"Host":"tcp://remote:2376","SkipTLSVerify":false}}}"#,
+        )
+        .unwrap();
+        fs::write(dir.join("config.json"), r#"{"currentContext":"remote"}"#).unwrap();
+
+        let config = ClientConfig {
+            config_dir: Some(dir.clone()),
+            ..Default::default()
+        };
+
+        let resolved = resolve(&config, &[]).unwrap();
+        assert_eq!(resolved.host, "tcp://remote:2376");
+        assert_eq!(
+            resolved.source,
+            ConfigSource::ContextConfig("remote".into())
+        );
+        assert_eq!(
+            resolved.tls,
+            Some(TlsConfig {
+                cert_path: dir.join("contexts/tls/0123abcd/docker"),
+                verify: true,
+            })
+        );
+
+        let resolved = resolve(&config, &[("DOCKER_HOST", "tcp://env:2375")]).unwrap();
+        assert_eq!(resolved.host, "tcp://env:2375");
+        assert_eq!(resolved.source, ConfigSource::HostEnv);
+        assert_eq!(resolved.tls, None);
+
+        let resolved = resolve(&config, &[("DOCKER_CONTEXT", "default")]).unwrap();
+        assert_eq!(resolved.host, DEFAULT_HOST);
+        assert_eq!(resolved.source, ConfigSource::Default);
+
+        let overridden = ClientConfig {
+            host: Some("unix:///run/user/1000/docker.sock".into()),
+            ..config.clone()
+        };
+        let resolved = resolve(&overridden, &[("DOCKER_HOST", "tcp://env:2375")]).unwrap();
+        assert_eq!(resolved.source, ConfigSource::HostOverride);
+
+        let missing = ClientConfig {
+            context: Some("missing".into()),
+            ..config
+        };
+        assert!(matches!(
+            resolve(&missing, &[]),
+            Err(Error::InvalidConfig(_))
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn registry_auth_from_config() {
+        let dir = env::temp_dir().join(format!("shiplift-auths-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("config.json"),
+            r#"{"auths":{
+                "https://index.docker.io/v1/":{"auth":"dXNlcjpwYTpzcw=="},
+                "registry.example.com:5000":{"auth":"","identitytoken":"refresh"},
+                "ghcr.io":{}
+            },"credsStore":"desktop"}"#,
+        )
+        .unwrap();
+        let config = ClientConfig {
+            config_dir: Some(dir.clone()),
+            ..Default::default()
+        };
+        let auth = |registry: &str| config.registry_auth_with(registry, &|_| None).unwrap();
+
+        match auth("docker.io") {
+            Some(RegistryAuth::Password {
+                username,
+                password,
+                server_address,
+                ..
+            }) => {
+                assert_eq!((username.as_str(), password.as_str()), ("user", "pa:ss"));
+                assert_eq!(
+                    server_address.as_deref(),
+                    Some("https://index.docker.io/v1/")
+                );
+            }
+            other => panic!("expected a password, got {:?}", other),
+        }
+        assert!(matches!(
+            auth("https://registry.example.com:5000/v2/"),
+            Some(RegistryAuth::Token { identity_token }) if identity_token == "refresh"
+        ));
+        assert!(auth("ghcr.io").is_none());
+        assert!(auth("quay.io").is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn resolve_tls_from_env() {
+        let config = ClientConfig {
+            host: Some("tcp://remote:2376".into()),
+            ..Default::default()
+        };
+        let tls = |vars: &[(&str, &str)]| resolve(&config, vars).unwrap().tls;
+
+        assert_eq!(
+            tls(&[("DOCKER_CERT_PATH", "/certs")]),
+            Some(TlsConfig {
+                cert_path: "/certs".into(),
+                verify: false,
+            })
+        );
+        assert_eq!(
+            tls(&[("DOCKER_TLS_VERIFY", "1"), ("DOCKER_CONFIG", "/config")]),
+            Some(TlsConfig {
+                cert_path: "/config".into(),
+                verify: true,
+            })
+        );
+        assert_eq!(
+            tls(&[("DOCKER_TLS_VERIFY", "1"), ("HOME", "/home/user")]),
+            Some(TlsConfig {
+                cert_path: "/home/user/.docker".into(),
+                verify: true,
+            })
+        );
+        assert_eq!(
+            tls(&[("DOCKER_TLS_VERIFY", ""), ("HOME", "/home/user")]),
+            None
+        );
+    }
+
+    #[cfg(not(feature = "tls"))]
+    #[test]
+    fn resolve_tls_needs_the_tls_feature() {
+        let vars = [("DOCKER_CERT_PATH", "/certs")];
+        let config = ClientConfig {
+            host: Some("tcp://remote:2376".into()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            resolve(&config, &vars),
+            Err(Error::InvalidConfig(_))
+        ));
+
+        let config = ClientConfig {
+            host: Some("unix:///var/run/docker.sock".into()),
+            ..Default::default()
+        };
+        assert!(resolve(&config, &vars).is_ok());
+    }
+}
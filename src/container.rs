@@ -2,26 +2,41 @@
 //!
 //! API Reference: <https://docs.docker.com/engine/api/v1.41/#tag/Container>
 
-use std::{collections::HashMap, hash::Hash, io, iter::Peekable, path::Path, time::Duration};
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    io,
+    iter::Peekable,
+    path::Path,
+    pin::Pin,
+    str::FromStr,
+    time::{Duration, Instant},
+};
 
 use futures_util::{
+    future::{self, Either},
     io::{AsyncRead, AsyncWrite},
-    stream::Stream,
-    TryStreamExt,
+    stream::{self, AbortHandle, SelectAll, Stream},
+    StreamExt, TryStreamExt,
 };
-use hyper::Body;
+use hyper::{body::Bytes, Body, StatusCode};
 use mime::Mime;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Map, Value};
 use url::form_urlencoded;
 
 use crate::{
-    docker::Docker,
+    capabilities::Feature,
+    de,
+    docker::{Docker, Event, EventFilter, EventFilterType, EventsOptions},
     errors::{Error, Result},
-    exec::{Exec, ExecContainerOptions},
+    exec::{CommandOutput, Exec, ExecContainerOptions},
+    filter::{self, Filter, LabelSelector},
+    idempotency::{self, IDEMPOTENCY_KEY_LABEL},
     image::ContainerConfig,
-    network::NetworkSettings,
-    transport::Payload,
+    network::{MappedPort, NetworkSettings},
+    tarball,
+    transport::{IntoRequestBody, Payload, StreamedBody},
     tty::{self, Multiplexer as TtyMultiPlexer},
 };
 
@@ -67,6 +82,49 @@ impl<'docker> Container<'docker> {
             .await
     }
 
+    /// Inspects the container along with its disk usage, reported in
+    /// [size_rw](ContainerDetails::size_rw) and [size_root_fs](ContainerDetails::size_root_fs).
+    /// Cheaper than [Docker::data_usage] for a single container, as only its own layer is
+    /// measured.
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ContainerInspect)
+    pub async fn inspect_with_size(&self) -> Result<ContainerDetails> {
+        self.docker
+            .get_json::<ContainerDetails>(&format!("/containers/{}/json?size=true", self.id)[..])
+            .await
+    }
+
+    /// Returns where on the docker host `container_port` over `protocol` (`tcp`, `udp` or
+    /// `sctp`) is published, or `None` if it isn't. Useful for containers started with random
+    /// host ports, as with `publish_all_ports` or a publish without a host port.
+    pub async fn mapped_port(
+        &self,
+        container_port: u16,
+        protocol: &str,
+    ) -> Result<Option<MappedPort>> {
+        Ok(self
+            .inspect()
+            .await?
+            .network_settings
+            .mapped_port(container_port, protocol))
+    }
+
+    /// Inspects the container and returns a builder creating it again, with the same name,
+    /// image reference, config and host config. Change what should differ on the builder, and
+    /// remove or rename this container before creating the new one, which would otherwise
+    /// conflict with its name.
+    ///
+    /// The config reported for a container includes defaults from its image, such as its
+    /// environment, which the new container then keeps even if it runs a newer image. Only the
+    /// network given by the host config's network mode is carried over.
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ContainerInspect)
+    pub async fn clone_spec(&self) -> Result<ContainerOptionsBuilder> {
+        Ok(ContainerOptionsBuilder::from_details(
+            &self.inspect().await?,
+        ))
+    }
+
     /// Returns a `top` view of information about the container process
     ///
     /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ContainerTop)
@@ -75,7 +133,7 @@ impl<'docker> Container<'docker> {
         psargs: Option<&str>,
     ) -> Result<Top> {
         let mut path = vec![format!("/containers/{}/top", self.id)];
-        if let Some(ref args) = psargs {
+        if let Some(args) = psargs {
             let encoded = form_urlencoded::Serializer::new(String::new())
                 .append_pair("ps_args", args)
                 .finish();
@@ -90,6 +148,16 @@ impl<'docker> Container<'docker> {
     pub fn logs(
         &self,
         opts: &LogsOptions,
+    ) -> impl Stream<Item = Result<tty::TtyChunk>> + Unpin + 'docker {
+        self.logs_output(opts, false)
+    }
+
+    /// The container's logs like [logs](Container::logs), read as the raw output of a tty when
+    /// the container runs with one
+    fn logs_output(
+        &self,
+        opts: &LogsOptions,
+        tty: bool,
     ) -> impl Stream<Item = Result<tty::TtyChunk>> + Unpin + 'docker {
         let mut path = vec![format!("/containers/{}/logs", self.id)];
         if let Some(query) = opts.serialize() {
@@ -98,7 +166,43 @@ impl<'docker> Container<'docker> {
 
         let stream = Box::pin(self.docker.stream_get(path.join("?")));
 
-        Box::pin(tty::decode(stream))
+        Box::pin(tty::decode_output(stream, tty))
+    }
+
+    /// Follows the container's logs like [logs](Container::logs) with `follow` set, but carries
+    /// on when the daemon ends the stream while the container is still running, as the
+    /// json-file log driver can when it rotates its files. Logs are requested again from the
+    /// timestamp of the last line received, skipping lines that were already yielded.
+    ///
+    /// Timestamps are requested to track progress and stripped from the lines again unless
+    /// `opts` asks for them. `tail` only applies to the first request. Lines are tracked by
+    /// timestamp, so this is best suited to containers without a tty, whose log lines arrive
+    /// one per chunk.
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ContainerLogs)
+    pub fn tail_logs(
+        &self,
+        opts: &LogsOptions,
+    ) -> impl Stream<Item = Result<tty::TtyChunk>> + Unpin + 'docker {
+        let mut params = opts.params.clone();
+        let keep_timestamps = params.get("timestamps").map(String::as_str) == Some("true");
+        params.insert("follow", true.to_string());
+        params.insert("timestamps", true.to_string());
+
+        let state = LogTail {
+            container: Container::new(self.docker, self.id.clone()),
+            params,
+            keep_timestamps,
+            logs: None,
+            last: None,
+            yielded_at_last: 0,
+            to_skip: 0,
+            done: false,
+        };
+        Box::pin(stream::unfold(state, |mut state| async move {
+            let item = state.next().await?;
+            Some((item, state))
+        }))
     }
 
     /// Attaches a multiplexed TCP stream to the container that can be used to read Stdout, Stderr and write Stdin.
@@ -156,7 +260,7 @@ impl<'docker> Container<'docker> {
         let reader = Box::pin(
             self.docker
                 .stream_get(format!("/containers/{}/stats", self.id))
-                .map_err(|e| io::Error::new(io::ErrorKind::Other, e)),
+                .map_err(io::Error::other),
         )
         .into_async_read();
 
@@ -171,21 +275,29 @@ impl<'docker> Container<'docker> {
 
     /// Start the container instance
     ///
+    /// Fails with [Error::PortAlreadyAllocated](crate::Error::PortAlreadyAllocated) when a host
+    /// port the container publishes is taken, and with
+    /// [Error::BindSourceMissing](crate::Error::BindSourceMissing) when the host path of a bind
+    /// mount does not exist. A container that is already running is not an error, but
+    /// [StateChange::AlreadyInState].
+    ///
     /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ContainerStart)
-    pub async fn start(&self) -> Result<()> {
-        self.docker
+    pub async fn start(&self) -> Result<StateChange> {
+        let started = self
+            .docker
             .post(&format!("/containers/{}/start", self.id)[..], None)
-            .await?;
-        Ok(())
+            .await;
+        StateChange::of(started).map_err(Error::start_failure)
     }
 
-    /// Stop the container instance
+    /// Stop the container instance. A container that is already stopped is not an error, but
+    /// [StateChange::AlreadyInState].
     ///
     /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ContainerStop)
     pub async fn stop(
         &self,
         wait: Option<Duration>,
-    ) -> Result<()> {
+    ) -> Result<StateChange> {
         let mut path = vec![format!("/containers/{}/stop", self.id)];
         if let Some(w) = wait {
             let encoded = form_urlencoded::Serializer::new(String::new())
@@ -194,8 +306,64 @@ impl<'docker> Container<'docker> {
 
             path.push(encoded)
         }
-        self.docker.post(&path.join("?"), None).await?;
-        Ok(())
+        StateChange::of(self.docker.post(&path.join("?"), None).await)
+    }
+
+    /// Stops the container, giving it `grace` to exit before the daemon kills it, and kills it
+    /// directly if the stop request fails or the daemon does not answer it within `grace` plus
+    /// a few seconds of slack. A container that is already stopped is not an error.
+    ///
+    /// Returns whether the container stopped or had to be killed by this method.
+    pub async fn stop_or_kill(
+        &self,
+        grace: Duration,
+    ) -> Result<StopOutcome> {
+        const SLACK: Duration = Duration::from_secs(10);
+
+        match tokio::time::timeout(grace + SLACK, self.stop(Some(grace))).await {
+            Ok(Ok(_)) => return Ok(StopOutcome::Stopped),
+            Ok(Err(Error::Fault { code, message })) if code == StatusCode::NOT_FOUND => {
+                return Err(Error::Fault { code, message })
+            }
+            // the stop request failed or hung, escalate
+            Ok(Err(_)) | Err(_) => (),
+        }
+
+        match self.kill(None).await {
+            Ok(()) => Ok(StopOutcome::Killed),
+            // the container exited in the meantime
+            Err(Error::Fault { code, .. }) if code == StatusCode::CONFLICT => {
+                Ok(StopOutcome::Stopped)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Brings the container down whatever state it's in, returning what that took. A paused
+    /// container is unpaused first, as it can't handle the stop signal while frozen, and one
+    /// waiting to be restarted by its restart policy is stopped so it stays down. Running
+    /// containers are stopped like [stop_or_kill](Container::stop_or_kill) does, with `grace`
+    /// to exit before being killed. Containers that were never started or already exited are
+    /// left as they are.
+    pub async fn ensure_stopped(
+        &self,
+        grace: Duration,
+    ) -> Result<StopAction> {
+        let state = self.inspect().await?.state;
+        if state.is_stopped() {
+            return Ok(StopAction::AlreadyStopped);
+        }
+        if !state.paused {
+            return Ok(StopAction::Stopped(self.stop_or_kill(grace).await?));
+        }
+
+        match self.unpause().await {
+            Ok(()) => (),
+            // the container was unpaused in the meantime
+            Err(Error::Fault { code, .. }) if code == StatusCode::CONFLICT => (),
+            Err(e) => return Err(e),
+        }
+        Ok(StopAction::Unpaused(self.stop_or_kill(grace).await?))
     }
 
     /// Restart the container instance
@@ -226,7 +394,7 @@ impl<'docker> Container<'docker> {
         let mut path = vec![format!("/containers/{}/kill", self.id)];
         if let Some(sig) = signal {
             let encoded = form_urlencoded::Serializer::new(String::new())
-                .append_pair("signal", &sig.to_owned())
+                .append_pair("signal", sig)
                 .finish();
             path.push(encoded)
         }
@@ -234,6 +402,22 @@ impl<'docker> Container<'docker> {
         Ok(())
     }
 
+    /// Changes the resources and restart policy of the container, which may be running
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ContainerUpdate)
+    pub async fn update(
+        &self,
+        opts: &UpdateOptions,
+    ) -> Result<ContainerUpdateInfo> {
+        let body: Body = opts.serialize()?.into();
+        self.docker
+            .post_json(
+                &format!("/containers/{}/update", self.id)[..],
+                Some((body, mime::APPLICATION_JSON)),
+            )
+            .await
+    }
+
     /// Rename the container instance
     ///
     /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ContainerRename)
@@ -263,6 +447,23 @@ impl<'docker> Container<'docker> {
         Ok(())
     }
 
+    /// Creates an image from the container's changes, such as to snapshot a running container.
+    /// The container is paused while it's committed unless [pause](CommitOptionsBuilder::pause)
+    /// is turned off.
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImageCommit)
+    pub async fn commit(
+        &self,
+        opts: &CommitOptions,
+    ) -> Result<CommitInfo> {
+        self.docker
+            .post_json(
+                &format!("/commit?{}", opts.serialize(&self.id)),
+                Payload::None,
+            )
+            .await
+    }
+
     /// Unpause the container instance
     ///
     /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ContainerUnpause)
@@ -277,11 +478,103 @@ impl<'docker> Container<'docker> {
     ///
     /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ContainerWait)
     pub async fn wait(&self) -> Result<Exit> {
+        self.wait_for(WaitCondition::NotRunning).await
+    }
+
+    /// Wait until the container meets `condition`, such as to wait for the next exit of a
+    /// container that is about to be started, or for one to be removed
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ContainerWait)
+    pub async fn wait_for(
+        &self,
+        condition: WaitCondition,
+    ) -> Result<Exit> {
+        self.docker.require(&condition.features()).await?;
         self.docker
-            .post_json(format!("/containers/{}/wait", self.id), Payload::None)
+            .post_json(condition.wait_path(&self.id), Payload::None)
             .await
     }
 
+    /// Waits for the container to exit and collects its output, like
+    /// `std::process::Command::output`.
+    ///
+    /// A container that hasn't been started yet is attached to, with stdin closed, and then
+    /// started, so its output is collected even if it is removed when it exits. The output of a
+    /// container that was already started is read from its logs. A non-zero exit code is not an
+    /// error by itself, use [CommandOutput::into_result](crate::exec::CommandOutput::into_result)
+    /// to treat it as one.
+    ///
+    /// The output of a container running with a tty isn't multiplexed, and is all collected as
+    /// stdout.
+    ///
+    /// Unless the container was removed on exit, it is inspected once it exited to fill in
+    /// [exit_info](crate::exec::CommandOutput::exit_info), telling an OOM kill from a crash or
+    /// a signal.
+    ///
+    /// Starting the container takes a second request slot while the wait holds one, so with a
+    /// [request limit](crate::Docker::with_request_limit) it needs a limit of at least 2.
+    pub async fn wait_with_output(&self) -> Result<CommandOutput> {
+        let details = self.inspect().await?;
+        let tty = details.config.tty;
+        if details.state.status != "created" {
+            let exit = self.wait().await?;
+            let logs = LogsOptions::builder().stdout(true).stderr(true).build();
+            let (stdout, stderr) = CommandOutput::collect(self.logs_output(&logs, tty)).await?;
+            return Ok(CommandOutput {
+                exit_code: exit.status_code,
+                stdout,
+                stderr,
+                exit_info: self.exit_info().await,
+            });
+        }
+
+        // attach and wait before starting so neither misses a container that exits right away
+        let connection = self.attach_raw().await?;
+        let mut attached = if tty {
+            TtyMultiPlexer::new_raw(connection)
+        } else {
+            TtyMultiPlexer::new(connection)
+        };
+        attached.close_stdin().await.map_err(Error::IO)?;
+        let condition = if details.host_config.auto_remove {
+            WaitCondition::Removed
+        } else {
+            WaitCondition::NextExit
+        };
+        self.docker.require(&condition.features()).await?;
+        let exit = self
+            .docker
+            .stream_post_responded(condition.wait_path(&self.id))
+            .await?;
+
+        self.start().await?;
+        let (stdout, stderr) = CommandOutput::collect(attached).await?;
+        let exit = exit
+            .try_fold(Vec::new(), |mut body, chunk| async move {
+                body.extend_from_slice(&chunk);
+                Ok(body)
+            })
+            .await?;
+        let exit: Exit = serde_json::from_slice(&exit)?;
+        let exit_info = if details.host_config.auto_remove {
+            None
+        } else {
+            self.exit_info().await
+        };
+
+        Ok(CommandOutput {
+            exit_code: exit.status_code,
+            stdout,
+            stderr,
+            exit_info,
+        })
+    }
+
+    /// How the exited container ended, looked up by inspecting it
+    async fn exit_info(&self) -> Option<ExitInfo> {
+        self.inspect().await.ok()?.state.exit_info()
+    }
+
     /// Delete the container instance
     ///
     /// Use remove instead to use the force/v options.
@@ -319,6 +612,23 @@ impl<'docker> Container<'docker> {
         Exec::create_and_start(self.docker, &self.id, opts)
     }
 
+    /// Execute a command in this container and wait for it to finish, collecting its output and
+    /// exit code
+    ///
+    /// A non-zero exit code is not an error by itself, use
+    /// [CommandOutput::into_result](crate::exec::CommandOutput::into_result) to treat it as one.
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#tag/Exec)
+    pub async fn exec_output(
+        &self,
+        opts: &ExecContainerOptions,
+    ) -> Result<CommandOutput> {
+        Exec::create(self.docker, &self.id, opts)
+            .await?
+            .output()
+            .await
+    }
+
     /// Copy a file/folder from the container.  The resulting stream is a tarball of the extracted
     /// files.
     ///
@@ -328,17 +638,42 @@ impl<'docker> Container<'docker> {
     /// ends in `/.`  then this indicates that only the contents of the path directory should be
     /// copied.  A symlink is always resolved to its target.
     ///
+    /// The tarball is yielded in the chunks it is received in, so it can be written out or
+    /// extracted without holding all of it in memory.
+    ///
     /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ContainerArchive)
     pub fn copy_from(
         &self,
         path: &Path,
-    ) -> impl Stream<Item = Result<Vec<u8>>> + 'docker {
+    ) -> impl Stream<Item = Result<Bytes>> + Unpin + 'docker {
         let path_arg = form_urlencoded::Serializer::new(String::new())
             .append_pair("path", &path.to_string_lossy())
             .finish();
 
         let endpoint = format!("/containers/{}/archive?{}", self.id, path_arg);
-        self.docker.stream_get(endpoint).map_ok(|c| c.to_vec())
+        Box::pin(self.docker.stream_get(endpoint))
+    }
+
+    /// Get information about a file/folder in the container without copying it.
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ContainerArchiveInfo)
+    pub async fn stat_path(
+        &self,
+        path: &Path,
+    ) -> Result<PathStat> {
+        let path_arg = form_urlencoded::Serializer::new(String::new())
+            .append_pair("path", &path.to_string_lossy())
+            .finish();
+
+        let headers = self
+            .docker
+            .head(&format!("/containers/{}/archive?{}", self.id, path_arg))
+            .await?;
+        let stat = headers.get(PATH_STAT_HEADER).ok_or_else(|| {
+            Error::InvalidResponse(format!("missing {} header", PATH_STAT_HEADER))
+        })?;
+
+        PathStat::from_header(stat.as_bytes())
     }
 
     /// Copy a byte slice as file into (see `bytes`) the container.
@@ -368,20 +703,24 @@ impl<'docker> Container<'docker> {
         )?;
         let data = ar.into_inner()?;
 
-        self.copy_to(Path::new("/"), data.into()).await?;
+        self.copy_to(Path::new("/"), Body::from(data)).await?;
         Ok(())
     }
 
     /// Copy a tarball (see `body`) to the container.
     ///
     /// The tarball will be copied to the container and extracted at the given location (see `path`).
+    /// A [StreamedBody](crate::transport::StreamedBody) is uploaded as it is read.
     ///
     /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/PutContainerArchive)
-    pub async fn copy_to(
+    pub async fn copy_to<B>(
         &self,
         path: &Path,
-        body: Body,
-    ) -> Result<()> {
+        body: B,
+    ) -> Result<()>
+    where
+        B: IntoRequestBody,
+    {
         let path_arg = form_urlencoded::Serializer::new(String::new())
             .append_pair("path", &path.to_string_lossy())
             .finish();
@@ -396,6 +735,57 @@ impl<'docker> Container<'docker> {
             .await?;
         Ok(())
     }
+
+    /// Copy the contents of a local directory (see `local_path`) into a directory of the
+    /// container (see `remote_path`), which must already exist.
+    ///
+    /// The archive is built while it is uploaded, so large trees are never held in memory.
+    /// Permissions are kept and symlinks are copied as links.
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/PutContainerArchive)
+    pub async fn upload_dir<L, R>(
+        &self,
+        local_path: L,
+        remote_path: R,
+    ) -> Result<()>
+    where
+        L: AsRef<Path>,
+        R: AsRef<Path>,
+    {
+        let archive = tarball::dir_stream(local_path.as_ref().to_path_buf());
+        self.copy_to(remote_path.as_ref(), StreamedBody::chunked(archive))
+            .await
+    }
+
+    /// Copy the contents of a directory of the container (see `remote_path`) into a local
+    /// directory (see `local_path`), creating it if needed.
+    ///
+    /// The archive is extracted while it is downloaded, so large trees are never held in memory.
+    /// Permissions are kept and symlinks are restored as links.
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ContainerArchive)
+    pub async fn download_dir<R, L>(
+        &self,
+        remote_path: R,
+        local_path: L,
+    ) -> Result<()>
+    where
+        R: AsRef<Path>,
+        L: AsRef<Path>,
+    {
+        // a trailing `/.` asks for the directory's contents rather than the directory itself
+        let remote_path = remote_path.as_ref().join(".");
+        let path_arg = form_urlencoded::Serializer::new(String::new())
+            .append_pair("path", &remote_path.to_string_lossy())
+            .finish();
+
+        let endpoint = format!("/containers/{}/archive?{}", self.id, path_arg);
+        tarball::unpack_stream(
+            self.docker.stream_get(endpoint),
+            local_path.as_ref().to_path_buf(),
+        )
+        .await
+    }
 }
 
 /// Interface for docker containers
@@ -427,6 +817,160 @@ impl<'docker> Containers<'docker> {
             .await
     }
 
+    /// Lists the container instances on the docker host like [list](Containers::list), yielding
+    /// each container as soon as it is parsed from the response instead of collecting them all.
+    /// Keeps memory flat on hosts with very many containers.
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ContainerList)
+    pub fn list_stream(
+        &self,
+        opts: &ContainerListOptions,
+    ) -> impl Stream<Item = Result<ContainerInfo>> + Unpin + 'docker {
+        let mut path = vec!["/containers/json".to_owned()];
+        if let Some(query) = opts.serialize() {
+            path.push(query)
+        }
+        Box::pin(de::array_elements(Box::pin(
+            self.docker.stream_get(path.join("?")),
+        )))
+    }
+
+    /// Removes stopped containers, narrowed down by the filters of `opts`
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ContainerPrune)
+    pub async fn prune(
+        &self,
+        opts: &ContainerPruneOptions,
+    ) -> Result<ContainerPruneInfo> {
+        let mut path = vec!["/containers/prune".to_owned()];
+        if let Some(query) = opts.serialize() {
+            path.push(query)
+        }
+        self.docker.post_json(path.join("?"), Payload::None).await
+    }
+
+    /// Lists all containers, running or not, labeled `key` with `value`
+    pub async fn with_label(
+        &self,
+        key: &str,
+        value: &str,
+    ) -> Result<Vec<ContainerInfo>> {
+        let opts = ContainerListOptions::builder()
+            .all()
+            .filter(LabelSelector::new().equals(key, value).filters())
+            .build();
+        self.list(&opts).await
+    }
+
+    /// Returns a single stream of stats for every running container matching `opts`, as pairs of
+    /// container ID and stats.
+    ///
+    /// Containers that start later are picked up and those that die are dropped, as reported by
+    /// the events stream. Each container yields at most one sample per `interval`.
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ContainerStats)
+    pub fn stats_all(
+        &self,
+        opts: &ContainerListOptions,
+        interval: Duration,
+    ) -> impl Stream<Item = Result<(String, Stats)>> + Unpin + 'docker {
+        let docker = self.docker;
+        let mut list_path = vec!["/containers/json".to_owned()];
+        if let Some(query) = opts.serialize() {
+            list_path.push(query)
+        }
+
+        let events = EventsOptions::builder()
+            .filter(vec![
+                EventFilter::Type(EventFilterType::Container),
+                EventFilter::Event("start".to_owned()),
+                EventFilter::Event("die".to_owned()),
+            ])
+            .build();
+
+        let state = StatsAll {
+            docker,
+            list_path: list_path.join("?"),
+            events: Box::pin(docker.events(&events)),
+            streams: SelectAll::new(),
+            tracked: HashMap::new(),
+            throttle: StatsThrottle::new(interval),
+            listed: false,
+        };
+
+        Box::pin(stream::unfold(state, |mut state| async move {
+            let item = state.next().await?;
+            Some((item, state))
+        }))
+    }
+
+    /// Samples the stats of the containers `ids` every `interval` with one-shot stats requests,
+    /// making at most `max_concurrent` of them at a time, and yields a snapshot of all of them
+    /// per interval.
+    ///
+    /// Unlike [stats_all](Containers::stats_all) no connection is held open between samples, so
+    /// this scales to many containers. Containers that no longer exist are reported once and
+    /// not sampled again, and the stream ends when none are left.
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ContainerStats)
+    pub fn stats_sampler<I, S>(
+        &self,
+        ids: I,
+        interval: Duration,
+        max_concurrent: usize,
+    ) -> StatsSampler<'docker>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let docker = self.docker;
+        let ids: Vec<String> = ids.into_iter().map(Into::into).collect();
+        let max_concurrent = max_concurrent.max(1);
+
+        let snapshots = stream::unfold(
+            (ids, None),
+            move |(mut ids, next_at): (Vec<String>, Option<tokio::time::Instant>)| async move {
+                if ids.is_empty() {
+                    return None;
+                }
+                if let Some(next_at) = next_at {
+                    tokio::time::sleep_until(next_at).await;
+                }
+                let next_at = tokio::time::Instant::now() + interval;
+
+                let samples: Vec<(String, Result<Stats>)> =
+                    stream::iter(ids.iter().cloned().map(|id| async move {
+                        let stats = docker
+                            .get_json::<Stats>(&format!("/containers/{}/stats?stream=false", id))
+                            .await;
+                        (id, stats)
+                    }))
+                    .buffer_unordered(max_concurrent)
+                    .collect()
+                    .await;
+
+                let mut snapshot = StatsSnapshot::default();
+                for (id, stats) in samples {
+                    match stats {
+                        Ok(stats) => snapshot.stats.push((id, stats)),
+                        Err(e) => {
+                            if matches!(e, Error::Fault { code, .. } if code == StatusCode::NOT_FOUND)
+                            {
+                                ids.retain(|tracked| *tracked != id);
+                            }
+                            snapshot.errors.push((id, e));
+                        }
+                    }
+                }
+                Some((snapshot, (ids, Some(next_at))))
+            },
+        );
+
+        StatsSampler {
+            snapshots: Box::pin(snapshots),
+        }
+    }
+
     /// Returns a reference to a set of operations available to a specific container instance
     pub fn get<S>(
         &self,
@@ -438,15 +982,75 @@ impl<'docker> Containers<'docker> {
         Container::new(self.docker, name)
     }
 
+    /// Creates and starts a container, waits for it to exit and collects its output and exit
+    /// code, see [Container::wait_with_output]
+    ///
+    /// The container is left in place once it has exited, unless it was created with
+    /// `auto_remove`. It is removed again if starting it or waiting for it fails. A non-zero
+    /// exit code is not an error by itself, use
+    /// [CommandOutput::into_result](crate::exec::CommandOutput::into_result) to treat it as one.
+    pub async fn run(
+        &self,
+        opts: &ContainerOptions,
+    ) -> Result<CommandOutput> {
+        let info = self.create(opts).await?;
+        let container = self.get(info.id);
+        match container.wait_with_output().await {
+            Err(e) => {
+                // the container may be gone already, as when it was removed on exit
+                let _ = container
+                    .remove(RmContainerOptions::builder().force(true).build())
+                    .await;
+                Err(e)
+            }
+            output => output,
+        }
+    }
+
     /// Returns a builder interface for creating a new container instance
+    ///
+    /// When `opts` has an
+    /// [idempotency key](ContainerOptionsBuilder::idempotency_key), a container created with the
+    /// same key is returned instead of creating another.
     pub async fn create(
         &self,
         opts: &ContainerOptions,
     ) -> Result<ContainerCreateInfo> {
+        idempotency::create(
+            &opts.body,
+            || self.create_new(opts),
+            |key| self.created_with_key(key),
+        )
+        .await
+    }
+
+    /// The container created with the idempotency `key`, if any
+    async fn created_with_key(
+        &self,
+        key: &str,
+    ) -> Result<Option<ContainerCreateInfo>> {
+        let containers = self.with_label(IDEMPOTENCY_KEY_LABEL, key).await?;
+        Ok(containers
+            .into_iter()
+            .next()
+            .map(|container| ContainerCreateInfo {
+                id: container.id,
+                warnings: Vec::new(),
+            }))
+    }
+
+    async fn create_new(
+        &self,
+        opts: &ContainerOptions,
+    ) -> Result<ContainerCreateInfo> {
+        self.docker.require(&opts.features()).await?;
         let body: Body = opts.serialize()?.into();
         let mut path = vec!["/containers/create".to_owned()];
 
         if let Some(ref name) = opts.name {
+            if !valid_container_name(name) {
+                return Err(Error::InvalidContainerName(name.clone()));
+            }
             path.push(
                 form_urlencoded::Serializer::new(String::new())
                     .append_pair("name", name)
@@ -457,90 +1061,415 @@ impl<'docker> Containers<'docker> {
         self.docker
             .post_json(&path.join("?"), Some((body, mime::APPLICATION_JSON)))
             .await
+            .map_err(|e| match e {
+                Error::Fault { code, message } if code == StatusCode::CONFLICT => {
+                    match conflicting_container_id(&message) {
+                        Some(id) => Error::ContainerNameConflict {
+                            name: opts.name.clone().unwrap_or_default(),
+                            id,
+                        },
+                        None => Error::Fault { code, message },
+                    }
+                }
+                e => e,
+            })
     }
 }
 
-/// Options for filtering container list results
-#[derive(Default, Debug)]
-pub struct ContainerListOptions {
+type LogChunks<'docker> = Pin<Box<dyn Stream<Item = Result<tty::TtyChunk>> + Send + 'docker>>;
+
+/// State behind [Container::tail_logs](Container::tail_logs)
+struct LogTail<'docker> {
+    container: Container<'docker>,
     params: HashMap<&'static str, String>,
+    keep_timestamps: bool,
+    logs: Option<LogChunks<'docker>>,
+    /// Timestamp of the last line yielded, in nanoseconds since the unix epoch
+    last: Option<i128>,
+    /// How many of the lines yielded carry the `last` timestamp
+    yielded_at_last: usize,
+    /// How many lines at the `last` timestamp the current request sends again
+    to_skip: usize,
+    done: bool,
 }
 
-impl ContainerListOptions {
-    /// return a new instance of a builder for options
-    pub fn builder() -> ContainerListOptionsBuilder {
-        ContainerListOptionsBuilder::default()
-    }
+impl<'docker> LogTail<'docker> {
+    /// How long to wait before requesting logs again after the daemon ended the stream
+    const RECONNECT_DELAY: Duration = Duration::from_millis(250);
+
+    async fn next(&mut self) -> Option<Result<tty::TtyChunk>> {
+        while !self.done {
+            let logs = match self.logs {
+                Some(ref mut logs) => logs,
+                None => {
+                    let logs = self.container.logs(&LogsOptions {
+                        params: self.params.clone(),
+                    });
+                    self.logs.insert(Box::pin(logs))
+                }
+            };
 
-    /// serialize options as a string. returns None if no options are defined
-    pub fn serialize(&self) -> Option<String> {
-        if self.params.is_empty() {
-            None
-        } else {
-            Some(
-                form_urlencoded::Serializer::new(String::new())
-                    .extend_pairs(&self.params)
-                    .finish(),
-            )
+            match logs.next().await {
+                Some(Ok(chunk)) => {
+                    if let Some(timestamp) = chunk.timestamp_nanos() {
+                        match self.last {
+                            Some(last) if timestamp < last => continue,
+                            Some(last) if timestamp == last => {
+                                if self.to_skip > 0 {
+                                    self.to_skip -= 1;
+                                    continue;
+                                }
+                                self.yielded_at_last += 1;
+                            }
+                            _ => {
+                                self.last = Some(timestamp);
+                                self.yielded_at_last = 1;
+                                self.to_skip = 0;
+                            }
+                        }
+                    }
+                    return Some(Ok(if self.keep_timestamps {
+                        chunk
+                    } else {
+                        chunk.without_timestamp()
+                    }));
+                }
+                Some(Err(e)) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+                None => {
+                    self.logs = None;
+                    match self.container.inspect().await {
+                        Ok(details) if details.state.running => {
+                            tokio::time::sleep(Self::RECONNECT_DELAY).await;
+                            // with nothing received yet, the tail still has to apply
+                            if let Some(last) = self.last {
+                                self.params.remove("tail");
+                                self.params.insert("since", since_nanos(last));
+                                // lines at the timestamp of `since` are sent again
+                                self.to_skip = self.yielded_at_last;
+                            }
+                        }
+                        Ok(_) => self.done = true,
+                        Err(e) => {
+                            self.done = true;
+                            return Some(Err(e));
+                        }
+                    }
+                }
+            }
         }
+        None
     }
 }
 
-/// Filter options for container listings
-pub enum ContainerFilter {
-    ExitCode(u64),
-    Status(String),
-    LabelName(String),
-    Label(String, String),
-    Name(String),
+/// Formats nanoseconds since the unix epoch as the `seconds.nanoseconds` form `since` accepts
+fn since_nanos(nanos: i128) -> String {
+    format!(
+        "{}.{:09}",
+        nanos.div_euclid(1_000_000_000),
+        nanos.rem_euclid(1_000_000_000)
+    )
 }
 
-/// Builder interface for `ContainerListOptions`
-#[derive(Default)]
-pub struct ContainerListOptionsBuilder {
-    params: HashMap<&'static str, String>,
+/// Stats of a set of containers sampled together, as yielded by a [StatsSampler]
+#[derive(Debug, Default)]
+pub struct StatsSnapshot {
+    /// Pairs of container ID and stats
+    pub stats: Vec<(String, Stats)>,
+    /// Pairs of container ID and the error sampling it failed with
+    pub errors: Vec<(String, Error)>,
 }
 
-impl ContainerListOptionsBuilder {
-    pub fn filter(
-        &mut self,
-        filters: Vec<ContainerFilter>,
-    ) -> &mut Self {
-        let mut param: HashMap<&str, Vec<String>> = HashMap::new();
-        for f in filters {
-            let (key, value) = match f {
-                ContainerFilter::ExitCode(c) => ("exited", c.to_string()),
-                ContainerFilter::Status(s) => ("status", s),
-                ContainerFilter::LabelName(n) => ("label", n),
-                ContainerFilter::Label(n, v) => ("label", format!("{}={}", n, v)),
-                ContainerFilter::Name(n) => ("name", n.to_string()),
+/// Stream of [StatsSnapshot]s returned by [Containers::stats_sampler]
+pub struct StatsSampler<'docker> {
+    snapshots: Pin<Box<dyn Stream<Item = StatsSnapshot> + Send + 'docker>>,
+}
+
+impl<'docker> Stream for StatsSampler<'docker> {
+    type Item = StatsSnapshot;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        self.snapshots.as_mut().poll_next(cx)
+    }
+}
+
+type ContainerStats<'docker> =
+    Pin<Box<dyn Stream<Item = (String, Result<Stats>)> + Send + 'docker>>;
+
+/// State behind [Containers::stats_all](Containers::stats_all)
+struct StatsAll<'docker> {
+    docker: &'docker Docker,
+    list_path: String,
+    events: Pin<Box<dyn Stream<Item = Result<Event>> + Send + 'docker>>,
+    streams: SelectAll<ContainerStats<'docker>>,
+    tracked: HashMap<String, AbortHandle>,
+    throttle: StatsThrottle,
+    listed: bool,
+}
+
+impl<'docker> StatsAll<'docker> {
+    async fn next(&mut self) -> Option<Result<(String, Stats)>> {
+        if !self.listed {
+            self.listed = true;
+            if let Err(e) = self.track_running().await {
+                return Some(Err(e));
+            }
+        }
+
+        loop {
+            let next = if self.streams.is_empty() {
+                Either::Left(self.events.next().await)
+            } else {
+                match future::select(self.events.next(), self.streams.next()).await {
+                    Either::Left((event, _)) => Either::Left(event),
+                    Either::Right((stats, _)) => Either::Right(stats),
+                }
             };
 
-            param.entry(key).or_insert_with(Vec::new).push(value);
+            match next {
+                // the events stream only ends when the connection to the daemon does
+                Either::Left(None) => return None,
+                Either::Left(Some(Err(e))) => return Some(Err(e)),
+                Either::Left(Some(Ok(event))) => match event.action.as_str() {
+                    "start" => {
+                        if let Err(e) = self.track_running().await {
+                            return Some(Err(e));
+                        }
+                    }
+                    "die" => {
+                        if let Some(handle) = self.tracked.remove(&event.actor.id) {
+                            handle.abort();
+                        }
+                        self.throttle.forget(&event.actor.id);
+                    }
+                    _ => (),
+                },
+                Either::Right(None) => (),
+                Either::Right(Some((id, Err(e)))) => {
+                    self.tracked.remove(&id);
+                    return Some(Err(e));
+                }
+                Either::Right(Some((id, Ok(stats)))) => {
+                    if self.throttle.admit(&id, Instant::now()) {
+                        return Some(Ok((id, stats)));
+                    }
+                }
+            }
         }
-        // structure is a a json encoded object mapping string keys to a list
-        // of string values
-        self.params
-            .insert("filters", serde_json::to_string(&param).unwrap());
-        self
     }
 
-    pub fn all(&mut self) -> &mut Self {
-        self.params.insert("all", "true".to_owned());
-        self
+    /// Starts streaming stats for running containers matching the filters that aren't yet tracked
+    async fn track_running(&mut self) -> Result<()> {
+        let containers: Vec<ContainerInfo> = self.docker.get_json(&self.list_path).await?;
+        for info in containers {
+            if self.tracked.contains_key(&info.id) {
+                continue;
+            }
+
+            let (stats, handle) = stream::abortable(Container::new(self.docker, &info.id).stats());
+            let id = info.id.clone();
+            self.streams
+                .push(Box::pin(stats.map(move |stats| (id.clone(), stats))));
+            self.tracked.insert(info.id, handle);
+        }
+        Ok(())
     }
+}
 
-    pub fn since(
-        &mut self,
-        since: &str,
-    ) -> &mut Self {
-        self.params.insert("since", since.to_owned());
-        self
+/// Limits stats samples to one per interval for each container
+struct StatsThrottle {
+    interval: Duration,
+    last: HashMap<String, Instant>,
+}
+
+impl StatsThrottle {
+    fn new(interval: Duration) -> Self {
+        StatsThrottle {
+            interval,
+            last: HashMap::new(),
+        }
     }
 
-    pub fn before(
+    fn admit(
         &mut self,
-        before: &str,
+        id: &str,
+        now: Instant,
+    ) -> bool {
+        match self.last.get(id) {
+            Some(last) if now.duration_since(*last) < self.interval => false,
+            _ => {
+                self.last.insert(id.to_owned(), now);
+                true
+            }
+        }
+    }
+
+    fn forget(
+        &mut self,
+        id: &str,
+    ) {
+        self.last.remove(id);
+    }
+}
+
+/// What [Container::wait_for](Container::wait_for) waits for
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum WaitCondition {
+    /// The container is not running, returning right away if it already exited or was never
+    /// started
+    #[default]
+    NotRunning,
+    /// The container exits the next time, even if it isn't running yet
+    NextExit,
+    /// The container is removed, such as one created with auto-remove once it exits
+    Removed,
+}
+
+impl WaitCondition {
+    /// The path of a request waiting for container `id`, leaving out the default condition
+    /// for daemons that don't know about conditions
+    fn wait_path(
+        &self,
+        id: &str,
+    ) -> String {
+        match self {
+            WaitCondition::NotRunning => format!("/containers/{}/wait", id),
+            condition => format!("/containers/{}/wait?condition={}", id, condition),
+        }
+    }
+
+    /// Features the condition needs
+    fn features(&self) -> Vec<Feature> {
+        match self {
+            WaitCondition::NotRunning => Vec::new(),
+            WaitCondition::NextExit | WaitCondition::Removed => vec![Feature::WaitCondition],
+        }
+    }
+}
+
+impl std::fmt::Display for WaitCondition {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        f.write_str(match self {
+            WaitCondition::NotRunning => "not-running",
+            WaitCondition::NextExit => "next-exit",
+            WaitCondition::Removed => "removed",
+        })
+    }
+}
+
+/// Whether a request to [start](Container::start) or [stop](Container::stop) a container
+/// changed its state
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StateChange {
+    /// The container was started or stopped
+    Changed,
+    /// The container was already running or stopped, so nothing was done
+    AlreadyInState,
+}
+
+impl StateChange {
+    /// Reads the daemon's `304 Not Modified` answer as [StateChange::AlreadyInState]
+    fn of<T>(response: Result<T>) -> Result<StateChange> {
+        match response {
+            Ok(_) => Ok(StateChange::Changed),
+            Err(Error::Fault { code, .. }) if code == StatusCode::NOT_MODIFIED => {
+                Ok(StateChange::AlreadyInState)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// How [Container::stop_or_kill](Container::stop_or_kill) brought a container down
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StopOutcome {
+    /// The container stopped, or was already stopped
+    Stopped,
+    /// Stopping the container failed or hung and it was killed
+    Killed,
+}
+
+/// What [Container::ensure_stopped](Container::ensure_stopped) did to bring a container down
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StopAction {
+    /// The container was never started or had already exited, so nothing was done
+    AlreadyStopped,
+    /// The container was running, or waiting to be restarted, and was brought down
+    Stopped(StopOutcome),
+    /// The container was paused, so it was unpaused and then brought down
+    Unpaused(StopOutcome),
+}
+
+/// Options for filtering container list results
+#[derive(Default, Debug)]
+pub struct ContainerListOptions {
+    params: HashMap<&'static str, String>,
+}
+
+/// Filter options for container listings
+pub enum ContainerFilter {
+    ExitCode(i64),
+    Status(String),
+    LabelName(String),
+    Label(String, String),
+    Name(String),
+}
+
+impl From<ContainerFilter> for Filter {
+    fn from(filter: ContainerFilter) -> Self {
+        match filter {
+            ContainerFilter::ExitCode(c) => Filter::new("exited", c.to_string()),
+            ContainerFilter::Status(s) => Filter::new("status", s),
+            ContainerFilter::LabelName(n) => Filter::label(n),
+            ContainerFilter::Label(n, v) => Filter::label_value(n, v),
+            ContainerFilter::Name(n) => Filter::name(n),
+        }
+    }
+}
+
+/// Builder interface for `ContainerListOptions`
+#[derive(Default)]
+pub struct ContainerListOptionsBuilder {
+    params: HashMap<&'static str, String>,
+}
+
+impl ContainerListOptionsBuilder {
+    pub fn filter<I, F>(
+        &mut self,
+        filters: I,
+    ) -> &mut Self
+    where
+        I: IntoIterator<Item = F>,
+        F: Into<Filter>,
+    {
+        self.params.insert("filters", filter::serialize(filters));
+        self
+    }
+
+    pub fn all(&mut self) -> &mut Self {
+        self.params.insert("all", "true".to_owned());
+        self
+    }
+
+    pub fn since(
+        &mut self,
+        since: &str,
+    ) -> &mut Self {
+        self.params.insert("since", since.to_owned());
+        self
+    }
+
+    pub fn before(
+        &mut self,
+        before: &str,
     ) -> &mut Self {
         self.params.insert("before", before.to_owned());
         self
@@ -550,19 +1479,78 @@ impl ContainerListOptionsBuilder {
         self.params.insert("size", "true".to_owned());
         self
     }
+}
 
-    pub fn build(&self) -> ContainerListOptions {
-        ContainerListOptions {
-            params: self.params.clone(),
+impl_query_options!(ContainerListOptions, ContainerListOptionsBuilder);
+
+/// Options for pruning stopped containers
+#[derive(Default, Debug)]
+pub struct ContainerPruneOptions {
+    params: HashMap<&'static str, String>,
+}
+
+/// Filter options for container pruning
+pub enum ContainerPruneFilter {
+    /// Containers created before this timestamp, which may be a unix timestamp, an RFC 3339
+    /// date or a duration relative to the daemon's time such as `10m`
+    Until(String),
+    /// Containers carrying a label with this name
+    LabelName(String),
+    /// Containers carrying a label with this name and value
+    Label(String, String),
+    /// Containers not carrying a label with this name
+    NoLabelName(String),
+    /// Containers not carrying a label with this name and value
+    NoLabel(String, String),
+}
+
+impl From<ContainerPruneFilter> for Filter {
+    fn from(filter: ContainerPruneFilter) -> Self {
+        match filter {
+            ContainerPruneFilter::Until(until) => Filter::new("until", until),
+            ContainerPruneFilter::LabelName(n) => Filter::label(n),
+            ContainerPruneFilter::Label(n, v) => Filter::label_value(n, v),
+            ContainerPruneFilter::NoLabelName(n) => Filter::label(n).negate(),
+            ContainerPruneFilter::NoLabel(n, v) => Filter::label_value(n, v).negate(),
         }
     }
 }
 
+/// Builder interface for `ContainerPruneOptions`
+#[derive(Default)]
+pub struct ContainerPruneOptionsBuilder {
+    params: HashMap<&'static str, String>,
+}
+
+impl ContainerPruneOptionsBuilder {
+    pub fn filter<I, F>(
+        &mut self,
+        filters: I,
+    ) -> &mut Self
+    where
+        I: IntoIterator<Item = F>,
+        F: Into<Filter>,
+    {
+        self.params.insert("filters", filter::serialize(filters));
+        self
+    }
+}
+
+impl_query_options!(ContainerPruneOptions, ContainerPruneOptionsBuilder);
+
 /// Interface for building a new docker container from an existing image
-#[derive(Serialize, Debug)]
+///
+/// Serializes to the `body` of the create request, next to the container's `name` when one is
+/// set, which is passed in the query rather than the body. It can be stored, compared and
+/// deserialized again, or written by hand, to create containers from a spec without going
+/// through the builder.
+///
+/// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/ContainerCreate>
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct ContainerOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
-    params: HashMap<&'static str, Value>,
+    body: Map<String, Value>,
 }
 
 /// Function to insert a JSON value into a tree where the desired
@@ -599,19 +1587,32 @@ impl ContainerOptions {
         ContainerOptionsBuilder::new(name)
     }
 
+    /// Options creating a container named `name` from the create request's JSON `body`
+    pub fn from_body(
+        name: Option<String>,
+        body: Map<String, Value>,
+    ) -> ContainerOptions {
+        ContainerOptions { name, body }
+    }
+
+    /// The JSON body of the create request
+    pub fn body(&self) -> &Map<String, Value> {
+        &self.body
+    }
+
     /// serialize options as a string. returns None if no options are defined
     pub fn serialize(&self) -> Result<String> {
-        serde_json::to_string(&self.to_json()).map_err(Error::from)
+        serde_json::to_string(&self.body).map_err(Error::from)
     }
 
-    fn to_json(&self) -> Value {
-        let mut body_members = Map::new();
-        // The HostConfig element gets initialized to an empty object,
-        // for backward compatibility.
-        body_members.insert("HostConfig".to_string(), Value::Object(Map::new()));
-        let mut body = Value::Object(body_members);
-        self.parse_from(&self.params, &mut body);
-        body
+    /// Options in use that not every daemon supports
+    pub(crate) fn features(&self) -> Vec<Feature> {
+        let mut features = Vec::new();
+        let host_config = self.body.get("HostConfig");
+        if host_config.is_some_and(|host_config| host_config.get("CgroupnsMode").is_some()) {
+            features.push(Feature::CgroupnsMode);
+        }
+        features
     }
 
     pub fn parse_from<'a, K, V>(
@@ -630,10 +1631,82 @@ impl ContainerOptions {
     }
 }
 
+/// The host config settings [ContainerOptionsBuilder::from_details] carries over, under the
+/// builder keys their setters use
+const CLONED_HOST_CONFIG: &[&str] = &[
+    "HostConfig.CpuShares",
+    "HostConfig.Memory",
+    "HostConfig.CgroupParent",
+    "HostConfig.BlkioWeightDevice",
+    "HostConfig.BlkioDeviceReadBps",
+    "HostConfig.BlkioDeviceWriteBps",
+    "HostConfig.BlkioDeviceReadIOps",
+    "HostConfig.BlkioDeviceWriteIOps",
+    "HostConfig.CpuPeriod",
+    "HostConfig.CpuQuota",
+    "HostConfig.CpuRealtimePeriod",
+    "HostConfig.CpuRealtimeRuntime",
+    "HostConfig.CpusetCpus",
+    "HostConfig.CpusetMems",
+    "HostConfig.Devices",
+    "HostConfig.DeviceCgroupRules",
+    "HostConfig.DeviceRequests",
+    "HostConfig.KernelMemoryTCP",
+    "HostConfig.MemoryReservation",
+    "HostConfig.MemorySwap",
+    "HostConfig.MemorySwappiness",
+    "HostConfig.OomKillDisable",
+    "HostConfig.Init",
+    "HostConfig.PidsLimit",
+    "HostConfig.Ulimits",
+    "HostConfig.CpuCount",
+    "HostConfig.CpuPercent",
+    "HostConfig.IOMaximumIOps",
+    "HostConfig.Binds",
+    "HostConfig.LogConfig.Type",
+    "HostConfig.LogConfig.Config",
+    "HostConfig.NetworkMode",
+    "HostConfig.PortBindings",
+    "HostConfig.RestartPolicy.Name",
+    "HostConfig.RestartPolicy.MaximumRetryCount",
+    "HostConfig.AutoRemove",
+    "HostConfig.VolumeDriver",
+    "HostConfig.VolumesFrom",
+    "HostConfig.Mounts",
+    "HostConfig.CapAdd",
+    "HostConfig.CapDrop",
+    "HostConfig.Dns",
+    "HostConfig.DnsOptions",
+    "HostConfig.DnsSearch",
+    "HostConfig.ExtraHosts",
+    "HostConfig.GroupAdd",
+    "HostConfig.IpcMode",
+    "HostConfig.Cgroup",
+    "HostConfig.Links",
+    "HostConfig.OomScoreAdj",
+    "HostConfig.PidMode",
+    "HostConfig.Privileged",
+    "HostConfig.PublishAllPorts",
+    "HostConfig.ReadonlyRootfs",
+    "HostConfig.SecurityOpt",
+    "HostConfig.StorageOpt",
+    "HostConfig.Tmpfs",
+    "HostConfig.UTSMode",
+    "HostConfig.UsernsMode",
+    "HostConfig.ShmSize",
+    "HostConfig.Sysctls",
+    "HostConfig.Runtime",
+    "HostConfig.ConsoleSize",
+    "HostConfig.Isolation",
+    "HostConfig.MaskedPaths",
+    "HostConfig.ReadonlyPaths",
+];
+
 #[derive(Default)]
 pub struct ContainerOptionsBuilder {
     name: Option<String>,
     params: HashMap<&'static str, Value>,
+    idempotency_key: Option<String>,
 }
 
 impl ContainerOptionsBuilder {
@@ -641,9 +1714,95 @@ impl ContainerOptionsBuilder {
         let mut params = HashMap::new();
 
         params.insert("Image", Value::String(image.to_owned()));
-        ContainerOptionsBuilder { name: None, params }
+        ContainerOptionsBuilder {
+            params,
+            ..Default::default()
+        }
+    }
+
+    /// Returns a builder for containers of `image` pre-populated from the image's config, see
+    /// [Image::container_options](crate::image::Image::container_options)
+    pub fn from_image_config(
+        image: &str,
+        config: &ContainerConfig,
+    ) -> Self {
+        let mut builder = ContainerOptionsBuilder::new(image);
+        let params = &mut builder.params;
+
+        if let Some(ref ports) = config.exposed_ports {
+            let ports: HashMap<&String, Value> = ports.keys().map(|p| (p, json!({}))).collect();
+            params.insert("ExposedPorts", json!(ports));
+        }
+        if let Some(ref volumes) = config.volumes {
+            let volumes: HashMap<&String, Value> = volumes.keys().map(|v| (v, json!({}))).collect();
+            params.insert("Volumes", json!(volumes));
+        }
+        if let Some(ref entrypoint) = config.entrypoint {
+            params.insert("Entrypoint", json!(entrypoint));
+        }
+        if let Some(ref cmd) = config.cmd {
+            params.insert("Cmd", json!(cmd));
+        }
+        if let Some(ref env) = config.env {
+            params.insert("Env", json!(env));
+        }
+        if !config.user.is_empty() {
+            params.insert("User", json!(config.user));
+        }
+        if !config.working_dir.is_empty() {
+            params.insert("WorkingDir", json!(config.working_dir));
+        }
+
+        builder
+    }
+
+    /// Returns a builder recreating an inspected container: its name, the image reference it
+    /// was created from and its config and host config, leaving out runtime state such as its
+    /// generated hostname. See [Container::clone_spec](Container::clone_spec).
+    pub fn from_details(details: &ContainerDetails) -> Self {
+        let config = &details.config;
+        let mut builder = ContainerOptionsBuilder::from_image_config(&config.image, config);
+        builder.name = Some(details.name.trim_start_matches('/').to_owned());
+        let params = &mut builder.params;
+
+        if let Some(ref labels) = config.labels {
+            params.insert("Labels", json!(labels));
+        }
+        // the daemon names containers' hosts after their id unless told otherwise
+        if !config.hostname.is_empty() && !details.id.starts_with(&config.hostname) {
+            params.insert("Hostname", json!(config.hostname));
+        }
+        if !config.domainname.is_empty() {
+            params.insert("Domainname", json!(config.domainname));
+        }
+        params.insert("Tty", json!(config.tty));
+        params.insert("OpenStdin", json!(config.open_stdin));
+        params.insert("StdinOnce", json!(config.stdin_once));
+        params.insert("AttachStdin", json!(config.attach_stdin));
+        params.insert("AttachStdout", json!(config.attach_stdout));
+        params.insert("AttachStderr", json!(config.attach_stderr));
+
+        // copied key by key, so setters called on the builder afterwards replace single settings
+        let host_config = json!({ "HostConfig": details.host_config });
+        for &key in CLONED_HOST_CONFIG {
+            let value = key
+                .split('.')
+                .try_fold(&host_config, |value, key| value.get(key));
+            if let Some(value) = value.filter(|value| !value.is_null()) {
+                params.insert(key, value.clone());
+            }
+        }
+        if let Some(nano_cpus) = details.host_config.nano_cpus {
+            params.insert("HostConfig.NanoCpus", json!(nano_cpus));
+        }
+
+        builder
     }
 
+    /// Names the container. [Containers::create](Containers::create) rejects names that don't
+    /// match `[a-zA-Z0-9][a-zA-Z0-9_.-]+` with
+    /// [Error::InvalidContainerName](crate::Error::InvalidContainerName), and names already in
+    /// use with [Error::ContainerNameConflict](crate::Error::ContainerNameConflict).
     pub fn name(
         &mut self,
         name: &str,
@@ -661,7 +1820,9 @@ impl ContainerOptionsBuilder {
         self
     }
 
-    /// Specify any bind mounts, taking the form of `/some/host/path:/some/container/path`
+    /// Specify any bind mounts, taking the form of `/some/host/path:/some/container/path`, or
+    /// `C:\some\host\path:C:\some\container\path` for windows containers.
+    /// See [Bind](struct.Bind.html) to build them.
     pub fn volumes(
         &mut self,
         volumes: Vec<&str>,
@@ -820,6 +1981,19 @@ impl ContainerOptionsBuilder {
         self
     }
 
+    /// Labels the container with `key` as its [idempotency key](crate::idempotency), so that
+    /// [create](crate::Containers::create) can be retried safely
+    pub fn idempotency_key<K>(
+        &mut self,
+        key: K,
+    ) -> &mut Self
+    where
+        K: Into<String>,
+    {
+        self.idempotency_key = Some(key.into());
+        self
+    }
+
     /// Whether to attach to `stdin`.
     pub fn attach_stdin(
         &mut self,
@@ -881,6 +2055,30 @@ impl ContainerOptionsBuilder {
         self
     }
 
+    /// Connects the container to `network` when it is created, with static IPv4 and IPv6
+    /// addresses on it. Either may be `None` for an address assigned by the network. Usually
+    /// combined with [network_mode](ContainerOptionsBuilder::network_mode) naming the same
+    /// network.
+    pub fn network_addresses(
+        &mut self,
+        network: &str,
+        ipv4_address: Option<&str>,
+        ipv6_address: Option<&str>,
+    ) -> &mut Self {
+        let mut ipam_config = Map::new();
+        if let Some(address) = ipv4_address {
+            ipam_config.insert("IPv4Address".to_owned(), json!(address));
+        }
+        if let Some(address) = ipv6_address {
+            ipam_config.insert("IPv6Address".to_owned(), json!(address));
+        }
+        self.params.insert(
+            "NetworkingConfig.EndpointsConfig",
+            json!({ network: { "IPAMConfig": ipam_config } }),
+        );
+        self
+    }
+
     pub fn env<E, S>(
         &mut self,
         envs: E,
@@ -893,6 +2091,48 @@ impl ContainerOptionsBuilder {
         self
     }
 
+    /// Adds environment variables from `(name, value)` pairs, such as a `HashMap`, joining them
+    /// into `NAME=VALUE` form. A variable that is already set is replaced.
+    pub fn env_map<I, K, V>(
+        &mut self,
+        vars: I,
+    ) -> &mut Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let mut env: Vec<String> = match self.params.remove("Env") {
+            Some(Value::Array(env)) => env
+                .into_iter()
+                .filter_map(|var| var.as_str().map(str::to_owned))
+                .collect(),
+            _ => Vec::new(),
+        };
+        for (name, value) in vars {
+            let name = name.as_ref();
+            env.retain(|var| var.split('=').next() != Some(name));
+            env.push(format!("{}={}", name, value.as_ref()));
+        }
+        self.params.insert("Env", json!(env));
+        self
+    }
+
+    /// Adds environment variables read from a file in the format of `docker run --env-file`:
+    /// one `NAME=VALUE` per line, with blank lines and lines starting with `#` ignored. A value
+    /// wrapped in matching single or double quotes has them removed, and a line holding only a
+    /// `NAME` takes its value from the current environment, if set there.
+    pub fn env_file<P>(
+        &mut self,
+        path: P,
+    ) -> Result<&mut Self>
+    where
+        P: AsRef<Path>,
+    {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(self.env_map(parse_env_file(&contents)?))
+    }
+
     pub fn cmd(
         &mut self,
         cmds: Vec<&str>,
@@ -917,14 +2157,31 @@ impl ContainerOptionsBuilder {
         self
     }
 
-    pub fn devices(
+    /// Host devices to make available in the container, such as `/dev/kvm`
+    pub fn devices<I, D>(
         &mut self,
-        devices: Vec<HashMap<String, String>>,
-    ) -> &mut Self {
+        devices: I,
+    ) -> &mut Self
+    where
+        I: IntoIterator<Item = D>,
+        D: Into<DeviceMapping>,
+    {
+        let devices: Vec<DeviceMapping> = devices.into_iter().map(Into::into).collect();
         self.params.insert("HostConfig.Devices", json!(devices));
         self
     }
 
+    /// Rules to add to the container's device cgroup, in the form `type major:minor access`,
+    /// e.g. `c 188:* rwm` for all USB serial ports
+    pub fn device_cgroup_rules(
+        &mut self,
+        rules: Vec<&str>,
+    ) -> &mut Self {
+        self.params
+            .insert("HostConfig.DeviceCgroupRules", json!(rules));
+        self
+    }
+
     pub fn log_driver(
         &mut self,
         log_driver: &str,
@@ -993,6 +2250,24 @@ impl ContainerOptionsBuilder {
         self
     }
 
+    /// `private` or `host` cgroup namespace. Requires API version 1.41.
+    pub fn cgroupns_mode(
+        &mut self,
+        mode: &str,
+    ) -> &mut Self {
+        self.params.insert("HostConfig.CgroupnsMode", json!(mode));
+        self
+    }
+
+    /// How the container is isolated from the host, only supported by windows daemons
+    pub fn isolation(
+        &mut self,
+        isolation: Isolation,
+    ) -> &mut Self {
+        self.params.insert("HostConfig.Isolation", json!(isolation));
+        self
+    }
+
     pub fn privileged(
         &mut self,
         set: bool,
@@ -1010,9 +2285,25 @@ impl ContainerOptionsBuilder {
     }
 
     pub fn build(&self) -> ContainerOptions {
+        let mut body_members = Map::new();
+        // The HostConfig element gets initialized to an empty object,
+        // for backward compatibility.
+        body_members.insert("HostConfig".to_string(), Value::Object(Map::new()));
+        let mut body = Value::Object(body_members);
+        for (k, v) in &self.params {
+            let key_string = k.to_string();
+            insert(&mut key_string.split('.').peekable(), v, &mut body)
+        }
+        let mut body = match body {
+            Value::Object(body) => body,
+            _ => unreachable!(),
+        };
+        if let Some(ref key) = self.idempotency_key {
+            idempotency::insert_key(&mut body, key);
+        }
         ContainerOptions {
             name: self.name.clone(),
-            params: self.params.clone(),
+            body,
         }
     }
 }
@@ -1023,26 +2314,6 @@ pub struct LogsOptions {
     params: HashMap<&'static str, String>,
 }
 
-impl LogsOptions {
-    /// return a new instance of a builder for options
-    pub fn builder() -> LogsOptionsBuilder {
-        LogsOptionsBuilder::default()
-    }
-
-    /// serialize options as a string. returns None if no options are defined
-    pub fn serialize(&self) -> Option<String> {
-        if self.params.is_empty() {
-            None
-        } else {
-            Some(
-                form_urlencoded::Serializer::new(String::new())
-                    .extend_pairs(&self.params)
-                    .finish(),
-            )
-        }
-    }
-}
-
 /// Builder interface for `LogsOptions`
 #[derive(Default)]
 pub struct LogsOptionsBuilder {
@@ -1050,45 +2321,15 @@ pub struct LogsOptionsBuilder {
 }
 
 impl LogsOptionsBuilder {
-    pub fn follow(
-        &mut self,
-        f: bool,
-    ) -> &mut Self {
-        self.params.insert("follow", f.to_string());
-        self
-    }
-
-    pub fn stdout(
-        &mut self,
-        s: bool,
-    ) -> &mut Self {
-        self.params.insert("stdout", s.to_string());
-        self
-    }
-
-    pub fn stderr(
-        &mut self,
-        s: bool,
-    ) -> &mut Self {
-        self.params.insert("stderr", s.to_string());
-        self
-    }
-
-    pub fn timestamps(
-        &mut self,
-        t: bool,
-    ) -> &mut Self {
-        self.params.insert("timestamps", t.to_string());
-        self
-    }
-
-    /// how_many can either be "all" or a to_string() of the number
-    pub fn tail(
-        &mut self,
-        how_many: &str,
-    ) -> &mut Self {
-        self.params.insert("tail", how_many.to_owned());
-        self
+    query_setters! {
+        follow: bool => "follow",
+        stdout: bool => "stdout",
+        stderr: bool => "stderr",
+        /// Prefix each line with its timestamp, which
+        /// [TtyChunk::timestamp](crate::tty::TtyChunk::timestamp) splits off again
+        timestamps: bool => "timestamps",
+        /// how_many can either be "all" or a to_string() of the number
+        tail: &str => "tail",
     }
 
     #[cfg(feature = "chrono")]
@@ -1112,70 +2353,228 @@ impl LogsOptionsBuilder {
         self.params.insert("since", timestamp.to_string());
         self
     }
-
-    pub fn build(&self) -> LogsOptions {
-        LogsOptions {
-            params: self.params.clone(),
-        }
-    }
 }
 
-/// Options for controlling log request results
-#[derive(Default, Debug)]
-pub struct RmContainerOptions {
-    params: HashMap<&'static str, String>,
+impl_query_options!(LogsOptions, LogsOptionsBuilder);
+
+/// Resource and restart policy changes for a container
+#[derive(Serialize, Debug)]
+pub struct UpdateOptions {
+    params: HashMap<&'static str, Value>,
 }
 
-impl RmContainerOptions {
+impl UpdateOptions {
     /// return a new instance of a builder for options
-    pub fn builder() -> RmContainerOptionsBuilder {
-        RmContainerOptionsBuilder::default()
-    }
-
-    /// serialize options as a string. returns None if no options are defined
-    pub fn serialize(&self) -> Option<String> {
-        if self.params.is_empty() {
-            None
-        } else {
-            Some(
-                form_urlencoded::Serializer::new(String::new())
-                    .extend_pairs(&self.params)
-                    .finish(),
-            )
-        }
+    pub fn builder() -> UpdateOptionsBuilder {
+        UpdateOptionsBuilder::default()
     }
 }
 
-/// Builder interface for `LogsOptions`
+impl_json_options!(UpdateOptions);
+
+/// Builder interface for `UpdateOptions`, with the resource setters of
+/// [ContainerOptionsBuilder](ContainerOptionsBuilder). Settings left unset keep their current
+/// value.
 #[derive(Default)]
-pub struct RmContainerOptionsBuilder {
-    params: HashMap<&'static str, String>,
+pub struct UpdateOptionsBuilder {
+    params: HashMap<&'static str, Value>,
 }
 
-impl RmContainerOptionsBuilder {
-    pub fn force(
+impl UpdateOptionsBuilder {
+    /// Memory limit in bytes
+    pub fn memory(
         &mut self,
-        f: bool,
+        memory: u64,
     ) -> &mut Self {
-        self.params.insert("force", f.to_string());
+        self.params.insert("Memory", json!(memory));
         self
     }
 
-    pub fn volumes(
+    /// Total memory limit (memory + swap) in bytes. Set to -1 to enable unlimited swap.
+    pub fn memory_swap(
+        &mut self,
+        memory_swap: i64,
+    ) -> &mut Self {
+        self.params.insert("MemorySwap", json!(memory_swap));
+        self
+    }
+
+    /// Memory soft limit in bytes, which the container is held to when the host runs low
+    pub fn memory_reservation(
+        &mut self,
+        memory_reservation: u64,
+    ) -> &mut Self {
+        self.params
+            .insert("MemoryReservation", json!(memory_reservation));
+        self
+    }
+
+    /// CPU quota in units of 10<sup>-9</sup> CPUs. Set to 0 for there to be no limit.
+    pub fn nano_cpus(
+        &mut self,
+        nano_cpus: u64,
+    ) -> &mut Self {
+        self.params.insert("NanoCpus", json!(nano_cpus));
+        self
+    }
+
+    /// CPU quota in units of CPUs. This is a wrapper around `nano_cpus` to do the unit conversion.
+    ///
+    /// See [`nano_cpus`](#method.nano_cpus).
+    pub fn cpus(
+        &mut self,
+        cpus: f64,
+    ) -> &mut Self {
+        self.nano_cpus((1_000_000_000.0 * cpus) as u64)
+    }
+
+    /// Sets an integer value representing the container's relative CPU weight versus other
+    /// containers.
+    pub fn cpu_shares(
+        &mut self,
+        cpu_shares: u32,
+    ) -> &mut Self {
+        self.params.insert("CpuShares", json!(cpu_shares));
+        self
+    }
+
+    /// CPUs the container may run on, such as `0-3` or `0,1`
+    pub fn cpuset_cpus(
+        &mut self,
+        cpus: &str,
+    ) -> &mut Self {
+        self.params.insert("CpusetCpus", json!(cpus));
+        self
+    }
+
+    /// Memory nodes the container may use, such as `0-3` or `0,1`. Only effective on NUMA
+    /// systems.
+    pub fn cpuset_mems(
+        &mut self,
+        mems: &str,
+    ) -> &mut Self {
+        self.params.insert("CpusetMems", json!(mems));
+        self
+    }
+
+    /// Maximum number of processes in the container. Set to -1 for there to be no limit.
+    pub fn pids_limit(
+        &mut self,
+        pids_limit: i64,
+    ) -> &mut Self {
+        self.params.insert("PidsLimit", json!(pids_limit));
+        self
+    }
+
+    pub fn restart_policy(
         &mut self,
-        s: bool,
+        name: &str,
+        maximum_retry_count: u64,
     ) -> &mut Self {
-        self.params.insert("v", s.to_string());
+        let mut policy = json!({ "Name": name });
+        if name == "on-failure" {
+            policy["MaximumRetryCount"] = json!(maximum_retry_count);
+        }
+        self.params.insert("RestartPolicy", policy);
+        self
+    }
+
+    pub fn build(&self) -> UpdateOptions {
+        UpdateOptions {
+            params: self.params.clone(),
+        }
+    }
+}
+
+/// Options for committing a container to an image
+#[derive(Default, Debug)]
+pub struct CommitOptions {
+    params: HashMap<&'static str, String>,
+    changes: Vec<String>,
+}
+
+impl CommitOptions {
+    /// return a new instance of a builder for options
+    pub fn builder() -> CommitOptionsBuilder {
+        CommitOptionsBuilder::default()
+    }
+
+    /// serialize options as a string, for committing the container `container`
+    pub fn serialize(
+        &self,
+        container: &str,
+    ) -> String {
+        form_urlencoded::Serializer::new(String::new())
+            .append_pair("container", container)
+            .extend_pairs(&self.params)
+            .extend_pairs(self.changes.iter().map(|change| ("changes", change)))
+            .finish()
+    }
+}
+
+#[derive(Default)]
+pub struct CommitOptionsBuilder {
+    params: HashMap<&'static str, String>,
+    changes: Vec<String>,
+}
+
+impl CommitOptionsBuilder {
+    query_setters! {
+        /// Repository name to give the image
+        repo: &str => "repo",
+        /// Tag to give the image
+        tag: &str => "tag",
+        /// Commit message recorded for the image
+        comment: &str => "comment",
+        /// Author recorded for the image, such as `Jane Doe <jane@example.com>`
+        author: &str => "author",
+        /// Whether the container is paused while it's committed, which is the default
+        pause: bool => "pause",
+    }
+
+    /// Dockerfile instructions to apply to the image, e.g. `ENV DEBUG=true`.
+    /// Supported instructions are CMD, ENTRYPOINT, ENV, EXPOSE, ONBUILD, USER, VOLUME and WORKDIR
+    pub fn changes<I, C>(
+        &mut self,
+        changes: I,
+    ) -> &mut Self
+    where
+        I: IntoIterator<Item = C>,
+        C: Into<String>,
+    {
+        self.changes.extend(changes.into_iter().map(Into::into));
         self
     }
 
-    pub fn build(&self) -> RmContainerOptions {
-        RmContainerOptions {
+    pub fn build(&self) -> CommitOptions {
+        CommitOptions {
             params: self.params.clone(),
+            changes: self.changes.clone(),
         }
     }
 }
 
+/// Options for controlling log request results
+#[derive(Default, Debug)]
+pub struct RmContainerOptions {
+    params: HashMap<&'static str, String>,
+}
+
+/// Builder interface for `LogsOptions`
+#[derive(Default)]
+pub struct RmContainerOptionsBuilder {
+    params: HashMap<&'static str, String>,
+}
+
+impl RmContainerOptionsBuilder {
+    query_setters! {
+        force: bool => "force",
+        volumes: bool => "v",
+    }
+}
+
+impl_query_options!(RmContainerOptions, RmContainerOptionsBuilder);
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct ContainerInfo {
@@ -1198,6 +2597,34 @@ pub struct ContainerInfo {
     pub size_root_fs: Option<i64>,
 }
 
+impl ContainerInfo {
+    /// Labels set on the container
+    pub fn labels(&self) -> HashMap<String, String> {
+        self.labels.clone()
+    }
+
+    /// Host port published for the container's `private_port` over `protocol` (`tcp`, `udp` or
+    /// `sctp`), preferring the IPv4 binding when the port is published on both IPv4 and IPv6
+    pub fn host_port(
+        &self,
+        private_port: u64,
+        protocol: &str,
+    ) -> Option<u64> {
+        let mut bindings = self
+            .ports
+            .iter()
+            .filter(|port| port.private_port == private_port && port.typ == protocol)
+            .filter(|port| port.public_port.is_some());
+        let first = bindings.next()?;
+        let port = if first.is_ipv6() {
+            bindings.find(|port| !port.is_ipv6()).unwrap_or(first)
+        } else {
+            first
+        };
+        port.public_port
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct ContainerDetails {
@@ -1228,6 +2655,62 @@ pub struct ContainerDetails {
     pub mounts: Vec<Mount>,
     pub config: ContainerConfig,
     pub network_settings: NetworkSettings,
+    /// Size of the files the container created or changed, only reported when inspecting with
+    /// [Container::inspect_with_size]
+    pub size_rw: Option<i64>,
+    /// Total size of the container's files, including its image, only reported when inspecting
+    /// with [Container::inspect_with_size]
+    pub size_root_fs: Option<i64>,
+}
+
+impl ContainerDetails {
+    /// Labels set on the container's config
+    pub fn labels(&self) -> HashMap<String, String> {
+        self.config.labels.clone().unwrap_or_default()
+    }
+
+    /// The container's disk quota in bytes, as set with the `size` storage option, if it has one
+    pub fn disk_quota(&self) -> Option<u64> {
+        let size = self.host_config.storage_opt.as_ref()?.get("size")?;
+        parse_size(size)
+    }
+}
+
+/// Parses a size such as `10G` or `512m` into bytes, with binary multiples like the daemon
+fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim().to_ascii_lowercase();
+    let size = size
+        .strip_suffix("ib")
+        .or_else(|| size.strip_suffix('b'))
+        .unwrap_or(&size);
+    let (number, multiplier) = match size.chars().last()? {
+        'k' => (&size[..size.len() - 1], 1u64 << 10),
+        'm' => (&size[..size.len() - 1], 1 << 20),
+        'g' => (&size[..size.len() - 1], 1 << 30),
+        't' => (&size[..size.len() - 1], 1 << 40),
+        'p' => (&size[..size.len() - 1], 1 << 50),
+        _ => (size, 1),
+    };
+    let number: f64 = number.trim().parse().ok()?;
+    if number.is_sign_negative() {
+        return None;
+    }
+    Some((number * multiplier as f64) as u64)
+}
+
+/// Formats bytes with binary multiples, like the docker CLI does for stats
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+    if bytes < 1024 {
+        return format!("{}B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.2}{}", size, UNITS[unit])
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -1251,7 +2734,8 @@ pub struct Mount {
 #[serde(rename_all = "PascalCase")]
 pub struct State {
     pub error: String,
-    pub exit_code: u64,
+    /// Signed, as windows containers report NTSTATUS codes as negative values
+    pub exit_code: i64,
     #[cfg(feature = "chrono")]
     pub finished_at: DateTime<Utc>,
     #[cfg(not(feature = "chrono"))]
@@ -1269,6 +2753,119 @@ pub struct State {
     pub status: String,
 }
 
+impl State {
+    /// Whether the container has no process and won't get one unless started: it was created
+    /// but never started, has exited, is dead or is being removed
+    pub fn is_stopped(&self) -> bool {
+        !self.running && !self.paused && !self.restarting
+    }
+
+    /// How the container's main process ended, `None` while it has not exited
+    pub fn exit_info(&self) -> Option<ExitInfo> {
+        if self.running || self.restarting || self.status == "created" {
+            return None;
+        }
+        Some(ExitInfo {
+            exit_code: self.exit_code,
+            oom_killed: self.oom_killed,
+            error: Some(self.error.clone()).filter(|error| !error.is_empty()),
+            #[cfg(feature = "chrono")]
+            finished_at: self.finished_at,
+            #[cfg(not(feature = "chrono"))]
+            finished_at: self.finished_at.clone(),
+        })
+    }
+}
+
+/// A one line summary such as `running`, `paused` or `exited (1)`
+impl std::fmt::Display for State {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        if self.paused {
+            return write!(f, "paused");
+        }
+        if self.restarting {
+            return write!(f, "restarting ({})", self.exit_code);
+        }
+        match self.exit_info() {
+            Some(exit) => write!(f, "{} ({})", self.status, exit.reason()),
+            None => write!(f, "{}", self.status),
+        }
+    }
+}
+
+/// How a container's main process ended
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExitInfo {
+    pub exit_code: i64,
+    /// Whether the kernel's OOM killer killed a process of the container
+    pub oom_killed: bool,
+    /// The error the daemon reported, such as failing to start the process
+    pub error: Option<String>,
+    #[cfg(feature = "chrono")]
+    pub finished_at: DateTime<Utc>,
+    #[cfg(not(feature = "chrono"))]
+    pub finished_at: String,
+}
+
+impl ExitInfo {
+    /// Classifies why the process exited
+    pub fn reason(&self) -> ExitReason {
+        ExitReason::classify(self.exit_code, self.oom_killed, self.error.as_deref())
+    }
+}
+
+/// Why a container's main process exited
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExitReason {
+    /// Exited with code 0
+    Success,
+    /// Killed by the kernel's OOM killer
+    OomKilled,
+    /// Terminated by a signal, which the daemon reports as exit code 128 plus the signal number.
+    /// A process exiting with such a code on its own is indistinguishable from this.
+    Signal(i32),
+    /// The daemon could not run the process, such as when its executable is missing
+    RuntimeError(String),
+    /// Exited with a non-zero code
+    Failure(i64),
+}
+
+impl ExitReason {
+    /// Classifies an exit from its code, whether the OOM killer struck, and the daemon's error.
+    /// The OOM killer takes precedence, as a process it kills also exits by `SIGKILL`.
+    pub fn classify(
+        exit_code: i64,
+        oom_killed: bool,
+        error: Option<&str>,
+    ) -> ExitReason {
+        match (exit_code, error) {
+            _ if oom_killed => ExitReason::OomKilled,
+            (_, Some(error)) if !error.is_empty() => ExitReason::RuntimeError(error.to_owned()),
+            (0, _) => ExitReason::Success,
+            (129..=192, _) => ExitReason::Signal((exit_code - 128) as i32),
+            _ => ExitReason::Failure(exit_code),
+        }
+    }
+}
+
+impl std::fmt::Display for ExitReason {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        match self {
+            ExitReason::Success => write!(f, "0"),
+            ExitReason::OomKilled => write!(f, "OOM killed"),
+            ExitReason::Signal(signal) => write!(f, "signal {}", signal),
+            ExitReason::RuntimeError(error) => write!(f, "error: {}", error),
+            ExitReason::Failure(code) => write!(f, "{}", code),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct HostConfig {
@@ -1289,16 +2886,18 @@ pub struct HostConfig {
     pub cpuset_cpus: Option<String>,
     pub cpuset_mems: Option<String>,
     pub devices: Option<Vec<DeviceMapping>>,
-    pub device_cgroup_rules: Option<String>,
+    pub device_cgroup_rules: Option<Vec<String>>,
     pub device_requests: Option<Vec<DeviceRequest>>,
+    /// Not reported by windows daemons
     #[serde(rename = "KernelMemoryTCP")]
-    pub kernel_memory_tcp: i64,
+    pub kernel_memory_tcp: Option<i64>,
     pub memory_reservation: Option<i64>,
     pub memory_swap: Option<i64>,
     pub memory_swappiness: Option<i64>,
     #[serde(rename = "NanoCPUs")]
     pub nano_cpus: Option<i64>,
-    pub oom_kill_disable: bool,
+    /// Not reported by windows daemons
+    pub oom_kill_disable: Option<bool>,
     pub init: Option<bool>,
     pub pids_limit: Option<i64>,
     pub ulimits: Option<Vec<Ulimit>>,
@@ -1344,11 +2943,124 @@ pub struct HostConfig {
     pub sysctls: Option<HashMap<String, String>>,
     pub runtime: String,
     pub console_size: Option<Vec<u64>>,
-    pub isolation: String,
+    pub isolation: Isolation,
     pub masked_paths: Option<Vec<String>>,
     pub readonly_paths: Option<Vec<String>>,
 }
 
+impl HostConfig {
+    /// The bind mounts of the container, leaving out any that can't be parsed
+    pub fn bind_mounts(&self) -> Vec<Bind> {
+        self.binds
+            .iter()
+            .flatten()
+            .filter_map(|bind| bind.parse().ok())
+            .collect()
+    }
+}
+
+/// How a container is isolated from the host. Anything but the default is only supported by
+/// windows daemons.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Isolation {
+    /// The daemon's default, which linux daemons report as an empty string
+    #[serde(alias = "")]
+    Default,
+    Process,
+    Hyperv,
+}
+
+/// A bind mount in the `source:target[:options]` form of `HostConfig.Binds`
+///
+/// Windows paths such as `C:\data` are understood, so a single letter volume name followed by
+/// an absolute target, such as `c:/data`, is read as a windows path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bind {
+    /// A host path or volume name
+    pub source: String,
+    /// The path in the container
+    pub target: String,
+    /// Comma separated options such as `ro`
+    pub options: Option<String>,
+}
+
+impl Bind {
+    pub fn new<S, T>(
+        source: S,
+        target: T,
+    ) -> Bind
+    where
+        S: Into<String>,
+        T: Into<String>,
+    {
+        Bind {
+            source: source.into(),
+            target: target.into(),
+            options: None,
+        }
+    }
+
+    pub fn read_only(mut self) -> Bind {
+        self.options = Some("ro".to_owned());
+        self
+    }
+}
+
+impl FromStr for Bind {
+    type Err = Error;
+
+    fn from_str(bind: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut rest = bind;
+        loop {
+            // the colon of a drive letter doesn't separate parts
+            let skip = if starts_with_drive(rest) { 2 } else { 0 };
+            match rest[skip..].find(':') {
+                Some(i) => {
+                    parts.push(&rest[..skip + i]);
+                    rest = &rest[skip + i + 1..];
+                }
+                None => {
+                    parts.push(rest);
+                    break;
+                }
+            }
+        }
+
+        match parts[..] {
+            [source, target] if !source.is_empty() && !target.is_empty() => {
+                Ok(Bind::new(source, target))
+            }
+            [source, target, options] if !source.is_empty() && !target.is_empty() => Ok(Bind {
+                options: Some(options.to_owned()),
+                ..Bind::new(source, target)
+            }),
+            _ => Err(Error::InvalidResponse(format!("invalid bind {:?}", bind))),
+        }
+    }
+}
+
+impl std::fmt::Display for Bind {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        write!(f, "{}:{}", self.source, self.target)?;
+        if let Some(ref options) = self.options {
+            write!(f, ":{}", options)?;
+        }
+        Ok(())
+    }
+}
+
+fn starts_with_drive(path: &str) -> bool {
+    matches!(
+        path.as_bytes(),
+        [letter, b':', b'\\' | b'/', ..] if letter.is_ascii_alphabetic()
+    )
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct ThrottleDevice {
@@ -1384,18 +3096,66 @@ pub struct LogConfig {
 #[serde(rename_all = "PascalCase")]
 pub struct Ulimit {
     pub name: String,
-    pub soft: u64,
-    pub hard: u64,
+    /// -1 for unlimited
+    pub soft: i64,
+    /// -1 for unlimited
+    pub hard: i64,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// A host device mapped into a container
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct DeviceMapping {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub path_on_host: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub path_in_container: Option<String>,
+    /// Any combination of `r`ead, `w`rite and `m`knod access
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub cgroup_permissions: Option<String>,
 }
 
+impl DeviceMapping {
+    /// Maps the host device at `path_on_host` to `path_in_container` with `rwm` permissions
+    pub fn new<H, C>(
+        path_on_host: H,
+        path_in_container: C,
+    ) -> Self
+    where
+        H: Into<String>,
+        C: Into<String>,
+    {
+        DeviceMapping {
+            path_on_host: Some(path_on_host.into()),
+            path_in_container: Some(path_in_container.into()),
+            cgroup_permissions: Some("rwm".to_owned()),
+        }
+    }
+
+    /// Restricts the container's access to the device
+    pub fn permissions<P>(
+        mut self,
+        permissions: P,
+    ) -> Self
+    where
+        P: Into<String>,
+    {
+        self.cgroup_permissions = Some(permissions.into());
+        self
+    }
+}
+
+impl From<HashMap<String, String>> for DeviceMapping {
+    /// Reads a mapping from its `PathOnHost`, `PathInContainer` and `CgroupPermissions` keys
+    fn from(mut device: HashMap<String, String>) -> Self {
+        DeviceMapping {
+            path_on_host: device.remove("PathOnHost"),
+            path_in_container: device.remove("PathInContainer"),
+            cgroup_permissions: device.remove("CgroupPermissions"),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct DeviceRequest {
@@ -1410,6 +3170,7 @@ pub struct DeviceRequest {
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Port {
+    #[serde(rename = "IP")]
     pub ip: Option<String>,
     pub private_port: u64,
     pub public_port: Option<u64>,
@@ -1417,13 +3178,61 @@ pub struct Port {
     pub typ: String,
 }
 
+impl Port {
+    fn is_ipv6(&self) -> bool {
+        self.ip.as_deref().is_some_and(|ip| ip.contains(':'))
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Stats {
+    #[cfg(feature = "chrono")]
+    pub read: DateTime<Utc>,
+    #[cfg(not(feature = "chrono"))]
     pub read: String,
+    /// When the previous sample was read. Zero valued (`0001-01-01T00:00:00Z`) on the first one.
+    #[cfg(feature = "chrono")]
+    pub preread: Option<DateTime<Utc>>,
+    #[cfg(not(feature = "chrono"))]
+    pub preread: Option<String>,
     pub networks: HashMap<String, NetworkStats>,
     pub memory_stats: MemoryStats,
     pub blkio_stats: BlkioStats,
     pub cpu_stats: CpuStats,
+    /// Sections of the sample not modeled above, such as `pids_stats` or ones added by newer
+    /// daemons, as sent. They are serialized back alongside the typed sections.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// A one line summary of memory, network and block I/O usage, like a row of `docker stats`
+impl std::fmt::Display for Stats {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        let (rx, tx) = self.networks.values().fold((0, 0), |(rx, tx), network| {
+            (rx + network.rx_bytes, tx + network.tx_bytes)
+        });
+        let (read, write) = self.blkio_stats.io_service_bytes_recursive.iter().fold(
+            (0, 0),
+            |(read, write), stat| match stat.op.to_ascii_lowercase().as_str() {
+                "read" => (read + stat.value, write),
+                "write" => (read, write + stat.value),
+                _ => (read, write),
+            },
+        );
+        write!(
+            f,
+            "mem {} / {}, net {} / {}, block {} / {}",
+            format_size(self.memory_stats.usage),
+            format_size(self.memory_stats.limit),
+            format_size(rx),
+            format_size(tx),
+            format_size(read),
+            format_size(write),
+        )
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -1440,6 +3249,8 @@ pub struct NetworkStats {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct MemoryStats {
+    /// Not reported on cgroup v2 hosts, where it is zero
+    #[serde(default)]
     pub max_usage: u64,
     pub usage: u64,
     pub failcnt: Option<u64>,
@@ -1447,8 +3258,18 @@ pub struct MemoryStats {
     pub stats: MemoryStat,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// Memory statistics of the container's cgroup. Hosts using cgroup v1 and v2 report different
+/// sets of them: fields the host doesn't report are zero, or `None` for those only cgroup v2
+/// reports.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct MemoryStat {
+    /// Anonymous memory, such as the heap, on cgroup v2
+    pub anon: Option<u64>,
+    /// File-backed memory, including the page cache, on cgroup v2
+    pub file: Option<u64>,
+    /// Memory allocated to kernel stacks on cgroup v2
+    pub kernel_stack: Option<u64>,
     pub total_pgmajfault: u64,
     pub cache: u64,
     pub mapped_file: u64,
@@ -1490,6 +3311,8 @@ pub struct CpuStats {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct CpuUsage {
+    /// Empty on cgroup v2 hosts
+    #[serde(default, deserialize_with = "crate::de::null_as_default")]
     pub percpu_usage: Vec<u64>,
     pub usage_in_usermode: u64,
     pub total_usage: u64,
@@ -1503,15 +3326,25 @@ pub struct ThrottlingData {
     pub throttled_time: u64,
 }
 
+/// Block I/O statistics. cgroup v2 hosts only report `io_service_bytes_recursive` and
+/// `io_serviced_recursive`, leaving the other lists empty.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BlkioStats {
+    #[serde(default, deserialize_with = "crate::de::null_as_default")]
     pub io_service_bytes_recursive: Vec<BlkioStat>,
+    #[serde(default, deserialize_with = "crate::de::null_as_default")]
     pub io_serviced_recursive: Vec<BlkioStat>,
+    #[serde(default, deserialize_with = "crate::de::null_as_default")]
     pub io_queue_recursive: Vec<BlkioStat>,
+    #[serde(default, deserialize_with = "crate::de::null_as_default")]
     pub io_service_time_recursive: Vec<BlkioStat>,
+    #[serde(default, deserialize_with = "crate::de::null_as_default")]
     pub io_wait_time_recursive: Vec<BlkioStat>,
+    #[serde(default, deserialize_with = "crate::de::null_as_default")]
     pub io_merged_recursive: Vec<BlkioStat>,
+    #[serde(default, deserialize_with = "crate::de::null_as_default")]
     pub io_time_recursive: Vec<BlkioStat>,
+    #[serde(default, deserialize_with = "crate::de::null_as_default")]
     pub sectors_recursive: Vec<BlkioStat>,
 }
 
@@ -1523,13 +3356,47 @@ pub struct BlkioStat {
     pub value: u64,
 }
 
+/// A path of the container's filesystem that differs from its image
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Change {
-    pub kind: u64,
+    pub kind: ChangeKind,
     pub path: String,
 }
 
+/// How a path of a container's filesystem differs from its image
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "u8", into = "u8")]
+pub enum ChangeKind {
+    Modified,
+    Added,
+    Deleted,
+    /// A kind this version of the crate doesn't know about
+    Unknown(u8),
+}
+
+impl From<u8> for ChangeKind {
+    fn from(kind: u8) -> Self {
+        match kind {
+            0 => ChangeKind::Modified,
+            1 => ChangeKind::Added,
+            2 => ChangeKind::Deleted,
+            other => ChangeKind::Unknown(other),
+        }
+    }
+}
+
+impl From<ChangeKind> for u8 {
+    fn from(kind: ChangeKind) -> Self {
+        match kind {
+            ChangeKind::Modified => 0,
+            ChangeKind::Added => 1,
+            ChangeKind::Deleted => 2,
+            ChangeKind::Unknown(other) => other,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Top {
@@ -1537,23 +3404,167 @@ pub struct Top {
     pub processes: Vec<Vec<String>>,
 }
 
+impl Top {
+    /// The processes as maps of column title, such as `PID` or `CMD`, to value
+    pub fn rows(&self) -> impl Iterator<Item = HashMap<&str, &str>> + '_ {
+        self.processes.iter().map(move |process| {
+            self.titles
+                .iter()
+                .map(String::as_str)
+                .zip(process.iter().map(String::as_str))
+                .collect()
+        })
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct ContainerCreateInfo {
     pub id: String,
-    pub warnings: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "crate::de::warnings")]
+    pub warnings: Vec<String>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
-pub struct Exit {
-    pub status_code: u64,
+pub struct ContainerUpdateInfo {
+    #[serde(default, deserialize_with = "crate::de::warnings")]
+    pub warnings: Vec<String>,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::container::ContainerFilter::{ExitCode, Label, LabelName, Status};
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CommitInfo {
+    /// ID of the image created
+    pub id: String,
+}
+
+/// What a container prune removed
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContainerPruneInfo {
+    /// Ids of the removed containers
+    pub containers_deleted: Option<Vec<String>>,
+    pub space_reclaimed: u64,
+}
+
+/// Header in which the daemon returns the stat of an archive path
+const PATH_STAT_HEADER: &str = "X-Docker-Container-Path-Stat";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PathStat {
+    pub name: String,
+    pub size: i64,
+    /// File mode and permission bits, as encoded by Go's `os.FileMode`
+    pub mode: u32,
+    #[cfg(feature = "chrono")]
+    pub mtime: DateTime<Utc>,
+    #[cfg(not(feature = "chrono"))]
+    pub mtime: String,
+    pub link_target: String,
+}
+
+impl PathStat {
+    /// Decodes the base64 encoded JSON the daemon sends in the path stat header
+    fn from_header(value: &[u8]) -> Result<PathStat> {
+        let json = base64::decode(value).map_err(|e| {
+            Error::InvalidResponse(format!("malformed {} header: {}", PATH_STAT_HEADER, e))
+        })?;
+
+        Ok(serde_json::from_slice(&json)?)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Exit {
+    pub status_code: i64,
+    /// Set when the daemon failed to wait for the container, or to run it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ExitError>,
+}
+
+impl Exit {
+    /// The message of the daemon's error, if it failed to wait for the container or to run it
+    pub fn error_message(&self) -> Option<&str> {
+        self.error.as_ref().map(|error| error.message.as_str())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ExitError {
+    pub message: String,
+}
+
+/// Whether `name` matches the pattern docker allows for container names,
+/// `/?[a-zA-Z0-9][a-zA-Z0-9_.-]+`
+fn valid_container_name(name: &str) -> bool {
+    let name = name.strip_prefix('/').unwrap_or(name);
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) if first.is_ascii_alphanumeric() => {}
+        _ => return false,
+    }
+    name.len() > 1 && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-')
+}
+
+/// Extracts the id of the container holding a name from the daemon's conflict message,
+/// `... is already in use by container "<id>". ...`
+fn conflicting_container_id(message: &str) -> Option<String> {
+    let start = message.find("in use by container \"")? + "in use by container \"".len();
+    let len = message[start..].find('"')?;
+    Some(message[start..start + len].to_owned())
+}
+
+/// Parses `docker run --env-file` style contents into `(name, value)` pairs
+fn parse_env_file(contents: &str) -> Result<Vec<(String, String)>> {
+    let mut vars = Vec::new();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim_start();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (name, value) = match line.split_once('=') {
+            Some((name, value)) => (name, Some(unquote(value))),
+            None => (line.trim_end(), None),
+        };
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(Error::IO(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid variable name on line {} of env file", number + 1),
+            )));
+        }
+        match value {
+            Some(value) => vars.push((name.to_owned(), value.to_owned())),
+            None => {
+                if let Ok(value) = std::env::var(name) {
+                    vars.push((name.to_owned(), value));
+                }
+            }
+        }
+    }
+    Ok(vars)
+}
+
+/// Strips one pair of matching surrounding quotes
+fn unquote(value: &str) -> &str {
+    for quote in &['"', '\''] {
+        if value.len() >= 2 && value.starts_with(*quote) && value.ends_with(*quote) {
+            return &value[1..value.len() - 1];
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        container::ContainerFilter::{ExitCode, Label, LabelName, Status},
+        mock::{MockDaemon, Route},
+    };
 
     #[test]
     fn container_options_simple() {
@@ -1566,6 +3577,98 @@ mod tests {
         );
     }
 
+    #[test]
+    fn stats_throttle() {
+        let mut throttle = StatsThrottle::new(Duration::from_secs(5));
+        let start = Instant::now();
+
+        assert!(throttle.admit("a", start));
+        assert!(throttle.admit("b", start));
+        assert!(!throttle.admit("a", start + Duration::from_secs(1)));
+        assert!(throttle.admit("a", start + Duration::from_secs(5)));
+
+        throttle.forget("b");
+        assert!(throttle.admit("b", start + Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn stats_cgroup_v2() {
+        let stats: Stats = serde_json::from_str(
+            r#"{
+                "read": "2024-01-01T00:00:01Z",
+                "preread": "2024-01-01T00:00:00Z",
+                "pids_stats": {"current": 3},
+                "networks": {},
+                "memory_stats": {
+                    "usage": 4096,
+                    "limit": 8192,
+                    "stats": {"anon": 1024, "file": 2048, "kernel_stack": 16, "inactive_file": 512}
+                },
+                "blkio_stats": {
+                    "io_service_bytes_recursive": [{"major": 8, "minor": 0, "op": "read", "value": 1}],
+                    "io_serviced_recursive": null,
+                    "io_queue_recursive": null,
+                    "io_service_time_recursive": null,
+                    "io_wait_time_recursive": null,
+                    "io_merged_recursive": null,
+                    "io_time_recursive": null,
+                    "sectors_recursive": null
+                },
+                "cpu_stats": {
+                    "cpu_usage": {"total_usage": 10, "usage_in_kernelmode": 4, "usage_in_usermode": 6},
+                    "system_cpu_usage": 100,
+                    "throttling_data": {"periods": 0, "throttled_periods": 0, "throttled_time": 0}
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let memory = &stats.memory_stats.stats;
+        assert_eq!(
+            (memory.anon, memory.file, memory.kernel_stack),
+            (Some(1024), Some(2048), Some(16))
+        );
+        assert_eq!((memory.inactive_file, memory.rss), (512, 0));
+        assert!(stats.blkio_stats.io_serviced_recursive.is_empty());
+        assert!(stats.cpu_stats.cpu_usage.percpu_usage.is_empty());
+        assert_eq!(stats.extra["pids_stats"], json!({"current": 3}));
+
+        let echoed = serde_json::to_value(&stats).unwrap();
+        assert_eq!(echoed["pids_stats"], json!({"current": 3}));
+    }
+
+    #[test]
+    fn container_options_from_image_config() {
+        let config: ContainerConfig = serde_json::from_value(json!({
+            "AttachStderr": false,
+            "AttachStdin": false,
+            "AttachStdout": false,
+            "Cmd": ["serve"],
+            "Domainname": "",
+            "Entrypoint": ["/app"],
+            "Env": ["PATH=/bin"],
+            "ExposedPorts": {"80/tcp": {}},
+            "Hostname": "",
+            "Image": "",
+            "OpenStdin": false,
+            "StdinOnce": false,
+            "Tty": false,
+            "User": "",
+            "Volumes": {"/data": {}},
+            "WorkingDir": "/srv"
+        }))
+        .unwrap();
+
+        let options = ContainerOptionsBuilder::from_image_config("test_image", &config)
+            .cmd(vec!["serve", "--debug"])
+            .build();
+
+        assert_eq!(
+            r#"{"Cmd":["serve","--debug"],"Entrypoint":["/app"],"Env":["PATH=/bin"],"ExposedPorts":{"80/tcp":{}},"HostConfig":{},"Image":"test_image","Volumes":{"/data":{}},"WorkingDir":"/srv"}"#,
+            options.serialize().unwrap()
+        );
+    }
+
     #[test]
     fn container_options_env() {
         let options = ContainerOptionsBuilder::new("test_image")
@@ -1593,6 +3696,58 @@ mod tests {
         );
     }
 
+    #[test]
+    fn container_options_env_map() {
+        let mut vars = std::collections::BTreeMap::new();
+        vars.insert("FOO", "a=b c");
+        vars.insert("BAR", "");
+
+        let options = ContainerOptionsBuilder::new("test_image")
+            .env(vec!["FOO=old", "KEEP=1"])
+            .env_map(vars)
+            .build();
+
+        assert_eq!(
+            r#"{"Env":["KEEP=1","BAR=","FOO=a=b c"],"HostConfig":{},"Image":"test_image"}"#,
+            options.serialize().unwrap()
+        );
+    }
+
+    #[test]
+    fn env_file_parsing() {
+        let vars = parse_env_file(
+            "# comment\n\nPLAIN=value\nQUOTED=\"two words\"\nSINGLE='x'\nEQ=a=b\nSHIPLIFT_UNSET_VAR\n",
+        )
+        .unwrap();
+        assert_eq!(
+            vars,
+            vec![
+                ("PLAIN".to_owned(), "value".to_owned()),
+                ("QUOTED".to_owned(), "two words".to_owned()),
+                ("SINGLE".to_owned(), "x".to_owned()),
+                ("EQ".to_owned(), "a=b".to_owned()),
+            ]
+        );
+
+        assert!(parse_env_file("BAD NAME=1").is_err());
+    }
+
+    #[test]
+    fn container_options_devices() {
+        let options = ContainerOptionsBuilder::new("test_image")
+            .devices(vec![
+                DeviceMapping::new("/dev/kvm", "/dev/kvm"),
+                DeviceMapping::new("/dev/ttyUSB0", "/dev/ttyS0").permissions("rw"),
+            ])
+            .device_cgroup_rules(vec!["c 188:* rwm"])
+            .build();
+
+        assert_eq!(
+            r#"{"HostConfig":{"DeviceCgroupRules":["c 188:* rwm"],"Devices":[{"CgroupPermissions":"rwm","PathInContainer":"/dev/kvm","PathOnHost":"/dev/kvm"},{"CgroupPermissions":"rw","PathInContainer":"/dev/ttyS0","PathOnHost":"/dev/ttyUSB0"}]},"Image":"test_image"}"#,
+            options.serialize().unwrap()
+        );
+    }
+
     #[test]
     fn container_options_user() {
         let options = ContainerOptionsBuilder::new("test_image")
@@ -1619,6 +3774,52 @@ mod tests {
         );
     }
 
+    #[test]
+    fn exit_reason_classify() {
+        assert_eq!(ExitReason::classify(0, false, None), ExitReason::Success);
+        assert_eq!(ExitReason::classify(137, true, None), ExitReason::OomKilled);
+        assert_eq!(
+            ExitReason::classify(137, false, None),
+            ExitReason::Signal(9)
+        );
+        assert_eq!(
+            ExitReason::classify(1, false, Some("")),
+            ExitReason::Failure(1)
+        );
+        assert_eq!(
+            ExitReason::classify(127, false, Some("exec: \"nope\": not found")),
+            ExitReason::RuntimeError("exec: \"nope\": not found".into())
+        );
+    }
+
+    #[test]
+    fn container_options_idempotency_key() {
+        let mut labels = HashMap::new();
+        labels.insert("env", "prod");
+        let options = ContainerOptionsBuilder::new("test_image")
+            .idempotency_key("0123")
+            .labels(&labels)
+            .build();
+
+        assert_eq!(
+            r#"{"HostConfig":{},"Image":"test_image","Labels":{"com.github.shiplift.idempotency-key":"0123","env":"prod"}}"#,
+            options.serialize().unwrap()
+        );
+    }
+
+    #[test]
+    fn container_options_network_addresses() {
+        let options = ContainerOptionsBuilder::new("test_image")
+            .network_mode("dual")
+            .network_addresses("dual", None, Some("2001:db8::10"))
+            .build();
+
+        assert_eq!(
+            options.body()["NetworkingConfig"],
+            json!({"EndpointsConfig": {"dual": {"IPAMConfig": {"IPv6Address": "2001:db8::10"}}}})
+        );
+    }
+
     #[test]
     fn container_options_expose() {
         let options = ContainerOptionsBuilder::new("test_image")
@@ -1707,6 +3908,189 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_disk_quota_sizes() {
+        assert_eq!(parse_size("10G"), Some(10 << 30));
+        assert_eq!(parse_size("512mb"), Some(512 << 20));
+        assert_eq!(parse_size("1.5KiB"), Some(1536));
+        assert_eq!(parse_size("4096"), Some(4096));
+        assert_eq!(parse_size("lots"), None);
+        assert_eq!(parse_size("-1g"), None);
+    }
+
+    #[test]
+    fn bind_parses_windows_paths() {
+        let bind: Bind = r"C:\data:C:\app\data:ro".parse().unwrap();
+        assert_eq!(bind, Bind::new(r"C:\data", r"C:\app\data").read_only());
+        assert_eq!(bind.to_string(), r"C:\data:C:\app\data:ro");
+
+        let bind: Bind = "/srv/data:/data".parse().unwrap();
+        assert_eq!(bind, Bind::new("/srv/data", "/data"));
+        let bind: Bind = r"cache:c:\cache".parse().unwrap();
+        assert_eq!(bind, Bind::new("cache", r"c:\cache"));
+
+        assert!("/srv/data".parse::<Bind>().is_err());
+        assert!("a:b:c:d".parse::<Bind>().is_err());
+    }
+
+    #[test]
+    fn isolation_from_linux_and_windows_daemons() {
+        let isolation: Vec<Isolation> =
+            serde_json::from_str(r#"["", "process", "hyperv"]"#).unwrap();
+        assert_eq!(
+            isolation,
+            vec![Isolation::Default, Isolation::Process, Isolation::Hyperv]
+        );
+        let options = ContainerOptionsBuilder::new("test_image")
+            .isolation(Isolation::Hyperv)
+            .build();
+        assert_eq!(
+            r#"{"HostConfig":{"Isolation":"hyperv"},"Image":"test_image"}"#,
+            options.serialize().unwrap()
+        );
+    }
+
+    #[test]
+    fn container_options_spec_round_trip() {
+        let options = ContainerOptionsBuilder::new("test_image")
+            .name("web")
+            .cgroupns_mode("private")
+            .build();
+
+        let spec = serde_json::to_string(&options).unwrap();
+        assert_eq!(
+            r#"{"name":"web","body":{"HostConfig":{"CgroupnsMode":"private"},"Image":"test_image"}}"#,
+            spec
+        );
+        let parsed: ContainerOptions = serde_json::from_str(&spec).unwrap();
+        assert_eq!(parsed, options);
+        assert_eq!(parsed.features(), vec![Feature::CgroupnsMode]);
+
+        let written = ContainerOptions::from_body(
+            None,
+            json!({"Image": "test_image"}).as_object().unwrap().clone(),
+        );
+        assert_eq!(r#"{"Image":"test_image"}"#, written.serialize().unwrap());
+
+        // a body field called name is not taken for the container's
+        let parsed: ContainerOptions =
+            serde_json::from_str(r#"{"body":{"Image":"test_image","name":"x"}}"#).unwrap();
+        assert_eq!(parsed.name, None);
+        assert_eq!(parsed.body()["name"], "x");
+    }
+
+    #[test]
+    fn wait_conditions_and_exit() {
+        assert_eq!(WaitCondition::default().to_string(), "not-running");
+        assert_eq!(WaitCondition::NextExit.to_string(), "next-exit");
+        assert_eq!(WaitCondition::Removed.to_string(), "removed");
+        assert_eq!(
+            WaitCondition::default().wait_path("web"),
+            "/containers/web/wait"
+        );
+        assert_eq!(
+            WaitCondition::NextExit.wait_path("web"),
+            "/containers/web/wait?condition=next-exit"
+        );
+        assert!(WaitCondition::default().features().is_empty());
+        assert_eq!(
+            WaitCondition::Removed.features(),
+            vec![Feature::WaitCondition]
+        );
+
+        let exit: Exit = serde_json::from_value(json!({
+            "StatusCode": 127,
+            "Error": {"Message": "exec: \"serve\": executable file not found in $PATH"}
+        }))
+        .unwrap();
+        assert_eq!(exit.status_code, 127);
+        assert_eq!(
+            exit.error_message(),
+            Some(r#"exec: "serve": executable file not found in $PATH"#)
+        );
+        let exit: Exit = serde_json::from_value(json!({"StatusCode": 0})).unwrap();
+        assert_eq!(exit.error_message(), None);
+    }
+
+    #[test]
+    fn state_change_of_not_modified() {
+        assert_eq!(StateChange::of(Ok("")).unwrap(), StateChange::Changed);
+        let not_modified: Result<String> = Err(Error::Fault {
+            code: StatusCode::NOT_MODIFIED,
+            message: "Not Modified".into(),
+        });
+        assert_eq!(
+            StateChange::of(not_modified).unwrap(),
+            StateChange::AlreadyInState
+        );
+        let conflict: Result<String> = Err(Error::Fault {
+            code: StatusCode::CONFLICT,
+            message: "is paused".into(),
+        });
+        assert!(StateChange::of(conflict).is_err());
+    }
+
+    #[test]
+    fn change_kinds() {
+        let changes: Vec<Change> = serde_json::from_value(json!([
+            {"Path": "/etc", "Kind": 0},
+            {"Path": "/etc/app.conf", "Kind": 1},
+            {"Path": "/tmp/cache", "Kind": 2},
+            {"Path": "/srv", "Kind": 7}
+        ]))
+        .unwrap();
+
+        let kinds: Vec<ChangeKind> = changes.iter().map(|change| change.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ChangeKind::Modified,
+                ChangeKind::Added,
+                ChangeKind::Deleted,
+                ChangeKind::Unknown(7)
+            ]
+        );
+        assert_eq!(
+            serde_json::to_value(&changes[1]).unwrap(),
+            json!({"Kind": 1, "Path": "/etc/app.conf"})
+        );
+    }
+
+    #[test]
+    fn top_rows() {
+        let top: Top = serde_json::from_value(json!({
+            "Titles": ["UID", "PID", "CMD"],
+            "Processes": [["root", "1", "nginx"], ["www", "7", "nginx: worker"]]
+        }))
+        .unwrap();
+
+        let rows: Vec<_> = top.rows().collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1]["PID"], "7");
+        assert_eq!(rows[1]["CMD"], "nginx: worker");
+    }
+
+    #[test]
+    fn container_prune_options_filters() {
+        let options = ContainerPruneOptions::builder()
+            .filter(vec![
+                ContainerPruneFilter::Until("24h".to_string()),
+                ContainerPruneFilter::NoLabel("keep".to_string(), "true".to_string()),
+            ])
+            .build();
+
+        let form = form_urlencoded::Serializer::new(String::new())
+            .append_pair("filters", r#"{"label!":["keep=true"],"until":["24h"]}"#)
+            .finish();
+
+        assert_eq!(form, options.serialize().unwrap());
+
+        let pruned: ContainerPruneInfo =
+            serde_json::from_str(r#"{"ContainersDeleted":["abc"],"SpaceReclaimed":42}"#).unwrap();
+        assert_eq!(pruned.containers_deleted, Some(vec!["abc".to_string()]));
+        assert_eq!(pruned.space_reclaimed, 42);
+    }
+
     #[test]
     fn container_list_options_multiple_labels() {
         let options = ContainerListOptions::builder()
@@ -1770,11 +4154,245 @@ mod tests {
         assert!(serialized.contains("%22exited%22%3A%5B%220%22%5D"));
     }
 
+    #[test]
+    fn path_stat_from_header() {
+        let header = base64::encode(
+            r#"{"name":"hosts","size":174,"mode":420,"mtime":"2021-06-01T10:00:00.123456789Z","linkTarget":""}"#,
+        );
+
+        let stat = PathStat::from_header(header.as_bytes()).unwrap();
+
+        assert_eq!(stat.name, "hosts");
+        assert_eq!(stat.size, 174);
+        assert_eq!(stat.mode, 0o644);
+        assert_eq!(stat.link_target, "");
+    }
+
+    #[test]
+    fn since_nanos_format() {
+        assert_eq!(
+            since_nanos(1_680_674_828_000_000_042),
+            "1680674828.000000042"
+        );
+    }
+
+    #[test]
+    fn container_info_host_port() {
+        let info: ContainerInfo = serde_json::from_str(
+            r#"{"Id":"8f2c1e9d4b7a","Names":["/web"],"Image":"nginx","ImageID":"sha256:61e0","Command":"nginx","Created":1700000000,"Ports":[{"IP":"::","PrivatePort":80,"PublicPort":49154,"Type":"tcp"},{"IP":"0.0.0.0","PrivatePort":80,"PublicPort":49153,"Type":"tcp"},{"PrivatePort":443,"Type":"tcp"},{"IP":"0.0.0.0","PrivatePort":53,"PublicPort":5353,"Type":"udp"}],"Labels":{},"State":"running","Status":"Up 2 minutes"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(info.host_port(80, "tcp"), Some(49153));
+        assert_eq!(info.host_port(53, "udp"), Some(5353));
+        assert_eq!(info.host_port(53, "tcp"), None);
+        assert_eq!(info.host_port(443, "tcp"), None);
+    }
+
+    #[test]
+    fn container_name_validation() {
+        assert!(valid_container_name("web-1"));
+        assert!(valid_container_name("/db_primary.v2"));
+        assert!(!valid_container_name("a"));
+        assert!(!valid_container_name("-web"));
+        assert!(!valid_container_name("web app"));
+        assert!(!valid_container_name("wéb"));
+        assert!(!valid_container_name(""));
+    }
+
+    #[test]
+    fn container_name_conflict_id() {
+        assert_eq!(
+            conflicting_container_id(
+                r#"Conflict. The container name "/web" is already in use by container "8f2c1e9d4b7a". You have to remove (or rename) that container to be able to reuse that name."#
+            ),
+            Some("8f2c1e9d4b7a".to_owned())
+        );
+        assert_eq!(conflicting_container_id("Conflict. Something else"), None);
+    }
+
+    #[test]
+    fn state_negative_exit_code() {
+        // STATUS_CONTROL_C_EXIT, as reported by a windows container
+        let state: State = serde_json::from_str(
+            r#"{"Error":"","ExitCode":-1073741510,"FinishedAt":"2023-04-05T06:07:08Z","OOMKilled":false,"Paused":false,"Pid":0,"Restarting":false,"Running":false,"StartedAt":"2023-04-05T06:00:00Z","Status":"exited","Config":{}}"#,
+        )
+        .unwrap();
+        assert_eq!(state.exit_code, -1_073_741_510);
+    }
+
+    /// Details of a container in the given `status`, running with a `tty` or not
+    fn container_details(
+        status: &str,
+        tty: bool,
+    ) -> String {
+        format!(
+            r#"{{
+            "Id": "4fa6e0f0c678", "Created": "2023-04-05T06:00:00Z", "Path": "nginx",
+            "Args": [], "Image": "sha256:a1b2", "ResolvConfPath": "", "HostnamePath": "",
+            "HostsPath": "", "LogPath": "", "Name": "/web", "RestartCount": 0,
+            "Driver": "overlay2", "Platform": "linux", "MountLabel": "", "ProcessLabel": "",
+            "AppArmorProfile": "", "Mounts": [],
+            "State": {{
+                "Error": "", "ExitCode": 0, "FinishedAt": "0001-01-01T00:00:00Z",
+                "OOMKilled": false, "Paused": false, "Pid": 42, "Restarting": false,
+                "Running": {running}, "StartedAt": "2023-04-05T06:00:01Z", "Status": "{status}"
+            }},
+            "GraphDriver": {{ "Name": "overlay2", "Data": {{}} }},
+            "Config": {{
+                "AttachStderr": false, "AttachStdin": false, "AttachStdout": false,
+                "Cmd": ["nginx"], "Domainname": "", "Entrypoint": null, "Env": ["A=1"],
+                "ExposedPorts": {{ "80/tcp": {{}} }}, "Hostname": "4fa6e0f0c678",
+                "Image": "nginx:latest", "Labels": {{ "app": "web" }}, "OnBuild": null,
+                "OpenStdin": false, "StdinOnce": false, "Tty": {tty}, "User": "",
+                "Volumes": null, "WorkingDir": ""
+            }},
+            "HostConfig": {{
+                "Binds": ["/srv:/usr/share/nginx/html:ro"], "ContainerIDFile": "",
+                "LogConfig": {{ "Type": "json-file", "Config": {{}} }}, "NetworkMode": "bridge",
+                "PortBindings": {{ "80/tcp": [{{ "HostIp": "", "HostPort": "8080" }}] }},
+                "RestartPolicy": {{ "Name": "always", "MaximumRetryCount": 0 }},
+                "AutoRemove": false, "VolumeDriver": "", "IpcMode": "private", "Cgroup": "",
+                "OomScoreAdj": 0, "Privileged": false, "PublishAllPorts": false,
+                "UTSMode": "", "UsernsMode": "", "ShmSize": 67108864, "Runtime": "runc",
+                "Isolation": "", "CpuCount": 0, "CpuPercent": 0, "IOMaximumIOps": 0,
+                "Memory": 0
+            }},
+            "NetworkSettings": {{
+                "Bridge": "", "Gateway": "172.17.0.1", "IPAddress": "172.17.0.2",
+                "IPPrefixLen": 16, "MacAddress": "02:42:ac:11:00:02", "Ports": null,
+                "Networks": {{}}
+            }}
+        }}"#,
+            status = status,
+            running = status == "running",
+            tty = tty,
+        )
+    }
+
+    #[test]
+    fn container_options_from_details() {
+        let details: ContainerDetails =
+            serde_json::from_str(&container_details("running", false)).unwrap();
+
+        let options = ContainerOptionsBuilder::from_details(&details)
+            .restart_policy("unless-stopped", 0)
+            .build();
+        let body = options.body();
+        assert_eq!(options.name.as_deref(), Some("web"));
+        assert_eq!(body["Image"], "nginx:latest");
+        assert_eq!(body["Labels"], json!({ "app": "web" }));
+        assert_eq!(body["ExposedPorts"], json!({ "80/tcp": {} }));
+        assert!(body.get("Hostname").is_none());
+        let host_config = &body["HostConfig"];
+        assert_eq!(
+            host_config["Binds"],
+            json!(["/srv:/usr/share/nginx/html:ro"])
+        );
+        assert_eq!(host_config["PortBindings"]["80/tcp"][0]["HostPort"], "8080");
+        assert_eq!(host_config["LogConfig"]["Type"], "json-file");
+        assert_eq!(host_config["RestartPolicy"]["Name"], "unless-stopped");
+        assert_eq!(host_config["ShmSize"], 67108864);
+    }
+
+    #[test]
+    fn state_is_stopped() {
+        let state = |status: &str, paused: bool, restarting: bool| -> State {
+            serde_json::from_value(json!({
+                "Error": "", "ExitCode": 0, "FinishedAt": "2023-04-05T06:07:08Z",
+                "OOMKilled": false, "Paused": paused, "Pid": 0, "Restarting": restarting,
+                "Running": status != "created" && status != "exited",
+                "StartedAt": "2023-04-05T06:00:00Z", "Status": status
+            }))
+            .unwrap()
+        };
+        assert!(state("created", false, false).is_stopped());
+        assert!(state("exited", false, false).is_stopped());
+        assert!(!state("running", false, false).is_stopped());
+        assert!(!state("paused", true, false).is_stopped());
+        assert!(!state("restarting", false, true).is_stopped());
+    }
+
+    #[test]
+    fn state_display() {
+        let state = |status: &str, exit_code: i64, oom_killed: bool| -> State {
+            serde_json::from_value(json!({
+                "Error": "", "ExitCode": exit_code, "FinishedAt": "2023-04-05T06:07:08Z",
+                "OOMKilled": oom_killed, "Paused": false, "Pid": 0, "Restarting": false,
+                "Running": status == "running", "StartedAt": "2023-04-05T06:00:00Z",
+                "Status": status
+            }))
+            .unwrap()
+        };
+        assert_eq!(state("running", 0, false).to_string(), "running");
+        assert_eq!(state("exited", 0, false).to_string(), "exited (0)");
+        assert_eq!(
+            state("exited", 143, false).to_string(),
+            "exited (signal 15)"
+        );
+        assert_eq!(
+            state("exited", 137, true).to_string(),
+            "exited (OOM killed)"
+        );
+    }
+
+    #[test]
+    fn format_sizes() {
+        assert_eq!(format_size(512), "512B");
+        assert_eq!(format_size(1536), "1.50KiB");
+        assert_eq!(format_size(3 << 30), "3.00GiB");
+    }
+
+    #[test]
+    fn ulimit_unlimited() {
+        let ulimit: Ulimit =
+            serde_json::from_str(r#"{"Name":"memlock","Soft":-1,"Hard":-1}"#).unwrap();
+        assert_eq!((ulimit.soft, ulimit.hard), (-1, -1));
+    }
+
+    #[test]
+    fn update_options() {
+        let options = UpdateOptions::builder()
+            .memory(256 * 1024 * 1024)
+            .cpus(1.5)
+            .cpuset_cpus("0-1")
+            .restart_policy("on-failure", 3)
+            .build();
+
+        assert_eq!(
+            serde_json::from_str::<Value>(&options.serialize().unwrap()).unwrap(),
+            json!({
+                "Memory": 268435456,
+                "NanoCpus": 1500000000,
+                "CpusetCpus": "0-1",
+                "RestartPolicy": {"Name": "on-failure", "MaximumRetryCount": 3}
+            })
+        );
+    }
+
+    #[test]
+    fn commit_options() {
+        let options = CommitOptions::builder()
+            .repo("app")
+            .tag("snapshot")
+            .pause(false)
+            .changes(vec!["ENV DEBUG=true", "EXPOSE 8080"])
+            .build();
+
+        let serialized = options.serialize("f00d");
+
+        assert!(serialized.starts_with("container=f00d&"));
+        assert!(serialized.contains("repo=app"));
+        assert!(serialized.contains("tag=snapshot"));
+        assert!(serialized.contains("pause=false"));
+        assert!(serialized.ends_with("changes=ENV+DEBUG%3Dtrue&changes=EXPOSE+8080"));
+    }
+
     #[cfg(feature = "chrono")]
     #[test]
     fn logs_options() {
-        let timestamp = chrono::NaiveDateTime::from_timestamp(2_147_483_647, 0);
-        let since = chrono::DateTime::<chrono::Utc>::from_utc(timestamp, chrono::Utc);
+        use chrono::TimeZone;
+        let since = chrono::Utc.timestamp_opt(2_147_483_647, 0).unwrap();
 
         let options = LogsOptionsBuilder::default()
             .follow(true)
@@ -1816,4 +4434,108 @@ mod tests {
         assert!(serialized.contains("tail=all"));
         assert!(serialized.contains("since=2147483647"));
     }
+
+    #[tokio::test]
+    async fn wait_with_output_reads_tty_logs_raw() {
+        let details = container_details("running", true);
+        let daemon = MockDaemon::start(vec![
+            Route::new("GET /containers/web/json", 200, &details),
+            Route::new("POST /containers/web/wait", 200, r#"{"StatusCode":0}"#),
+            Route::new("GET /containers/web/logs", 200, "hello\n"),
+        ]);
+
+        let output = Container::new(daemon.docker(), "web")
+            .wait_with_output()
+            .await
+            .unwrap();
+
+        assert_eq!(output.exit_code, 0);
+        assert_eq!(output.stdout, b"hello\n");
+        assert!(output.stderr.is_empty());
+    }
+
+    /// A frame of multiplexed stdout as the daemon sends logs, for a line logged at `second`
+    fn log_frame(
+        second: u32,
+        line: &str,
+    ) -> String {
+        let payload = format!("2023-04-05T06:07:{:02}.000000001Z {}\n", second, line);
+        let mut frame = String::from("\u{1}\0\0\0\0\0\0");
+        frame.push(char::from(payload.len() as u8));
+        frame + &payload
+    }
+
+    #[tokio::test]
+    async fn tail_logs_keeps_lines_sharing_a_timestamp() {
+        let first = log_frame(8, "a") + &log_frame(8, "b");
+        let again = first.clone() + &log_frame(8, "c") + &log_frame(9, "d");
+        let (running, exited) = (
+            container_details("running", false),
+            container_details("exited", false),
+        );
+        let daemon = MockDaemon::start(vec![
+            Route::new("GET /containers/web/logs", 200, &first).once(),
+            Route::new("GET /containers/web/json", 200, &running).once(),
+            Route::new("GET /containers/web/logs", 200, &again),
+            Route::new("GET /containers/web/json", 200, &exited),
+        ]);
+
+        let lines: Vec<_> = Container::new(daemon.docker(), "web")
+            .tail_logs(&LogsOptions::builder().stdout(true).build())
+            .map(|chunk| chunk.unwrap().to_vec())
+            .collect()
+            .await;
+
+        assert_eq!(
+            lines,
+            vec![
+                b"a\n".to_vec(),
+                b"b\n".to_vec(),
+                b"c\n".to_vec(),
+                b"d\n".to_vec()
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_removes_the_container_when_it_fails() {
+        let daemon = MockDaemon::start(vec![
+            Route::new(
+                "POST /containers/create",
+                201,
+                r#"{"Id":"web","Warnings":[]}"#,
+            ),
+            Route::new(
+                "GET /containers/web/json",
+                500,
+                r#"{"message":"inspect failed"}"#,
+            ),
+            Route::new("DELETE /containers/web", 204, ""),
+        ]);
+
+        let opts = ContainerOptions::builder("busybox").build();
+        assert!(Containers::new(daemon.docker()).run(&opts).await.is_err());
+        assert!(daemon
+            .requests()
+            .iter()
+            .any(|request| request.starts_with("DELETE /containers/web?force=true ")));
+    }
+
+    #[test]
+    fn container_details_device_cgroup_rules() {
+        let mut details: Value =
+            serde_json::from_str(&container_details("running", false)).unwrap();
+        details["HostConfig"]["DeviceCgroupRules"] = json!(["c 188:* rwm"]);
+        let details: ContainerDetails = serde_json::from_value(details).unwrap();
+        assert_eq!(
+            details.host_config.device_cgroup_rules,
+            Some(vec!["c 188:* rwm".to_owned()])
+        );
+
+        let options = ContainerOptionsBuilder::from_details(&details).build();
+        assert_eq!(
+            options.body()["HostConfig"]["DeviceCgroupRules"],
+            json!(["c 188:* rwm"])
+        );
+    }
 }
@@ -1,5 +1,5 @@
-use chrono::{DateTime, Utc};
-use serde::Deserialize;
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{de, Deserialize};
 
 pub(crate) fn datetime_from_unix_timestamp<'de, D>(
     deserializer: D
@@ -7,8 +7,10 @@ pub(crate) fn datetime_from_unix_timestamp<'de, D>(
 where
     D: serde::Deserializer<'de>,
 {
-    let timestamp = chrono::NaiveDateTime::from_timestamp(i64::deserialize(deserializer)?, 0);
-    Ok(DateTime::<Utc>::from_utc(timestamp, Utc))
+    let timestamp = i64::deserialize(deserializer)?;
+    Utc.timestamp_opt(timestamp, 0)
+        .single()
+        .ok_or_else(|| de::Error::custom(format!("invalid unix timestamp: {}", timestamp)))
 }
 
 pub(crate) fn datetime_from_nano_timestamp<'de, D>(
@@ -18,9 +20,10 @@ where
     D: serde::Deserializer<'de>,
 {
     let timestamp_nano = u64::deserialize(deserializer)?;
-    let timestamp = chrono::NaiveDateTime::from_timestamp(
+    Utc.timestamp_opt(
         (timestamp_nano / 1_000_000_000) as i64,
         (timestamp_nano % 1_000_000_000) as u32,
-    );
-    Ok(DateTime::<Utc>::from_utc(timestamp, Utc))
+    )
+    .single()
+    .ok_or_else(|| de::Error::custom(format!("invalid nano timestamp: {}", timestamp_nano)))
 }
@@ -0,0 +1,250 @@
+//! Deserialization helpers shared between the typed representations
+
+use std::{collections::VecDeque, marker::PhantomData};
+
+use futures_util::stream::{self, Stream, StreamExt};
+use hyper::body::Bytes;
+use serde::{de::DeserializeOwned, Deserialize, Deserializer};
+
+use crate::errors::{self, Error};
+
+/// Deserializes daemon warnings into a list, whether they were sent as a single (possibly empty)
+/// `Warning` string, a `Warnings` list or `null`.
+pub(crate) fn warnings<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Warnings {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match Option::<Warnings>::deserialize(deserializer)? {
+        Some(Warnings::One(warning)) if !warning.is_empty() => vec![warning],
+        Some(Warnings::Many(warnings)) => warnings,
+        _ => Vec::new(),
+    })
+}
+
+/// Deserializes `null` as the default of the type, for fields some daemons or hosts send as
+/// `null` rather than leaving out or sending empty
+pub(crate) fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// Splits a JSON array arriving in chunks into its elements, deserializing each as soon as it is
+/// complete, so that a large array is never held in memory at once
+struct ArrayElements<T> {
+    /// The bytes of the element being read
+    element: Vec<u8>,
+    opened: bool,
+    closed: bool,
+    /// How deep into objects and arrays the element being read is
+    nesting: usize,
+    in_string: bool,
+    escaped: bool,
+    elements: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned> ArrayElements<T> {
+    fn new() -> Self {
+        ArrayElements {
+            element: Vec::new(),
+            opened: false,
+            closed: false,
+            nesting: 0,
+            in_string: false,
+            escaped: false,
+            elements: PhantomData,
+        }
+    }
+
+    /// Reads a chunk, pushing the elements it completes to `out`
+    fn read(
+        &mut self,
+        chunk: &[u8],
+        out: &mut VecDeque<errors::Result<T>>,
+    ) -> errors::Result<()> {
+        for &byte in chunk {
+            if self.closed {
+                if !byte.is_ascii_whitespace() {
+                    return Err(unexpected(byte));
+                }
+                continue;
+            }
+            if !self.opened {
+                match byte {
+                    b'[' => self.opened = true,
+                    _ if byte.is_ascii_whitespace() => (),
+                    _ => return Err(unexpected(byte)),
+                }
+                continue;
+            }
+            if self.in_string {
+                self.element.push(byte);
+                match byte {
+                    _ if self.escaped => self.escaped = false,
+                    b'\\' => self.escaped = true,
+                    b'"' => {
+                        self.in_string = false;
+                        if self.nesting == 0 {
+                            self.complete(out);
+                        }
+                    }
+                    _ => (),
+                }
+                continue;
+            }
+            match byte {
+                b'"' => {
+                    self.element.push(byte);
+                    self.in_string = true;
+                }
+                b'{' | b'[' => {
+                    self.element.push(byte);
+                    self.nesting += 1;
+                }
+                b']' if self.nesting == 0 => {
+                    self.complete(out);
+                    self.closed = true;
+                }
+                b'}' | b']' => {
+                    if self.nesting == 0 {
+                        return Err(unexpected(byte));
+                    }
+                    self.element.push(byte);
+                    self.nesting -= 1;
+                    if self.nesting == 0 {
+                        self.complete(out);
+                    }
+                }
+                b',' if self.nesting == 0 => self.complete(out),
+                _ if byte.is_ascii_whitespace() && self.nesting == 0 => self.complete(out),
+                _ => self.element.push(byte),
+            }
+        }
+        Ok(())
+    }
+
+    /// Deserializes the element read so far, if any
+    fn complete(
+        &mut self,
+        out: &mut VecDeque<errors::Result<T>>,
+    ) {
+        if !self.element.is_empty() {
+            out.push_back(serde_json::from_slice(&self.element).map_err(Error::from));
+            self.element.clear();
+        }
+    }
+
+    /// Checks that the whole array was read once the chunks end
+    fn finish(&self) -> errors::Result<()> {
+        if self.closed {
+            Ok(())
+        } else {
+            Err(Error::InvalidResponse(
+                "the response ended before the end of the array".into(),
+            ))
+        }
+    }
+}
+
+fn unexpected(byte: u8) -> Error {
+    Error::InvalidResponse(format!("unexpected {:?} in a JSON array", char::from(byte)))
+}
+
+/// Streams the elements of a JSON array from the chunks of a response body as they are parsed
+pub(crate) fn array_elements<S, T>(chunks: S) -> impl Stream<Item = errors::Result<T>>
+where
+    S: Stream<Item = errors::Result<Bytes>> + Unpin,
+    T: DeserializeOwned,
+{
+    let state = (Some(chunks), ArrayElements::new(), VecDeque::new());
+    stream::unfold(state, |(mut chunks, mut decoder, mut pending)| async move {
+        loop {
+            if let Some(element) = pending.pop_front() {
+                return Some((element, (chunks, decoder, pending)));
+            }
+            let result = match chunks.as_mut()?.next().await {
+                Some(Ok(chunk)) => decoder.read(&chunk, &mut pending),
+                Some(Err(e)) => Err(e),
+                None => {
+                    chunks = None;
+                    decoder.finish()
+                }
+            };
+            if let Err(e) = result {
+                return Some((Err(e), (None, decoder, pending)));
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct CreateInfo {
+        #[serde(default, alias = "Warning", deserialize_with = "super::warnings")]
+        warnings: Vec<String>,
+    }
+
+    fn warnings_of(json: &str) -> Vec<String> {
+        serde_json::from_str::<CreateInfo>(json).unwrap().warnings
+    }
+
+    #[test]
+    fn warnings_from_list() {
+        assert_eq!(warnings_of(r#"{"Warnings":["a","b"]}"#), vec!["a", "b"]);
+        assert!(warnings_of(r#"{"Warnings":null}"#).is_empty());
+        assert!(warnings_of(r#"{}"#).is_empty());
+    }
+
+    #[test]
+    fn warnings_from_single_string() {
+        assert_eq!(warnings_of(r#"{"Warning":"a"}"#), vec!["a"]);
+        assert!(warnings_of(r#"{"Warning":""}"#).is_empty());
+    }
+
+    #[tokio::test]
+    async fn array_elements_across_chunks() {
+        use futures_util::{stream, StreamExt};
+        use hyper::body::Bytes;
+        use serde_json::{json, Value};
+
+        let json = r#" [{"Id":"a","Names":["/x]"],"Labels":{"q":"\"}"}}, 1 ,"s,]" , {"Id":"b"}] "#;
+        let chunks = json
+            .as_bytes()
+            .chunks(3)
+            .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+            .collect::<Vec<_>>();
+        let elements: Vec<Value> = super::array_elements(stream::iter(chunks))
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(
+            elements,
+            vec![
+                json!({"Id": "a", "Names": ["/x]"], "Labels": {"q": "\"}"}}),
+                json!(1),
+                json!("s,]"),
+                json!({"Id": "b"}),
+            ]
+        );
+
+        let truncated = stream::iter(vec![Ok(Bytes::from_static(b"[{\"Id\":\"a\"},"))]);
+        let results: Vec<crate::errors::Result<Value>> =
+            super::array_elements(truncated).collect().await;
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+        assert_eq!(results.len(), 2);
+    }
+}
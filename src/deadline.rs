@@ -0,0 +1,103 @@
+//! Deadlines on individual calls.
+//!
+//! A call's future only connects to the daemon once it is polled, so a deadline set with
+//! [WithDeadline::with_deadline] covers connecting, sending the request and reading the whole
+//! response. This suits latency-sensitive probes, such as a health check pinging the daemon,
+//! where waiting on a stuck connection is worse than failing fast.
+//!
+//! ```no_run
+//! # async {
+//! use shiplift::{Docker, WithDeadline};
+//! use std::time::Duration;
+//!
+//! let docker = Docker::new();
+//! match docker.ping().with_deadline(Duration::from_millis(500)).await {
+//!     Ok(_) => println!("daemon is up"),
+//!     Err(e) => eprintln!("daemon is unhealthy: {}", e),
+//! }
+//! # };
+//! ```
+
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use pin_project::pin_project;
+
+use crate::errors::{Error, Result};
+
+/// Adds a deadline to the futures of the client's calls
+pub trait WithDeadline: Future + Sized {
+    /// Fails with [Error::DeadlineExceeded] if the call doesn't complete within `deadline`.
+    /// The call is cancelled when the deadline passes.
+    fn with_deadline(
+        self,
+        deadline: Duration,
+    ) -> Deadline<Self>;
+}
+
+impl<F, T> WithDeadline for F
+where
+    F: Future<Output = Result<T>>,
+{
+    fn with_deadline(
+        self,
+        deadline: Duration,
+    ) -> Deadline<Self> {
+        Deadline {
+            inner: tokio::time::timeout(deadline, self),
+            deadline,
+        }
+    }
+}
+
+/// A call with a deadline, returned by [WithDeadline::with_deadline]
+#[pin_project]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Deadline<F> {
+    #[pin]
+    inner: tokio::time::Timeout<F>,
+    deadline: Duration,
+}
+
+impl<F, T> Future for Deadline<F>
+where
+    F: Future<Output = Result<T>>,
+{
+    type Output = Result<T>;
+
+    fn poll(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Self::Output> {
+        let this = self.project();
+        match this.inner.poll(cx) {
+            Poll::Ready(Ok(result)) => Poll::Ready(result),
+            Poll::Ready(Err(_)) => Poll::Ready(Err(Error::DeadlineExceeded(*this.deadline))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn deadline_exceeded() {
+        let slow = async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(())
+        };
+        assert!(matches!(
+            slow.with_deadline(Duration::from_millis(10)).await,
+            Err(Error::DeadlineExceeded(deadline)) if deadline == Duration::from_millis(10)
+        ));
+
+        let fast = async { Ok(1) };
+        assert_eq!(fast.with_deadline(Duration::from_secs(5)).await.unwrap(), 1);
+    }
+}
@@ -0,0 +1,68 @@
+//! The daemon's debug endpoints, for engine troubleshooting tools.
+//!
+//! The daemon only serves these when it runs with debug enabled, such as with `dockerd --debug`
+//! or `"debug": true` in its `daemon.json`. They are not part of the versioned API, so their
+//! responses are returned as raw bytes.
+
+use futures_util::{future, TryStreamExt};
+
+use crate::{docker::Docker, errors::Result};
+
+/// Interface for the debug endpoints of a docker daemon
+pub struct DebugEndpoints<'docker> {
+    docker: &'docker Docker,
+}
+
+impl<'docker> DebugEndpoints<'docker> {
+    /// Exports an interface for the daemon's debug endpoints
+    pub fn new(docker: &'docker Docker) -> Self {
+        DebugEndpoints { docker }
+    }
+
+    /// Whether the daemon runs with debug enabled, and so serves its debug endpoints
+    pub async fn enabled(&self) -> Result<bool> {
+        Ok(self.docker.info().await?.debug)
+    }
+
+    /// The raw response of any endpoint of the daemon, such as `/debug/pprof/heap?debug=1`
+    pub async fn get(
+        &self,
+        endpoint: &str,
+    ) -> Result<Vec<u8>> {
+        self.docker
+            .stream_get(endpoint.to_owned())
+            .try_fold(Vec::new(), |mut body, chunk| {
+                body.extend_from_slice(&chunk);
+                future::ok(body)
+            })
+            .await
+    }
+
+    /// The daemon's exported variables, as JSON
+    pub async fn vars(&self) -> Result<Vec<u8>> {
+        self.get("/debug/vars").await
+    }
+
+    /// A named runtime profile, such as `heap`, `goroutine`, `block` or `mutex`, in pprof's
+    /// protobuf format
+    pub async fn pprof(
+        &self,
+        profile: &str,
+    ) -> Result<Vec<u8>> {
+        self.get(&format!("/debug/pprof/{}", profile)).await
+    }
+
+    /// A CPU profile recorded over `seconds`, in pprof's protobuf format
+    pub async fn cpu_profile(
+        &self,
+        seconds: u64,
+    ) -> Result<Vec<u8>> {
+        self.get(&format!("/debug/pprof/profile?seconds={}", seconds))
+            .await
+    }
+
+    /// The stack traces of all of the daemon's goroutines, as text
+    pub async fn goroutine_stacks(&self) -> Result<Vec<u8>> {
+        self.get("/debug/pprof/goroutine?debug=2").await
+    }
+}
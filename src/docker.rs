@@ -2,25 +2,47 @@
 //!
 //! API Reference: <https://docs.docker.com/engine/api/v1.41/>
 
-use std::{collections::HashMap, env, io, path::Path};
+use std::{
+    collections::HashMap,
+    env, fmt, io,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use futures_util::{stream::Stream, TryStreamExt};
-use hyper::{client::HttpConnector, Body, Client, Method};
+use futures_util::{stream::Stream, StreamExt, TryFutureExt, TryStreamExt};
+use hyper::{body::Bytes, client::HttpConnector, Body, Client, HeaderMap, Method, StatusCode};
 use mime::Mime;
 use serde::{de, Deserialize, Serialize};
+use tokio::sync::OnceCell;
 use url::form_urlencoded;
 
 use crate::{
-    container::Containers,
+    capabilities::{Capabilities, Feature, VersionSupport},
+    compat::Compatibility,
+    config::{ClientConfig, TlsConfig},
+    container::{Containers, Isolation},
     errors::{Error, Result},
     image::Images,
     network::Networks,
+    node::Nodes,
+    plugin::Plugins,
+    secret::Secrets,
     service::Services,
-    transport::{Headers, Payload, Transport},
+    swarm::Swarm,
+    task::Tasks,
+    transport::{
+        Headers, IntoRequestBody, Payload, RequestLimiter, RequestPermit, ShutdownSignal, Transport,
+    },
+    usage::{DataUsage, HostUsage, StatsSample},
     volume::Volumes,
     Uri,
 };
 
+#[cfg(feature = "debug-endpoints")]
+use crate::debug::DebugEndpoints;
+#[cfg(feature = "metrics")]
+use crate::instrument::RequestMetrics;
+
 #[cfg(feature = "chrono")]
 use crate::datetime::{datetime_from_nano_timestamp, datetime_from_unix_timestamp};
 #[cfg(feature = "chrono")]
@@ -38,92 +60,161 @@ use hyperlocal::UnixConnector;
 #[derive(Clone)]
 pub struct Docker {
     transport: Transport,
+    limiter: Option<Arc<RequestLimiter>>,
+    shutdown: Arc<ShutdownSignal>,
+    capabilities: Arc<OnceCell<Capabilities>>,
+    versions: Arc<OnceCell<VersionSupport>>,
+    tls: Option<TlsConfig>,
+    max_response_size: Option<usize>,
+    compatibility: Compatibility,
 }
 
-fn get_http_connector() -> HttpConnector {
+fn get_http_connector(keepalive: Option<Duration>) -> HttpConnector {
     let mut http = HttpConnector::new();
     http.enforce_http(false);
+    http.set_keepalive(keepalive);
 
     http
 }
 
+/// Expands the shorthands the docker CLI accepts for `DOCKER_HOST`: a bare `host:port` or
+/// `:port` means tcp, and an empty tcp host means localhost
+fn expand_docker_host(host: &str) -> String {
+    let host = if host.contains("://") {
+        host.to_owned()
+    } else {
+        format!("tcp://{}", host)
+    };
+    match host.strip_prefix("tcp://") {
+        Some(rest) if rest.is_empty() || rest.starts_with(':') => {
+            format!("tcp://localhost{}", rest)
+        }
+        _ => host,
+    }
+}
+
+/// Builds a url hyper can dial for a tcp docker host. The docker-specific `tcp` scheme becomes
+/// `https` when TLS is configured and `http` otherwise, defaulting to the daemon's 2376 and 2375
+/// ports respectively. Fails when TLS is called for but the `tls` feature isn't enabled.
+fn tcp_host_url(
+    scheme: &str,
+    host: &str,
+    port: Option<u16>,
+    tls: bool,
+) -> Result<String> {
+    let (scheme, default_port) = match scheme {
+        "tcp" if tls => ("https", 2376),
+        "tcp" => ("http", 2375),
+        "https" => ("https", 443),
+        other => (other, 80),
+    };
+    if scheme == "https" && !cfg!(feature = "tls") {
+        return Err(Error::InvalidConfig(format!(
+            "connecting to {} over TLS needs the tls feature",
+            host
+        )));
+    }
+    Ok(format!(
+        "{}://{}:{}",
+        scheme,
+        host,
+        port.unwrap_or(default_port)
+    ))
+}
+
 #[cfg(feature = "tls")]
-fn get_docker_for_tcp(tcp_host_str: String) -> Docker {
-    let http = get_http_connector();
-    if let Ok(ref certs) = env::var("DOCKER_CERT_PATH") {
+fn get_docker_for_tcp(
+    tcp_host_str: String,
+    keepalive: Option<Duration>,
+    tls: Option<TlsConfig>,
+) -> Docker {
+    let http = get_http_connector(keepalive);
+    let mut docker = if let Some(ref tls) = tls {
+        let certs = &tls.cert_path;
         // fixme: don't unwrap before you know what's in the box
         // https://github.com/hyperium/hyper/blob/master/src/net.rs#L427-L428
         let mut connector = SslConnector::builder(SslMethod::tls()).unwrap();
         connector.set_cipher_list("DEFAULT").unwrap();
-        let cert = &format!("{}/cert.pem", certs);
-        let key = &format!("{}/key.pem", certs);
         connector
-            .set_certificate_file(&Path::new(cert), SslFiletype::PEM)
+            .set_certificate_file(certs.join("cert.pem"), SslFiletype::PEM)
             .unwrap();
         connector
-            .set_private_key_file(&Path::new(key), SslFiletype::PEM)
+            .set_private_key_file(certs.join("key.pem"), SslFiletype::PEM)
             .unwrap();
-        if env::var("DOCKER_TLS_VERIFY").is_ok() {
-            let ca = &format!("{}/ca.pem", certs);
-            connector.set_ca_file(&Path::new(ca)).unwrap();
+        if tls.verify {
+            connector.set_ca_file(certs.join("ca.pem")).unwrap();
         }
 
-        // If we are attempting to connec to the docker daemon via tcp
-        // we need to convert the scheme to `https` to let hyper connect.
-        // Otherwise, hyper will reject the connection since it does not
-        // recongnize `tcp` as a valid `http` scheme.
-        let tcp_host_str = if tcp_host_str.contains("tcp://") {
-            tcp_host_str.replace("tcp://", "https://")
-        } else {
-            tcp_host_str
-        };
-
-        Docker {
-            transport: Transport::EncryptedTcp {
-                client: Client::builder()
-                    .build(HttpsConnector::with_connector(http, connector).unwrap()),
-                host: tcp_host_str,
-            },
-        }
+        Docker::from_transport(Transport::EncryptedTcp {
+            client: Client::builder()
+                .build(HttpsConnector::with_connector(http, connector).unwrap()),
+            host: tcp_host_str,
+        })
     } else {
-        Docker {
-            transport: Transport::Tcp {
-                client: Client::builder().build(http),
-                host: tcp_host_str,
-            },
-        }
-    }
+        Docker::from_transport(Transport::Tcp {
+            client: Client::builder().build(http),
+            host: tcp_host_str,
+        })
+    };
+    docker.tls = tls;
+    docker
 }
 
 #[cfg(not(feature = "tls"))]
-fn get_docker_for_tcp(tcp_host_str: String) -> Docker {
-    let http = get_http_connector();
-    Docker {
-        transport: Transport::Tcp {
-            client: Client::builder().build(http),
-            host: tcp_host_str,
-        },
-    }
+fn get_docker_for_tcp(
+    tcp_host_str: String,
+    keepalive: Option<Duration>,
+    _tls: Option<TlsConfig>,
+) -> Docker {
+    let http = get_http_connector(keepalive);
+    Docker::from_transport(Transport::Tcp {
+        client: Client::builder().build(http),
+        host: tcp_host_str,
+    })
 }
 
 // https://docs.docker.com/reference/api/docker_remote_api_v1.17/
 impl Docker {
+    fn from_transport(transport: Transport) -> Docker {
+        Docker {
+            transport,
+            limiter: None,
+            shutdown: Arc::new(ShutdownSignal::new()),
+            capabilities: Arc::new(OnceCell::new()),
+            versions: Arc::new(OnceCell::new()),
+            tls: None,
+            max_response_size: None,
+            compatibility: Compatibility::default(),
+        }
+    }
+
     /// constructs a new Docker instance for a docker host listening at a url specified by an env var `DOCKER_HOST`,
     /// falling back on unix:///var/run/docker.sock
+    ///
+    /// Panics if `DOCKER_HOST` is not a url this build can connect to, see
+    /// [try_new](Docker::try_new) for a fallible version.
     pub fn new() -> Docker {
+        Docker::try_new().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like [new](Docker::new), but returns an error instead of panicking when `DOCKER_HOST`
+    /// is not a valid url, or uses a scheme this build can't connect over, such as `unix`
+    /// without the `unix-socket` feature.
+    pub fn try_new() -> Result<Docker> {
         match env::var("DOCKER_HOST").ok() {
             Some(host) => {
                 #[cfg(feature = "unix-socket")]
                 if let Some(path) = host.strip_prefix("unix://") {
-                    return Docker::unix(path);
+                    return Ok(Docker::unix(path));
                 }
-                let host = host.parse().expect("invalid url");
-                Docker::host(host)
+                Docker::try_host(expand_docker_host(&host).parse()?)
             }
             #[cfg(feature = "unix-socket")]
-            None => Docker::unix("/var/run/docker.sock"),
+            None => Ok(Docker::unix("/var/run/docker.sock")),
             #[cfg(not(feature = "unix-socket"))]
-            None => panic!("Unix socket support is disabled"),
+            None => Err(Error::UnsupportedScheme(
+                "unix:///var/run/docker.sock".to_owned(),
+            )),
         }
     }
 
@@ -134,41 +225,209 @@ impl Docker {
     where
         S: Into<String>,
     {
-        Docker {
-            transport: Transport::Unix {
-                client: Client::builder()
-                    .pool_max_idle_per_host(0)
-                    .build(UnixConnector),
-                path: socket_path.into(),
-            },
-        }
+        Docker::from_transport(Transport::Unix {
+            client: Client::builder()
+                .pool_max_idle_per_host(0)
+                .build(UnixConnector),
+            path: socket_path.into(),
+        })
     }
 
     /// constructs a new Docker instance for docker host listening at the given host url
+    ///
+    /// Panics if the url's scheme is not supported, see [try_host](Docker::try_host) for a
+    /// fallible version.
     pub fn host(host: Uri) -> Docker {
-        let tcp_host_str = format!(
-            "{}://{}:{}",
-            host.scheme_str().unwrap(),
-            host.host().unwrap().to_owned(),
-            host.port_u16().unwrap_or(80)
+        Docker::try_host(host).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like [host](Docker::host), but returns
+    /// [Error::UnsupportedScheme](crate::Error::UnsupportedScheme) instead of panicking when the
+    /// url's scheme is not one of `http`, `https`, `tcp`, or `unix` with the `unix-socket`
+    /// feature.
+    ///
+    /// A `tcp://` url is dialed over https when `DOCKER_CERT_PATH` is set and http otherwise,
+    /// defaulting to port 2376 or 2375 like the docker CLI.
+    pub fn try_host(host: Uri) -> Result<Docker> {
+        Docker::connect(host, TlsConfig::from_env())
+    }
+
+    /// Constructs a new Docker instance for the docker host the environment and the docker
+    /// CLI's configuration select, with `overrides` taking precedence over both.
+    ///
+    /// The host is resolved with the same precedence as the docker CLI: see
+    /// [ClientConfig::resolve](crate::config::ClientConfig::resolve). Which setting won is
+    /// logged at debug level, and can be inspected up front by resolving the config directly.
+    pub fn from_env_and_config(overrides: &ClientConfig) -> Result<Docker> {
+        let resolved = overrides.resolve()?;
+        log::debug!(
+            "connecting to docker host {} from {}",
+            resolved.host,
+            resolved.source
         );
+        #[cfg(feature = "unix-socket")]
+        if let Some(path) = resolved.host.strip_prefix("unix://") {
+            return Ok(Docker::unix(path));
+        }
+        Docker::connect(expand_docker_host(&resolved.host).parse()?, resolved.tls)
+    }
 
+    fn connect(
+        host: Uri,
+        tls: Option<TlsConfig>,
+    ) -> Result<Docker> {
         match host.scheme_str() {
             #[cfg(feature = "unix-socket")]
-            Some("unix") => Docker {
-                transport: Transport::Unix {
-                    client: Client::builder().build(UnixConnector),
-                    path: host.path().to_owned(),
-                },
-            },
+            Some("unix") => Ok(Docker::from_transport(Transport::Unix {
+                client: Client::builder().build(UnixConnector),
+                path: host.path().to_owned(),
+            })),
+            Some(scheme @ "http") | Some(scheme @ "https") | Some(scheme @ "tcp") => {
+                let tcp_host_str = tcp_host_url(
+                    scheme,
+                    host.host().unwrap_or("localhost"),
+                    host.port_u16(),
+                    tls.is_some(),
+                )?;
+                Ok(get_docker_for_tcp(tcp_host_str, None, tls))
+            }
+            _ => Err(Error::UnsupportedScheme(host.to_string())),
+        }
+    }
 
-            #[cfg(not(feature = "unix-socket"))]
-            Some("unix") => panic!("Unix socket support is disabled"),
+    /// Limits the number of requests this client, and any of its clones, will have in flight
+    /// against the docker daemon at once.
+    ///
+    /// Requests over `max_in_flight` wait for a free slot, a limit of 0 being treated as 1. If
+    /// `max_queued` is set, requests arriving while that many are already waiting fail with
+    /// [Error::RequestQueueFull](crate::Error::RequestQueueFull) instead of queueing.
+    ///
+    /// Streaming and attached requests only hold their slot until the daemon has responded, so
+    /// long-lived streams such as events or followed logs don't starve other requests. The wait
+    /// of [wait_with_output](crate::Container::wait_with_output) is the exception, holding its
+    /// slot until the container exits.
+    pub fn with_request_limit(
+        mut self,
+        max_in_flight: usize,
+        max_queued: Option<usize>,
+    ) -> Docker {
+        self.limiter = Some(Arc::new(RequestLimiter::new(max_in_flight, max_queued)));
+        self
+    }
 
-            _ => get_docker_for_tcp(tcp_host_str),
+    /// Limits the size of the responses this client, and any of its clones, reads whole, such
+    /// as those of inspects and lists, to `max_bytes`. Larger responses fail with
+    /// [Error::ResponseTooLarge](crate::Error::ResponseTooLarge) as soon as their size is known,
+    /// rather than being read into memory.
+    ///
+    /// Streamed responses, such as logs, events, exports or pull progress, are read piece by
+    /// piece and are not limited.
+    pub fn with_max_response_size(
+        mut self,
+        max_bytes: usize,
+    ) -> Docker {
+        self.max_response_size = Some(max_bytes);
+        self
+    }
+
+    /// Reads the responses of this client, and any of its clones, the way the daemon it talks
+    /// to needs, such as podman or balenaEngine. See [compat](crate::compat) for what each
+    /// profile tolerates.
+    ///
+    /// Streamed responses, such as pull progress, are read strictly regardless.
+    pub fn with_compatibility(
+        mut self,
+        compatibility: Compatibility,
+    ) -> Docker {
+        self.compatibility = compatibility;
+        self
+    }
+
+    /// Enables TCP keepalive on connections to a daemon reached over TCP, probing connections
+    /// that have been idle for `idle`.
+    ///
+    /// Attached and exec sessions can sit idle for long stretches, and NAT gateways and
+    /// firewalls silently drop connections they consider dead, leaving the session hanging.
+    /// Keepalive probes keep such connections alive and surface broken ones as errors. Has no
+    /// effect on unix socket connections.
+    pub fn with_tcp_keepalive(
+        mut self,
+        idle: Duration,
+    ) -> Docker {
+        let host = match &self.transport {
+            Transport::Tcp { host, .. } => host.clone(),
+            #[cfg(feature = "tls")]
+            Transport::EncryptedTcp { host, .. } => host.clone(),
+            #[cfg(feature = "unix-socket")]
+            Transport::Unix { .. } => return self,
+        };
+        self.transport = get_docker_for_tcp(host, Some(idle), self.tls.clone()).transport;
+        self
+    }
+
+    /// The url this client dials the daemon at, or `None` when it connects over a unix socket
+    #[cfg(feature = "testing")]
+    pub(crate) fn tcp_host(&self) -> Option<&str> {
+        match &self.transport {
+            Transport::Tcp { host, .. } => Some(host),
+            #[cfg(feature = "tls")]
+            Transport::EncryptedTcp { host, .. } => Some(host),
+            #[cfg(feature = "unix-socket")]
+            Transport::Unix { .. } => None,
+        }
+    }
+
+    /// Returns a client for another unix socket serving a docker-like API, such as one exposed
+    /// by an engine plugin, so its endpoints can be driven with the same typed interfaces.
+    ///
+    /// The new client shares this one's connection client when it uses a unix socket too, as
+    /// well as its request limit, maximum response size and shutdown.
+    /// [Capabilities](Docker::capabilities) are fetched from the other socket.
+    #[cfg(feature = "unix-socket")]
+    pub fn with_socket<S>(
+        &self,
+        socket_path: S,
+    ) -> Docker
+    where
+        S: Into<String>,
+    {
+        let client = match &self.transport {
+            Transport::Unix { client, .. } => client.clone(),
+            _ => Client::builder()
+                .pool_max_idle_per_host(0)
+                .build(UnixConnector),
+        };
+        Docker {
+            transport: Transport::Unix {
+                client,
+                path: socket_path.into(),
+            },
+            limiter: self.limiter.clone(),
+            shutdown: self.shutdown.clone(),
+            capabilities: Arc::new(OnceCell::new()),
+            versions: Arc::new(OnceCell::new()),
+            tls: None,
+            max_response_size: self.max_response_size,
+            compatibility: self.compatibility,
         }
     }
 
+    /// Shuts this client, and all of its clones, down.
+    ///
+    /// Streams that are still running, such as events or followed logs, end and attached
+    /// connections are closed, so nothing keeps the runtime alive on the client's behalf. Any
+    /// request made afterwards fails with [Error::Shutdown](crate::Error::Shutdown). Idle pooled
+    /// connections are released once the last clone of the client is dropped.
+    pub fn shutdown(&self) {
+        self.shutdown.trigger();
+    }
+
+    /// Returns true once [shutdown](Docker::shutdown) has been called on this client or any of
+    /// its clones
+    pub fn is_shutdown(&self) -> bool {
+        self.shutdown.is_triggered()
+    }
+
     /// Exports an interface for interacting with docker images
     pub fn images(&'_ self) -> Images<'_> {
         Images::new(self)
@@ -184,6 +443,33 @@ impl Docker {
         Services::new(self)
     }
 
+    pub fn swarm(&'_ self) -> Swarm<'_> {
+        Swarm::new(self)
+    }
+
+    pub fn nodes(&'_ self) -> Nodes<'_> {
+        Nodes::new(self)
+    }
+
+    pub fn tasks(&'_ self) -> Tasks<'_> {
+        Tasks::new(self)
+    }
+
+    pub fn secrets(&'_ self) -> Secrets<'_> {
+        Secrets::new(self)
+    }
+
+    pub fn plugins(&'_ self) -> Plugins<'_> {
+        Plugins::new(self)
+    }
+
+    /// Exports an interface for the daemon's debug endpoints, which it serves when running with
+    /// debug enabled
+    #[cfg(feature = "debug-endpoints")]
+    pub fn debug_endpoints(&'_ self) -> DebugEndpoints<'_> {
+        DebugEndpoints::new(self)
+    }
+
     pub fn networks(&'_ self) -> Networks<'_> {
         Networks::new(self)
     }
@@ -197,52 +483,290 @@ impl Docker {
         self.get_json("/version").await
     }
 
+    /// Returns what the daemon supports, such as its API version and experimental flag, which
+    /// options that not every daemon supports are checked against, and whether it runs
+    /// rootless or in a swarm. Fetched once per client and its clones.
+    pub async fn capabilities(&self) -> Result<Capabilities> {
+        self.capabilities
+            .get_or_try_init(|| async {
+                let (versions, info, builder_version) =
+                    futures_util::try_join!(self.versions(), self.info(), self.builder_version())?;
+                Ok(Capabilities::from_daemon(
+                    versions,
+                    &info,
+                    builder_version.as_deref(),
+                ))
+            })
+            .await
+            .copied()
+    }
+
+    /// What the daemon supports by its version, fetched once per client and its clones
+    async fn versions(&self) -> Result<VersionSupport> {
+        self.versions
+            .get_or_try_init(|| async { VersionSupport::from_version(&self.version().await?) })
+            .await
+            .copied()
+    }
+
+    /// The builder version the daemon reports in the `Builder-Version` header of its pings
+    async fn builder_version(&self) -> Result<Option<String>> {
+        let (_, headers) = self
+            .request_with_headers(Method::GET, "/_ping", Payload::None, Headers::None)
+            .await?;
+        Ok(headers
+            .get("Builder-Version")
+            .and_then(|version| version.to_str().ok())
+            .map(str::to_owned))
+    }
+
+    /// Fails with [Error::UnsupportedFeature](crate::Error::UnsupportedFeature) if the daemon
+    /// doesn't support one of `features`. Only asks the daemon for its version, and only when
+    /// there is something to check.
+    pub(crate) async fn require(
+        &self,
+        features: &[Feature],
+    ) -> Result<()> {
+        if features.is_empty() {
+            return Ok(());
+        }
+        let versions = self.versions().await?;
+        features
+            .iter()
+            .try_for_each(|feature| versions.check(*feature))
+    }
+
     /// Returns information associated with the docker daemon
     pub async fn info(&self) -> Result<Info> {
         self.get_json("/info").await
     }
 
+    /// Returns the disk usage of images, containers and volumes
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/SystemDataUsage)
+    pub async fn data_usage(&self) -> Result<DataUsage> {
+        self.get_json("/system/df").await
+    }
+
+    /// Returns a snapshot of the host's resource usage, combining daemon info, disk usage and a
+    /// stats sample of every running container. Sampling takes about a second, as the daemon
+    /// needs two samples to compute CPU usage.
+    pub async fn host_usage(&self) -> Result<HostUsage> {
+        let (info, disk) = futures_util::future::try_join(self.info(), self.data_usage()).await?;
+
+        let running = disk
+            .containers
+            .iter()
+            .flatten()
+            .filter(|container| container.state == "running");
+        let samples = futures_util::future::join_all(running.map(|container| async move {
+            let sample = self
+                .get_json::<StatsSample>(&format!(
+                    "/containers/{}/stats?stream=false",
+                    container.id
+                ))
+                .await;
+            (container, sample)
+        }))
+        .await;
+
+        let mut containers = Vec::new();
+        for (container, sample) in samples {
+            match sample {
+                Ok(sample) => {
+                    containers.push(sample.usage(container.id.clone(), container.names.clone()))
+                }
+                // the container went away since the disk usage was listed
+                Err(Error::Fault { code, .. })
+                    if code == StatusCode::NOT_FOUND || code == StatusCode::CONFLICT => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(HostUsage {
+            name: info.name,
+            n_cpu: info.n_cpu,
+            mem_total: info.mem_total,
+            containers,
+            disk,
+        })
+    }
+
     /// Returns a simple ping response indicating the docker daemon is accessible
     pub async fn ping(&self) -> Result<String> {
         self.get("/_ping").await
     }
 
+    /// Waits until the daemon answers pings, such as after starting dockerd, retrying with a
+    /// growing delay for up to `timeout`. Returns the last failure if the daemon doesn't answer
+    /// in time.
+    pub async fn wait_ready(
+        &self,
+        timeout: Duration,
+    ) -> Result<()> {
+        const INITIAL_DELAY: Duration = Duration::from_millis(50);
+        const MAX_DELAY: Duration = Duration::from_secs(1);
+
+        let deadline = Instant::now() + timeout;
+        let mut delay = INITIAL_DELAY;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let err = match tokio::time::timeout(remaining, self.ping()).await {
+                Ok(Ok(_)) => return Ok(()),
+                Ok(Err(err)) => err,
+                Err(_) => Error::IO(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "the daemon did not answer in time",
+                )),
+            };
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(err);
+            }
+            tokio::time::sleep(delay.min(remaining)).await;
+            delay = (delay * 2).min(MAX_DELAY);
+        }
+    }
+
     /// Returns a stream of docker events
     pub fn events<'docker>(
         &'docker self,
         opts: &EventsOptions,
     ) -> impl Stream<Item = Result<Event>> + Unpin + 'docker {
+        let compatibility = self.compatibility;
+        Box::pin(
+            Self::event_lines(self.stream_get(Self::events_path(opts)))
+                .and_then(move |line| async move { compatibility.decode(&line) }),
+        )
+    }
+
+    /// Returns a stream of all docker events on the host from now on, unfiltered. Use the
+    /// accessors on [Event](Event) such as [container_id](Event::container_id) and
+    /// [exit_code](Event::exit_code) to pick out the common attributes.
+    pub fn system_events(&self) -> impl Stream<Item = Result<Event>> + Unpin + '_ {
+        self.events(&EventsOptions::default())
+    }
+
+    /// Returns a stream of docker events like [events](Docker::events), pairing each typed event
+    /// with the raw JSON it was parsed from, without the trailing newline. The raw JSON can be
+    /// forwarded as is, including any fields `Event` does not model.
+    pub fn events_raw<'docker>(
+        &'docker self,
+        opts: &EventsOptions,
+    ) -> impl Stream<Item = Result<(Event, Bytes)>> + Unpin + 'docker {
+        let compatibility = self.compatibility;
+        Box::pin(
+            Self::event_lines(self.stream_get(Self::events_path(opts))).and_then(
+                move |line| async move {
+                    let event = compatibility.decode(&line)?;
+                    let raw = Bytes::from(line.trim_end_matches(&['\r', '\n'][..]).to_owned());
+                    Ok((event, raw))
+                },
+            ),
+        )
+    }
+
+    /// Returns a stream of docker events like [events](Docker::events) once the daemon has
+    /// accepted the subscription, so that no event after this resolves is missed
+    pub(crate) async fn subscribe_events<'docker>(
+        &'docker self,
+        opts: &EventsOptions,
+    ) -> Result<impl Stream<Item = Result<Event>> + Send + 'docker> {
+        let chunks = self
+            .stream_chunks_responded(
+                Method::GET,
+                Self::events_path(opts),
+                Option::<(Body, Mime)>::None,
+                Some(Vec::default()),
+            )
+            .await?
+            .take_until(self.shutdown.triggered());
+        let compatibility = self.compatibility;
+        Ok(Self::event_lines(chunks)
+            .and_then(move |line| async move { compatibility.decode(&line) }))
+    }
+
+    fn events_path(opts: &EventsOptions) -> String {
         let mut path = vec!["/events".to_owned()];
         if let Some(query) = opts.serialize() {
             path.push(query);
         }
-        let reader = Box::pin(
-            self.stream_get(path.join("?"))
-                .map_err(|e| io::Error::new(io::ErrorKind::Other, e)),
-        )
-        .into_async_read();
+        path.join("?")
+    }
+
+    /// The newline delimited JSON lines of the events endpoint
+    fn event_lines<'docker>(
+        chunks: impl Stream<Item = Result<hyper::body::Bytes>> + Send + 'docker
+    ) -> impl Stream<Item = Result<String>> + Send + 'docker {
+        let reader = Box::pin(chunks.map_err(io::Error::other)).into_async_read();
 
         let codec = futures_codec::LinesCodec {};
 
-        Box::pin(
-            futures_codec::FramedRead::new(reader, codec)
-                .map_err(Error::IO)
-                .and_then(|s: String| async move {
-                    serde_json::from_str(&s).map_err(Error::SerdeJsonError)
-                }),
-        )
+        futures_codec::FramedRead::new(reader, codec).map_err(Error::IO)
     }
 
     //
     // Utility functions to make requests
     //
 
+    async fn acquire_permit(&self) -> Result<Option<RequestPermit>> {
+        if self.shutdown.is_triggered() {
+            return Err(Error::Shutdown);
+        }
+        match self.limiter {
+            Some(ref limiter) => limiter.acquire().await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    async fn request<B, H>(
+        &self,
+        method: Method,
+        endpoint: impl AsRef<str>,
+        body: Option<(B, Mime)>,
+        headers: Option<H>,
+    ) -> Result<String>
+    where
+        B: IntoRequestBody,
+        H: IntoIterator<Item = (&'static str, String)>,
+    {
+        let (string, _) = self
+            .request_with_headers(method, endpoint, body, headers)
+            .await?;
+
+        Ok(string)
+    }
+
+    async fn request_with_headers<B, H>(
+        &self,
+        method: Method,
+        endpoint: impl AsRef<str>,
+        body: Option<(B, Mime)>,
+        headers: Option<H>,
+    ) -> Result<(String, HeaderMap)>
+    where
+        B: IntoRequestBody,
+        H: IntoIterator<Item = (&'static str, String)>,
+    {
+        let _permit = self.acquire_permit().await?;
+        #[cfg(feature = "metrics")]
+        let metrics = RequestMetrics::start(&method, endpoint.as_ref());
+        let result = self
+            .transport
+            .request_with_limit(method, endpoint, body, headers, self.max_response_size)
+            .await;
+        #[cfg(feature = "metrics")]
+        metrics.finish(&result);
+
+        result
+    }
+
     pub(crate) async fn get(
         &self,
         endpoint: &str,
     ) -> Result<String> {
-        self.transport
-            .request(Method::GET, endpoint, Payload::None, Headers::None)
+        self.request(Method::GET, endpoint, Payload::None, Headers::None)
             .await
     }
 
@@ -250,12 +774,34 @@ impl Docker {
         &self,
         endpoint: &str,
     ) -> Result<T> {
-        let raw_string = self
-            .transport
-            .request(Method::GET, endpoint, Payload::None, Headers::None)
+        let (value, _) = self.get_json_and_headers(endpoint).await?;
+
+        Ok(value)
+    }
+
+    /// Like `get_json` but also returns the response headers, for endpoints that put
+    /// information there
+    pub(crate) async fn get_json_and_headers<T: serde::de::DeserializeOwned>(
+        &self,
+        endpoint: &str,
+    ) -> Result<(T, HeaderMap)> {
+        let (raw_string, headers) = self
+            .request_with_headers(Method::GET, endpoint, Payload::None, Headers::None)
+            .await?;
+
+        Ok((self.compatibility.decode(&raw_string)?, headers))
+    }
+
+    /// Sends a `HEAD` request, returning only the response headers
+    pub(crate) async fn head(
+        &self,
+        endpoint: &str,
+    ) -> Result<HeaderMap> {
+        let (_, headers) = self
+            .request_with_headers(Method::HEAD, endpoint, Payload::None, Headers::None)
             .await?;
 
-        Ok(serde_json::from_str::<T>(&raw_string)?)
+        Ok(headers)
     }
 
     pub(crate) async fn post(
@@ -263,18 +809,16 @@ impl Docker {
         endpoint: &str,
         body: Option<(Body, Mime)>,
     ) -> Result<String> {
-        self.transport
-            .request(Method::POST, endpoint, body, Headers::None)
+        self.request(Method::POST, endpoint, body, Headers::None)
             .await
     }
 
-    pub(crate) async fn put(
+    pub(crate) async fn put<B: IntoRequestBody>(
         &self,
         endpoint: &str,
-        body: Option<(Body, Mime)>,
+        body: Option<(B, Mime)>,
     ) -> Result<String> {
-        self.transport
-            .request(Method::PUT, endpoint, body, Headers::None)
+        self.request(Method::PUT, endpoint, body, Headers::None)
             .await
     }
 
@@ -285,14 +829,13 @@ impl Docker {
     ) -> Result<T>
     where
         T: serde::de::DeserializeOwned,
-        B: Into<Body>,
+        B: IntoRequestBody,
     {
         let string = self
-            .transport
             .request(Method::POST, endpoint, body, Headers::None)
             .await?;
 
-        Ok(serde_json::from_str::<T>(&string)?)
+        self.compatibility.decode(&string)
     }
 
     pub(crate) async fn post_json_headers<'a, T, B, H>(
@@ -303,23 +846,39 @@ impl Docker {
     ) -> Result<T>
     where
         T: serde::de::DeserializeOwned,
-        B: Into<Body>,
+        B: IntoRequestBody,
         H: IntoIterator<Item = (&'static str, String)> + 'a,
     {
-        let string = self
-            .transport
-            .request(Method::POST, endpoint, body, headers)
+        let (value, _) = self.post_json_and_headers(endpoint, body, headers).await?;
+
+        Ok(value)
+    }
+
+    /// Like `post_json_headers` but also returns the response headers, for endpoints that put
+    /// information there
+    pub(crate) async fn post_json_and_headers<'a, T, B, H>(
+        &self,
+        endpoint: impl AsRef<str>,
+        body: Option<(B, Mime)>,
+        headers: Option<H>,
+    ) -> Result<(T, HeaderMap)>
+    where
+        T: serde::de::DeserializeOwned,
+        B: IntoRequestBody,
+        H: IntoIterator<Item = (&'static str, String)> + 'a,
+    {
+        let (string, response_headers) = self
+            .request_with_headers(Method::POST, endpoint, body, headers)
             .await?;
 
-        Ok(serde_json::from_str::<T>(&string)?)
+        Ok((self.compatibility.decode(&string)?, response_headers))
     }
 
     pub(crate) async fn delete(
         &self,
         endpoint: &str,
     ) -> Result<String> {
-        self.transport
-            .request(Method::DELETE, endpoint, Payload::None, Headers::None)
+        self.request(Method::DELETE, endpoint, Payload::None, Headers::None)
             .await
     }
 
@@ -328,16 +887,62 @@ impl Docker {
         endpoint: &str,
     ) -> Result<T> {
         let string = self
-            .transport
             .request(Method::DELETE, endpoint, Payload::None, Headers::None)
             .await?;
 
-        Ok(serde_json::from_str::<T>(&string)?)
+        self.compatibility.decode(&string)
+    }
+
+    fn stream_chunks<'a, B, H>(
+        &'a self,
+        method: Method,
+        endpoint: impl AsRef<str> + 'a,
+        body: Option<(B, Mime)>,
+        headers: Option<H>,
+    ) -> impl Stream<Item = Result<hyper::body::Bytes>> + 'a
+    where
+        B: IntoRequestBody + 'a,
+        H: IntoIterator<Item = (&'static str, String)> + 'a,
+    {
+        self.stream_chunks_responded(method, endpoint, body, headers)
+            .try_flatten_stream()
+            .take_until(self.shutdown.triggered())
+    }
+
+    /// Like [stream_chunks](Docker::stream_chunks), resolving once the daemon has responded
+    async fn stream_chunks_responded<'a, B, H>(
+        &'a self,
+        method: Method,
+        endpoint: impl AsRef<str> + 'a,
+        body: Option<(B, Mime)>,
+        headers: Option<H>,
+    ) -> Result<impl Stream<Item = Result<hyper::body::Bytes>> + 'a>
+    where
+        B: IntoRequestBody + 'a,
+        H: IntoIterator<Item = (&'static str, String)> + 'a,
+    {
+        // The slot is only held until the daemon responds, not for the life of the stream
+        let _permit = self.acquire_permit().await?;
+        #[cfg(feature = "metrics")]
+        let metrics = RequestMetrics::start(&method, endpoint.as_ref());
+        let result = self
+            .transport
+            .get_chunk_stream(method, endpoint, body, headers)
+            .await;
+        #[cfg(feature = "metrics")]
+        let result = {
+            metrics.finish(&result);
+            result.map(|stream| {
+                stream.inspect(move |chunk| match chunk {
+                    Ok(bytes) => metrics.streamed(bytes.len()),
+                    Err(_) => metrics.error(),
+                })
+            })
+        };
+
+        result
     }
 
-    /// Send a streaming post request.
-    ///
-    /// Use stream_post_into_values if the endpoint returns JSON values
     pub(crate) fn stream_post<'a, H>(
         &'a self,
         endpoint: impl AsRef<str> + 'a,
@@ -347,24 +952,44 @@ impl Docker {
     where
         H: IntoIterator<Item = (&'static str, String)> + 'a,
     {
-        self.transport
-            .stream_chunks(Method::POST, endpoint, body, headers)
+        self.stream_chunks(Method::POST, endpoint, body, headers)
+    }
+
+    /// Send a post request, returning once the daemon has responded with a stream of the
+    /// response body. Unlike [stream_post](Docker::stream_post), which only sends the request
+    /// once the stream is polled, the request is known to have reached the daemon. The request
+    /// slot is held until the stream is dropped.
+    pub(crate) async fn stream_post_responded<'a>(
+        &'a self,
+        endpoint: impl AsRef<str> + 'a,
+    ) -> Result<impl Stream<Item = Result<hyper::body::Bytes>> + 'a> {
+        let permit = self.acquire_permit().await?;
+        let chunks = self
+            .transport
+            .get_chunk_stream(Method::POST, endpoint, Payload::None, Headers::None)
+            .await?;
+        Ok(chunks
+            .take_until(self.shutdown.triggered())
+            .inspect(move |_| {
+                let _held = &permit;
+            }))
     }
 
     /// Send a streaming post request that returns a stream of JSON values
     ///
     /// Assumes that each received chunk contains one or more JSON values
-    pub(crate) fn stream_post_into<'a, H, T>(
+    pub(crate) fn stream_post_into<'a, B, H, T>(
         &'a self,
         endpoint: impl AsRef<str> + 'a,
-        body: Option<(Body, Mime)>,
+        body: Option<(B, Mime)>,
         headers: Option<H>,
     ) -> impl Stream<Item = Result<T>> + 'a
     where
+        B: IntoRequestBody + 'a,
         H: IntoIterator<Item = (&'static str, String)> + 'a,
         T: de::DeserializeOwned,
     {
-        self.stream_post(endpoint, body, headers)
+        self.stream_chunks(Method::POST, endpoint, body, headers)
             .and_then(|chunk| async move {
                 let stream = futures_util::stream::iter(
                     serde_json::Deserializer::from_slice(&chunk)
@@ -383,8 +1008,7 @@ impl Docker {
         endpoint: impl AsRef<str> + Unpin + 'a,
     ) -> impl Stream<Item = Result<hyper::body::Bytes>> + 'a {
         let headers = Some(Vec::default());
-        self.transport
-            .stream_chunks(Method::GET, endpoint, Option::<(Body, Mime)>::None, headers)
+        self.stream_chunks(Method::GET, endpoint, Option::<(Body, Mime)>::None, headers)
     }
 
     pub(crate) async fn stream_post_upgrade<'a>(
@@ -392,9 +1016,17 @@ impl Docker {
         endpoint: impl AsRef<str> + 'a,
         body: Option<(Body, Mime)>,
     ) -> Result<impl futures_util::io::AsyncRead + futures_util::io::AsyncWrite + 'a> {
-        self.transport
+        let _permit = self.acquire_permit().await?;
+        #[cfg(feature = "metrics")]
+        let metrics = RequestMetrics::start(&Method::POST, endpoint.as_ref());
+        let result = self
+            .transport
             .stream_upgrade(Method::POST, endpoint, body)
-            .await
+            .await;
+        #[cfg(feature = "metrics")]
+        metrics.finish(&result);
+
+        result.map(|connection| self.shutdown.guard(connection))
     }
 }
 
@@ -408,6 +1040,7 @@ impl Default for Docker {
 #[derive(Default, Debug)]
 pub struct EventsOptions {
     params: HashMap<&'static str, String>,
+    filters: EventFilters,
 }
 
 impl EventsOptions {
@@ -417,61 +1050,112 @@ impl EventsOptions {
 
     /// serialize options as a string. returns None if no options are defined
     pub fn serialize(&self) -> Option<String> {
-        if self.params.is_empty() {
+        let filters = if self.filters.is_empty() {
+            None
+        } else {
+            serde_json::to_string(&self.filters).ok()
+        };
+
+        if self.params.is_empty() && filters.is_none() {
             None
         } else {
             Some(
                 form_urlencoded::Serializer::new(String::new())
                     .extend_pairs(&self.params)
+                    .extend_pairs(filters.iter().map(|filters| ("filters", filters)))
                     .finish(),
             )
         }
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum EventFilterType {
     Container,
     Image,
     Volume,
     Network,
     Daemon,
+    Service,
 }
 
-fn event_filter_type_to_string(filter: EventFilterType) -> &'static str {
-    match filter {
-        EventFilterType::Container => "container",
-        EventFilterType::Image => "image",
-        EventFilterType::Volume => "volume",
-        EventFilterType::Network => "network",
-        EventFilterType::Daemon => "daemon",
-    }
-}
-
-/// Filter options for image listings
+/// Filter options for event streams
 pub enum EventFilter {
     Container(String),
     Event(String),
     Image(String),
+    /// Events for objects carrying a label with the given name
     Label(String),
+    /// Events for objects carrying a label with the given name and value
+    LabelValue(String, String),
     Type(EventFilterType),
     Volume(String),
     Network(String),
     Daemon(String),
+    Service(String),
+}
+
+/// Event filters as sent to the daemon, mapping each filter key to the values to match
+#[derive(Clone, Default, Debug, Serialize)]
+struct EventFilters {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    container: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    event: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    image: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    label: Vec<String>,
+    #[serde(rename = "type", skip_serializing_if = "Vec::is_empty")]
+    typ: Vec<EventFilterType>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    volume: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    network: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    daemon: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    service: Vec<String>,
+}
+
+impl EventFilters {
+    fn push(
+        &mut self,
+        filter: EventFilter,
+    ) {
+        match filter {
+            EventFilter::Container(n) => self.container.push(n),
+            EventFilter::Event(n) => self.event.push(n),
+            EventFilter::Image(n) => self.image.push(n),
+            EventFilter::Label(n) => self.label.push(n),
+            EventFilter::LabelValue(n, v) => self.label.push(format!("{}={}", n, v)),
+            EventFilter::Type(t) => self.typ.push(t),
+            EventFilter::Volume(n) => self.volume.push(n),
+            EventFilter::Network(n) => self.network.push(n),
+            EventFilter::Daemon(n) => self.daemon.push(n),
+            EventFilter::Service(n) => self.service.push(n),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.container.is_empty()
+            && self.event.is_empty()
+            && self.image.is_empty()
+            && self.label.is_empty()
+            && self.typ.is_empty()
+            && self.volume.is_empty()
+            && self.network.is_empty()
+            && self.daemon.is_empty()
+            && self.service.is_empty()
+    }
 }
 
 /// Builder interface for `EventOptions`
 #[derive(Default)]
 pub struct EventsOptionsBuilder {
     params: HashMap<&'static str, String>,
-    events: Vec<String>,
-    containers: Vec<String>,
-    images: Vec<String>,
-    labels: Vec<String>,
-    volumes: Vec<String>,
-    networks: Vec<String>,
-    daemons: Vec<String>,
-    types: Vec<String>,
+    filters: EventFilters,
 }
 
 impl EventsOptionsBuilder {
@@ -493,56 +1177,21 @@ impl EventsOptionsBuilder {
         self
     }
 
+    /// Adds filters to the events stream. Filters from repeated calls accumulate.
     pub fn filter(
         &mut self,
         filters: Vec<EventFilter>,
     ) -> &mut Self {
-        let mut params = HashMap::new();
         for f in filters {
-            match f {
-                EventFilter::Container(n) => {
-                    self.containers.push(n);
-                    params.insert("container", self.containers.clone())
-                }
-                EventFilter::Event(n) => {
-                    self.events.push(n);
-                    params.insert("event", self.events.clone())
-                }
-                EventFilter::Image(n) => {
-                    self.images.push(n);
-                    params.insert("image", self.images.clone())
-                }
-                EventFilter::Label(n) => {
-                    self.labels.push(n);
-                    params.insert("label", self.labels.clone())
-                }
-                EventFilter::Volume(n) => {
-                    self.volumes.push(n);
-                    params.insert("volume", self.volumes.clone())
-                }
-                EventFilter::Network(n) => {
-                    self.networks.push(n);
-                    params.insert("network", self.networks.clone())
-                }
-                EventFilter::Daemon(n) => {
-                    self.daemons.push(n);
-                    params.insert("daemon", self.daemons.clone())
-                }
-                EventFilter::Type(n) => {
-                    let event_type = event_filter_type_to_string(n).to_string();
-                    self.types.push(event_type);
-                    params.insert("type", self.types.clone())
-                }
-            };
+            self.filters.push(f);
         }
-        self.params
-            .insert("filters", serde_json::to_string(&params).unwrap());
         self
     }
 
     pub fn build(&self) -> EventsOptions {
         EventsOptions {
             params: self.params.clone(),
+            filters: self.filters.clone(),
         }
     }
 }
@@ -557,6 +1206,7 @@ pub struct Version {
     pub os: String,
     pub arch: String,
     pub kernel_version: String,
+    pub experimental: Option<bool>,
     #[cfg(feature = "chrono")]
     pub build_time: DateTime<Utc>,
     #[cfg(not(feature = "chrono"))]
@@ -568,6 +1218,9 @@ pub struct Version {
 pub struct Info {
     pub containers: u64,
     pub images: u64,
+    /// Whether the daemon runs with debug enabled
+    #[serde(default)]
+    pub debug: bool,
     pub driver: String,
     pub docker_root_dir: String,
     pub driver_status: Vec<Vec<String>>,
@@ -583,9 +1236,33 @@ pub struct Info {
     pub n_goroutines: u64,
     pub name: String,
     pub operating_system: String,
+    /// `linux` or `windows`
+    #[serde(rename = "OSType")]
+    pub os_type: String,
+    /// The default isolation of containers, only reported by windows daemons
+    pub isolation: Option<Isolation>,
     // pub RegistryConfig:???
     pub swap_limit: bool,
     pub system_time: Option<String>,
+    /// `1` or `2`, not reported by daemons older than API version 1.40
+    pub cgroup_version: Option<String>,
+    /// Security features the daemon runs with, such as `name=seccomp,profile=default` or
+    /// `name=rootless`
+    #[serde(default)]
+    pub security_options: Vec<String>,
+    pub swarm: Option<SwarmInfo>,
+}
+
+/// The swarm membership of a daemon, as reported by its info
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SwarmInfo {
+    #[serde(rename = "NodeID")]
+    pub node_id: String,
+    /// `inactive`, `pending`, `active`, `error` or `locked`
+    pub local_node_state: String,
+    #[serde(default)]
+    pub control_available: bool,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -612,6 +1289,35 @@ pub struct Event {
     pub time_nano: u64,
 }
 
+/// A one line summary such as `2021-06-01T10:00:00Z container start 4f8a2c1e9b3d (name=web)`
+impl fmt::Display for Event {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        #[cfg(feature = "chrono")]
+        write!(
+            f,
+            "{}",
+            self.time.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+        )?;
+        #[cfg(not(feature = "chrono"))]
+        write!(f, "{}", self.time)?;
+
+        let id = &self.actor.id;
+        let id = if id.len() == 64 && id.bytes().all(|b| b.is_ascii_hexdigit()) {
+            &id[..12]
+        } else {
+            &id[..]
+        };
+        write!(f, " {} {} {}", self.typ, self.action, id)?;
+        if let Some(name) = self.actor.attributes.get("name") {
+            write!(f, " (name={})", name)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Actor {
     #[serde(rename = "ID")]
@@ -620,6 +1326,47 @@ pub struct Actor {
     pub attributes: HashMap<String, String>,
 }
 
+impl Actor {
+    /// Looks up an attribute of the actor
+    pub fn attribute(
+        &self,
+        key: &str,
+    ) -> Option<&str> {
+        self.attributes.get(key).map(String::as_str)
+    }
+
+    /// The actor's name, such as a container or network name
+    pub fn name(&self) -> Option<&str> {
+        self.attribute("name")
+    }
+}
+
+impl Event {
+    /// Id of the container the event is about, for container events
+    pub fn container_id(&self) -> Option<&str> {
+        if self.typ == "container" {
+            Some(&self.actor.id)
+        } else {
+            None
+        }
+    }
+
+    /// Image the event is about: the image a container runs for container events, or the
+    /// image's name for image events
+    pub fn image_name(&self) -> Option<&str> {
+        match self.typ.as_str() {
+            "container" => self.actor.attribute("image"),
+            "image" => self.actor.name().or(Some(&self.actor.id)),
+            _ => None,
+        }
+    }
+
+    /// Exit code of the container, for `die` events
+    pub fn exit_code(&self) -> Option<i64> {
+        self.actor.attribute("exitCode")?.parse().ok()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #[cfg(feature = "unix-socket")]
@@ -648,4 +1395,230 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn event_accessors() {
+        use super::Event;
+
+        let event: Event = serde_json::from_str(
+            r#"{"Type":"container","Action":"die","Actor":{"ID":"8f2c1e9d4b7a","Attributes":{"exitCode":"137","image":"nginx:1.25","name":"web"}},"scope":"local","time":1700000000,"timeNano":1700000000000000000}"#,
+        )
+        .unwrap();
+        assert_eq!(event.container_id(), Some("8f2c1e9d4b7a"));
+        assert_eq!(event.image_name(), Some("nginx:1.25"));
+        assert_eq!(event.exit_code(), Some(137));
+        assert_eq!(event.actor.name(), Some("web"));
+
+        let event: Event = serde_json::from_str(
+            r#"{"Type":"image","Action":"pull","Actor":{"ID":"nginx:1.25","Attributes":{"name":"nginx"}},"scope":"local","time":1700000000,"timeNano":1700000000000000000}"#,
+        )
+        .unwrap();
+        assert_eq!(event.container_id(), None);
+        assert_eq!(event.image_name(), Some("nginx"));
+        assert_eq!(event.exit_code(), None);
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn docker_host_formats() {
+        use super::{expand_docker_host, tcp_host_url, Uri};
+
+        let url = |docker_host: &str, tls: bool| {
+            let uri: Uri = expand_docker_host(docker_host).parse().unwrap();
+            tcp_host_url(
+                uri.scheme_str().unwrap(),
+                uri.host().unwrap(),
+                uri.port_u16(),
+                tls,
+            )
+            .unwrap()
+        };
+
+        assert_eq!(url("tcp://10.0.0.2:2375", false), "http://10.0.0.2:2375");
+        assert_eq!(url("tcp://10.0.0.2", false), "http://10.0.0.2:2375");
+        assert_eq!(url("tcp://10.0.0.2", true), "https://10.0.0.2:2376");
+        assert_eq!(
+            url("tcp://docker.example.com:4243", true),
+            "https://docker.example.com:4243"
+        );
+        assert_eq!(url("10.0.0.2:2375", false), "http://10.0.0.2:2375");
+        assert_eq!(url(":2376", true), "https://localhost:2376");
+        assert_eq!(url("tcp://", false), "http://localhost:2375");
+        assert_eq!(url("http://localhost:8000", true), "http://localhost:8000");
+        assert_eq!(
+            url("https://docker.example.com", false),
+            "https://docker.example.com:443"
+        );
+        assert_eq!(
+            url("http://docker.example.com", false),
+            "http://docker.example.com:80"
+        );
+    }
+
+    #[test]
+    fn unsupported_host_scheme() {
+        use super::Docker;
+        let err = Docker::try_host("ssh://user@host".parse().unwrap())
+            .err()
+            .unwrap();
+        assert!(matches!(err, crate::Error::UnsupportedScheme(url) if url == "ssh://user@host/"));
+        assert!(Docker::try_host("tcp://localhost:2375".parse().unwrap()).is_ok());
+    }
+
+    #[cfg(feature = "unix-socket")]
+    #[test]
+    fn with_socket_shares_shutdown() {
+        use super::Docker;
+        let d = Docker::unix("/var/run/docker.sock").with_request_limit(4, None);
+        let plugin = d.with_socket("/run/docker/plugins/example.sock");
+        assert_eq!(
+            format!("{:?}", plugin.transport),
+            "Unix(/run/docker/plugins/example.sock)"
+        );
+        assert!(plugin.limiter.is_some());
+        d.shutdown();
+        assert!(plugin.is_shutdown());
+    }
+
+    #[test]
+    fn tcp_keepalive_keeps_host() {
+        use super::Docker;
+        let d = Docker::host("http://localhost:8000".parse().unwrap())
+            .with_request_limit(4, None)
+            .with_tcp_keepalive(std::time::Duration::from_secs(30));
+        assert_eq!(format!("{:?}", d.transport), "Tcp(http://localhost:8000)");
+        assert!(d.limiter.is_some());
+    }
+
+    #[tokio::test]
+    async fn wait_ready_gives_up_after_timeout() {
+        use super::Docker;
+        use std::time::{Duration, Instant};
+
+        // nothing listens on port 1
+        let d = Docker::host("http://127.0.0.1:1".parse().unwrap());
+        let started = Instant::now();
+        assert!(d.wait_ready(Duration::from_millis(300)).await.is_err());
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(300));
+        assert!(elapsed < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn events_options_accumulate_filters() {
+        use super::{EventFilter, EventFilterType, EventsOptions};
+
+        let opts = EventsOptions::builder()
+            .filter(vec![EventFilter::Type(EventFilterType::Container)])
+            .filter(vec![
+                EventFilter::Label("com.example.app".to_string()),
+                EventFilter::LabelValue("env".to_string(), "prod".to_string()),
+            ])
+            .build();
+
+        let serialized = opts.serialize().unwrap();
+        let filters = url::form_urlencoded::parse(serialized.as_bytes())
+            .find(|(k, _)| k == "filters")
+            .map(|(_, v)| v.into_owned())
+            .unwrap();
+        assert_eq!(
+            filters,
+            r#"{"label":["com.example.app","env=prod"],"type":["container"]}"#
+        );
+
+        assert!(EventsOptions::default().serialize().is_none());
+    }
+
+    #[test]
+    fn event_display() {
+        let event: super::Event = serde_json::from_str(
+            r#"{"Type":"container","Action":"start","Actor":{"ID":"4f8a2c1e9b3d7a6f5e4d3c2b1a0f9e8d7c6b5a4f3e2d1c0b9a8f7e6d5c4b3a2f","Attributes":{"image":"nginx","name":"web"}},"time":1622541600,"timeNano":1622541600000000000}"#,
+        )
+        .unwrap();
+
+        #[cfg(feature = "chrono")]
+        assert_eq!(
+            event.to_string(),
+            "2021-06-01T10:00:00Z container start 4f8a2c1e9b3d (name=web)"
+        );
+        #[cfg(not(feature = "chrono"))]
+        assert_eq!(
+            event.to_string(),
+            "1622541600 container start 4f8a2c1e9b3d (name=web)"
+        );
+    }
+
+    #[tokio::test]
+    async fn stream_post_responded_holds_its_slot() {
+        use crate::{
+            mock::{MockDaemon, Route},
+            Error,
+        };
+        let daemon = MockDaemon::start(vec![Route::new("POST /wait", 200, "{}")]);
+        let docker = daemon.docker().clone().with_request_limit(1, Some(0));
+
+        let response = docker.stream_post_responded("/wait").await.unwrap();
+        assert!(matches!(docker.ping().await, Err(Error::RequestQueueFull)));
+        drop(response);
+        assert!(!matches!(docker.ping().await, Err(Error::RequestQueueFull)));
+    }
+
+    const VERSION: &str = r#"{"Version":"20.10.0","ApiVersion":"1.41","GitCommit":"eeddea2",
+        "GoVersion":"go1.13.15","Os":"linux","Arch":"amd64","KernelVersion":"5.4.0",
+        "BuildTime":"2020-12-08T18:58:04Z"}"#;
+
+    #[tokio::test]
+    async fn require_only_checks_the_version() {
+        use crate::{
+            capabilities::Feature,
+            mock::{MockDaemon, Route},
+        };
+        let daemon = MockDaemon::start(vec![
+            Route::new("GET /version", 200, VERSION),
+            Route::new("GET /info", 500, r#"{"message":"info is broken"}"#),
+        ]);
+
+        let docker = daemon.docker();
+        docker.require(&[Feature::CgroupnsMode]).await.unwrap();
+        assert!(docker.require(&[Feature::ClusterVolume]).await.is_err());
+        assert!(docker.capabilities().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn capabilities_read_the_builder_version_of_pings() {
+        use crate::mock::{MockDaemon, Route};
+        let info = r#"{"Containers":0,"Images":0,"Driver":"overlay2",
+            "DockerRootDir":"/var/lib/docker","DriverStatus":[],"ID":"abc","KernelVersion":"5.4.0",
+            "MemTotal":1,"MemoryLimit":true,"NCPU":1,"NEventsListener":0,"NGoroutines":1,
+            "Name":"host","OperatingSystem":"Ubuntu","OSType":"linux","SwapLimit":true}"#;
+        let daemon = MockDaemon::start(vec![
+            Route::new("GET /version", 200, VERSION),
+            Route::new("GET /info", 200, info),
+            Route::new("GET /_ping", 200, "OK").header("Builder-Version", "2"),
+        ]);
+
+        let capabilities = daemon.docker().capabilities().await.unwrap();
+        assert!(capabilities.buildkit_available);
+        assert_eq!(capabilities.api_version.to_string(), "1.41");
+    }
+
+    #[cfg(not(feature = "tls"))]
+    #[test]
+    fn tls_hosts_need_the_tls_feature() {
+        use super::tcp_host_url;
+        use crate::Error;
+
+        assert_eq!(
+            tcp_host_url("tcp", "10.0.0.2", None, false).unwrap(),
+            "http://10.0.0.2:2375"
+        );
+        assert!(matches!(
+            tcp_host_url("tcp", "10.0.0.2", None, true),
+            Err(Error::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            tcp_host_url("https", "docker.example.com", None, false),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
 }
@@ -2,7 +2,7 @@
 
 use hyper::{self, http, StatusCode};
 use serde_json::Error as SerdeError;
-use std::{error::Error as StdError, fmt, string::FromUtf8Error};
+use std::{error::Error as StdError, fmt, string::FromUtf8Error, time::Duration};
 
 use futures_util::io::Error as IoError;
 
@@ -23,6 +23,69 @@ pub enum Error {
         message: String,
     },
     ConnectionNotUpgraded,
+    RequestQueueFull,
+    Shutdown,
+    /// A command run through one of the output collecting helpers exited with a non-zero code
+    NonZeroExit {
+        code: i64,
+        stderr: String,
+    },
+    /// The daemon reported an error partway through a streamed operation such as a pull or build
+    OperationFailed(String),
+    /// The registry throttled the operation. `retry_after` is set when the registry said how
+    /// long to back off for.
+    RateLimited {
+        message: String,
+        retry_after: Option<Duration>,
+    },
+    /// An existing object does not match the spec it was expected to have
+    SpecMismatch {
+        name: String,
+        differences: Vec<String>,
+    },
+    /// A container name that doesn't match the pattern docker allows,
+    /// `[a-zA-Z0-9][a-zA-Z0-9_.-]+`
+    InvalidContainerName(String),
+    /// The name a container was to be created with is taken by the container `id`
+    ContainerNameConflict {
+        name: String,
+        id: String,
+    },
+    /// A container could not start because a host port it publishes is taken
+    PortAlreadyAllocated {
+        port: u16,
+    },
+    /// A container could not start because the host path of a bind mount does not exist
+    BindSourceMissing {
+        path: String,
+    },
+    /// An option the daemon doesn't support was used, detected before making the request
+    UnsupportedFeature {
+        feature: String,
+        reason: String,
+    },
+    /// The docker host url has a scheme this build can't connect over, such as `unix` without
+    /// the `unix-socket` feature or `ssh`
+    UnsupportedScheme(String),
+    /// The docker CLI's configuration or a context it refers to is missing or malformed
+    InvalidConfig(String),
+    /// A pulled image did not have the digest it was expected to have
+    DigestMismatch {
+        expected: String,
+        id: String,
+        repo_digests: Vec<String>,
+    },
+    /// A pull that resolves with the pulled image was given no image to pull, such as one only
+    /// importing from a `src` url
+    MissingImageReference,
+    /// A call did not complete within the deadline set with
+    /// [with_deadline](crate::WithDeadline::with_deadline)
+    DeadlineExceeded(Duration),
+    /// A response body was larger than the client's
+    /// [maximum response size](crate::Docker::with_max_response_size)
+    ResponseTooLarge {
+        limit: usize,
+    },
 }
 
 impl From<SerdeError> for Error {
@@ -33,6 +96,17 @@ impl From<SerdeError> for Error {
 
 impl From<hyper::Error> for Error {
     fn from(error: hyper::Error) -> Error {
+        // a streamed request body failing to be read, such as a directory being archived as it is
+        // uploaded, is reported as the IO error it failed with
+        if error.is_user() {
+            let mut cause = error.source();
+            while let Some(source) = cause {
+                if let Some(io) = source.downcast_ref::<IoError>() {
+                    return Error::IO(IoError::new(io.kind(), io.to_string()));
+                }
+                cause = source.source();
+            }
+        }
         Error::Hyper(error)
     }
 }
@@ -82,6 +156,83 @@ impl fmt::Display for Error {
                 f,
                 "expected the docker host to upgrade the HTTP connection but it did not"
             ),
+            Error::RequestQueueFull => write!(
+                f,
+                "too many requests are already waiting for a free slot on this client"
+            ),
+            Error::Shutdown => write!(f, "the client has been shut down"),
+            Error::NonZeroExit { code, stderr } => {
+                write!(f, "command exited with code {}", code)?;
+                if !stderr.is_empty() {
+                    write!(f, ": {}", stderr.trim_end())?;
+                }
+                Ok(())
+            }
+            Error::OperationFailed(ref message) => write!(f, "{}", message),
+            Error::RateLimited {
+                message,
+                retry_after,
+            } => {
+                write!(f, "rate limited: {}", message)?;
+                if let Some(retry_after) = retry_after {
+                    write!(f, " (retry after {}s)", retry_after.as_secs())?;
+                }
+                Ok(())
+            }
+            Error::SpecMismatch { name, differences } => write!(
+                f,
+                "{} does not match its expected spec: {}",
+                name,
+                differences.join(", ")
+            ),
+            Error::InvalidContainerName(ref name) => write!(
+                f,
+                "invalid container name {:?}, only [a-zA-Z0-9][a-zA-Z0-9_.-]+ are allowed",
+                name
+            ),
+            Error::ContainerNameConflict { name, id } => write!(
+                f,
+                "container name {} is already in use by container {}",
+                name, id
+            ),
+            Error::PortAlreadyAllocated { port } => {
+                write!(f, "host port {} is already allocated", port)
+            }
+            Error::BindSourceMissing { path } => {
+                write!(f, "bind mount source path {} does not exist", path)
+            }
+            Error::UnsupportedFeature { feature, reason } => {
+                write!(f, "{} is not supported: {}", feature, reason)
+            }
+            Error::UnsupportedScheme(ref url) => {
+                write!(f, "unsupported scheme in docker host url {}", url)
+            }
+            Error::InvalidConfig(ref message) => {
+                write!(f, "invalid docker configuration: {}", message)
+            }
+            Error::DigestMismatch {
+                expected,
+                id,
+                repo_digests,
+            } => write!(
+                f,
+                "expected image digest {} but pulled {} with digests [{}]",
+                expected,
+                id,
+                repo_digests.join(", ")
+            ),
+            Error::MissingImageReference => {
+                write!(
+                    f,
+                    "the pull options don't name an image to resolve once pulled"
+                )
+            }
+            Error::DeadlineExceeded(deadline) => {
+                write!(f, "call did not complete within {:?}", deadline)
+            }
+            Error::ResponseTooLarge { limit } => {
+                write!(f, "response body exceeds the limit of {} bytes", limit)
+            }
         }
     }
 }
@@ -97,3 +248,157 @@ impl StdError for Error {
         }
     }
 }
+
+impl Error {
+    /// Turns an error reported by the daemon into [Error::RateLimited](Error::RateLimited) if
+    /// it is a registry's rate limit response, as with Docker Hub's `toomanyrequests`.
+    pub(crate) fn rate_limited(self) -> Error {
+        match self {
+            Error::Fault { code, message }
+                if code == StatusCode::TOO_MANY_REQUESTS || is_rate_limit_message(&message) =>
+            {
+                Error::RateLimited {
+                    retry_after: retry_after(&message),
+                    message,
+                }
+            }
+            Error::OperationFailed(message) if is_rate_limit_message(&message) => {
+                Error::RateLimited {
+                    retry_after: retry_after(&message),
+                    message,
+                }
+            }
+            other => other,
+        }
+    }
+
+    /// Turns the daemon's error for a container that failed to start into a structured variant
+    /// for the causes callers can act on, a taken host port or a missing bind mount source
+    pub(crate) fn start_failure(self) -> Error {
+        match self {
+            Error::Fault { code, message } => {
+                if let Some(port) = allocated_port(&message) {
+                    Error::PortAlreadyAllocated { port }
+                } else if let Some(path) = missing_bind_source(&message) {
+                    Error::BindSourceMissing { path }
+                } else {
+                    Error::Fault { code, message }
+                }
+            }
+            other => other,
+        }
+    }
+}
+
+/// Extracts the port from `Bind for 0.0.0.0:8080 failed: port is already allocated` or
+/// `listen tcp4 0.0.0.0:8080: bind: address already in use`
+fn allocated_port(message: &str) -> Option<u16> {
+    let address = if let Some(start) = message.find("Bind for ") {
+        let rest = &message[start + "Bind for ".len()..];
+        if !rest.contains("port is already allocated") {
+            return None;
+        }
+        rest.split(" failed").next()?
+    } else if message.contains("address already in use") {
+        let start = message.find("listen ")? + "listen ".len();
+        let rest = &message[start..];
+        rest.split_whitespace().nth(1)?.trim_end_matches(':')
+    } else {
+        return None;
+    };
+    address.rsplit(':').next()?.parse().ok()
+}
+
+/// Extracts the path from `bind source path does not exist: /srv/data`
+fn missing_bind_source(message: &str) -> Option<String> {
+    let start = message.find("bind source path does not exist: ")?
+        + "bind source path does not exist: ".len();
+    Some(message[start..].trim_end().to_owned())
+}
+
+fn is_rate_limit_message(message: &str) -> bool {
+    let message = message.to_ascii_lowercase();
+    message.contains("toomanyrequests")
+        || message.contains("too many requests")
+        || message.contains("rate limit")
+}
+
+/// Extracts the number of seconds from a `Retry-After: N` or `retry after Ns` hint in a message
+fn retry_after(message: &str) -> Option<Duration> {
+    let message = message.to_ascii_lowercase();
+    let start = message
+        .find("retry-after")
+        .or_else(|| message.find("retry after"))?
+        + "retry after".len();
+    let seconds: String = message[start..]
+        .trim_start_matches(|c: char| c == ':' || c == '=' || c.is_whitespace())
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect();
+    seconds.parse().ok().map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_limited_recognizes_registry_responses() {
+        let error = Error::OperationFailed(
+            "toomanyrequests: You have reached your pull rate limit. You may increase the limit \
+             by authenticating and upgrading: https://www.docker.com/increase-rate-limit"
+                .into(),
+        )
+        .rate_limited();
+        match error {
+            Error::RateLimited { retry_after, .. } => assert_eq!(retry_after, None),
+            other => panic!("expected a rate limit error, got {:?}", other),
+        }
+
+        let error = Error::Fault {
+            code: StatusCode::TOO_MANY_REQUESTS,
+            message: "slow down, Retry-After: 30".into(),
+        }
+        .rate_limited();
+        match error {
+            Error::RateLimited { retry_after, .. } => {
+                assert_eq!(retry_after, Some(Duration::from_secs(30)))
+            }
+            other => panic!("expected a rate limit error, got {:?}", other),
+        }
+
+        let error = Error::OperationFailed("manifest unknown".into()).rate_limited();
+        assert!(matches!(error, Error::OperationFailed(_)));
+    }
+
+    #[test]
+    fn start_failure_recognizes_causes() {
+        let fault = |message: &str| Error::Fault {
+            code: StatusCode::INTERNAL_SERVER_ERROR,
+            message: message.into(),
+        };
+
+        let error = fault(
+            "driver failed programming external connectivity on endpoint web (8f2c1e9d): Bind for \
+             0.0.0.0:8080 failed: port is already allocated",
+        )
+        .start_failure();
+        assert!(matches!(error, Error::PortAlreadyAllocated { port: 8080 }));
+
+        let error = fault(
+            "driver failed programming external connectivity on endpoint web (8f2c1e9d): Error \
+             starting userland proxy: listen tcp4 0.0.0.0:5432: bind: address already in use",
+        )
+        .start_failure();
+        assert!(matches!(error, Error::PortAlreadyAllocated { port: 5432 }));
+
+        let error = fault(
+            "invalid mount config for type \"bind\": bind source path does not exist: /srv/data",
+        )
+        .start_failure();
+        assert!(matches!(error, Error::BindSourceMissing { ref path } if path == "/srv/data"));
+
+        let error = fault("OCI runtime create failed").start_failure();
+        assert!(matches!(error, Error::Fault { .. }));
+    }
+}
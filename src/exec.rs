@@ -2,18 +2,14 @@
 //!
 //! API Reference: <https://docs.docker.com/engine/api/v1.41/#tag/Exec>
 
-use std::{
-    collections::{BTreeMap, HashMap},
-    hash::Hash,
-    iter,
-};
+use std::{collections::HashMap, iter};
 
-use futures_util::{stream::Stream, TryFutureExt};
+use futures_util::{stream::Stream, TryFutureExt, TryStreamExt};
 use hyper::Body;
 use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
 
 use crate::{
+    container::{ExitInfo, ExitReason},
     errors::{Error, Result},
     tty, Docker,
 };
@@ -93,6 +89,7 @@ impl<'docker> Exec<'docker> {
         // the stream. But for backwards compatability, we have to return the error inside of the
         // stream.
         let body_result = opts.serialize();
+        let tty = opts.tty();
 
         // To not tie the lifetime of `container_id` to the stream, we convert it to an (owned)
         // endpoint outside of the stream.
@@ -114,7 +111,7 @@ impl<'docker> Exec<'docker> {
                     None::<iter::Empty<_>>,
                 ));
 
-                Ok(tty::decode(stream))
+                Ok(tty::decode_output(stream, tty))
             }
             .try_flatten_stream(),
         )
@@ -135,33 +132,87 @@ impl<'docker> Exec<'docker> {
         Exec::new(docker, id)
     }
 
-    /// Starts this exec instance returning a multiplexed tty stream
+    /// Starts this exec instance returning a stream of its output
+    ///
+    /// The exec instance is inspected first to learn whether it runs with a
+    /// [tty](ExecContainerOptionsBuilder::tty). Its output is demultiplexed into stdout and
+    /// stderr chunks if not, and yielded as stdout chunks as is if so, as the daemon doesn't
+    /// multiplex the output of a tty.
     ///
     /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ExecStart)
     pub fn start(&self) -> impl Stream<Item = Result<tty::TtyChunk>> + 'docker {
         // We must take ownership of the docker reference to not needlessly tie the stream to the
         // lifetime of `self`.
         let docker = self.docker;
-        // We convert `self.id` into the (owned) endpoint outside of the stream to not needlessly
+        // We convert `self.id` into the (owned) endpoints outside of the stream to not needlessly
         // tie the stream to the lifetime of `self`.
+        let inspect_endpoint = format!("/exec/{}/json", &self.id);
         let endpoint = format!("/exec/{}/start", &self.id);
         Box::pin(
             async move {
+                let details: ExecDetails = docker.get_json(&inspect_endpoint).await?;
                 let stream = Box::pin(docker.stream_post(
                     endpoint,
                     Some(("{}".into(), mime::APPLICATION_JSON)),
                     None::<iter::Empty<_>>,
                 ));
 
-                Ok(tty::decode(stream))
+                Ok(tty::decode_output(stream, details.process_config.tty))
             }
             .try_flatten_stream(),
         )
     }
 
+    /// Starts this exec instance on a hijacked connection, returning a
+    /// [Multiplexer](crate::tty::Multiplexer) that streams its output and writes to its stdin.
+    ///
+    /// The exec instance must have been created with
+    /// [attach_stdin](ExecContainerOptionsBuilder::attach_stdin) for input to reach the command.
+    /// Use [close_stdin](crate::tty::Multiplexer::close_stdin) to send EOF while still reading
+    /// the remaining output.
+    ///
+    /// The output of an exec instance running with a [tty](ExecContainerOptionsBuilder::tty) is
+    /// read as stdout as is, as the daemon doesn't multiplex it.
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ExecStart)
+    pub async fn attach(&self) -> Result<tty::Multiplexer<'docker>> {
+        let tty = self.inspect().await?.process_config.tty;
+        let connection = self
+            .docker
+            .stream_post_upgrade(
+                format!("/exec/{}/start", &self.id),
+                Some((r#"{"Detach":false}"#.into(), mime::APPLICATION_JSON)),
+            )
+            .await?;
+
+        if tty {
+            Ok(tty::Multiplexer::new_raw(connection))
+        } else {
+            Ok(tty::Multiplexer::new(connection))
+        }
+    }
+
+    /// Starts this exec instance and waits for it to finish, collecting its output and exit code
+    ///
+    /// A non-zero exit code is not an error by itself, use
+    /// [CommandOutput::into_result](CommandOutput::into_result) to treat it as one.
+    pub async fn output(&self) -> Result<CommandOutput> {
+        let (stdout, stderr) = CommandOutput::collect(self.start()).await?;
+        let exit_code = self.inspect().await?.exit_code.ok_or_else(|| {
+            Error::InvalidResponse("exec instance finished without an exit code".into())
+        })?;
+
+        Ok(CommandOutput {
+            exit_code,
+            stdout,
+            stderr,
+            exit_info: None,
+        })
+    }
+
     /// Inspect this exec instance to aquire detailed information
     ///
-    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ExecInpsect)
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ExecInspect)
     pub async fn inspect(&self) -> Result<ExecDetails> {
         self.docker
             .get_json(&format!("/exec/{}/json", &self.id)[..])
@@ -176,12 +227,70 @@ impl<'docker> Exec<'docker> {
         &self,
         opts: &ExecResizeOptions,
     ) -> Result<()> {
-        let body: Body = opts.serialize()?.into();
+        let mut path = vec![format!("/exec/{}/resize", &self.id)];
+        if let Some(query) = opts.serialize() {
+            path.push(query);
+        }
+        self.docker.post(&path.join("?"), None).await?;
+        Ok(())
+    }
+}
 
-        self.docker
-            .post_json(
-                &format!("/exec/{}/resize", &self.id)[..],
-                Some((body, mime::APPLICATION_JSON)),
+/// Output of a command that ran to completion
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CommandOutput {
+    pub exit_code: i64,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    /// How the container ended, for the output of a container's main process when it could be
+    /// inspected after exiting
+    pub exit_info: Option<ExitInfo>,
+}
+
+impl CommandOutput {
+    /// Returns true if the command exited with code 0
+    pub fn success(&self) -> bool {
+        self.exit_code == 0
+    }
+
+    /// Classifies why the command exited, from [exit_info](CommandOutput::exit_info) when
+    /// known and from the exit code alone otherwise
+    pub fn exit_reason(&self) -> ExitReason {
+        match self.exit_info {
+            Some(ref info) => info.reason(),
+            None => ExitReason::classify(self.exit_code, false, None),
+        }
+    }
+
+    /// Turns a non-zero exit code into an [Error::NonZeroExit](crate::Error::NonZeroExit)
+    /// carrying the command's stderr
+    pub fn into_result(self) -> Result<CommandOutput> {
+        if self.success() {
+            Ok(self)
+        } else {
+            Err(Error::NonZeroExit {
+                code: self.exit_code,
+                stderr: String::from_utf8_lossy(&self.stderr).into_owned(),
+            })
+        }
+    }
+
+    /// Drains a tty stream into separate stdout and stderr buffers
+    pub(crate) async fn collect<S>(stream: S) -> Result<(Vec<u8>, Vec<u8>)>
+    where
+        S: Stream<Item = Result<tty::TtyChunk>>,
+    {
+        stream
+            .try_fold(
+                (Vec::new(), Vec::new()),
+                |(mut stdout, mut stderr), chunk| async move {
+                    match chunk {
+                        tty::TtyChunk::StdOut(bytes) => stdout.extend(bytes),
+                        tty::TtyChunk::StdErr(bytes) => stderr.extend(bytes),
+                        tty::TtyChunk::StdIn(_) => (),
+                    }
+                    Ok((stdout, stderr))
+                },
             )
             .await
     }
@@ -219,6 +328,11 @@ impl ExecContainerOptions {
 
         serde_json::to_string(&body).map_err(Error::from)
     }
+
+    /// Whether the command runs with a tty, and so its output isn't multiplexed
+    pub(crate) fn tty(&self) -> bool {
+        self.params_bool.get("Tty").copied().unwrap_or(false)
+    }
 }
 
 #[derive(Default)]
@@ -234,10 +348,7 @@ impl ExecContainerOptionsBuilder {
         cmds: Vec<&str>,
     ) -> &mut Self {
         for cmd in cmds {
-            self.params
-                .entry("Cmd")
-                .or_insert_with(Vec::new)
-                .push(cmd.to_owned());
+            self.params.entry("Cmd").or_default().push(cmd.to_owned());
         }
         self
     }
@@ -248,14 +359,21 @@ impl ExecContainerOptionsBuilder {
         envs: Vec<&str>,
     ) -> &mut Self {
         for env in envs {
-            self.params
-                .entry("Env")
-                .or_insert_with(Vec::new)
-                .push(env.to_owned());
+            self.params.entry("Env").or_default().push(env.to_owned());
         }
         self
     }
 
+    /// Attach to stdin of the exec command, so it can be written to through
+    /// [Exec::attach](Exec::attach)
+    pub fn attach_stdin(
+        &mut self,
+        stdin: bool,
+    ) -> &mut Self {
+        self.params_bool.insert("AttachStdin", stdin);
+        self
+    }
+
     /// Attach to stdout of the exec command
     pub fn attach_stdout(
         &mut self,
@@ -274,6 +392,16 @@ impl ExecContainerOptionsBuilder {
         self
     }
 
+    /// Allocate a pseudo-TTY for the exec command. Its stdout and stderr are then streamed
+    /// together as stdout.
+    pub fn tty(
+        &mut self,
+        tty: bool,
+    ) -> &mut Self {
+        self.params_bool.insert("Tty", tty);
+        self
+    }
+
     pub fn build(&self) -> ExecContainerOptions {
         ExecContainerOptions {
             params: self.params.clone(),
@@ -282,75 +410,28 @@ impl ExecContainerOptionsBuilder {
     }
 }
 
-/// Interface for creating volumes
-#[derive(Serialize, Debug)]
+/// Size of the TTY an exec instance runs with
+#[derive(Default, Debug)]
 pub struct ExecResizeOptions {
-    params: HashMap<&'static str, Value>,
-}
-
-impl ExecResizeOptions {
-    /// serialize options as a string. returns None if no options are defined
-    pub fn serialize(&self) -> Result<String> {
-        serde_json::to_string(&self.params).map_err(Error::from)
-    }
-
-    pub fn parse_from<'a, K, V>(
-        &self,
-        params: &'a HashMap<K, V>,
-        body: &mut BTreeMap<String, Value>,
-    ) where
-        &'a HashMap<K, V>: IntoIterator,
-        K: ToString + Eq + Hash,
-        V: Serialize,
-    {
-        for (k, v) in params.iter() {
-            let key = k.to_string();
-            let value = serde_json::to_value(v).unwrap();
-
-            body.insert(key, value);
-        }
-    }
-
-    /// return a new instance of a builder for options
-    pub fn builder() -> ExecResizeOptionsBuilder {
-        ExecResizeOptionsBuilder::new()
-    }
+    params: HashMap<&'static str, String>,
 }
 
 #[derive(Default)]
 pub struct ExecResizeOptionsBuilder {
-    params: HashMap<&'static str, Value>,
+    params: HashMap<&'static str, String>,
 }
 
 impl ExecResizeOptionsBuilder {
-    pub(crate) fn new() -> Self {
-        let params = HashMap::new();
-        ExecResizeOptionsBuilder { params }
-    }
-
-    pub fn height(
-        &mut self,
-        height: u64,
-    ) -> &mut Self {
-        self.params.insert("Name", json!(height));
-        self
-    }
-
-    pub fn width(
-        &mut self,
-        width: u64,
-    ) -> &mut Self {
-        self.params.insert("Name", json!(width));
-        self
-    }
-
-    pub fn build(&self) -> ExecResizeOptions {
-        ExecResizeOptions {
-            params: self.params.clone(),
-        }
+    query_setters! {
+        /// Height of the TTY in characters
+        height: u64 => "h",
+        /// Width of the TTY in characters
+        width: u64 => "w",
     }
 }
 
+impl_query_options!(ExecResizeOptions, ExecResizeOptionsBuilder);
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct ExecDetails {
@@ -358,7 +439,7 @@ pub struct ExecDetails {
     #[serde(rename = "ContainerID")]
     pub container_id: String,
     pub detach_keys: String,
-    pub exit_code: Option<u64>,
+    pub exit_code: Option<i64>,
     #[serde(rename = "ID")]
     pub id: String,
     pub open_stderr: bool,
@@ -377,3 +458,85 @@ pub struct ProcessConfig {
     pub tty: bool,
     pub user: Option<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_output_into_result() {
+        let output = CommandOutput {
+            exit_code: 0,
+            stdout: b"ok".to_vec(),
+            stderr: Vec::new(),
+            exit_info: None,
+        };
+        assert_eq!(output.clone().into_result().unwrap(), output);
+
+        let output = CommandOutput {
+            exit_code: 2,
+            stdout: Vec::new(),
+            stderr: b"no such file\n".to_vec(),
+            exit_info: None,
+        };
+        match output.into_result() {
+            Err(Error::NonZeroExit { code, stderr }) => {
+                assert_eq!(code, 2);
+                assert_eq!(stderr, "no such file\n");
+            }
+            other => panic!("expected a non-zero exit error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn command_output_collect() {
+        let chunks = futures_util::stream::iter(vec![
+            Ok(tty::TtyChunk::StdOut(b"a".to_vec())),
+            Ok(tty::TtyChunk::StdErr(b"b".to_vec())),
+            Ok(tty::TtyChunk::StdOut(b"c".to_vec())),
+        ]);
+
+        let (stdout, stderr) = futures::executor::block_on(CommandOutput::collect(chunks)).unwrap();
+        assert_eq!(stdout, b"ac");
+        assert_eq!(stderr, b"b");
+    }
+
+    #[test]
+    fn exec_options_tty() {
+        let opts = ExecContainerOptions::builder()
+            .cmd(vec!["sh"])
+            .tty(true)
+            .build();
+        assert!(opts.tty());
+        assert!(opts.serialize().unwrap().contains(r#""Tty":true"#));
+        assert!(!ExecContainerOptions::builder().build().tty());
+    }
+
+    #[test]
+    fn exec_resize_options() {
+        let opts = ExecResizeOptions::builder().width(120).height(40).build();
+        let query = opts.serialize().unwrap();
+        let mut pairs: Vec<&str> = query.split('&').collect();
+        pairs.sort_unstable();
+        assert_eq!(pairs, vec!["h=40", "w=120"]);
+    }
+
+    #[test]
+    fn exec_details_of_finished_exec() {
+        let details: ExecDetails = serde_json::from_str(
+            r#"{
+                "CanRemove": false, "ContainerID": "b53ee82b53a4", "DetachKeys": "",
+                "ExitCode": 2, "ID": "f33bbfb39f5b", "OpenStderr": true, "OpenStdin": false,
+                "OpenStdout": true, "Running": false, "Pid": 42,
+                "ProcessConfig": {
+                    "arguments": ["-c", "exit 2"], "entrypoint": "sh", "privileged": false,
+                    "tty": true, "user": ""
+                }
+            }"#,
+        )
+        .unwrap();
+        assert!(!details.running);
+        assert_eq!(details.exit_code, Some(2));
+        assert!(details.process_config.tty);
+    }
+}
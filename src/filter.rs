@@ -0,0 +1,268 @@
+//! Filters for narrowing down list results.
+//!
+//! Every list endpoint takes its filters as a JSON object mapping a key to the values to match.
+//! A [`Filter`](struct.Filter.html) is one such `key=value` pair, or `key!=value` when negated.
+//! The per-endpoint filter enums, such as `ContainerFilter`, convert into a `Filter`, and any
+//! filter supported by the daemon that has no typed constructor can be expressed with
+//! [`Filter::new`](struct.Filter.html#method.new).
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde_json::{Map, Value};
+
+/// A single `key=value` or `key!=value` filter on list results
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Filter {
+    key: String,
+    value: String,
+    negated: bool,
+}
+
+impl Filter {
+    /// Matches results where `key` equals `value`
+    pub fn new<K, V>(
+        key: K,
+        value: V,
+    ) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        Filter {
+            key: key.into(),
+            value: value.into(),
+            negated: false,
+        }
+    }
+
+    /// Matches results where `key` does not equal `value`
+    pub fn not<K, V>(
+        key: K,
+        value: V,
+    ) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        Filter::new(key, value).negate()
+    }
+
+    /// Inverts the filter, turning `key=value` into `key!=value` and back
+    pub fn negate(mut self) -> Self {
+        self.negated = !self.negated;
+        self
+    }
+
+    /// Matches results carrying a label with the given name, whatever its value
+    pub fn label<N>(name: N) -> Self
+    where
+        N: Into<String>,
+    {
+        Filter::new("label", name)
+    }
+
+    /// Matches results carrying a label with the given name and value
+    pub fn label_value<N, V>(
+        name: N,
+        value: V,
+    ) -> Self
+    where
+        N: Into<String>,
+        V: Into<String>,
+    {
+        Filter::new("label", format!("{}={}", name.into(), value.into()))
+    }
+
+    /// Matches results by name
+    pub fn name<N>(name: N) -> Self
+    where
+        N: Into<String>,
+    {
+        Filter::new("name", name)
+    }
+
+    /// Matches results by id
+    pub fn id<I>(id: I) -> Self
+    where
+        I: Into<String>,
+    {
+        Filter::new("id", id)
+    }
+
+    /// Matches dangling, or with `false` non-dangling, images and volumes
+    pub fn dangling(dangling: bool) -> Self {
+        Filter::new("dangling", dangling.to_string())
+    }
+
+    /// Matches results by driver
+    pub fn driver<D>(driver: D) -> Self
+    where
+        D: Into<String>,
+    {
+        Filter::new("driver", driver)
+    }
+
+    /// The key this filter is sent under. Negated filters use the daemon's `key!` form.
+    pub fn key(&self) -> String {
+        if self.negated {
+            format!("{}!", self.key)
+        } else {
+            self.key.clone()
+        }
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn is_negated(&self) -> bool {
+        self.negated
+    }
+}
+
+/// A set of label requirements, usable both as list filters sent to the daemon and to check the
+/// labels of objects already fetched, such as to find the objects a controller owns
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LabelSelector {
+    requirements: Vec<(String, Option<String>)>,
+}
+
+impl LabelSelector {
+    /// A selector matching everything until requirements are added
+    pub fn new() -> Self {
+        LabelSelector::default()
+    }
+
+    /// Requires a label with the given name, whatever its value
+    pub fn has<N>(
+        mut self,
+        name: N,
+    ) -> Self
+    where
+        N: Into<String>,
+    {
+        self.requirements.push((name.into(), None));
+        self
+    }
+
+    /// Requires a label with the given name and value
+    pub fn equals<N, V>(
+        mut self,
+        name: N,
+        value: V,
+    ) -> Self
+    where
+        N: Into<String>,
+        V: Into<String>,
+    {
+        self.requirements.push((name.into(), Some(value.into())));
+        self
+    }
+
+    /// Whether `labels` meet every requirement
+    pub fn matches(
+        &self,
+        labels: &HashMap<String, String>,
+    ) -> bool {
+        self.requirements
+            .iter()
+            .all(|(name, value)| match (labels.get(name), value) {
+                (Some(actual), Some(value)) => actual == value,
+                (Some(_), None) => true,
+                (None, _) => false,
+            })
+    }
+
+    /// The requirements as list filters
+    pub fn filters(&self) -> Vec<Filter> {
+        self.requirements
+            .iter()
+            .map(|(name, value)| match value {
+                Some(value) => Filter::label_value(name.as_str(), value.as_str()),
+                None => Filter::label(name.as_str()),
+            })
+            .collect()
+    }
+}
+
+/// Describes how the `existing` labels of an object differ from the `wanted` labels of a create
+/// request body, in the order of the wanted label names. Labels not asked for are not compared.
+pub(crate) fn label_differences(
+    wanted: &Map<String, Value>,
+    existing: &HashMap<String, String>,
+) -> Vec<String> {
+    let mut keys: Vec<&String> = wanted.keys().collect();
+    keys.sort();
+    keys.into_iter()
+        .filter_map(|key| {
+            let wanted = wanted[key].as_str().unwrap_or_default();
+            match existing.get(key) {
+                Some(value) if value == wanted => None,
+                Some(value) => Some(format!("label {} is {} instead of {}", key, value, wanted)),
+                None => Some(format!("label {} is missing", key)),
+            }
+        })
+        .collect()
+}
+
+/// Encodes filters as the JSON object of keys to lists of values expected by the daemon
+pub(crate) fn serialize<I, F>(filters: I) -> String
+where
+    I: IntoIterator<Item = F>,
+    F: Into<Filter>,
+{
+    let mut param: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for filter in filters {
+        let filter = filter.into();
+        param.entry(filter.key()).or_default().push(filter.value);
+    }
+    serde_json::to_string(&param).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn label_selector_matches_and_filters() {
+        let selector = LabelSelector::new()
+            .has("com.example.owner")
+            .equals("env", "prod");
+
+        let mut labels = HashMap::new();
+        labels.insert("com.example.owner".to_owned(), "deployer".to_owned());
+        assert!(!selector.matches(&labels));
+        labels.insert("env".to_owned(), "prod".to_owned());
+        assert!(selector.matches(&labels));
+        labels.insert("env".to_owned(), "dev".to_owned());
+        assert!(!selector.matches(&labels));
+
+        assert_eq!(
+            serialize(selector.filters()),
+            r#"{"label":["com.example.owner","env=prod"]}"#
+        );
+    }
+
+    #[test]
+    fn serialize_groups_values_by_key() {
+        let filters = vec![
+            Filter::label("com.example.app"),
+            Filter::label_value("env", "prod"),
+            Filter::not("label", "tier=db"),
+            Filter::dangling(true),
+        ];
+
+        assert_eq!(
+            serialize(filters),
+            r#"{"dangling":["true"],"label":["com.example.app","env=prod"],"label!":["tier=db"]}"#
+        );
+    }
+
+    #[test]
+    fn negate_round_trips() {
+        let filter = Filter::name("web").negate();
+        assert!(filter.is_negated());
+        assert_eq!(filter.key(), "name!");
+        assert_eq!(filter.negate(), Filter::name("web"));
+    }
+}
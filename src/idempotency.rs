@@ -0,0 +1,129 @@
+//! Idempotent creation of containers, networks and volumes.
+//!
+//! Options given an idempotency key label the object they create with it. Creating with such
+//! options looks for an object labeled with the key before creating one, and again after a
+//! failed attempt, so retrying a create that timed out adopts the object the earlier attempt
+//! created instead of creating a second one.
+
+use std::{
+    future::Future,
+    process,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{Map, Value};
+
+use crate::errors::Result;
+
+/// Label carrying the idempotency key a container, network or volume was created with
+pub const IDEMPOTENCY_KEY_LABEL: &str = "com.github.shiplift.idempotency-key";
+
+/// Generates a key for [IDEMPOTENCY_KEY_LABEL] unique to this process and call. Generate one per
+/// object to create and reuse it across retries.
+pub fn idempotency_key() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or_default();
+    format!(
+        "{:x}-{:x}-{:x}",
+        nanos,
+        process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Adds `key` as the [IDEMPOTENCY_KEY_LABEL] to the labels of a create request `body`
+pub(crate) fn insert_key(
+    body: &mut Map<String, Value>,
+    key: &str,
+) {
+    let labels = body
+        .entry("Labels")
+        .or_insert_with(|| Value::Object(Map::new()));
+    if let Value::Object(labels) = labels {
+        labels.insert(IDEMPOTENCY_KEY_LABEL.to_owned(), Value::from(key));
+    }
+}
+
+/// The [IDEMPOTENCY_KEY_LABEL] among the labels of a create request `body`
+pub(crate) fn key_of(body: &Map<String, Value>) -> Option<&str> {
+    body.get("Labels")?.get(IDEMPOTENCY_KEY_LABEL)?.as_str()
+}
+
+/// Creates an object with `create` from a request `body`. When the body carries an idempotency
+/// key, the object `created_with_key` finds for it is returned instead, looked up before
+/// creating and again after a failed attempt, which may have created it nonetheless.
+pub(crate) async fn create<'a, T, C, L>(
+    body: &'a Map<String, Value>,
+    create: impl FnOnce() -> C,
+    created_with_key: impl Fn(&'a str) -> L,
+) -> Result<T>
+where
+    C: Future<Output = Result<T>>,
+    L: Future<Output = Result<Option<T>>>,
+{
+    let key = match key_of(body) {
+        Some(key) => key,
+        None => return create().await,
+    };
+    if let Some(created) = created_with_key(key).await? {
+        return Ok(created);
+    }
+    match create().await {
+        Err(e) => created_with_key(key).await.ok().flatten().ok_or(e),
+        created => created,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+    use std::cell::Cell;
+
+    #[test]
+    fn idempotency_key_label() {
+        assert_ne!(idempotency_key(), idempotency_key());
+
+        let mut body = Map::new();
+        assert_eq!(key_of(&body), None);
+        body.insert("Labels".to_owned(), serde_json::json!({"env": "prod"}));
+        insert_key(&mut body, "0123");
+        assert_eq!(key_of(&body), Some("0123"));
+        assert_eq!(body["Labels"]["env"], "prod");
+    }
+
+    #[test]
+    fn create_adopts_object_of_failed_attempt() {
+        let mut body = Map::new();
+        insert_key(&mut body, "0123");
+        let lookups = Cell::new(0);
+
+        let created = futures::executor::block_on(create(
+            &body,
+            || async { Err(Error::Shutdown) },
+            |key| {
+                lookups.set(lookups.get() + 1);
+                let found = (lookups.get() > 1).then(|| key.to_owned());
+                async move { Ok(found) }
+            },
+        ));
+
+        assert_eq!(created.unwrap(), "0123");
+        assert_eq!(lookups.get(), 2);
+    }
+
+    #[test]
+    fn create_without_key_skips_lookups() {
+        let created = futures::executor::block_on(create(
+            &Map::new(),
+            || async { Ok("created".to_owned()) },
+            |_| async { panic!("looked up an object without a key") },
+        ));
+
+        assert_eq!(created.unwrap(), "created");
+    }
+}
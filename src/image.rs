@@ -2,20 +2,48 @@
 //!
 //! API Reference: <https://docs.docker.com/engine/api/v1.41/#tag/Image>
 
-use std::{collections::HashMap, io::Read, iter};
-
-use futures_util::{stream::Stream, TryFutureExt, TryStreamExt};
-use hyper::Body;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    io::{self, Read},
+    iter,
+    pin::Pin,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+use futures_util::{
+    future,
+    stream::{self, Stream},
+    StreamExt, TryFutureExt, TryStreamExt,
+};
+use hyper::{body::Bytes, StatusCode};
 use serde::{Deserialize, Serialize};
 use url::form_urlencoded;
 
-use crate::{docker::Docker, errors::Result, tarball, transport::tar};
+use crate::{
+    capabilities::Feature,
+    container::ContainerOptionsBuilder,
+    docker::Docker,
+    errors::{Error, Result},
+    filter::{self, Filter, LabelSelector},
+    options::encode_query,
+    tarball,
+    transport::{tar, Headers, Payload, StreamedBody, UploadProgress},
+};
 
 #[cfg(feature = "chrono")]
 use crate::datetime::datetime_from_unix_timestamp;
 #[cfg(feature = "chrono")]
 use chrono::{DateTime, Utc};
 
+/// Number of images [Images::exist] inspects at a time
+pub const EXIST_CONCURRENCY: usize = 8;
+
 /// Interface for accessing and manipulating a named docker image
 ///
 /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#tag/Image)
@@ -48,6 +76,31 @@ impl<'docker> Image<'docker> {
             .await
     }
 
+    /// Inspects a named image's details, including the [manifests](ImageDetails::manifests) of
+    /// each platform variant and attestation of the image. Only daemons using the containerd
+    /// image store report these.
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.48/#operation/ImageInspect)
+    pub async fn inspect_manifests(&self) -> Result<ImageDetails> {
+        self.docker.require(&[Feature::ImageManifests]).await?;
+        self.docker
+            .get_json(&format!("/images/{}/json?manifests=1", self.name)[..])
+            .await
+    }
+
+    /// Inspects the image and returns a builder for containers of it, pre-populated with the
+    /// image's exposed ports, volumes, entrypoint, command, environment, user and working dir.
+    /// Settings made on the builder afterwards take precedence over the image's.
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImageInspect)
+    pub async fn container_options(&self) -> Result<ContainerOptionsBuilder> {
+        let details = self.inspect().await?;
+        Ok(ContainerOptionsBuilder::from_image_config(
+            &self.name,
+            &details.config,
+        ))
+    }
+
     /// Lists the history of the images set of changes
     ///
     /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImageHistory)
@@ -91,6 +144,25 @@ impl<'docker> Image<'docker> {
         let _ = self.docker.post(&path.join("?"), None).await?;
         Ok(())
     }
+
+    /// Pushes the image, which must be named after the repository it's pushed to, to its
+    /// registry
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImagePush)
+    pub fn push(
+        &self,
+        opts: &PushOptions,
+    ) -> impl Stream<Item = Result<ImageBuildChunk>> + Unpin + 'docker {
+        let mut path = vec![format!("/images/{}/push", self.name)];
+        if let Some(query) = opts.serialize() {
+            path.push(query);
+        }
+        Box::pin(
+            self.docker
+                .stream_post_into(path.join("?"), Payload::None, opts.headers())
+                .map(|chunk| chunk.map_err(Error::rate_limited)),
+        )
+    }
 }
 
 /// Interface for docker images
@@ -118,28 +190,93 @@ impl<'docker> Images<'docker> {
 
         // To not tie the lifetime of `opts` to the 'stream, we do the tarring work outside of the
         // stream. But for backwards compatability, we have to return the error inside of the
-        // stream.
-        let mut bytes = Vec::default();
-        let tar_result = tarball::dir(&mut bytes, opts.path.as_str());
+        // stream. A context directory is archived as it is uploaded instead.
+        let context = match opts.context {
+            Some(ref entries) => {
+                let mut bytes = Vec::default();
+                tarball::entries(
+                    &mut bytes,
+                    entries
+                        .iter()
+                        .map(|e| (e.path.as_str(), e.bytes.as_slice(), e.mode)),
+                )
+                .map(|_| {
+                    let length = bytes.len() as u64;
+                    StreamedBody::sized(stream::once(future::ok::<_, io::Error>(bytes)), length)
+                })
+            }
+            None => {
+                let path = opts.path.clone();
+                Ok(StreamedBody::chunked(tarball::writer_stream(
+                    move |writer| tarball::dir(writer, &path),
+                )))
+            }
+        };
+        let context = match opts.upload_progress {
+            Some(UploadProgressCallback(ref progress)) => {
+                let progress = progress.clone();
+                context.map(|body| body.on_progress(move |sent| progress(sent)))
+            }
+            None => context,
+        };
+        let headers = opts.headers();
+        let features = opts.features();
+        let leftovers = if opts.cleanup_on_failure {
+            Some(BuildLeftovers::default())
+        } else {
+            None
+        };
 
         // We must take ownership of the Docker reference. If we don't then the lifetime of 'stream
         // is incorrectly tied to `self`.
         let docker = self.docker;
-        Box::pin(
+        let chunks = Box::pin(
             async move {
                 // Bubble up error inside the stream for backwards compatability
-                tar_result?;
+                let context = context?;
+                docker.require(&features).await?;
 
-                let value_stream = docker.stream_post_into(
-                    endpoint.join("?"),
-                    Some((Body::from(bytes), tar())),
-                    None::<iter::Empty<_>>,
-                );
+                let value_stream =
+                    docker.stream_post_into(endpoint.join("?"), Some((context, tar())), headers);
 
                 Ok(value_stream)
             }
             .try_flatten_stream(),
-        )
+        );
+
+        Box::pin(futures_util::stream::unfold(
+            (chunks, leftovers),
+            move |(mut chunks, mut leftovers)| async move {
+                let chunk = chunks.next().await?;
+                if let Some(ref mut tracked) = leftovers {
+                    match chunk {
+                        Ok(ImageBuildChunk::Update { ref stream }) => tracked.record(stream),
+                        Ok(ImageBuildChunk::Error { .. }) | Err(_) => {
+                            if let Some(tracked) = leftovers.take() {
+                                tracked.remove(docker).await;
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+                Some((chunk, (chunks, leftovers)))
+            },
+        ))
+    }
+
+    /// Builds an image like [build](Images::build), returning a handle that streams the build's
+    /// progress and can [abort](BuildHandle::abort) it
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImageBuild)
+    pub fn build_handle(
+        &self,
+        opts: &BuildOptions,
+    ) -> BuildHandle<'docker> {
+        BuildHandle {
+            chunks: Box::pin(self.build(opts)),
+            docker: self.docker,
+            built: BuildLeftovers::default(),
+        }
     }
 
     /// Lists the docker images on the current docker host
@@ -158,6 +295,18 @@ impl<'docker> Images<'docker> {
             .await
     }
 
+    /// Lists the images labeled `key` with `value`
+    pub async fn with_label(
+        &self,
+        key: &str,
+        value: &str,
+    ) -> Result<Vec<ImageInfo>> {
+        let opts = ImageListOptions::builder()
+            .filter(LabelSelector::new().equals(key, value).filters())
+            .build();
+        self.list(&opts).await
+    }
+
     /// Returns a reference to a set of operations available for a named image
     pub fn get<S>(
         &self,
@@ -169,6 +318,34 @@ impl<'docker> Images<'docker> {
         Image::new(self.docker, name)
     }
 
+    /// Checks which of the image references `refs` are present locally, such as to decide which
+    /// images to pull ahead of scheduling containers. Returns the ID of each image found, keyed
+    /// by the reference it was found by; references of images that aren't present are left out.
+    ///
+    /// At most [EXIST_CONCURRENCY] images are inspected at a time.
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImageInspect)
+    pub async fn exist(
+        &self,
+        refs: &[&str],
+    ) -> Result<HashMap<String, String>> {
+        stream::iter(refs)
+            .map(|name| async move {
+                match self.get(*name).inspect().await {
+                    Ok(details) => Ok(Some((name.to_string(), details.id))),
+                    Err(Error::Fault {
+                        code: StatusCode::NOT_FOUND,
+                        ..
+                    }) => Ok(None),
+                    Err(e) => Err(e),
+                }
+            })
+            .buffer_unordered(EXIST_CONCURRENCY)
+            .try_filter_map(future::ok)
+            .try_collect()
+            .await
+    }
+
     /// Search for docker images by term
     ///
     /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImageSearch)
@@ -186,6 +363,9 @@ impl<'docker> Images<'docker> {
 
     /// Pull and create a new docker images from an existing image
     ///
+    /// When the registry throttles the pull the stream yields an
+    /// [Error::RateLimited](crate::Error::RateLimited) instead of an error chunk.
+    ///
     /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImagePull)
     pub fn pull(
         &self,
@@ -195,11 +375,184 @@ impl<'docker> Images<'docker> {
         if let Some(query) = opts.serialize() {
             path.push(query);
         }
-        let headers = opts
-            .auth_header()
-            .map(|a| iter::once(("X-Registry-Auth", a)));
+        let pull = self
+            .docker
+            .stream_post_into(path.join("?"), Payload::None, opts.headers())
+            .map(|chunk| match chunk {
+                Ok(ImageBuildChunk::Error {
+                    error,
+                    error_detail,
+                }) => match Error::OperationFailed(error).rate_limited() {
+                    Error::OperationFailed(error) => Ok(ImageBuildChunk::Error {
+                        error,
+                        error_detail,
+                    }),
+                    rate_limited => Err(rate_limited),
+                },
+                chunk => chunk.map_err(Error::rate_limited),
+            });
+
+        // the pulled image is only checked, and possibly removed, when the pull went through
+        let failed = Arc::new(AtomicBool::new(false));
+        let pull = pull.inspect({
+            let failed = failed.clone();
+            move |chunk| {
+                if matches!(chunk, Err(_) | Ok(ImageBuildChunk::Error { .. })) {
+                    failed.store(true, Ordering::SeqCst);
+                }
+            }
+        });
+
+        let docker = self.docker;
+        let verification = opts
+            .verify
+            .clone()
+            .and_then(|verify| opts.pulled_reference().map(|reference| (verify, reference)));
+        let verified = futures_util::stream::once(async move {
+            match verification {
+                Some((verify, reference)) if !failed.load(Ordering::SeqCst) => {
+                    verify.check(docker, &reference).await
+                }
+                _ => Ok(()),
+            }
+        })
+        .filter_map(|result| async move { result.err().map(Err) });
+
+        Box::pin(pull.chain(verified))
+    }
+
+    /// Pulls an image like [pull](Images::pull), reporting progress to `handler` instead of
+    /// returning a stream
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImagePull)
+    pub async fn pull_with_progress(
+        &self,
+        opts: &PullOptions,
+        handler: &mut (dyn ProgressHandler + Send),
+    ) -> Result<()> {
+        report_progress(self.pull(opts), handler).await
+    }
+
+    /// Pulls an image like [pull](Images::pull), consuming its progress internally, and
+    /// resolves once the pull completed, with the ID of the pulled image and the digest it
+    /// resolved to
+    ///
+    /// Fails with [Error::MissingImageReference](crate::Error::MissingImageReference), without
+    /// pulling, when `opts` don't name an image.
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImagePull)
+    pub async fn pull_and_wait(
+        &self,
+        opts: &PullOptions,
+    ) -> Result<PulledImage> {
+        let reference = opts
+            .pulled_reference()
+            .ok_or(Error::MissingImageReference)?;
+        let mut progress = Box::pin(pull_progress(self.docker, opts));
+        let mut digest = None;
+        while let Some((_, item)) = progress.next().await {
+            digest = item?.digest;
+        }
+
+        let details = self.get(reference).inspect().await?;
+        Ok(PulledImage {
+            id: details.id,
+            digest,
+        })
+    }
+
+    /// Pulls several images, at most `max_concurrent` at a time, merging their progress into a
+    /// single stream of pairs of image reference and a summary of that image's pull so far.
+    ///
+    /// A pull that fails yields its error as its last item, without stopping the others. The
+    /// last item of a successful pull is a summary with [done](PullProgress::done) set.
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImagePull)
+    pub fn pull_all<I>(
+        &self,
+        pulls: I,
+        max_concurrent: usize,
+    ) -> impl Stream<Item = (String, Result<PullProgress>)> + Unpin + 'docker
+    where
+        I: IntoIterator<Item = PullOptions>,
+    {
+        let docker = self.docker;
+        let pulls: Vec<PullOptions> = pulls.into_iter().collect();
+        Box::pin(
+            futures_util::stream::iter(pulls)
+                .map(move |opts| Box::pin(pull_progress(docker, &opts)))
+                .flatten_unordered(max_concurrent.max(1)),
+        )
+    }
+
+    /// Builds an image like [build](Images::build), reporting progress to `handler` instead of
+    /// returning a stream
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImageBuild)
+    pub async fn build_with_progress(
+        &self,
+        opts: &BuildOptions,
+        handler: &mut (dyn ProgressHandler + Send),
+    ) -> Result<()> {
+        report_progress(self.build(opts), handler).await
+    }
+
+    /// Tags `src_ref` as `dest_ref` and pushes it, reporting the push's progress to `handler`,
+    /// such as to promote `app:ci-1234` to `registry.example.com/app:1.2.0` once it passed its
+    /// checks. `dest_ref` is pushed as `latest` when it has no tag.
+    ///
+    /// The destination tag is removed locally again afterwards, whether the push succeeded or
+    /// not, unless it existed before or removing it would remove the image, as it does when
+    /// `src_ref` is the ID of an image without tags.
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImagePush)
+    pub async fn promote(
+        &self,
+        src_ref: &str,
+        dest_ref: &str,
+        auth: Option<RegistryAuth>,
+        handler: &mut (dyn ProgressHandler + Send),
+    ) -> Result<()> {
+        let dest = ImageRef::from(dest_ref);
+        let tag = dest.tag().unwrap_or("latest");
+        let dest_name = format!("{}:{}", dest.repository(), tag);
+
+        let source = self.get(src_ref).inspect().await?;
+        let existed = match self.get(dest_name.as_str()).inspect().await {
+            Ok(_) => true,
+            Err(Error::Fault {
+                code: StatusCode::NOT_FOUND,
+                ..
+            }) => false,
+            Err(e) => return Err(e),
+        };
+        self.get(src_ref)
+            .tag(
+                &TagOptions::builder()
+                    .repo(dest.repository())
+                    .tag(tag)
+                    .build(),
+            )
+            .await?;
 
-        Box::pin(self.docker.stream_post_into(path.join("?"), None, headers))
+        let mut opts = PushOptions::builder();
+        opts.tag(tag);
+        if let Some(auth) = auth {
+            opts.auth(auth);
+        }
+        let pushed =
+            report_progress(self.get(dest.repository()).push(&opts.build()), handler).await;
+
+        let tagged = source
+            .repo_tags
+            .iter()
+            .flatten()
+            .any(|name| name != "<none>:<none>");
+        if existed || !tagged {
+            return pushed;
+        }
+        let removed = self.get(dest_name).delete().await;
+        pushed.and(removed.map(|_| ()))
     }
 
     /// exports a collection of named images,
@@ -219,11 +572,15 @@ impl<'docker> Images<'docker> {
             .map_ok(|c| c.to_vec())
     }
 
-    /// imports an image or set of images from a given tarball source
+    /// loads an image or set of images from a given tarball source, as produced by `docker save`
     /// source can be uncompressed on compressed via gzip, bzip2 or xz
     ///
+    /// The tarball is read into memory before it is uploaded. To upload it as it is read, use
+    /// [load_body](Images::load_body) with a
+    /// [StreamedBody::from_reader](crate::transport::StreamedBody::from_reader).
+    ///
     /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImageLoad)
-    pub fn import<R>(
+    pub fn load<R>(
         self,
         mut tarball: R,
     ) -> impl Stream<Item = Result<ImageBuildChunk>> + Unpin + 'docker
@@ -233,19 +590,206 @@ impl<'docker> Images<'docker> {
         Box::pin(
             async move {
                 let mut bytes = Vec::default();
-
                 tarball.read_to_end(&mut bytes)?;
-
-                let value_stream = self.docker.stream_post_into(
-                    "/images/load",
-                    Some((Body::from(bytes), tar())),
-                    None::<iter::Empty<_>>,
-                );
-                Ok(value_stream)
+                let length = bytes.len() as u64;
+                let body =
+                    StreamedBody::sized(stream::once(future::ok::<_, io::Error>(bytes)), length);
+                Ok(self.load_body(body))
             }
             .try_flatten_stream(),
         )
     }
+
+    /// Loads images like [load](Images::load) from a tarball given as a streamed body, which
+    /// can report the progress of its upload with
+    /// [on_progress](crate::transport::StreamedBody::on_progress)
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImageLoad)
+    pub fn load_body(
+        self,
+        tarball: StreamedBody,
+    ) -> impl Stream<Item = Result<ImageBuildChunk>> + Unpin + 'docker {
+        Box::pin(self.docker.stream_post_into(
+            "/images/load",
+            Some((tarball, tar())),
+            None::<iter::Empty<_>>,
+        ))
+    }
+
+    /// Loads images like [load](Images::load), reporting progress to `handler` instead of
+    /// returning a stream
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImageLoad)
+    pub async fn load_with_progress<R>(
+        self,
+        tarball: R,
+        handler: &mut (dyn ProgressHandler + Send),
+    ) -> Result<()>
+    where
+        R: Read + Send + 'docker,
+    {
+        report_progress(self.load(tarball), handler).await
+    }
+
+    /// loads an image or set of images from a given tarball source, like [load](Images::load)
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImageLoad)
+    #[deprecated(
+        since = "0.8.0",
+        note = "Please use `Images::load`, or `Images::import_rootfs` to create an image from a \
+                root filesystem tarball. This will be removed in 0.9.0."
+    )]
+    pub fn import<R>(
+        self,
+        tarball: R,
+    ) -> impl Stream<Item = Result<ImageBuildChunk>> + Unpin + 'docker
+    where
+        R: Read + Send + 'docker,
+    {
+        self.load(tarball)
+    }
+
+    /// creates a new image from a streamed root filesystem tarball, like `docker import`
+    /// the tarball can be uncompressed or compressed via gzip, bzip2 or xz
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/ImageCreate)
+    pub fn import_rootfs<S, O, E>(
+        self,
+        rootfs: S,
+        opts: &ImportOptions,
+    ) -> impl Stream<Item = Result<ImageBuildChunk>> + Unpin + 'docker
+    where
+        S: Stream<Item = std::result::Result<O, E>> + Send + 'static,
+        O: Into<Bytes> + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        let path = format!("/images/create?{}", opts.serialize());
+
+        Box::pin(self.docker.stream_post_into(
+            path,
+            Some((StreamedBody::chunked(rootfs), tar())),
+            None::<iter::Empty<_>>,
+        ))
+    }
+}
+
+/// A reference to an image in the form `[registry/]repository[:tag][@digest]`
+///
+/// A reference with a digest pins the exact image content, regardless of what its tag points
+/// to at the time it is pulled.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ImageRef {
+    repository: String,
+    tag: Option<String>,
+    digest: Option<String>,
+}
+
+impl ImageRef {
+    /// Creates a reference to an image repository, without tag or digest
+    pub fn new<R>(repository: R) -> ImageRef
+    where
+        R: Into<String>,
+    {
+        ImageRef {
+            repository: repository.into(),
+            tag: None,
+            digest: None,
+        }
+    }
+
+    /// Sets the tag of the reference
+    pub fn with_tag<T>(
+        mut self,
+        tag: T,
+    ) -> ImageRef
+    where
+        T: Into<String>,
+    {
+        self.tag = Some(tag.into());
+        self
+    }
+
+    /// Sets the content digest of the reference, e.g. `sha256:...`
+    pub fn with_digest<D>(
+        mut self,
+        digest: D,
+    ) -> ImageRef
+    where
+        D: Into<String>,
+    {
+        self.digest = Some(digest.into());
+        self
+    }
+
+    /// The repository, including the registry host if there is one
+    pub fn repository(&self) -> &str {
+        &self.repository
+    }
+
+    pub fn tag(&self) -> Option<&str> {
+        self.tag.as_deref()
+    }
+
+    pub fn digest(&self) -> Option<&str> {
+        self.digest.as_deref()
+    }
+}
+
+impl FromStr for ImageRef {
+    type Err = std::convert::Infallible;
+
+    fn from_str(reference: &str) -> std::result::Result<Self, Self::Err> {
+        let (name, digest) = match reference.split_once('@') {
+            Some((name, digest)) => (name, Some(digest.to_owned())),
+            None => (reference, None),
+        };
+        // A colon before the last slash separates a registry host from its port, not a tag
+        let tag_start = name.rfind('/').map(|i| i + 1).unwrap_or_default();
+        let (repository, tag) = match name[tag_start..].rfind(':') {
+            Some(i) => (
+                &name[..tag_start + i],
+                Some(name[tag_start + i + 1..].to_owned()),
+            ),
+            None => (name, None),
+        };
+
+        Ok(ImageRef {
+            repository: repository.to_owned(),
+            tag,
+            digest,
+        })
+    }
+}
+
+impl From<&str> for ImageRef {
+    fn from(reference: &str) -> Self {
+        match reference.parse() {
+            Ok(image_ref) => image_ref,
+            Err(infallible) => match infallible {},
+        }
+    }
+}
+
+impl fmt::Display for ImageRef {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        f.write_str(&self.repository)?;
+        if let Some(ref tag) = self.tag {
+            write!(f, ":{}", tag)?;
+        }
+        if let Some(ref digest) = self.digest {
+            write!(f, "@{}", digest)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<ImageRef> for String {
+    fn from(image_ref: ImageRef) -> Self {
+        image_ref.to_string()
+    }
 }
 
 #[derive(Clone, Serialize, Debug)]
@@ -347,8 +891,8 @@ impl RegistryAuthBuilder {
 
     pub fn build(&self) -> RegistryAuth {
         RegistryAuth::Password {
-            username: self.username.clone().unwrap_or_else(String::new),
-            password: self.password.clone().unwrap_or_else(String::new),
+            username: self.username.clone().unwrap_or_default(),
+            password: self.password.clone().unwrap_or_default(),
             email: self.email.clone(),
             server_address: self.server_address.clone(),
         }
@@ -360,26 +904,6 @@ pub struct TagOptions {
     pub params: HashMap<&'static str, String>,
 }
 
-impl TagOptions {
-    /// return a new instance of a builder for options
-    pub fn builder() -> TagOptionsBuilder {
-        TagOptionsBuilder::default()
-    }
-
-    /// serialize options as a string. returns None if no options are defined
-    pub fn serialize(&self) -> Option<String> {
-        if self.params.is_empty() {
-            None
-        } else {
-            Some(
-                form_urlencoded::Serializer::new(String::new())
-                    .extend_pairs(&self.params)
-                    .finish(),
-            )
-        }
-    }
-}
-
 #[derive(Default)]
 pub struct TagOptionsBuilder {
     params: HashMap<&'static str, String>,
@@ -407,99 +931,325 @@ impl TagOptionsBuilder {
         self.params.insert("tag", t.into());
         self
     }
-
-    pub fn build(&self) -> TagOptions {
-        TagOptions {
-            params: self.params.clone(),
-        }
-    }
 }
 
+impl_query_options!(TagOptions, TagOptionsBuilder);
+
+/// Options for pushing an image to a registry
 #[derive(Default, Debug)]
-pub struct PullOptions {
+pub struct PushOptions {
     auth: Option<RegistryAuth>,
     params: HashMap<&'static str, String>,
 }
 
-impl PullOptions {
+impl PushOptions {
     /// return a new instance of a builder for options
-    pub fn builder() -> PullOptionsBuilder {
-        PullOptionsBuilder::default()
+    pub fn builder() -> PushOptionsBuilder {
+        PushOptionsBuilder::default()
     }
 
     /// serialize options as a string. returns None if no options are defined
     pub fn serialize(&self) -> Option<String> {
-        if self.params.is_empty() {
-            None
-        } else {
-            Some(
-                form_urlencoded::Serializer::new(String::new())
-                    .extend_pairs(&self.params)
-                    .finish(),
-            )
-        }
+        encode_query(&self.params)
     }
 
-    pub(crate) fn auth_header(&self) -> Option<String> {
-        self.auth.clone().map(|a| a.serialize())
+    /// Registry authentication to send along with the request. The daemon requires the header
+    /// even for registries that don't authenticate, so an empty one is sent without auth.
+    pub(crate) fn headers(&self) -> Headers {
+        let auth = self
+            .auth
+            .as_ref()
+            .map(RegistryAuth::serialize)
+            .unwrap_or_else(|| base64::encode_config("{}", base64::URL_SAFE));
+        Some(vec![("X-Registry-Auth", auth)])
     }
 }
 
-pub struct PullOptionsBuilder {
+#[derive(Default)]
+pub struct PushOptionsBuilder {
     auth: Option<RegistryAuth>,
     params: HashMap<&'static str, String>,
 }
 
-impl Default for PullOptionsBuilder {
-    fn default() -> Self {
-        let mut params = HashMap::new();
-        params.insert("tag", "latest".to_string());
-
-        PullOptionsBuilder { auth: None, params }
-    }
-}
-
-impl PullOptionsBuilder {
-    ///  Name of the image to pull. The name may include a tag or digest.
-    /// This parameter may only be used when pulling an image.
-    /// If an untagged value is provided and no `tag` is provided, _all_
-    /// tags will be pulled
-    /// The pull is cancelled if the HTTP connection is closed.
-    pub fn image<I>(
-        &mut self,
-        img: I,
-    ) -> &mut Self
-    where
-        I: Into<String>,
-    {
-        self.params.insert("fromImage", img.into());
-        self
-    }
-
-    pub fn src<S>(
+impl PushOptionsBuilder {
+    /// The tag to push, all of the repository's tags are pushed when unset
+    pub fn tag<T>(
         &mut self,
-        s: S,
+        t: T,
     ) -> &mut Self
     where
-        S: Into<String>,
+        T: Into<String>,
     {
-        self.params.insert("fromSrc", s.into());
+        self.params.insert("tag", t.into());
         self
     }
 
-    /// Repository name given to an image when it is imported. The repo may include a tag.
-    /// This parameter may only be used when importing an image.
-    ///
-    /// By default a `latest` tag is added when calling
-    /// [PullOptionsBuilder::default](PullOptionsBuilder::default].
-    pub fn repo<R>(
+    pub fn auth(
         &mut self,
-        r: R,
-    ) -> &mut Self
-    where
-        R: Into<String>,
-    {
-        self.params.insert("repo", r.into());
+        auth: RegistryAuth,
+    ) -> &mut Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    pub fn build(&mut self) -> PushOptions {
+        PushOptions {
+            auth: self.auth.take(),
+            params: self.params.clone(),
+        }
+    }
+}
+
+/// Options for creating an image from a root filesystem tarball
+#[derive(Default, Debug)]
+pub struct ImportOptions {
+    params: HashMap<&'static str, String>,
+    changes: Vec<String>,
+}
+
+impl ImportOptions {
+    /// return a new instance of a builder for options
+    pub fn builder() -> ImportOptionsBuilder {
+        ImportOptionsBuilder::default()
+    }
+
+    /// serialize options as a string. the rootfs is always read from the request body
+    pub fn serialize(&self) -> String {
+        form_urlencoded::Serializer::new(String::new())
+            .append_pair("fromSrc", "-")
+            .extend_pairs(&self.params)
+            .extend_pairs(self.changes.iter().map(|change| ("changes", change)))
+            .finish()
+    }
+}
+
+#[derive(Default)]
+pub struct ImportOptionsBuilder {
+    params: HashMap<&'static str, String>,
+    changes: Vec<String>,
+}
+
+impl ImportOptionsBuilder {
+    /// Repository name to give the imported image
+    pub fn repo<R>(
+        &mut self,
+        r: R,
+    ) -> &mut Self
+    where
+        R: Into<String>,
+    {
+        self.params.insert("repo", r.into());
+        self
+    }
+
+    /// Tag to give the imported image
+    pub fn tag<T>(
+        &mut self,
+        t: T,
+    ) -> &mut Self
+    where
+        T: Into<String>,
+    {
+        self.params.insert("tag", t.into());
+        self
+    }
+
+    /// Commit message recorded for the imported image
+    pub fn message<M>(
+        &mut self,
+        m: M,
+    ) -> &mut Self
+    where
+        M: Into<String>,
+    {
+        self.params.insert("message", m.into());
+        self
+    }
+
+    /// Dockerfile instructions to apply to the imported image, e.g. `ENV DEBUG=true`.
+    /// Supported instructions are CMD, ENTRYPOINT, ENV, EXPOSE, ONBUILD, USER, VOLUME and WORKDIR
+    pub fn changes<I, C>(
+        &mut self,
+        changes: I,
+    ) -> &mut Self
+    where
+        I: IntoIterator<Item = C>,
+        C: Into<String>,
+    {
+        self.changes.extend(changes.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn build(&self) -> ImportOptions {
+        ImportOptions {
+            params: self.params.clone(),
+            changes: self.changes.clone(),
+        }
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct PullOptions {
+    auth: Option<RegistryAuth>,
+    params: HashMap<&'static str, String>,
+    headers: Vec<(&'static str, String)>,
+    verify: Option<DigestVerification>,
+}
+
+impl PullOptions {
+    /// return a new instance of a builder for options
+    pub fn builder() -> PullOptionsBuilder {
+        PullOptionsBuilder::default()
+    }
+
+    /// serialize options as a string. returns None if no options are defined
+    pub fn serialize(&self) -> Option<String> {
+        encode_query(&self.params)
+    }
+
+    /// headers to send along with the request, including registry authentication
+    pub(crate) fn headers(&self) -> Headers {
+        with_auth_header(&self.auth, &self.headers)
+    }
+
+    /// The reference the daemon stores a pulled image under, `None` when not pulling an image
+    fn pulled_reference(&self) -> Option<String> {
+        let repository = self.params.get("fromImage")?;
+        Some(match self.params.get("tag") {
+            Some(tag) if tag.contains(':') => format!("{}@{}", repository, tag),
+            Some(tag) if !tag.is_empty() => format!("{}:{}", repository, tag),
+            _ => repository.clone(),
+        })
+    }
+}
+
+/// Expected digest of a pulled image, checked once the pull completes
+#[derive(Clone, Debug)]
+struct DigestVerification {
+    digest: String,
+    remove_on_mismatch: bool,
+}
+
+impl DigestVerification {
+    /// Whether an image with the given id and repo digests has the expected digest
+    fn matches(
+        &self,
+        id: &str,
+        repo_digests: &[String],
+    ) -> bool {
+        id == self.digest
+            || repo_digests
+                .iter()
+                .any(|repo_digest| repo_digest.rsplit('@').next() == Some(self.digest.as_str()))
+    }
+
+    async fn check(
+        &self,
+        docker: &Docker,
+        reference: &str,
+    ) -> Result<()> {
+        let image = Image::new(docker, reference);
+        let details = image.inspect().await?;
+        let repo_digests = details.repo_digests.unwrap_or_default();
+        if self.matches(&details.id, &repo_digests) {
+            return Ok(());
+        }
+
+        if self.remove_on_mismatch {
+            image.delete().await?;
+        }
+        Err(Error::DigestMismatch {
+            expected: self.digest.clone(),
+            id: details.id,
+            repo_digests,
+        })
+    }
+}
+
+pub struct PullOptionsBuilder {
+    auth: Option<RegistryAuth>,
+    params: HashMap<&'static str, String>,
+    headers: Vec<(&'static str, String)>,
+    verify_digest: Option<String>,
+    remove_on_mismatch: bool,
+}
+
+impl Default for PullOptionsBuilder {
+    fn default() -> Self {
+        let mut params = HashMap::new();
+        params.insert("tag", "latest".to_string());
+
+        PullOptionsBuilder {
+            auth: None,
+            params,
+            headers: Vec::new(),
+            verify_digest: None,
+            remove_on_mismatch: false,
+        }
+    }
+}
+
+impl PullOptionsBuilder {
+    ///  Name of the image to pull. The name may include a tag or digest, which then takes the
+    /// place of the `tag`. This parameter may only be used when pulling an image.
+    /// If an untagged value is provided and no `tag` is provided, _all_
+    /// tags will be pulled
+    /// The pull is cancelled if the HTTP connection is closed.
+    pub fn image<I>(
+        &mut self,
+        img: I,
+    ) -> &mut Self
+    where
+        I: Into<String>,
+    {
+        let img = img.into();
+        let image_ref = ImageRef::from(img.as_str());
+        match (image_ref.tag, image_ref.digest) {
+            (_, Some(digest)) => self.digest(digest),
+            (Some(tag), None) => self.tag(tag),
+            (None, None) => self,
+        };
+        self.params.insert("fromImage", image_ref.repository);
+        self
+    }
+
+    /// Content digest (e.g. `sha256:...`) of the image to pull, pinning the exact image instead
+    /// of whatever its tag currently points to. Replaces any `tag`.
+    pub fn digest<D>(
+        &mut self,
+        d: D,
+    ) -> &mut Self
+    where
+        D: Into<String>,
+    {
+        self.params.insert("tag", d.into());
+        self
+    }
+
+    pub fn src<S>(
+        &mut self,
+        s: S,
+    ) -> &mut Self
+    where
+        S: Into<String>,
+    {
+        self.params.insert("fromSrc", s.into());
+        self
+    }
+
+    /// Repository name given to an image when it is imported. The repo may include a tag.
+    /// This parameter may only be used when importing an image.
+    ///
+    /// By default a `latest` tag is added when calling
+    /// [PullOptionsBuilder::default](PullOptionsBuilder::default].
+    pub fn repo<R>(
+        &mut self,
+        r: R,
+    ) -> &mut Self
+    where
+        R: Into<String>,
+    {
+        self.params.insert("repo", r.into());
         self
     }
 
@@ -524,18 +1274,102 @@ impl PullOptionsBuilder {
         self
     }
 
+    /// Sets an extra header to send along with the request, replacing any previous value
+    pub fn header<V>(
+        &mut self,
+        name: &'static str,
+        value: V,
+    ) -> &mut Self
+    where
+        V: Into<String>,
+    {
+        set_header(&mut self.headers, name, value.into());
+        self
+    }
+
+    /// Verifies, once the pull completes, that the pulled image has the given content digest
+    /// (e.g. `sha256:...`) among its repo digests, or as its id. On a mismatch the pull stream
+    /// ends with an [Error::DigestMismatch](crate::Error::DigestMismatch).
+    ///
+    /// Unlike [digest](PullOptionsBuilder::digest) this pulls by tag, and checks that the tag
+    /// resolved to the expected image.
+    pub fn verify_digest<D>(
+        &mut self,
+        digest: D,
+    ) -> &mut Self
+    where
+        D: Into<String>,
+    {
+        self.verify_digest = Some(digest.into());
+        self
+    }
+
+    /// Removes the pulled image reference again when it fails
+    /// [digest verification](PullOptionsBuilder::verify_digest)
+    pub fn remove_on_mismatch(
+        &mut self,
+        remove: bool,
+    ) -> &mut Self {
+        self.remove_on_mismatch = remove;
+        self
+    }
+
     pub fn build(&mut self) -> PullOptions {
         PullOptions {
             auth: self.auth.take(),
             params: self.params.clone(),
+            headers: self.headers.clone(),
+            verify: self.verify_digest.clone().map(|digest| DigestVerification {
+                digest,
+                remove_on_mismatch: self.remove_on_mismatch,
+            }),
+        }
+    }
+}
+
+/// A file of an in-memory build context
+#[derive(Clone, Debug, PartialEq)]
+pub struct BuildContextEntry {
+    pub path: String,
+    pub bytes: Vec<u8>,
+    pub mode: u32,
+}
+
+impl BuildContextEntry {
+    /// A file at `path`, relative to the root of the context, with mode 644
+    pub fn new<P, B>(
+        path: P,
+        bytes: B,
+    ) -> Self
+    where
+        P: Into<String>,
+        B: Into<Vec<u8>>,
+    {
+        BuildContextEntry {
+            path: path.into(),
+            bytes: bytes.into(),
+            mode: 0o644,
         }
     }
+
+    /// Sets the file's permission bits, e.g. `0o755` for an executable script
+    pub fn with_mode(
+        mut self,
+        mode: u32,
+    ) -> Self {
+        self.mode = mode;
+        self
+    }
 }
 
 #[derive(Default, Debug)]
 pub struct BuildOptions {
     pub path: String,
+    context: Option<Vec<BuildContextEntry>>,
     params: HashMap<&'static str, String>,
+    headers: Vec<(&'static str, String)>,
+    cleanup_on_failure: bool,
+    upload_progress: Option<UploadProgressCallback>,
 }
 
 impl BuildOptions {
@@ -549,16 +1383,38 @@ impl BuildOptions {
         BuildOptionsBuilder::new(path)
     }
 
+    /// return a new instance of a builder for options that builds from files held in memory
+    /// instead of a directory on disk. the context is expected to contain a Dockerfile
+    pub fn builder_with_context<I>(entries: I) -> BuildOptionsBuilder
+    where
+        I: IntoIterator<Item = BuildContextEntry>,
+    {
+        BuildOptionsBuilder {
+            context: Some(entries.into_iter().collect()),
+            ..Default::default()
+        }
+    }
+
     /// serialize options as a string. returns None if no options are defined
     pub fn serialize(&self) -> Option<String> {
-        if self.params.is_empty() {
+        encode_query(&self.params)
+    }
+
+    /// Options in use that not every daemon supports
+    pub(crate) fn features(&self) -> Vec<Feature> {
+        let mut features = Vec::new();
+        if self.params.get("squash").map(String::as_str) == Some("true") {
+            features.push(Feature::BuildSquash);
+        }
+        features
+    }
+
+    /// headers to send along with the request, including registry configuration
+    pub(crate) fn headers(&self) -> Headers {
+        if self.headers.is_empty() {
             None
         } else {
-            Some(
-                form_urlencoded::Serializer::new(String::new())
-                    .extend_pairs(&self.params)
-                    .finish(),
-            )
+            Some(self.headers.clone())
         }
     }
 }
@@ -566,7 +1422,11 @@ impl BuildOptions {
 #[derive(Default)]
 pub struct BuildOptionsBuilder {
     path: String,
+    context: Option<Vec<BuildContextEntry>>,
     params: HashMap<&'static str, String>,
+    headers: Vec<(&'static str, String)>,
+    cleanup_on_failure: bool,
+    upload_progress: Option<UploadProgressCallback>,
 }
 
 impl BuildOptionsBuilder {
@@ -642,6 +1502,34 @@ impl BuildOptionsBuilder {
         self
     }
 
+    /// Removes the intermediate containers and images a failed build leaves behind, as
+    /// reported by its progress output, before the failure is yielded. Images taken from the
+    /// build cache are kept. Also sets `forcerm`.
+    pub fn cleanup_on_failure(
+        &mut self,
+        cleanup: bool,
+    ) -> &mut Self {
+        self.cleanup_on_failure = cleanup;
+        if cleanup {
+            self.forcerm(true);
+        }
+        self
+    }
+
+    /// Calls `progress` as the build context is uploaded to the daemon, with the bytes sent so
+    /// far and, for contexts held in memory, the total. Contexts read from a directory are
+    /// archived as they are uploaded, so their total isn't known up front.
+    pub fn upload_progress<F>(
+        &mut self,
+        progress: F,
+    ) -> &mut Self
+    where
+        F: Fn(UploadProgress) + Send + Sync + 'static,
+    {
+        self.upload_progress = Some(UploadProgressCallback(Arc::new(progress)));
+        self
+    }
+
     /// `bridge`, `host`, `none`, `container:<name|id>`, or a custom network name.
     pub fn network_mode<T>(
         &mut self,
@@ -670,50 +1558,223 @@ impl BuildOptionsBuilder {
         self
     }
 
+    /// squash the newly built layers into a single layer. requires the daemon to run with
+    /// experimental features enabled
+    pub fn squash(
+        &mut self,
+        squash: bool,
+    ) -> &mut Self {
+        self.params.insert("squash", squash.to_string());
+        self
+    }
+
+    /// Labels to set on the built image
+    pub fn labels(
+        &mut self,
+        labels: &HashMap<&str, &str>,
+    ) -> &mut Self {
+        self.params
+            .insert("labels", serde_json::to_string(labels).unwrap_or_default());
+        self
+    }
+
     // todo: memswap
     // todo: cpusetcpus
     // todo: cpuperiod
     // todo: cpuquota
     // todo: buildargs
 
+    /// Credentials for the registries the build may pull base images from, keyed by registry
+    /// hostname
+    pub fn registry_config<I, R>(
+        &mut self,
+        registries: I,
+    ) -> &mut Self
+    where
+        I: IntoIterator<Item = (R, RegistryAuth)>,
+        R: Into<String>,
+    {
+        let registries = registries
+            .into_iter()
+            .map(|(registry, auth)| (registry.into(), auth))
+            .collect::<HashMap<String, RegistryAuth>>();
+        let config = serde_json::to_string(&registries)
+            .map(|c| base64::encode_config(&c, base64::URL_SAFE))
+            .unwrap_or_default();
+        set_header(&mut self.headers, "X-Registry-Config", config);
+        self
+    }
+
+    /// Sets an extra header to send along with the request, replacing any previous value
+    pub fn header<V>(
+        &mut self,
+        name: &'static str,
+        value: V,
+    ) -> &mut Self
+    where
+        V: Into<String>,
+    {
+        set_header(&mut self.headers, name, value.into());
+        self
+    }
+
     pub fn build(&self) -> BuildOptions {
         BuildOptions {
             path: self.path.clone(),
+            context: self.context.clone(),
             params: self.params.clone(),
+            headers: self.headers.clone(),
+            cleanup_on_failure: self.cleanup_on_failure,
+            upload_progress: self.upload_progress.clone(),
         }
     }
 }
 
-/// Filter options for image listings
-pub enum ImageFilter {
-    Dangling,
-    LabelName(String),
-    Label(String, String),
+/// Receives the progress of a build context upload, shared by the options built from a builder
+#[derive(Clone)]
+struct UploadProgressCallback(Arc<dyn Fn(UploadProgress) + Send + Sync>);
+
+impl fmt::Debug for UploadProgressCallback {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        f.write_str("UploadProgressCallback")
+    }
 }
 
-/// Options for filtering image list results
-#[derive(Default, Debug)]
-pub struct ImageListOptions {
-    params: HashMap<&'static str, String>,
+/// A running build, returned by [Images::build_handle]. Streams the build's progress like
+/// [Images::build] does.
+pub struct BuildHandle<'docker> {
+    chunks: Pin<Box<dyn Stream<Item = Result<ImageBuildChunk>> + Send + 'docker>>,
+    docker: &'docker Docker,
+    /// Containers and images the build reported creating so far
+    built: BuildLeftovers,
 }
 
-impl ImageListOptions {
-    pub fn builder() -> ImageListOptionsBuilder {
-        ImageListOptionsBuilder::default()
+impl<'docker> BuildHandle<'docker> {
+    /// Aborts the build by closing its connection, which makes the daemon cancel it.
+    /// Intermediate containers and images the build already created are left behind.
+    pub fn abort(self) {}
+
+    /// Aborts the build like [abort](BuildHandle::abort), then removes the intermediate
+    /// containers and the images the build reported creating in the progress streamed so far,
+    /// the way [cleanup_on_failure](BuildOptionsBuilder::cleanup_on_failure) does for failed
+    /// builds, so a cancelled job cleans up after itself. Failures to remove them are ignored.
+    pub async fn abort_and_clean_up(self) {
+        let BuildHandle { docker, built, .. } = self;
+        built.remove(docker).await;
     }
-    pub fn serialize(&self) -> Option<String> {
-        if self.params.is_empty() {
-            None
-        } else {
-            Some(
-                form_urlencoded::Serializer::new(String::new())
-                    .extend_pairs(&self.params)
-                    .finish(),
-            )
+}
+
+impl<'docker> Stream for BuildHandle<'docker> {
+    type Item = Result<ImageBuildChunk>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let chunk = self.chunks.as_mut().poll_next(cx);
+        if let Poll::Ready(Some(Ok(ImageBuildChunk::Update { stream }))) = &chunk {
+            self.built.record(stream);
         }
+        chunk
     }
 }
 
+/// Containers and images a build created, tracked from its progress output to remove them if
+/// the build fails
+#[derive(Debug, Default, PartialEq)]
+struct BuildLeftovers {
+    containers: Vec<String>,
+    images: Vec<String>,
+    /// Whether a step ran a container, so the next image reported is one it committed rather
+    /// than the base image or one from the cache
+    committing: bool,
+}
+
+impl BuildLeftovers {
+    fn record(
+        &mut self,
+        output: &str,
+    ) {
+        for line in output.lines().map(str::trim) {
+            if let Some(id) = line.strip_prefix("---> Running in ") {
+                self.containers.push(id.to_owned());
+                self.committing = true;
+            } else if let Some(id) = line.strip_prefix("Removing intermediate container ") {
+                self.containers.retain(|container| container != id);
+            } else if let Some(id) = line.strip_prefix("---> ") {
+                if self.committing && !id.contains(' ') {
+                    self.images.push(id.to_owned());
+                }
+                self.committing = false;
+            }
+        }
+    }
+
+    /// Removes what was tracked, ignoring failures as the build's own error is what matters
+    async fn remove(
+        self,
+        docker: &Docker,
+    ) {
+        for container in &self.containers {
+            let _ = docker
+                .delete(&format!("/containers/{}?force=true", container))
+                .await;
+        }
+        // the newest image first, as the older ones are its parents
+        for image in self.images.iter().rev() {
+            let _ = docker.delete(&format!("/images/{}", image)).await;
+        }
+    }
+}
+
+/// Filter options for image listings
+pub enum ImageFilter {
+    Dangling,
+    LabelName(String),
+    Label(String, String),
+}
+
+impl ImageFilter {
+    /// Matches images carrying a label with the given key, whatever its value
+    pub fn label_exists<K>(key: K) -> Self
+    where
+        K: Into<String>,
+    {
+        ImageFilter::LabelName(key.into())
+    }
+
+    /// Matches images carrying a label with the given key and value
+    pub fn label_eq<K, V>(
+        key: K,
+        value: V,
+    ) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        ImageFilter::Label(key.into(), value.into())
+    }
+}
+
+impl From<ImageFilter> for Filter {
+    fn from(filter: ImageFilter) -> Self {
+        match filter {
+            ImageFilter::Dangling => Filter::dangling(true),
+            ImageFilter::LabelName(n) => Filter::label(n),
+            ImageFilter::Label(n, v) => Filter::label_value(n, v),
+        }
+    }
+}
+
+/// Options for filtering image list results
+#[derive(Default, Debug)]
+pub struct ImageListOptions {
+    params: HashMap<&'static str, String>,
+}
+
 /// Builder interface for `ImageListOptions`
 #[derive(Default)]
 pub struct ImageListOptionsBuilder {
@@ -742,32 +1803,21 @@ impl ImageListOptionsBuilder {
         self
     }
 
-    pub fn filter(
+    pub fn filter<I, F>(
         &mut self,
-        filters: Vec<ImageFilter>,
-    ) -> &mut Self {
-        let mut param = HashMap::new();
-        for f in filters {
-            match f {
-                ImageFilter::Dangling => param.insert("dangling", vec![true.to_string()]),
-                ImageFilter::LabelName(n) => param.insert("label", vec![n]),
-                ImageFilter::Label(n, v) => param.insert("label", vec![format!("{}={}", n, v)]),
-            };
-        }
-        // structure is a a json encoded object mapping string keys to a list
-        // of string values
-        self.params
-            .insert("filters", serde_json::to_string(&param).unwrap());
+        filters: I,
+    ) -> &mut Self
+    where
+        I: IntoIterator<Item = F>,
+        F: Into<Filter>,
+    {
+        self.params.insert("filters", filter::serialize(filters));
         self
     }
-
-    pub fn build(&self) -> ImageListOptions {
-        ImageListOptions {
-            params: self.params.clone(),
-        }
-    }
 }
 
+impl_query_options!(ImageListOptions, ImageListOptionsBuilder);
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SearchResult {
     pub description: String,
@@ -788,9 +1838,69 @@ pub struct ImageInfo {
     pub id: String,
     pub parent_id: String,
     pub labels: Option<HashMap<String, String>>,
+    /// Tags of the image, `<none>:<none>` for an untagged image on older daemons
     pub repo_tags: Option<Vec<String>>,
+    /// Digests the image is known by in registries, `<none>@<none>` if there are none
     pub repo_digests: Option<Vec<String>>,
+    pub size: u64,
+    /// Size shared with other images, -1 if not calculated
+    pub shared_size: i64,
     pub virtual_size: u64,
+    /// Number of containers using the image, -1 if not calculated
+    pub containers: i64,
+}
+
+impl ImageInfo {
+    /// Labels set on the image
+    pub fn labels(&self) -> HashMap<String, String> {
+        self.labels.clone().unwrap_or_default()
+    }
+
+    /// The tags of the image, leaving out the `<none>:<none>` placeholder of untagged images
+    pub fn tags(&self) -> Vec<ImageRef> {
+        references(&self.repo_tags, "<none>:<none>")
+    }
+
+    /// The digests of the image, leaving out the `<none>@<none>` placeholder
+    pub fn digests(&self) -> Vec<ImageRef> {
+        references(&self.repo_digests, "<none>@<none>")
+    }
+
+    /// Groups images by the repositories they are tagged in or have digests for, such as to
+    /// find the images of a repository to clean up. An image known in several repositories is
+    /// part of each group, dangling images are not part of any.
+    pub fn group_by_repository<'a, I>(images: I) -> BTreeMap<String, Vec<&'a ImageInfo>>
+    where
+        I: IntoIterator<Item = &'a ImageInfo>,
+    {
+        let mut groups: BTreeMap<String, Vec<&'a ImageInfo>> = BTreeMap::new();
+        for image in images {
+            let mut repositories: Vec<_> = image
+                .tags()
+                .into_iter()
+                .chain(image.digests())
+                .map(|image_ref| image_ref.repository().to_owned())
+                .collect();
+            repositories.sort();
+            repositories.dedup();
+            for repository in repositories {
+                groups.entry(repository).or_default().push(image);
+            }
+        }
+        groups
+    }
+}
+
+fn references(
+    references: &Option<Vec<String>>,
+    placeholder: &str,
+) -> Vec<ImageRef> {
+    references
+        .iter()
+        .flatten()
+        .filter(|reference| reference.as_str() != placeholder)
+        .map(|reference| ImageRef::from(reference.as_str()))
+        .collect()
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -812,6 +1922,197 @@ pub struct ImageDetails {
     pub repo_digests: Option<Vec<String>>,
     pub size: u64,
     pub virtual_size: u64,
+    /// OCI descriptor of the image, reported by daemons using the containerd image store
+    pub descriptor: Option<OciDescriptor>,
+    #[serde(rename = "RootFS")]
+    pub root_fs: Option<RootFs>,
+    /// Manifests of the image, only reported by [Image::inspect_manifests]
+    pub manifests: Option<Vec<ImageManifestSummary>>,
+}
+
+impl ImageDetails {
+    /// Labels set on the image's config
+    pub fn labels(&self) -> HashMap<String, String> {
+        self.config.labels.clone().unwrap_or_default()
+    }
+
+    /// OCI annotations of the image's manifest, empty if the daemon does not report them
+    pub fn annotations(&self) -> HashMap<String, String> {
+        self.descriptor
+            .as_ref()
+            .and_then(|descriptor| descriptor.annotations.clone())
+            .unwrap_or_default()
+    }
+
+    /// Media type of the image's manifest or index, if the daemon reports it
+    pub fn media_type(&self) -> Option<MediaType> {
+        self.descriptor
+            .as_ref()
+            .map(OciDescriptor::parsed_media_type)
+    }
+}
+
+/// The layers of an image's filesystem
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RootFs {
+    /// Always `layers`
+    #[serde(rename = "Type")]
+    pub kind: String,
+    /// Digests of the uncompressed layers, bottom first
+    #[serde(default)]
+    pub layers: Vec<String>,
+}
+
+/// A manifest of an image in the containerd image store
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ImageManifestSummary {
+    #[serde(rename = "ID")]
+    pub id: String,
+    pub descriptor: OciDescriptor,
+    /// Whether all of the manifest's content is available locally
+    pub available: bool,
+    /// `image`, `attestation` or `unknown`
+    pub kind: String,
+    /// Set for manifests of `image` kind
+    pub image_data: Option<ImageManifestData>,
+}
+
+/// The platform an image manifest is built for
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ImageManifestData {
+    pub platform: OciPlatform,
+    /// Ids of containers using the manifest
+    #[serde(default)]
+    pub containers: Vec<String>,
+}
+
+/// An OCI platform
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OciPlatform {
+    pub architecture: String,
+    pub os: String,
+    pub variant: Option<String>,
+}
+
+/// The media type of image content, telling OCI and Docker schema 2 content apart
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MediaType {
+    /// `application/vnd.docker.distribution.manifest.v2+json`
+    DockerManifest,
+    /// `application/vnd.docker.distribution.manifest.list.v2+json`
+    DockerManifestList,
+    /// `application/vnd.docker.container.image.v1+json`
+    DockerConfig,
+    /// `application/vnd.docker.image.rootfs.diff.tar.gzip` and its foreign variant
+    DockerLayer,
+    /// `application/vnd.oci.image.manifest.v1+json`
+    OciManifest,
+    /// `application/vnd.oci.image.index.v1+json`
+    OciIndex,
+    /// `application/vnd.oci.image.config.v1+json`
+    OciConfig,
+    /// `application/vnd.oci.image.layer.v1.tar`, with an optional `+gzip` or `+zstd` suffix,
+    /// and its non-distributable variants
+    OciLayer(Compression),
+    Other(String),
+}
+
+/// How a layer is compressed
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Compression {
+    Uncompressed,
+    Gzip,
+    Zstd,
+}
+
+impl MediaType {
+    /// Whether the media type is one of the OCI image spec's
+    pub fn is_oci(&self) -> bool {
+        matches!(
+            self,
+            MediaType::OciManifest
+                | MediaType::OciIndex
+                | MediaType::OciConfig
+                | MediaType::OciLayer(_)
+        )
+    }
+
+    /// Whether the media type is one of Docker's image manifest schema 2
+    pub fn is_docker(&self) -> bool {
+        matches!(
+            self,
+            MediaType::DockerManifest
+                | MediaType::DockerManifestList
+                | MediaType::DockerConfig
+                | MediaType::DockerLayer
+        )
+    }
+
+    /// Whether the media type is an index of manifests for several platforms
+    pub fn is_index(&self) -> bool {
+        matches!(self, MediaType::DockerManifestList | MediaType::OciIndex)
+    }
+
+    /// How a layer of this media type is compressed, `None` if it isn't a layer
+    pub fn compression(&self) -> Option<Compression> {
+        match self {
+            MediaType::DockerLayer => Some(Compression::Gzip),
+            MediaType::OciLayer(compression) => Some(*compression),
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for MediaType {
+    fn from(media_type: &str) -> Self {
+        match media_type {
+            "application/vnd.docker.distribution.manifest.v2+json" => MediaType::DockerManifest,
+            "application/vnd.docker.distribution.manifest.list.v2+json" => {
+                MediaType::DockerManifestList
+            }
+            "application/vnd.docker.container.image.v1+json" => MediaType::DockerConfig,
+            "application/vnd.docker.image.rootfs.diff.tar.gzip"
+            | "application/vnd.docker.image.rootfs.foreign.diff.tar.gzip" => MediaType::DockerLayer,
+            "application/vnd.oci.image.manifest.v1+json" => MediaType::OciManifest,
+            "application/vnd.oci.image.index.v1+json" => MediaType::OciIndex,
+            "application/vnd.oci.image.config.v1+json" => MediaType::OciConfig,
+            other => {
+                let layer = other
+                    .strip_prefix("application/vnd.oci.image.layer.v1.tar")
+                    .or_else(|| {
+                        other
+                            .strip_prefix("application/vnd.oci.image.layer.nondistributable.v1.tar")
+                    });
+                match layer {
+                    Some("") => MediaType::OciLayer(Compression::Uncompressed),
+                    Some("+gzip") => MediaType::OciLayer(Compression::Gzip),
+                    Some("+zstd") => MediaType::OciLayer(Compression::Zstd),
+                    _ => MediaType::Other(other.to_owned()),
+                }
+            }
+        }
+    }
+}
+
+impl OciDescriptor {
+    /// The descriptor's [media type](OciDescriptor::media_type), classified
+    pub fn parsed_media_type(&self) -> MediaType {
+        MediaType::from(self.media_type.as_str())
+    }
+}
+
+/// An OCI content descriptor
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OciDescriptor {
+    pub media_type: String,
+    pub digest: String,
+    pub size: u64,
+    pub annotations: Option<HashMap<String, String>>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -835,6 +2136,7 @@ pub struct ContainerConfig {
     pub stdin_once: bool,
     pub tty: bool,
     pub user: String,
+    pub volumes: Option<HashMap<String, HashMap<String, String>>>,
     pub working_dir: String,
 }
 
@@ -861,6 +2163,12 @@ pub struct History {
     #[cfg(not(feature = "chrono"))]
     pub created: u64,
     pub created_by: String,
+    /// Size of the layer the step added, 0 for steps that only changed the config
+    #[serde(default)]
+    pub size: i64,
+    pub tags: Option<Vec<String>>,
+    #[serde(default)]
+    pub comment: String,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -893,26 +2201,626 @@ pub enum ImageBuildChunk {
     },
 }
 
+impl ImageBuildChunk {
+    /// The content digest the daemon resolved the image to, as reported by a pull's
+    /// `Digest: sha256:...` status or the summary of a push.
+    pub fn digest(&self) -> Option<&str> {
+        match self {
+            ImageBuildChunk::PullStatus { status, .. } => status.strip_prefix("Digest: "),
+            ImageBuildChunk::Digest { aux } => aux.digest.as_deref(),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Aux {
-    #[serde(rename = "ID")]
-    id: String,
+    /// The built image's ID, empty for a push
+    #[serde(rename = "ID", default)]
+    pub id: String,
+    /// The pushed tag
+    #[serde(rename = "Tag")]
+    pub tag: Option<String>,
+    /// The pushed manifest's content digest
+    #[serde(rename = "Digest")]
+    pub digest: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ErrorDetail {
-    message: String,
+    pub message: String,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ProgressDetail {
-    current: Option<u64>,
-    total: Option<u64>,
+    pub current: Option<u64>,
+    pub total: Option<u64>,
+}
+
+/// Receives the progress of a pull, build or load, as an alternative to consuming its stream of
+/// [ImageBuildChunk](ImageBuildChunk)s. Every method does nothing by default.
+pub trait ProgressHandler {
+    /// A layer reported progress, with the bytes done and in total when the daemon knows them
+    fn on_layer_progress(
+        &mut self,
+        _layer_id: &str,
+        _status: &str,
+        _current: Option<u64>,
+        _total: Option<u64>,
+    ) {
+    }
+
+    /// A line of build output, or a status that isn't about a single layer
+    fn on_message(
+        &mut self,
+        _message: &str,
+    ) {
+    }
+
+    /// The operation failed, either as reported by the daemon or while talking to it
+    fn on_error(
+        &mut self,
+        _error: &str,
+    ) {
+    }
+
+    /// The operation finished successfully. `image` is the built image's ID or the pulled or
+    /// pushed image's digest, when the daemon reported one
+    fn on_done(
+        &mut self,
+        _image: Option<&str>,
+    ) {
+    }
+}
+
+/// Summary of an image pull's progress across its layers
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PullProgress {
+    /// Layers of the image the daemon reported so far
+    pub layers: usize,
+    /// Layers that already existed or finished downloading and extracting
+    pub layers_done: usize,
+    /// Bytes downloaded so far
+    pub downloaded: u64,
+    /// Bytes to download in total, over the layers whose size the daemon reported
+    pub download_total: u64,
+    /// The digest the image resolved to, once the daemon reported it
+    pub digest: Option<String>,
+    /// Whether the pull finished successfully
+    pub done: bool,
+}
+
+/// An image pulled by [Images::pull_and_wait](Images::pull_and_wait)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PulledImage {
+    pub id: String,
+    /// The digest the image resolved to, unless the daemon didn't report one, as for images it
+    /// already had
+    pub digest: Option<String>,
+}
+
+#[derive(Default)]
+struct LayerProgress {
+    downloaded: u64,
+    total: u64,
+    done: bool,
+}
+
+/// Accumulates a pull's chunks into a [PullProgress](PullProgress)
+#[derive(Default)]
+struct PullTracker {
+    layers: HashMap<String, LayerProgress>,
+    digest: Option<String>,
+}
+
+impl PullTracker {
+    fn update(
+        &mut self,
+        chunk: &ImageBuildChunk,
+    ) {
+        if let Some(digest) = chunk.digest() {
+            self.digest = Some(digest.to_owned());
+        }
+        let (status, id, progress_detail) = match chunk {
+            ImageBuildChunk::PullStatus {
+                status,
+                id: Some(id),
+                progress_detail,
+                ..
+            } => (status.as_str(), id, progress_detail),
+            _ => return,
+        };
+        // the id of the opening `Pulling from <repository>` status is the tag, not a layer
+        if status.starts_with("Pulling from") {
+            return;
+        }
+
+        let layer = self.layers.entry(id.clone()).or_default();
+        match status {
+            "Downloading" => {
+                if let Some(detail) = progress_detail {
+                    layer.downloaded = detail.current.unwrap_or(layer.downloaded);
+                    layer.total = detail.total.unwrap_or(layer.total);
+                }
+            }
+            "Download complete" => layer.downloaded = layer.total,
+            "Pull complete" | "Already exists" => {
+                layer.downloaded = layer.total;
+                layer.done = true;
+            }
+            _ => {}
+        }
+    }
+
+    fn progress(
+        &self,
+        done: bool,
+    ) -> PullProgress {
+        PullProgress {
+            layers: self.layers.len(),
+            layers_done: self.layers.values().filter(|layer| layer.done).count(),
+            downloaded: self.layers.values().map(|layer| layer.downloaded).sum(),
+            download_total: self.layers.values().map(|layer| layer.total).sum(),
+            digest: self.digest.clone(),
+            done,
+        }
+    }
+}
+
+/// A pull as a stream of progress summaries labeled with the pulled reference, ending with a
+/// done summary or the error the pull failed with
+fn pull_progress<'docker>(
+    docker: &'docker Docker,
+    opts: &PullOptions,
+) -> impl Stream<Item = (String, Result<PullProgress>)> + 'docker {
+    let image = opts.pulled_reference().unwrap_or_default();
+    let pull = Images::new(docker).pull(opts);
+
+    futures_util::stream::unfold(Some((pull, PullTracker::default())), move |state| {
+        let image = image.clone();
+        async move {
+            let (mut pull, mut tracker) = state?;
+            let item = match pull.next().await {
+                Some(Ok(ImageBuildChunk::Error { error, .. })) => {
+                    return Some((
+                        (image, Err(Error::OperationFailed(error).rate_limited())),
+                        None,
+                    ));
+                }
+                Some(Ok(chunk)) => {
+                    tracker.update(&chunk);
+                    tracker.progress(false)
+                }
+                Some(Err(e)) => return Some(((image, Err(e)), None)),
+                None => return Some(((image, Ok(tracker.progress(true))), None)),
+            };
+            Some(((image, Ok(item)), Some((pull, tracker))))
+        }
+    })
+}
+
+/// Drains a stream of image chunks into a progress handler
+async fn report_progress<S>(
+    stream: S,
+    handler: &mut (dyn ProgressHandler + Send),
+) -> Result<()>
+where
+    S: Stream<Item = Result<ImageBuildChunk>> + Unpin,
+{
+    let mut stream = stream;
+    let mut image = None;
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                handler.on_error(&e.to_string());
+                return Err(e);
+            }
+        };
+
+        if let Some(digest) = chunk.digest() {
+            image = Some(digest.to_owned());
+        }
+        match chunk {
+            ImageBuildChunk::Update { stream } => handler.on_message(&stream),
+            ImageBuildChunk::Error { error, .. } => {
+                handler.on_error(&error);
+                return Err(Error::OperationFailed(error).rate_limited());
+            }
+            ImageBuildChunk::Digest { aux } => image = Some(aux.digest.unwrap_or(aux.id)),
+            ImageBuildChunk::PullStatus {
+                status,
+                id: Some(id),
+                progress_detail,
+                ..
+            } => {
+                let (current, total) = progress_detail
+                    .map(|detail| (detail.current, detail.total))
+                    .unwrap_or_default();
+                handler.on_layer_progress(&id, &status, current, total)
+            }
+            ImageBuildChunk::PullStatus { status, .. } => handler.on_message(&status),
+        }
+    }
+
+    handler.on_done(image.as_deref());
+    Ok(())
+}
+
+/// Sets a header in a list of extra request headers, replacing any previous value
+pub(crate) fn set_header(
+    headers: &mut Vec<(&'static str, String)>,
+    name: &'static str,
+    value: String,
+) {
+    headers.retain(|(existing, _)| !existing.eq_ignore_ascii_case(name));
+    headers.push((name, value));
+}
+
+/// Combines registry authentication with any extra request headers
+pub(crate) fn with_auth_header(
+    auth: &Option<RegistryAuth>,
+    headers: &[(&'static str, String)],
+) -> Headers {
+    let mut headers = headers.to_vec();
+    if let Some(auth) = auth {
+        set_header(&mut headers, "X-Registry-Auth", auth.serialize());
+    }
+
+    if headers.is_empty() {
+        None
+    } else {
+        Some(headers)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mock::{MockDaemon, Route};
+
+    #[derive(Default)]
+    struct RecordingHandler {
+        events: Vec<String>,
+    }
+
+    impl ProgressHandler for RecordingHandler {
+        fn on_layer_progress(
+            &mut self,
+            layer_id: &str,
+            status: &str,
+            current: Option<u64>,
+            total: Option<u64>,
+        ) {
+            self.events.push(format!(
+                "layer {} {} {:?}/{:?}",
+                layer_id, status, current, total
+            ));
+        }
+
+        fn on_message(
+            &mut self,
+            message: &str,
+        ) {
+            self.events.push(format!("message {}", message));
+        }
+
+        fn on_error(
+            &mut self,
+            error: &str,
+        ) {
+            self.events.push(format!("error {}", error));
+        }
+
+        fn on_done(
+            &mut self,
+            image: Option<&str>,
+        ) {
+            self.events.push(format!("done {:?}", image));
+        }
+    }
+
+    fn chunks(json: &str) -> impl Stream<Item = Result<ImageBuildChunk>> + Unpin {
+        let chunks = serde_json::Deserializer::from_str(json)
+            .into_iter::<ImageBuildChunk>()
+            .map(|chunk| chunk.map_err(Error::from))
+            .collect::<Vec<_>>();
+        futures_util::stream::iter(chunks)
+    }
+
+    #[test]
+    fn pull_tracker_progress() {
+        let started = futures::executor::block_on(
+            chunks(
+                r#"{"status":"Pulling from library/nginx","id":"1.25"}
+                {"status":"Already exists","progressDetail":{},"id":"a1"}
+                {"status":"Pulling fs layer","progressDetail":{},"id":"b2"}
+                {"status":"Downloading","progressDetail":{"current":512,"total":2048},"progress":"[==>  ]","id":"b2"}
+                {"status":"Pulling fs layer","progressDetail":{},"id":"c3"}"#,
+            )
+            .collect::<Vec<_>>(),
+        );
+        let mut tracker = PullTracker::default();
+        for chunk in &started {
+            tracker.update(chunk.as_ref().unwrap());
+        }
+        assert_eq!(
+            tracker.progress(false),
+            PullProgress {
+                layers: 3,
+                layers_done: 1,
+                downloaded: 512,
+                download_total: 2048,
+                digest: None,
+                done: false,
+            }
+        );
+
+        let completed = futures::executor::block_on(
+            chunks(
+                r#"{"status":"Pull complete","progressDetail":{},"id":"b2"}
+                {"status":"Digest: sha256:0123"}"#,
+            )
+            .collect::<Vec<_>>(),
+        );
+        for chunk in &completed {
+            tracker.update(chunk.as_ref().unwrap());
+        }
+        let progress = tracker.progress(true);
+        assert_eq!((progress.layers_done, progress.downloaded), (2, 2048));
+        assert_eq!(progress.digest.as_deref(), Some("sha256:0123"));
+    }
+
+    #[test]
+    fn image_list_options_label_filters() {
+        let opts = ImageListOptions::builder()
+            .filter(vec![
+                ImageFilter::label_exists("com.example.keep"),
+                ImageFilter::label_eq("tier", "web"),
+            ])
+            .build();
+        assert_eq!(
+            opts.serialize().unwrap(),
+            "filters=%7B%22label%22%3A%5B%22com.example.keep%22%2C%22tier%3Dweb%22%5D%7D"
+        );
+    }
+
+    #[test]
+    fn pull_options_pulled_reference() {
+        let opts = PullOptions::builder().image("alpine").build();
+        assert_eq!(opts.pulled_reference().as_deref(), Some("alpine:latest"));
+
+        let opts = PullOptions::builder().image("alpine@sha256:0123").build();
+        assert_eq!(
+            opts.pulled_reference().as_deref(),
+            Some("alpine@sha256:0123")
+        );
+
+        let opts = PullOptions::builder().src("-").build();
+        assert_eq!(opts.pulled_reference(), None);
+    }
+
+    #[test]
+    fn digest_verification_matches() {
+        let opts = PullOptions::builder()
+            .image("alpine:3.18")
+            .verify_digest("sha256:0123")
+            .remove_on_mismatch(true)
+            .build();
+        let verify = opts.verify.unwrap();
+        assert!(verify.remove_on_mismatch);
+
+        assert!(verify.matches("sha256:ffff", &["alpine@sha256:0123".to_string()]));
+        assert!(verify.matches("sha256:0123", &[]));
+        assert!(!verify.matches("sha256:ffff", &["alpine@sha256:4567".to_string()]));
+    }
+
+    #[test]
+    fn report_pull_progress() {
+        let stream = chunks(
+            r#"{"status":"Pulling from library/busybox","id":"latest"}
+            {"status":"Downloading","progressDetail":{"current":10,"total":20},"id":"abc"}
+            {"status":"Digest: sha256:123"}"#,
+        );
+        let mut handler = RecordingHandler::default();
+        futures::executor::block_on(report_progress(stream, &mut handler)).unwrap();
+
+        assert_eq!(
+            handler.events,
+            vec![
+                "layer latest Pulling from library/busybox None/None",
+                "layer abc Downloading Some(10)/Some(20)",
+                "message Digest: sha256:123",
+                "done Some(\"sha256:123\")",
+            ]
+        );
+    }
+
+    #[test]
+    fn report_build_error() {
+        let stream = chunks(
+            r#"{"stream":"Step 1/2 : FROM scratch"}
+            {"error":"boom","errorDetail":{"message":"boom"}}"#,
+        );
+        let mut handler = RecordingHandler::default();
+        let result = futures::executor::block_on(report_progress(stream, &mut handler));
+
+        assert!(matches!(result, Err(Error::OperationFailed(ref e)) if e == "boom"));
+        assert_eq!(
+            handler.events,
+            vec!["message Step 1/2 : FROM scratch", "error boom"]
+        );
+    }
+
+    #[test]
+    fn report_push_progress() {
+        let stream = chunks(
+            r#"{"status":"The push refers to repository [registry.example.com/app]"}
+            {"status":"Pushed","progressDetail":{},"id":"abc"}
+            {"status":"1.2.0: digest: sha256:456 size: 528"}
+            {"progressDetail":{},"aux":{"Tag":"1.2.0","Digest":"sha256:456","Size":528}}"#,
+        );
+        let mut handler = RecordingHandler::default();
+        futures::executor::block_on(report_progress(stream, &mut handler)).unwrap();
+
+        assert_eq!(
+            handler.events,
+            vec![
+                "message The push refers to repository [registry.example.com/app]",
+                "layer abc Pushed None/None",
+                "message 1.2.0: digest: sha256:456 size: 528",
+                "done Some(\"sha256:456\")",
+            ]
+        );
+    }
+
+    #[test]
+    fn push_options_headers() {
+        let anonymous = PushOptions::builder().tag("1.2.0").build();
+        assert_eq!(anonymous.serialize(), Some("tag=1.2.0".to_string()));
+        assert_eq!(
+            anonymous.headers(),
+            Some(vec![("X-Registry-Auth", "e30=".to_string())])
+        );
+
+        let auth = RegistryAuth::token("abc");
+        let authenticated = PushOptions::builder().auth(auth.clone()).build();
+        assert_eq!(
+            authenticated.headers(),
+            Some(vec![("X-Registry-Auth", auth.serialize())])
+        );
+    }
+
+    #[test]
+    fn pull_options_headers() {
+        assert!(PullOptions::builder()
+            .image("busybox")
+            .build()
+            .headers()
+            .is_none());
+
+        let auth = RegistryAuth::token("abc");
+        let opts = PullOptions::builder()
+            .image("busybox")
+            .header("X-Meta-Trace", "1")
+            .header("X-Registry-Auth", "overridden")
+            .auth(auth.clone())
+            .build();
+
+        assert_eq!(
+            opts.headers(),
+            Some(vec![
+                ("X-Meta-Trace", "1".to_string()),
+                ("X-Registry-Auth", auth.serialize()),
+            ])
+        );
+    }
+
+    #[test]
+    fn build_options_registry_config() {
+        let auth = RegistryAuth::token("abc");
+        let opts = BuildOptions::builder(".")
+            .registry_config(vec![("docker.io", auth)])
+            .build();
+
+        let headers = opts.headers().unwrap();
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers[0].0, "X-Registry-Config");
+
+        let config = base64::decode_config(&headers[0].1, base64::URL_SAFE).unwrap();
+        let config: serde_json::Value = serde_json::from_slice(&config).unwrap();
+        assert_eq!(config["docker.io"]["identitytoken"], "abc");
+    }
+
+    #[test]
+    fn import_options_serialize() {
+        let opts = ImportOptions::builder()
+            .repo("example")
+            .tag("latest")
+            .changes(vec!["ENV DEBUG=true", "WORKDIR /app"])
+            .build();
+
+        let serialized = opts.serialize();
+        let pairs: Vec<(String, String)> = form_urlencoded::parse(serialized.as_bytes())
+            .into_owned()
+            .collect();
+
+        assert_eq!(pairs[0], ("fromSrc".to_string(), "-".to_string()));
+        assert!(pairs.contains(&("repo".to_string(), "example".to_string())));
+        assert!(pairs.contains(&("tag".to_string(), "latest".to_string())));
+        assert_eq!(
+            pairs
+                .iter()
+                .filter(|(k, _)| k == "changes")
+                .map(|(_, v)| v.as_str())
+                .collect::<Vec<_>>(),
+            vec!["ENV DEBUG=true", "WORKDIR /app"]
+        );
+        assert_eq!(
+            ImportOptions::default().serialize(),
+            "fromSrc=-".to_string()
+        );
+    }
+
+    #[test]
+    fn image_ref_parse() {
+        let image_ref: ImageRef = "ubuntu".parse().unwrap();
+        assert_eq!(image_ref, ImageRef::new("ubuntu"));
+
+        let image_ref = ImageRef::from("localhost:5000/library/ubuntu:20.04");
+        assert_eq!(image_ref.repository(), "localhost:5000/library/ubuntu");
+        assert_eq!(image_ref.tag(), Some("20.04"));
+        assert_eq!(image_ref.digest(), None);
+
+        let image_ref = ImageRef::from("localhost:5000/ubuntu@sha256:abc");
+        assert_eq!(image_ref.repository(), "localhost:5000/ubuntu");
+        assert_eq!(image_ref.tag(), None);
+        assert_eq!(image_ref.digest(), Some("sha256:abc"));
+    }
+
+    #[test]
+    fn image_ref_display_round_trips() {
+        for reference in &[
+            "ubuntu",
+            "ubuntu:20.04",
+            "ubuntu@sha256:abc",
+            "ubuntu:20.04@sha256:abc",
+            "localhost:5000/ubuntu:20.04",
+        ] {
+            assert_eq!(ImageRef::from(*reference).to_string(), *reference);
+        }
+    }
+
+    #[test]
+    fn pull_options_image_with_digest() {
+        let options = PullOptions::builder().image("ubuntu@sha256:abc").build();
+
+        assert_eq!(options.params.get("fromImage").unwrap(), "ubuntu");
+        assert_eq!(options.params.get("tag").unwrap(), "sha256:abc");
+    }
+
+    #[test]
+    fn pull_options_digest_replaces_tag() {
+        let options = PullOptions::builder()
+            .image("ubuntu:20.04")
+            .digest("sha256:abc")
+            .build();
+
+        assert_eq!(options.params.get("fromImage").unwrap(), "ubuntu");
+        assert_eq!(options.params.get("tag").unwrap(), "sha256:abc");
+    }
+
+    #[test]
+    fn image_build_chunk_digest() {
+        let chunk: ImageBuildChunk =
+            serde_json::from_str(r#"{"status":"Digest: sha256:abc"}"#).unwrap();
+        assert_eq!(chunk.digest(), Some("sha256:abc"));
+
+        let chunk: ImageBuildChunk =
+            serde_json::from_str(r#"{"status":"Pulling fs layer","id":"123"}"#).unwrap();
+        assert_eq!(chunk.digest(), None);
+    }
 
     /// Test registry auth with token
     #[test]
@@ -953,4 +2861,330 @@ mod tests {
             options.serialize()
         );
     }
+
+    #[test]
+    fn build_options_labels() {
+        let labels: HashMap<&str, &str> = [("ci.job", "1234")].iter().cloned().collect();
+        let options = BuildOptions::builder("/tmp").labels(&labels).build();
+
+        assert!(options
+            .serialize()
+            .unwrap()
+            .contains("labels=%7B%22ci.job%22%3A%221234%22%7D"));
+    }
+
+    #[test]
+    fn build_leftovers_from_progress() {
+        let mut leftovers = BuildLeftovers::default();
+        for output in [
+            "Step 1/4 : FROM alpine\n",
+            " ---> 0ac33e5f5afa\n",
+            "Step 2/4 : RUN apk add curl\n",
+            " ---> Using cache\n ---> 5d1f2a3b4c5d\n",
+            "Step 3/4 : RUN echo hi > /hi\n",
+            " ---> Running in 1a2b3c4d5e6f\n",
+            "Removing intermediate container 1a2b3c4d5e6f\n",
+            " ---> 9f8e7d6c5b4a\n",
+            "Step 4/4 : RUN false\n",
+            " ---> Running in 6f5e4d3c2b1a\n",
+        ] {
+            leftovers.record(output);
+        }
+
+        assert_eq!(leftovers.containers, vec!["6f5e4d3c2b1a"]);
+        assert_eq!(leftovers.images, vec!["9f8e7d6c5b4a"]);
+    }
+
+    #[test]
+    fn image_info_group_by_repository() {
+        let images: Vec<ImageInfo> = serde_json::from_str(
+            r#"[
+                {"Created": 1600000000, "Id": "sha256:a", "ParentId": "", "Labels": null,
+                 "RepoTags": ["localhost:5000/app:1", "localhost:5000/app:latest", "app:1"],
+                 "RepoDigests": ["localhost:5000/app@sha256:1111"],
+                 "Size": 100, "SharedSize": -1, "VirtualSize": 100, "Containers": 2},
+                {"Created": 1600000000, "Id": "sha256:b", "ParentId": "sha256:a", "Labels": null,
+                 "RepoTags": ["<none>:<none>"], "RepoDigests": ["app@sha256:2222"],
+                 "Size": 50, "SharedSize": -1, "VirtualSize": 50, "Containers": -1},
+                {"Created": 1600000000, "Id": "sha256:c", "ParentId": "", "Labels": null,
+                 "RepoTags": null, "RepoDigests": ["<none>@<none>"],
+                 "Size": 10, "SharedSize": -1, "VirtualSize": 10, "Containers": 0}
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(images[0].tags().len(), 3);
+        assert_eq!(images[0].containers, 2);
+        assert!(images[1].tags().is_empty());
+        assert!(images[2].digests().is_empty());
+
+        let groups = ImageInfo::group_by_repository(&images);
+        let ids = |repository: &str| -> Vec<&str> {
+            groups[repository]
+                .iter()
+                .map(|image| image.id.as_str())
+                .collect()
+        };
+        assert_eq!(groups.len(), 2);
+        assert_eq!(ids("localhost:5000/app"), vec!["sha256:a"]);
+        assert_eq!(ids("app"), vec!["sha256:a", "sha256:b"]);
+    }
+
+    #[test]
+    fn media_type_classification() {
+        assert_eq!(
+            MediaType::from("application/vnd.oci.image.layer.v1.tar+zstd").compression(),
+            Some(Compression::Zstd)
+        );
+        assert_eq!(
+            MediaType::from("application/vnd.oci.image.layer.nondistributable.v1.tar"),
+            MediaType::OciLayer(Compression::Uncompressed)
+        );
+        assert_eq!(
+            MediaType::from("application/vnd.docker.image.rootfs.diff.tar.gzip").compression(),
+            Some(Compression::Gzip)
+        );
+        assert!(MediaType::from("application/vnd.oci.image.index.v1+json").is_index());
+        assert!(
+            MediaType::from("application/vnd.docker.distribution.manifest.v2+json").is_docker()
+        );
+        assert_eq!(
+            MediaType::from("application/vnd.oci.image.layer.v1.tar+lz4"),
+            MediaType::Other("application/vnd.oci.image.layer.v1.tar+lz4".into())
+        );
+    }
+
+    #[test]
+    fn image_manifests() {
+        let manifest: ImageManifestSummary = serde_json::from_str(
+            r#"{
+                "ID": "sha256:95869fbcf224d947ace8d61d0e931d49e31bb7fc67fffbbe9c3198c33aa8e93f",
+                "Descriptor": {
+                    "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                    "digest": "sha256:95869fbcf224d947ace8d61d0e931d49e31bb7fc67fffbbe9c3198c33aa8e93f",
+                    "size": 424
+                },
+                "Available": true,
+                "Size": {"Total": 8213251, "Content": 3987495},
+                "Kind": "image",
+                "ImageData": {
+                    "Platform": {"architecture": "arm64", "os": "linux", "variant": "v8"},
+                    "Containers": [],
+                    "Size": {"Unpacked": 3987495}
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert!(manifest.descriptor.parsed_media_type().is_oci());
+        assert_eq!(
+            manifest.image_data.unwrap().platform.variant.as_deref(),
+            Some("v8")
+        );
+    }
+
+    /// The inspect response of an image with the given id and repo digests
+    fn image_details(
+        id: &str,
+        repo_digests: &[&str],
+    ) -> String {
+        serde_json::json!({
+            "Id": id,
+            "RepoTags": [],
+            "RepoDigests": repo_digests,
+            "Parent": "",
+            "Comment": "",
+            "Created": "2024-01-01T00:00:00Z",
+            "DockerVersion": "",
+            "Author": "",
+            "Config": {
+                "AttachStderr": false,
+                "AttachStdin": false,
+                "AttachStdout": false,
+                "Domainname": "",
+                "Hostname": "",
+                "Image": "",
+                "OpenStdin": false,
+                "StdinOnce": false,
+                "Tty": false,
+                "User": "",
+                "WorkingDir": ""
+            },
+            "Architecture": "amd64",
+            "Os": "linux",
+            "Size": 0,
+            "VirtualSize": 0
+        })
+        .to_string()
+    }
+
+    #[tokio::test]
+    async fn pull_progress_recognizes_rate_limits() {
+        let daemon = MockDaemon::start(vec![Route::new(
+            "POST /images/create",
+            200,
+            r#"{"status":"Pulling from library/busybox","id":"latest"}
+            {"errorDetail":{"message":"toomanyrequests: slow down"},"error":"toomanyrequests: slow down"}"#,
+        )]);
+        let opts = PullOptions::builder().image("busybox").build();
+        let progress: Vec<_> = pull_progress(daemon.docker(), &opts).collect().await;
+
+        assert!(matches!(
+            progress.last(),
+            Some((image, Err(Error::RateLimited { .. }))) if image == "busybox:latest"
+        ));
+        let requests = daemon.requests();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].contains("fromImage=busybox"));
+    }
+
+    #[tokio::test]
+    async fn pull_skips_digest_check_after_errors() {
+        let daemon = MockDaemon::start(vec![Route::new(
+            "POST /images/create",
+            200,
+            r#"{"errorDetail":{"message":"manifest unknown"},"error":"manifest unknown"}"#,
+        )]);
+        let opts = PullOptions::builder()
+            .image("alpine")
+            .verify_digest("sha256:0123")
+            .build();
+        let chunks: Vec<_> = daemon.docker().images().pull(&opts).collect().await;
+
+        assert_eq!(chunks.len(), 1);
+        assert!(matches!(chunks[0], Ok(ImageBuildChunk::Error { .. })));
+        assert_eq!(daemon.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn pull_checks_digest_once_done() {
+        let daemon = MockDaemon::start(vec![
+            Route::new(
+                "POST /images/create",
+                200,
+                r#"{"status":"Digest: sha256:4567"}"#,
+            ),
+            Route::new(
+                "GET /images/alpine:latest/json",
+                200,
+                &image_details("sha256:ffff", &["alpine@sha256:4567"]),
+            ),
+        ]);
+        let opts = PullOptions::builder()
+            .image("alpine")
+            .verify_digest("sha256:0123")
+            .build();
+        let chunks: Vec<_> = daemon.docker().images().pull(&opts).collect().await;
+
+        assert!(matches!(
+            chunks.last(),
+            Some(Err(Error::DigestMismatch { .. }))
+        ));
+        assert_eq!(daemon.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn pull_and_wait_resolves_pulled_image() {
+        let daemon = MockDaemon::start(vec![
+            Route::new(
+                "POST /images/create",
+                200,
+                r#"{"status":"Pulling from library/alpine","id":"3.19"}
+                {"status":"Digest: sha256:4567"}
+                {"status":"Status: Downloaded newer image for alpine:3.19"}"#,
+            ),
+            Route::new(
+                "GET /images/alpine:3.19/json",
+                200,
+                &image_details("sha256:ffff", &["alpine@sha256:4567"]),
+            ),
+        ]);
+        let opts = PullOptions::builder().image("alpine:3.19").build();
+        let pulled = daemon.docker().images().pull_and_wait(&opts).await.unwrap();
+
+        assert_eq!(
+            pulled,
+            PulledImage {
+                id: "sha256:ffff".into(),
+                digest: Some("sha256:4567".into()),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn pull_and_wait_requires_an_image() {
+        let daemon = MockDaemon::start(Vec::new());
+        let opts = PullOptions::builder()
+            .src("https://example.com/rootfs.tar")
+            .build();
+
+        assert!(matches!(
+            daemon.docker().images().pull_and_wait(&opts).await,
+            Err(Error::MissingImageReference)
+        ));
+        assert!(daemon.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn build_handle_cleans_up_after_aborting() {
+        let daemon = MockDaemon::start(vec![
+            Route::new("DELETE /containers/", 204, ""),
+            Route::new("DELETE /images/", 200, "[]"),
+            Route::new(
+                "POST /build",
+                200,
+                r#"{"stream":"Step 1/3 : FROM alpine\n"}
+                {"stream":" ---> 0ac33e5f5afa\n"}
+                {"stream":"Step 2/3 : RUN echo hi > /hi\n"}
+                {"stream":" ---> Running in 1a2b3c4d5e6f\n"}
+                {"stream":"Removing intermediate container 1a2b3c4d5e6f\n"}
+                {"stream":" ---> 9f8e7d6c5b4a\n"}
+                {"stream":"Step 3/3 : RUN sleep 3600\n"}
+                {"stream":" ---> Running in 7c8d9e0f1a2b\n"}"#,
+            ),
+        ]);
+        let opts =
+            BuildOptions::builder_with_context(vec![BuildContextEntry::new("Dockerfile", "")])
+                .build();
+        let mut build = daemon.docker().images().build_handle(&opts);
+        while build.next().await.is_some() {}
+        build.abort_and_clean_up().await;
+
+        assert_eq!(
+            daemon.requests()[1..],
+            [
+                "DELETE /containers/7c8d9e0f1a2b?force=true HTTP/1.1",
+                "DELETE /images/9f8e7d6c5b4a HTTP/1.1",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn build_reports_unreadable_context_as_io_error() {
+        let daemon = MockDaemon::start(vec![Route::new("POST /build", 200, "")]);
+        let opts = BuildOptions::builder("/nonexistent/shiplift/context").build();
+        let chunks: Vec<_> = daemon.docker().images().build(&opts).collect().await;
+
+        assert!(
+            matches!(chunks.last(), Some(Err(Error::IO(_)))),
+            "{:?}",
+            chunks
+        );
+    }
+
+    #[tokio::test]
+    async fn load_reads_borrowed_tarballs() {
+        let daemon = MockDaemon::start(vec![Route::new(
+            "POST /images/load",
+            200,
+            r#"{"stream":"Loaded image: alpine:latest\n"}"#,
+        )]);
+        let tarball = b"alpine image tarball".to_vec();
+        let chunks: Vec<_> = daemon.docker().images().load(&tarball[..]).collect().await;
+
+        assert!(matches!(
+            &chunks[..],
+            [Ok(ImageBuildChunk::Update { stream })] if stream.starts_with("Loaded image")
+        ));
+    }
 }
@@ -0,0 +1,202 @@
+//! Request metrics reported through the [metrics](https://docs.rs/metrics) facade.
+//!
+//! Every request made through a [Docker](crate::Docker) client is counted and timed, labelled
+//! by HTTP method and endpoint. Endpoints are reported as templates (e.g.
+//! `/containers/{id}/json`) so ids and names don't blow up label cardinality.
+//!
+//! The following metrics are emitted:
+//!
+//! * `shiplift_requests_total` - counter of requests sent to the daemon
+//! * `shiplift_request_errors_total` - counter of requests that failed, including streams that
+//!   failed part way through
+//! * `shiplift_request_duration_seconds` - histogram of the time until the daemon responded
+//! * `shiplift_streamed_bytes_total` - counter of bytes received from streaming endpoints
+
+use std::time::Instant;
+
+use hyper::Method;
+
+use crate::Result;
+
+/// Path segments naming an operation rather than an object, when they follow a resource
+/// collection or an object id.
+const ACTIONS: &[&str] = &[
+    "archive",
+    "attach",
+    "changes",
+    "connect",
+    "create",
+    "disconnect",
+    "exec",
+    "export",
+    "get",
+    "history",
+    "json",
+    "kill",
+    "load",
+    "logs",
+    "pause",
+    "prune",
+    "push",
+    "rename",
+    "resize",
+    "restart",
+    "search",
+    "start",
+    "stats",
+    "stop",
+    "tag",
+    "top",
+    "unpause",
+    "update",
+    "wait",
+];
+
+/// Resource collections holding no objects, whose paths are all literal, such as `/system/df`
+/// and `/swarm/init`
+const LITERAL_COLLECTIONS: &[&str] = &["build", "session", "swarm", "system"];
+
+/// Converts a request path into a low-cardinality endpoint label by replacing object ids and
+/// names with `{id}`.
+fn endpoint_label(endpoint: &str) -> String {
+    let path = endpoint.split('?').next().unwrap_or_default();
+    let mut segments = path
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>();
+
+    if segments
+        .first()
+        .map(|s| s.starts_with('v') && s[1..].parse::<f32>().is_ok())
+        .unwrap_or_default()
+    {
+        segments.remove(0);
+    }
+
+    let label = match segments.as_slice() {
+        [] => Vec::new(),
+        [collection, ..] if LITERAL_COLLECTIONS.contains(collection) => segments.clone(),
+        [collection] => vec![*collection],
+        [collection, action] if ACTIONS.contains(action) => vec![*collection, *action],
+        // Image names may contain slashes, so everything between the collection and the action
+        // is part of the id
+        [collection, .., action] if segments.len() > 2 && ACTIONS.contains(action) => {
+            vec![*collection, "{id}", *action]
+        }
+        [collection, ..] => vec![*collection, "{id}"],
+    };
+
+    format!("/{}", label.join("/"))
+}
+
+/// Metrics for a single request to the daemon
+pub(crate) struct RequestMetrics {
+    method: String,
+    endpoint: String,
+    started: Instant,
+}
+
+impl RequestMetrics {
+    /// Counts a new request and starts timing it
+    pub(crate) fn start(
+        method: &Method,
+        endpoint: &str,
+    ) -> Self {
+        let metrics = RequestMetrics {
+            method: method.as_str().to_owned(),
+            endpoint: endpoint_label(endpoint),
+            started: Instant::now(),
+        };
+        ::metrics::counter!(
+            "shiplift_requests_total",
+            "method" => metrics.method.clone(),
+            "endpoint" => metrics.endpoint.clone()
+        )
+        .increment(1);
+
+        metrics
+    }
+
+    /// Records how long the daemon took to respond, and whether the request failed
+    pub(crate) fn finish<T>(
+        &self,
+        result: &Result<T>,
+    ) {
+        ::metrics::histogram!(
+            "shiplift_request_duration_seconds",
+            "method" => self.method.clone(),
+            "endpoint" => self.endpoint.clone()
+        )
+        .record(self.started.elapsed().as_secs_f64());
+
+        if result.is_err() {
+            self.error();
+        }
+    }
+
+    /// Counts a failure of the request
+    pub(crate) fn error(&self) {
+        ::metrics::counter!(
+            "shiplift_request_errors_total",
+            "method" => self.method.clone(),
+            "endpoint" => self.endpoint.clone()
+        )
+        .increment(1);
+    }
+
+    /// Counts bytes received from a streaming response
+    pub(crate) fn streamed(
+        &self,
+        bytes: usize,
+    ) {
+        ::metrics::counter!(
+            "shiplift_streamed_bytes_total",
+            "method" => self.method.clone(),
+            "endpoint" => self.endpoint.clone()
+        )
+        .increment(bytes as u64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::endpoint_label;
+
+    #[test]
+    fn endpoint_label_collections() {
+        assert_eq!(endpoint_label("/_ping"), "/_ping");
+        assert_eq!(
+            endpoint_label("/containers/json?all=true"),
+            "/containers/json"
+        );
+        assert_eq!(
+            endpoint_label("/images/create?fromImage=x"),
+            "/images/create"
+        );
+        assert_eq!(endpoint_label("/v1.41/info"), "/info");
+    }
+
+    #[test]
+    fn endpoint_label_objects() {
+        assert_eq!(
+            endpoint_label("/containers/abc/json"),
+            "/containers/{id}/json"
+        );
+        assert_eq!(endpoint_label("/containers/abc"), "/containers/{id}");
+        assert_eq!(endpoint_label("/exec/abc/start"), "/exec/{id}/start");
+        assert_eq!(
+            endpoint_label("/images/library/ubuntu/json"),
+            "/images/{id}/json"
+        );
+        assert_eq!(endpoint_label("/images/library/ubuntu"), "/images/{id}");
+    }
+
+    #[test]
+    fn endpoint_label_literal_paths() {
+        assert_eq!(endpoint_label("/system/df"), "/system/df");
+        assert_eq!(endpoint_label("/v1.41/swarm/init"), "/swarm/init");
+        assert_eq!(endpoint_label("/swarm/unlockkey"), "/swarm/unlockkey");
+        assert_eq!(endpoint_label("/build/prune?all=1"), "/build/prune");
+        assert_eq!(endpoint_label("/build?t=web"), "/build");
+    }
+}
@@ -18,17 +18,42 @@
 //! ```
 
 pub mod errors;
+#[macro_use]
+mod options;
+#[cfg(feature = "metrics")]
+pub mod instrument;
 pub mod transport;
 pub mod tty;
 
+pub mod capabilities;
+pub mod compat;
+pub mod config;
 pub mod container;
+pub mod deadline;
+#[cfg(feature = "debug-endpoints")]
+pub mod debug;
 pub mod docker;
 pub mod exec;
+pub mod filter;
+pub mod idempotency;
 pub mod image;
 pub mod network;
+pub mod node;
+pub mod plugin;
+#[cfg(feature = "registry")]
+pub mod registry;
+pub mod secret;
 pub mod service;
+pub mod swarm;
+pub mod task;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod usage;
 pub mod volume;
 
+mod de;
+#[cfg(test)]
+mod mock;
 mod tarball;
 
 #[cfg(feature = "chrono")]
@@ -37,23 +62,41 @@ mod datetime;
 pub use hyper::Uri;
 
 pub use crate::{
+    capabilities::{ApiVersion, Capabilities, Feature},
+    compat::Compatibility,
+    config::{ClientConfig, ConfigSource, ResolvedConfig, TlsConfig},
     container::{
-        Container, ContainerFilter, ContainerListOptions, ContainerOptions, Containers,
-        LogsOptions, RmContainerOptions,
+        Bind, ChangeKind, CommitOptions, Container, ContainerFilter, ContainerListOptions,
+        ContainerOptions, ContainerPruneFilter, ContainerPruneOptions, Containers, DeviceMapping,
+        ExitInfo, ExitReason, Isolation, LogsOptions, RmContainerOptions, StateChange,
+        StatsSampler, StatsSnapshot, StopAction, StopOutcome, UpdateOptions, WaitCondition,
     },
+    deadline::WithDeadline,
     docker::{Docker, EventsOptions},
     errors::{Error, Result},
-    exec::{Exec, ExecContainerOptions, ExecResizeOptions},
+    exec::{CommandOutput, Exec, ExecContainerOptions, ExecResizeOptions},
+    filter::{Filter, LabelSelector},
     image::{
-        BuildOptions, Image, ImageFilter, ImageListOptions, Images, PullOptions, RegistryAuth,
-        TagOptions,
+        BuildContextEntry, BuildHandle, BuildOptions, Image, ImageFilter, ImageListOptions,
+        ImageRef, Images, ImportOptions, ProgressHandler, PullOptions, PullProgress, PulledImage,
+        PushOptions, RegistryAuth, TagOptions,
     },
     network::{
-        ContainerConnectionOptions, Network, NetworkCreateOptions, NetworkListOptions, Networks,
+        ContainerConnectionOptions, MappedPort, Network, NetworkCreateOptions, NetworkListOptions,
+        Networks,
     },
-    service::{Service, ServiceFilter, ServiceListOptions, ServiceOptions, Services},
-    transport::Transport,
-    volume::{Volume, VolumeCreateOptions, Volumes},
+    node::{NodeFilter, NodeListOptions, Nodes},
+    plugin::{PluginPrivilege, Plugins},
+    secret::{Secret, SecretOptions, Secrets},
+    service::{
+        PublishedEndpoint, Service, ServiceDiff, ServiceFilter, ServiceListOptions, ServiceOptions,
+        Services,
+    },
+    swarm::{CaConfig, ExternalCa, Swarm, SwarmSpec, SwarmUpdateOptions},
+    task::{TaskFilter, TaskListOptions, Tasks},
+    transport::{IntoRequestBody, StreamedBody, Transport, UploadProgress},
+    usage::{ContainerUsage, DataUsage, HostUsage},
+    volume::{Volume, VolumeCreateOptions, VolumeListOptions, Volumes},
 };
 
 macro_rules! reexport {
@@ -84,9 +127,10 @@ macro_rules! reexport {
 reexport! {
     mod builder;
     container::{
-        ContainerListOptions, ContainerListOptionsBuilder, ContainerOptions,
-        ContainerOptionsBuilder, LogsOptions, LogsOptionsBuilder, RmContainerOptions,
-        RmContainerOptionsBuilder,
+        CommitOptions, CommitOptionsBuilder, ContainerListOptions, ContainerListOptionsBuilder,
+        ContainerOptions, ContainerOptionsBuilder, ContainerPruneOptions,
+        ContainerPruneOptionsBuilder, LogsOptions, LogsOptionsBuilder, RmContainerOptions,
+        RmContainerOptionsBuilder, UpdateOptions, UpdateOptionsBuilder,
     };
     docker::{EventsOptions, EventFilterType, EventFilter, EventsOptionsBuilder};
     exec::{
@@ -96,17 +140,22 @@ reexport! {
     image::{
         RegistryAuth, RegistryAuthBuilder, TagOptions, TagOptionsBuilder, PullOptions,
         PullOptionsBuilder, BuildOptions, BuildOptionsBuilder, ImageFilter, ImageListOptions,
-        ImageListOptionsBuilder,
+        ImageListOptionsBuilder, ImportOptions, ImportOptionsBuilder, PushOptions,
+        PushOptionsBuilder,
     };
     network::{
-        NetworkListOptions, NetworkCreateOptions, NetworkCreateOptionsBuilder,
+        NetworkListOptions, NetworkListOptionsBuilder, NetworkCreateOptions,
+        NetworkCreateOptionsBuilder,
         ContainerConnectionOptions, ContainerConnectionOptionsBuilder,
     };
     service::{
         ServiceListOptions, ServiceFilter, ServiceListOptionsBuilder, ServiceOptions,
         ServiceOptionsBuilder,
     };
-    volume::{VolumeCreateOptions, VolumeCreateOptionsBuilder};
+    volume::{
+        VolumeCreateOptions, VolumeCreateOptionsBuilder, VolumeListOptions,
+        VolumeListOptionsBuilder,
+    };
 }
 
 reexport! {
@@ -114,16 +163,20 @@ reexport! {
     container::{
         ContainerInfo as Container, ContainerDetails, Mount, State, HostConfig, Port, Stats,
         MemoryStats, MemoryStat, NetworkStats as Network, CpuStats, CpuUsage, ThrottlingData, BlkioStats, BlkioStat, Change,
-        Top, ContainerCreateInfo, Exit,
+        ChangeKind, Top, ContainerCreateInfo, ContainerUpdateInfo, CommitInfo, ContainerPruneInfo,
+        Exit,
     };
-    docker::{Version, Info, Event, Actor};
+    docker::{Version, Info, SwarmInfo, Event, Actor};
     exec::{ExecDetails, ProcessConfig};
     image::{
         SearchResult, ImageInfo as Image, ImageDetails, ContainerConfig, History, Status,
+        OciDescriptor, RootFs, ImageManifestSummary, ImageManifestData, OciPlatform, MediaType,
+        Compression,
     };
     network::{
         NetworkSettings, NetworkEntry, Ipam, NetworkDetails,
-        NetworkContainerDetails, NetworkCreateInfo,
+        NetworkContainerDetails, NetworkCreateInfo, NetworkServiceInfo, NetworkTaskInfo,
+        NetworkPeer,
     };
     service::{
         ServicesInfo as Services, ServiceInfo as Service, ObjectVersion, Endpoint, EndpointSpec,
@@ -131,5 +184,10 @@ reexport! {
         TaskSpec, Mode, Replicated, ReplicatedJob, UpdateConfig, RollbackConfig,
         NetworkAttachmentConfig, ServiceCreateInfo,
     };
-    volume::{VolumeCreateInfo, VolumesInfo as Volumes, VolumeInfo as Volume};
+    volume::{
+        VolumeCreateInfo, VolumesInfo as Volumes, VolumeInfo as Volume, ClusterVolume,
+        ClusterVolumeSpec, AccessMode, AccessScope, AccessSharing, ClusterVolumeSecret,
+        AccessibilityRequirements, Topology, CapacityRange, Availability, ClusterVolumeInfo,
+        PublishStatus,
+    };
 }
@@ -0,0 +1,177 @@
+//! A canned-response daemon for unit tests that need to go over the wire
+
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use crate::Docker;
+
+/// A response served for requests whose request line starts with a given prefix
+pub(crate) struct Route {
+    prefix: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+    once: bool,
+    used: AtomicBool,
+}
+
+impl Route {
+    pub(crate) fn new(
+        prefix: &str,
+        status: u16,
+        body: &str,
+    ) -> Route {
+        Route {
+            prefix: prefix.to_owned(),
+            status,
+            headers: Vec::new(),
+            body: body.to_owned(),
+            once: false,
+            used: AtomicBool::new(false),
+        }
+    }
+
+    /// Serves only the first request matching the route, leaving later ones to the next routes
+    pub(crate) fn once(mut self) -> Route {
+        self.once = true;
+        self
+    }
+
+    /// Adds a header to the response
+    pub(crate) fn header(
+        mut self,
+        name: &str,
+        value: &str,
+    ) -> Route {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+}
+
+/// Serves `routes` on a local port until the test process exits, matching each request against
+/// the routes in order. Unmatched requests get a 404.
+pub(crate) struct MockDaemon {
+    requests: Arc<Mutex<Vec<String>>>,
+    docker: Docker,
+}
+
+impl MockDaemon {
+    pub(crate) fn start(routes: Vec<Route>) -> MockDaemon {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let routes = Arc::new(routes);
+
+        let recorded = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => return,
+                };
+                let (recorded, routes) = (recorded.clone(), routes.clone());
+                thread::spawn(move || serve(stream, &routes, &recorded));
+            }
+        });
+
+        MockDaemon {
+            requests,
+            docker: Docker::host(format!("tcp://127.0.0.1:{}", port).parse().unwrap()),
+        }
+    }
+
+    pub(crate) fn docker(&self) -> &Docker {
+        &self.docker
+    }
+
+    /// The request lines received so far, e.g. `GET /version HTTP/1.1`
+    pub(crate) fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn serve(
+    stream: TcpStream,
+    routes: &[Route],
+    recorded: &Mutex<Vec<String>>,
+) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut stream = stream;
+    loop {
+        let mut request_line = String::new();
+        if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+            return;
+        }
+        let request_line = request_line.trim_end().to_owned();
+
+        let (mut content_length, mut chunked) = (0, false);
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header).unwrap_or(0) == 0 {
+                return;
+            }
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap_or(0);
+                }
+                if name.eq_ignore_ascii_case("transfer-encoding") {
+                    chunked = value.trim().eq_ignore_ascii_case("chunked");
+                }
+            }
+        }
+        if chunked {
+            if skip_chunked_body(&mut reader).is_err() {
+                return;
+            }
+        } else if reader.read_exact(&mut vec![0; content_length]).is_err() {
+            return;
+        }
+        recorded.lock().unwrap().push(request_line.clone());
+
+        let not_found = Route::new("", 404, r#"{"message":"no such route"}"#);
+        let route = routes
+            .iter()
+            .find(|route| {
+                request_line.starts_with(&route.prefix)
+                    && !(route.once && route.used.swap(true, Ordering::SeqCst))
+            })
+            .unwrap_or(&not_found);
+        let mut response = format!(
+            "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\n",
+            route.status,
+            route.body.len()
+        );
+        for (name, value) in &route.headers {
+            response.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        response.push_str("\r\n");
+        response.push_str(&route.body);
+        if stream.write_all(response.as_bytes()).is_err() {
+            return;
+        }
+    }
+}
+
+fn skip_chunked_body(reader: &mut impl BufRead) -> std::io::Result<()> {
+    loop {
+        let mut size = String::new();
+        reader.read_line(&mut size)?;
+        let size = usize::from_str_radix(size.trim(), 16).unwrap_or(0);
+        // each chunk, the last empty one included, is followed by a CRLF
+        reader.read_exact(&mut vec![0; size + 2])?;
+        if size == 0 {
+            return Ok(());
+        }
+    }
+}
@@ -7,16 +7,29 @@ use std::{
     hash::Hash,
 };
 
-use hyper::Body;
+use hyper::{Body, StatusCode};
 use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
-use url::form_urlencoded;
+use serde_json::{json, Map, Value};
 
 use crate::{
     docker::Docker,
     errors::{Error, Result},
+    filter::{self, Filter, LabelSelector},
+    idempotency::{self, IDEMPOTENCY_KEY_LABEL},
 };
 
+/// Driver option naming the linux bridge device of a bridge network
+pub const BRIDGE_NAME_OPTION: &str = "com.docker.network.bridge.name";
+/// Driver option enabling or disabling communication between containers of a bridge network
+pub const BRIDGE_ENABLE_ICC_OPTION: &str = "com.docker.network.bridge.enable_icc";
+/// Driver option enabling or disabling IP masquerading of a bridge network's outgoing traffic
+pub const BRIDGE_ENABLE_IP_MASQUERADE_OPTION: &str =
+    "com.docker.network.bridge.enable_ip_masquerade";
+/// Driver option setting the default host IP of ports published on a bridge network
+pub const BRIDGE_HOST_BINDING_IPV4_OPTION: &str = "com.docker.network.bridge.host_binding_ipv4";
+/// Driver option setting the MTU of a network's interfaces
+pub const DRIVER_MTU_OPTION: &str = "com.docker.network.driver.mtu";
+
 /// Interface for docker network
 ///
 /// API Reference: <https://docs.docker.com/engine/api/v1.41/#tag/Network>
@@ -44,6 +57,18 @@ impl<'docker> Networks<'docker> {
         self.docker.get_json(&path.join("?")).await
     }
 
+    /// Lists the networks labeled `key` with `value`
+    pub async fn with_label(
+        &self,
+        key: &str,
+        value: &str,
+    ) -> Result<Vec<NetworkDetails>> {
+        let opts = NetworkListOptions::builder()
+            .filter(LabelSelector::new().equals(key, value).filters())
+            .build();
+        self.list(&opts).await
+    }
+
     /// Returns a reference to a set of operations available to a specific network instance
     pub fn get<S>(
         &self,
@@ -57,18 +82,100 @@ impl<'docker> Networks<'docker> {
 
     /// Create a new Network instance
     ///
+    /// When `opts` has an
+    /// [idempotency key](NetworkCreateOptionsBuilder::idempotency_key), a network created with
+    /// the same key is returned instead of creating another.
+    ///
     /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/NetworkCreate>
     pub async fn create(
         &self,
         opts: &NetworkCreateOptions,
     ) -> Result<NetworkCreateInfo> {
-        let body: Body = opts.serialize()?.into();
-        let path = vec!["/networks/create".to_owned()];
+        idempotency::create(
+            &opts.params,
+            || self.create_new(opts),
+            |key| self.created_with_key(key),
+        )
+        .await
+    }
+
+    /// The network created with the idempotency `key`, if any
+    async fn created_with_key(
+        &self,
+        key: &str,
+    ) -> Result<Option<NetworkCreateInfo>> {
+        let networks = self.with_label(IDEMPOTENCY_KEY_LABEL, key).await?;
+        Ok(networks
+            .into_iter()
+            .next()
+            .map(|network| NetworkCreateInfo {
+                id: network.id,
+                warnings: Vec::new(),
+            }))
+    }
 
+    async fn create_new(
+        &self,
+        opts: &NetworkCreateOptions,
+    ) -> Result<NetworkCreateInfo> {
+        let body: Body = opts.serialize()?.into();
         self.docker
-            .post_json(&path.join("?"), Some((body, mime::APPLICATION_JSON)))
+            .post_json("/networks/create", Some((body, mime::APPLICATION_JSON)))
             .await
     }
+
+    /// Returns the network named in `opts`, creating it if it does not exist yet.
+    ///
+    /// An existing network is compatible when it has the driver and all labels `opts` ask for.
+    /// With `strict` an incompatible network is an
+    /// [Error::SpecMismatch](crate::Error::SpecMismatch), otherwise it is returned as is.
+    pub async fn ensure(
+        &self,
+        opts: &NetworkCreateOptions,
+        strict: bool,
+    ) -> Result<NetworkDetails> {
+        let network = match self.find(opts.name()).await? {
+            Some(network) => network,
+            None => match self.create(opts).await {
+                Ok(created) => return self.get(created.id).inspect().await,
+                // created concurrently by someone else
+                Err(Error::Fault { code, .. }) if code == StatusCode::CONFLICT => {
+                    self.find(opts.name()).await?.ok_or_else(|| {
+                        Error::InvalidResponse(format!(
+                            "network {} conflicts but does not exist",
+                            opts.name()
+                        ))
+                    })?
+                }
+                Err(e) => return Err(e),
+            },
+        };
+
+        let differences = opts.differences(&network);
+        if strict && !differences.is_empty() {
+            return Err(Error::SpecMismatch {
+                name: network.name,
+                differences,
+            });
+        }
+        Ok(network)
+    }
+
+    /// Looks up a network by its exact name
+    async fn find(
+        &self,
+        name: &str,
+    ) -> Result<Option<NetworkDetails>> {
+        let opts = NetworkListOptions::builder()
+            .filter(vec![Filter::name(name)])
+            .build();
+        // the name filter matches substrings
+        Ok(self
+            .list(&opts)
+            .await?
+            .into_iter()
+            .find(|network| network.name == name))
+    }
 }
 
 /// Interface for accessing and manipulating a docker network
@@ -106,6 +213,17 @@ impl<'docker> Network<'docker> {
             .await
     }
 
+    /// Inspects the network like [inspect](Network::inspect), additionally reporting the swarm
+    /// services attached to it, with their virtual IPs and tasks, and the nodes it spans. Only
+    /// swarm-scoped networks, such as overlays, have services and peers.
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/NetworkInspect>
+    pub async fn inspect_verbose(&self) -> Result<NetworkDetails> {
+        self.docker
+            .get_json(&format!("/networks/{}?verbose=true", self.id)[..])
+            .await
+    }
+
     /// Delete the network instance
     ///
     /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/NetworkDelete>
@@ -136,6 +254,90 @@ impl<'docker> Network<'docker> {
         self.do_connection("disconnect", opts).await
     }
 
+    /// Adds `aliases` to the names `container` can be reached by on this network.
+    ///
+    /// The daemon can't change the aliases of a connected container, so it is disconnected and
+    /// connected again with the new aliases and its static addresses kept. Connections to it on
+    /// this network are cut in the process. If it can't be connected again, it is connected
+    /// with its previous aliases instead and the error is returned.
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/NetworkConnect>
+    pub async fn add_aliases(
+        &self,
+        container: &str,
+        aliases: &[&str],
+    ) -> Result<()> {
+        self.update_aliases(container, |current| {
+            for alias in aliases {
+                if !current.iter().any(|existing| existing == alias) {
+                    current.push(alias.to_string());
+                }
+            }
+        })
+        .await
+    }
+
+    /// Removes `aliases` from the names `container` can be reached by on this network, the same
+    /// way [add_aliases](Network::add_aliases) adds them
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/NetworkConnect>
+    pub async fn remove_aliases(
+        &self,
+        container: &str,
+        aliases: &[&str],
+    ) -> Result<()> {
+        self.update_aliases(container, |current| {
+            current.retain(|existing| !aliases.contains(&existing.as_str()))
+        })
+        .await
+    }
+
+    async fn update_aliases<F>(
+        &self,
+        container: &str,
+        update: F,
+    ) -> Result<()>
+    where
+        F: FnOnce(&mut Vec<String>),
+    {
+        let details = self.docker.containers().get(container).inspect().await?;
+        let entry = details
+            .network_settings
+            .networks
+            .iter()
+            .find(|(name, entry)| **name == self.id || entry.network_id.starts_with(&self.id))
+            .map(|(_, entry)| entry)
+            .ok_or_else(|| Error::Fault {
+                code: StatusCode::NOT_FOUND,
+                message: format!(
+                    "container {} is not connected to network {}",
+                    container, self.id
+                ),
+            })?;
+
+        let previous = entry.aliases.clone().unwrap_or_default();
+        let mut aliases = previous.clone();
+        update(&mut aliases);
+        if aliases == previous {
+            return Ok(());
+        }
+
+        self.disconnect(&ContainerConnectionOptions::builder(container).build())
+            .await?;
+        match self
+            .connect(&entry.reconnection_options(container, &aliases))
+            .await
+        {
+            Err(e) => {
+                let _ = self
+                    .connect(&entry.reconnection_options(container, &previous))
+                    .await;
+                Err(e)
+            }
+            connected => connected,
+        }
+    }
+
     async fn do_connection(
         &self,
         segment: &str,
@@ -159,25 +361,35 @@ pub struct NetworkListOptions {
     params: HashMap<&'static str, String>,
 }
 
-impl NetworkListOptions {
-    /// serialize options as a string. returns None if no options are defined
-    pub fn serialize(&self) -> Option<String> {
-        if self.params.is_empty() {
-            None
-        } else {
-            Some(
-                form_urlencoded::Serializer::new(String::new())
-                    .extend_pairs(&self.params)
-                    .finish(),
-            )
-        }
+/// Builder interface for `NetworkListOptions`
+#[derive(Default)]
+pub struct NetworkListOptionsBuilder {
+    params: HashMap<&'static str, String>,
+}
+
+impl NetworkListOptionsBuilder {
+    pub fn filter<I, F>(
+        &mut self,
+        filters: I,
+    ) -> &mut Self
+    where
+        I: IntoIterator<Item = F>,
+        F: Into<Filter>,
+    {
+        self.params.insert("filters", filter::serialize(filters));
+        self
     }
 }
 
+impl_query_options!(NetworkListOptions, NetworkListOptionsBuilder);
+
 /// Interface for creating new docker network
-#[derive(Serialize, Debug)]
+///
+/// Serializes to the body of the create request, and can be deserialized from one.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct NetworkCreateOptions {
-    params: HashMap<&'static str, Value>,
+    params: Map<String, Value>,
 }
 
 impl NetworkCreateOptions {
@@ -186,9 +398,14 @@ impl NetworkCreateOptions {
         NetworkCreateOptionsBuilder::new(name)
     }
 
-    /// serialize options as a string. returns None if no options are defined
-    pub fn serialize(&self) -> Result<String> {
-        serde_json::to_string(&self.params).map_err(Error::from)
+    /// Options sending the JSON `body` as is, such as one deserialized from a stored spec
+    pub fn from_body(body: Map<String, Value>) -> NetworkCreateOptions {
+        NetworkCreateOptions { params: body }
+    }
+
+    /// The JSON body of the request
+    pub fn body(&self) -> &Map<String, Value> {
+        &self.params
     }
 
     pub fn parse_from<'a, K, V>(
@@ -209,16 +426,52 @@ impl NetworkCreateOptions {
     }
 }
 
+impl_json_options!(NetworkCreateOptions);
+
+impl NetworkCreateOptions {
+    pub(crate) fn name(&self) -> &str {
+        self.params
+            .get("Name")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+    }
+
+    /// Describes how an existing network differs from the driver and labels these options ask
+    /// for. Anything not asked for is not compared.
+    pub(crate) fn differences(
+        &self,
+        network: &NetworkDetails,
+    ) -> Vec<String> {
+        let mut differences = Vec::new();
+        if let Some(driver) = self.params.get("Driver").and_then(Value::as_str) {
+            if network.driver != driver {
+                differences.push(format!(
+                    "driver is {} instead of {}",
+                    network.driver, driver
+                ));
+            }
+        }
+        if let Some(Value::Object(labels)) = self.params.get("Labels") {
+            differences.extend(filter::label_differences(labels, &network.labels()));
+        }
+        differences
+    }
+}
+
 #[derive(Default)]
 pub struct NetworkCreateOptionsBuilder {
     params: HashMap<&'static str, Value>,
+    idempotency_key: Option<String>,
 }
 
 impl NetworkCreateOptionsBuilder {
     pub(crate) fn new(name: &str) -> Self {
         let mut params = HashMap::new();
         params.insert("Name", json!(name));
-        NetworkCreateOptionsBuilder { params }
+        NetworkCreateOptionsBuilder {
+            params,
+            idempotency_key: None,
+        }
     }
 
     pub fn driver(
@@ -239,10 +492,82 @@ impl NetworkCreateOptionsBuilder {
         self
     }
 
+    /// Sets a driver specific option, adding to those set before
+    pub fn driver_opt(
+        &mut self,
+        key: &str,
+        value: &str,
+    ) -> &mut Self {
+        let options = self.params.entry("Options").or_insert_with(|| json!({}));
+        if let Some(options) = options.as_object_mut() {
+            options.insert(key.to_owned(), json!(value));
+        }
+        self
+    }
+
+    /// Name of the linux bridge device, see [BRIDGE_NAME_OPTION]
+    pub fn bridge_name(
+        &mut self,
+        name: &str,
+    ) -> &mut Self {
+        self.driver_opt(BRIDGE_NAME_OPTION, name)
+    }
+
+    /// Whether containers on the bridge can talk to each other, see [BRIDGE_ENABLE_ICC_OPTION]
+    pub fn bridge_icc(
+        &mut self,
+        enable: bool,
+    ) -> &mut Self {
+        self.driver_opt(BRIDGE_ENABLE_ICC_OPTION, &enable.to_string())
+    }
+
+    /// Whether outgoing traffic is masqueraded, see [BRIDGE_ENABLE_IP_MASQUERADE_OPTION]
+    pub fn bridge_ip_masquerade(
+        &mut self,
+        enable: bool,
+    ) -> &mut Self {
+        self.driver_opt(BRIDGE_ENABLE_IP_MASQUERADE_OPTION, &enable.to_string())
+    }
+
+    /// Default host IP of published ports, see [BRIDGE_HOST_BINDING_IPV4_OPTION]
+    pub fn bridge_host_binding_ipv4(
+        &mut self,
+        ip: &str,
+    ) -> &mut Self {
+        self.driver_opt(BRIDGE_HOST_BINDING_IPV4_OPTION, ip)
+    }
+
+    /// MTU of the network's interfaces, see [DRIVER_MTU_OPTION]
+    pub fn mtu(
+        &mut self,
+        mtu: u32,
+    ) -> &mut Self {
+        self.driver_opt(DRIVER_MTU_OPTION, &mtu.to_string())
+    }
+
+    /// Labels the network with `key` as its [idempotency key](crate::idempotency), so that
+    /// [create](crate::Networks::create) can be retried safely
+    pub fn idempotency_key<K>(
+        &mut self,
+        key: K,
+    ) -> &mut Self
+    where
+        K: Into<String>,
+    {
+        self.idempotency_key = Some(key.into());
+        self
+    }
+
     pub fn build(&self) -> NetworkCreateOptions {
-        NetworkCreateOptions {
-            params: self.params.clone(),
+        let mut params = self
+            .params
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect();
+        if let Some(ref key) = self.idempotency_key {
+            idempotency::insert_key(&mut params, key);
         }
+        NetworkCreateOptions { params }
     }
 }
 
@@ -253,11 +578,6 @@ pub struct ContainerConnectionOptions {
 }
 
 impl ContainerConnectionOptions {
-    /// serialize options as a string. returns None if no options are defined
-    pub fn serialize(&self) -> Result<String> {
-        serde_json::to_string(&self.params).map_err(Error::from)
-    }
-
     pub fn parse_from<'a, K, V>(
         &self,
         params: &'a HashMap<K, V>,
@@ -281,6 +601,8 @@ impl ContainerConnectionOptions {
     }
 }
 
+impl_json_options!(ContainerConnectionOptions);
+
 #[derive(Default)]
 pub struct ContainerConnectionOptionsBuilder {
     params: HashMap<&'static str, Value>,
@@ -297,11 +619,53 @@ impl ContainerConnectionOptionsBuilder {
         &mut self,
         aliases: Vec<&str>,
     ) -> &mut Self {
-        self.params
-            .insert("EndpointConfig", json!({ "Aliases": json!(aliases) }));
+        self.endpoint_config()
+            .insert("Aliases".to_owned(), json!(aliases));
+        self
+    }
+
+    /// A static IPv4 address for the container on the network
+    pub fn ipv4_address(
+        &mut self,
+        address: &str,
+    ) -> &mut Self {
+        self.ipam_config()
+            .insert("IPv4Address".to_owned(), json!(address));
         self
     }
 
+    /// A static IPv6 address for the container on the network, which must have IPv6 enabled
+    pub fn ipv6_address(
+        &mut self,
+        address: &str,
+    ) -> &mut Self {
+        self.ipam_config()
+            .insert("IPv6Address".to_owned(), json!(address));
+        self
+    }
+
+    fn endpoint_config(&mut self) -> &mut Map<String, Value> {
+        match self
+            .params
+            .entry("EndpointConfig")
+            .or_insert_with(|| json!({}))
+        {
+            Value::Object(config) => config,
+            _ => unreachable!(),
+        }
+    }
+
+    fn ipam_config(&mut self) -> &mut Map<String, Value> {
+        match self
+            .endpoint_config()
+            .entry("IPAMConfig")
+            .or_insert_with(|| json!({}))
+        {
+            Value::Object(config) => config,
+            _ => unreachable!(),
+        }
+    }
+
     pub fn force(&mut self) -> &mut Self {
         self.params.insert("Force", json!(true));
         self
@@ -325,11 +689,67 @@ pub struct NetworkSettings {
     pub ip_address: String,
     #[serde(rename = "IPPrefixLen")]
     pub ip_prefix_len: u64,
+    #[serde(rename = "IPv6Gateway", default)]
+    pub ipv6_gateway: String,
+    #[serde(rename = "GlobalIPv6Address", default)]
+    pub global_ipv6_address: String,
+    #[serde(rename = "GlobalIPv6PrefixLen", default)]
+    pub global_ipv6_prefix_len: u64,
     pub mac_address: String,
     pub ports: Option<PortDescription>,
     pub networks: HashMap<String, NetworkEntry>,
 }
 
+impl NetworkSettings {
+    /// The host address a container port is published on, for `protocol` `tcp`, `udp` or
+    /// `sctp`. Prefers the IPv4 binding when the port is published on both IPv4 and IPv6.
+    pub fn mapped_port(
+        &self,
+        container_port: u16,
+        protocol: &str,
+    ) -> Option<MappedPort> {
+        let bindings = self
+            .ports
+            .as_ref()?
+            .get(&format!("{}/{}", container_port, protocol))?
+            .as_ref()?;
+        let mapped: Vec<MappedPort> = bindings
+            .iter()
+            .filter_map(|binding| {
+                Some(MappedPort {
+                    host_ip: binding.get("HostIp").cloned().unwrap_or_default(),
+                    host_port: binding.get("HostPort")?.parse().ok()?,
+                })
+            })
+            .collect();
+        mapped
+            .iter()
+            .find(|port| !port.is_ipv6())
+            .or_else(|| mapped.first())
+            .cloned()
+    }
+}
+
+/// Where on the docker host a container port is published
+#[derive(Clone, Debug, PartialEq)]
+pub struct MappedPort {
+    /// The address the port is bound to, `0.0.0.0` or `::` when bound on all of the host's
+    /// interfaces
+    pub host_ip: String,
+    pub host_port: u16,
+}
+
+impl MappedPort {
+    /// Whether the port is bound on all of the host's interfaces rather than a single address
+    pub fn is_unspecified(&self) -> bool {
+        self.host_ip.is_empty() || self.host_ip == "0.0.0.0" || self.host_ip == "::"
+    }
+
+    fn is_ipv6(&self) -> bool {
+        self.host_ip.contains(':')
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct NetworkEntry {
@@ -355,14 +775,35 @@ pub struct NetworkEntry {
     pub ipam_config: Option<EndpointIPAMConfig>,
 }
 
+impl NetworkEntry {
+    /// Options connecting `container` to the network again like this entry, with `aliases`
+    fn reconnection_options(
+        &self,
+        container: &str,
+        aliases: &[String],
+    ) -> ContainerConnectionOptions {
+        let mut opts = ContainerConnectionOptions::builder(container);
+        opts.aliases(aliases.iter().map(String::as_str).collect());
+        if let Some(ipam) = &self.ipam_config {
+            if !ipam.ipv4_address.is_empty() {
+                opts.ipv4_address(&ipam.ipv4_address);
+            }
+            if !ipam.ipv6_address.is_empty() {
+                opts.ipv6_address(&ipam.ipv6_address);
+            }
+        }
+        opts.build()
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[allow(clippy::upper_case_acronyms)]
 pub struct EndpointIPAMConfig {
-    #[serde(rename = "IPv4Address")]
+    #[serde(rename = "IPv4Address", default)]
     pub ipv4_address: String,
-    #[serde(rename = "IPv6Address")]
+    #[serde(rename = "IPv6Address", default)]
     pub ipv6_address: String,
-    #[serde(rename = "LinkLocalIPs")]
+    #[serde(rename = "LinkLocalIPs", default)]
     pub link_local_ips: Vec<String>,
 }
 
@@ -390,6 +831,58 @@ pub struct NetworkDetails {
     pub containers: HashMap<String, NetworkContainerDetails>,
     pub options: Option<HashMap<String, String>>,
     pub labels: Option<HashMap<String, String>>,
+    /// Swarm services attached to the network by name, only reported by
+    /// [Network::inspect_verbose]
+    pub services: Option<HashMap<String, NetworkServiceInfo>>,
+    /// The nodes the network spans, only reported by [Network::inspect_verbose]
+    pub peers: Option<Vec<NetworkPeer>>,
+}
+
+impl NetworkDetails {
+    /// Labels set on the network
+    pub fn labels(&self) -> HashMap<String, String> {
+        self.labels.clone().unwrap_or_default()
+    }
+}
+
+/// How a swarm service is reachable on a network
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct NetworkServiceInfo {
+    /// The virtual IP the service is load balanced behind, empty for services using DNS round
+    /// robin
+    #[serde(rename = "VIP")]
+    pub vip: String,
+    /// Ports published through the routing mesh, such as `Target: 80, Publish: 8080`
+    pub ports: Option<Vec<String>>,
+    /// Index of the service's load balancer on this node
+    #[serde(rename = "LocalLBIndex")]
+    pub local_lb_index: u64,
+    pub tasks: Option<Vec<NetworkTaskInfo>>,
+}
+
+/// A task of a swarm service attached to a network
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct NetworkTaskInfo {
+    pub name: String,
+    #[serde(rename = "EndpointID")]
+    pub endpoint_id: String,
+    /// The task's address on the network, which the service's virtual IP balances over
+    #[serde(rename = "EndpointIP")]
+    pub endpoint_ip: String,
+    /// Driver specific details, such as the `Host IP` of the node running the task
+    pub info: Option<HashMap<String, String>>,
+}
+
+/// A node a swarm-scoped network spans
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct NetworkPeer {
+    pub name: String,
+    /// The node's address on the underlay network
+    #[serde(rename = "IP")]
+    pub ip: String,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -408,5 +901,189 @@ pub struct NetworkContainerDetails {
 #[serde(rename_all = "PascalCase")]
 pub struct NetworkCreateInfo {
     pub id: String,
-    pub warning: String,
+    #[serde(default, alias = "Warning", deserialize_with = "crate::de::warnings")]
+    pub warnings: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn network_settings_mapped_port() {
+        let settings: NetworkSettings = serde_json::from_str(
+            r#"{"Bridge":"","Gateway":"172.17.0.1","IPAddress":"172.17.0.2","IPPrefixLen":16,"MacAddress":"02:42:ac:11:00:02","Networks":{},"Ports":{"80/tcp":[{"HostIp":"::","HostPort":"49154"},{"HostIp":"0.0.0.0","HostPort":"49153"}],"443/tcp":null,"53/udp":[{"HostIp":"127.0.0.1","HostPort":"5353"}]}}"#,
+        )
+        .unwrap();
+
+        let http = settings.mapped_port(80, "tcp").unwrap();
+        assert_eq!(
+            http,
+            MappedPort {
+                host_ip: "0.0.0.0".into(),
+                host_port: 49153
+            }
+        );
+        assert!(http.is_unspecified());
+        assert_eq!(settings.mapped_port(53, "udp").unwrap().host_port, 5353);
+        assert_eq!(settings.mapped_port(443, "tcp"), None);
+        assert_eq!(settings.mapped_port(53, "tcp"), None);
+    }
+
+    #[test]
+    fn network_details_verbose_services() {
+        let network: NetworkDetails = serde_json::from_str(
+            r#"{
+                "Name": "web", "Id": "k3m", "Scope": "swarm", "Driver": "overlay",
+                "EnableIPv6": false, "IPAM": {"Driver": "default", "Options": null, "Config": []},
+                "Internal": false, "Attachable": false, "Containers": {}, "Options": {},
+                "Labels": {},
+                "Services": {
+                    "web_api": {
+                        "VIP": "10.0.1.2", "Ports": ["Target: 80, Publish: 8080"],
+                        "LocalLBIndex": 260,
+                        "Tasks": [{
+                            "Name": "web_api.1.x7q", "EndpointID": "e1", "EndpointIP": "10.0.1.3",
+                            "Info": {"Host IP": "192.168.1.10"}
+                        }]
+                    }
+                },
+                "Peers": [{"Name": "node-1", "IP": "192.168.1.10"}]
+            }"#,
+        )
+        .unwrap();
+
+        let services = network.services.unwrap();
+        let api = &services["web_api"];
+        assert_eq!(api.vip, "10.0.1.2");
+        assert_eq!(api.local_lb_index, 260);
+        let task = &api.tasks.as_ref().unwrap()[0];
+        assert_eq!(task.endpoint_ip, "10.0.1.3");
+        assert_eq!(task.info.as_ref().unwrap()["Host IP"], "192.168.1.10");
+        assert_eq!(network.peers.unwrap()[0].ip, "192.168.1.10");
+    }
+
+    #[test]
+    fn container_connection_options_addresses() {
+        let opts = ContainerConnectionOptions::builder("web")
+            .ipv6_address("2001:db8::10")
+            .aliases(vec!["api"])
+            .ipv4_address("172.20.0.10")
+            .build();
+
+        assert_eq!(
+            serde_json::from_str::<Value>(&opts.serialize().unwrap()).unwrap(),
+            json!({
+                "Container": "web",
+                "EndpointConfig": {
+                    "Aliases": ["api"],
+                    "IPAMConfig": {"IPv4Address": "172.20.0.10", "IPv6Address": "2001:db8::10"}
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn network_entry_reconnection_options() {
+        let entry: NetworkEntry = serde_json::from_value(json!({
+            "NetworkID": "0123",
+            "EndpointID": "4567",
+            "Gateway": "172.20.0.1",
+            "IPAddress": "172.20.0.10",
+            "IPPrefixLen": 16,
+            "IPv6Gateway": "",
+            "GlobalIPv6Address": "",
+            "GlobalIPv6PrefixLen": 0,
+            "MacAddress": "02:42:ac:14:00:0a",
+            "Links": null,
+            "Aliases": ["web"],
+            "IPAMConfig": {"IPv4Address": "172.20.0.10"}
+        }))
+        .unwrap();
+
+        let opts = entry.reconnection_options("web", &["web".to_owned(), "api".to_owned()]);
+        assert_eq!(
+            serde_json::from_str::<Value>(&opts.serialize().unwrap()).unwrap(),
+            json!({
+                "Container": "web",
+                "EndpointConfig": {
+                    "Aliases": ["web", "api"],
+                    "IPAMConfig": {"IPv4Address": "172.20.0.10"}
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn network_create_options_differences() {
+        let network: NetworkDetails = serde_json::from_value(json!({
+            "Name": "backend",
+            "Id": "0123",
+            "Scope": "local",
+            "Driver": "bridge",
+            "EnableIPv6": false,
+            "IPAM": {"Driver": "default", "Config": []},
+            "Internal": false,
+            "Attachable": false,
+            "Containers": {},
+            "Labels": {"app": "shop", "tier": "db"},
+        }))
+        .unwrap();
+
+        let mut labels = HashMap::new();
+        labels.insert("app".to_owned(), "shop".to_owned());
+        let opts = NetworkCreateOptions::builder("backend")
+            .driver("bridge")
+            .label(labels.clone())
+            .build();
+        assert!(opts.differences(&network).is_empty());
+
+        labels.insert("app".to_owned(), "blog".to_owned());
+        labels.insert("env".to_owned(), "prod".to_owned());
+        let opts = NetworkCreateOptions::builder("backend")
+            .driver("overlay")
+            .label(labels)
+            .build();
+        assert_eq!(
+            opts.differences(&network),
+            vec![
+                "driver is bridge instead of overlay",
+                "label app is shop instead of blog",
+                "label env is missing",
+            ]
+        );
+    }
+
+    #[test]
+    fn network_create_options_bridge_driver_options() {
+        let opts = NetworkCreateOptions::builder("backend")
+            .driver("bridge")
+            .bridge_name("br-backend")
+            .bridge_icc(false)
+            .mtu(1400)
+            .driver_opt(BRIDGE_ENABLE_IP_MASQUERADE_OPTION, "true")
+            .build();
+        assert_eq!(
+            opts.body()["Options"],
+            json!({
+                "com.docker.network.bridge.name": "br-backend",
+                "com.docker.network.bridge.enable_icc": "false",
+                "com.docker.network.bridge.enable_ip_masquerade": "true",
+                "com.docker.network.driver.mtu": "1400",
+            })
+        );
+    }
+
+    #[test]
+    fn network_create_options_spec_round_trip() {
+        let opts = NetworkCreateOptions::builder("backend")
+            .driver("bridge")
+            .build();
+        let spec = serde_json::to_string(&opts).unwrap();
+        assert_eq!(spec, r#"{"Driver":"bridge","Name":"backend"}"#);
+
+        let parsed: NetworkCreateOptions = serde_json::from_str(&spec).unwrap();
+        assert_eq!(parsed, opts);
+        assert_eq!(parsed.name(), "backend");
+    }
 }
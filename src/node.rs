@@ -0,0 +1,198 @@
+//! Inspect the nodes of a swarm.
+//!
+//! API Reference: <https://docs.docker.com/engine/api/v1.41/#tag/Node>
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::Result,
+    filter::{self, Filter},
+    service::ObjectVersion,
+    Docker,
+};
+
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, Utc};
+
+/// Interface for the nodes of a swarm
+///
+/// API Reference: <https://docs.docker.com/engine/api/v1.41/#tag/Node>
+pub struct Nodes<'docker> {
+    docker: &'docker Docker,
+}
+
+impl<'docker> Nodes<'docker> {
+    /// Exports an interface for interacting with swarm nodes
+    pub fn new(docker: &'docker Docker) -> Self {
+        Nodes { docker }
+    }
+
+    /// Lists the nodes of the swarm the docker host is a manager of
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/NodeList>
+    pub async fn list(
+        &self,
+        opts: &NodeListOptions,
+    ) -> Result<Vec<NodeInfo>> {
+        let mut path = vec!["/nodes".to_owned()];
+        if let Some(query) = opts.serialize() {
+            path.push(query);
+        }
+        self.docker.get_json(&path.join("?")).await
+    }
+}
+
+/// Filter options for node list results
+pub enum NodeFilter {
+    Id(String),
+    /// Nodes carrying an engine label with the given name, or `name=value`
+    Label(String),
+    Membership(NodeMembership),
+    Name(String),
+    /// Nodes carrying a node label with the given name, or `name=value`
+    NodeLabel(String),
+    Role(NodeRole),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeMembership {
+    Accepted,
+    Pending,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeRole {
+    Manager,
+    Worker,
+}
+
+impl NodeMembership {
+    fn as_str(&self) -> &'static str {
+        match self {
+            NodeMembership::Accepted => "accepted",
+            NodeMembership::Pending => "pending",
+        }
+    }
+}
+
+impl NodeRole {
+    fn as_str(&self) -> &'static str {
+        match self {
+            NodeRole::Manager => "manager",
+            NodeRole::Worker => "worker",
+        }
+    }
+}
+
+impl From<NodeFilter> for Filter {
+    fn from(filter: NodeFilter) -> Self {
+        match filter {
+            NodeFilter::Id(i) => Filter::id(i),
+            NodeFilter::Label(l) => Filter::label(l),
+            NodeFilter::Membership(m) => Filter::new("membership", m.as_str()),
+            NodeFilter::Name(n) => Filter::name(n),
+            NodeFilter::NodeLabel(l) => Filter::new("node.label", l),
+            NodeFilter::Role(r) => Filter::new("role", r.as_str()),
+        }
+    }
+}
+
+/// Options for filtering node list results
+#[derive(Default, Debug)]
+pub struct NodeListOptions {
+    params: HashMap<&'static str, String>,
+}
+
+/// Builder interface for `NodeListOptions`
+#[derive(Default)]
+pub struct NodeListOptionsBuilder {
+    params: HashMap<&'static str, String>,
+}
+
+impl NodeListOptionsBuilder {
+    pub fn filter<I, F>(
+        &mut self,
+        filters: I,
+    ) -> &mut Self
+    where
+        I: IntoIterator<Item = F>,
+        F: Into<Filter>,
+    {
+        self.params.insert("filters", filter::serialize(filters));
+        self
+    }
+}
+
+impl_query_options!(NodeListOptions, NodeListOptionsBuilder);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct NodeInfo {
+    #[serde(rename = "ID")]
+    pub id: String,
+    pub version: ObjectVersion,
+    #[cfg(feature = "chrono")]
+    pub created_at: DateTime<Utc>,
+    #[cfg(not(feature = "chrono"))]
+    pub created_at: String,
+    #[cfg(feature = "chrono")]
+    pub updated_at: DateTime<Utc>,
+    #[cfg(not(feature = "chrono"))]
+    pub updated_at: String,
+    pub spec: NodeSpec,
+    pub description: Option<NodeDescription>,
+    pub status: NodeStatus,
+    pub manager_status: Option<ManagerStatus>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct NodeSpec {
+    pub name: Option<String>,
+    pub labels: Option<HashMap<String, String>>,
+    pub role: NodeRole,
+    pub availability: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct NodeDescription {
+    pub hostname: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct NodeStatus {
+    pub state: String,
+    pub message: Option<String>,
+    pub addr: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ManagerStatus {
+    pub leader: Option<bool>,
+    pub reachability: String,
+    pub addr: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_filters_serialize() {
+        assert_eq!(
+            filter::serialize(vec![
+                NodeFilter::Role(NodeRole::Manager),
+                NodeFilter::Membership(NodeMembership::Accepted),
+                NodeFilter::NodeLabel("zone=eu".into()),
+            ]),
+            r#"{"membership":["accepted"],"node.label":["zone=eu"],"role":["manager"]}"#
+        );
+    }
+}
@@ -0,0 +1,130 @@
+//! Shared plumbing for the `*Options` types and their builders.
+//!
+//! Query string options keep their values in a `params: HashMap<&'static str, String>` and JSON
+//! body options in a `params: HashMap<&'static str, Value>`. The macros here generate the
+//! `builder()`, `serialize()` and `build()` boilerplate around those maps, and simple setters,
+//! so every module encodes its options the same way.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+use url::form_urlencoded;
+
+use crate::errors::{Error, Result};
+
+/// Encodes query parameters as a query string. returns None if there are no parameters
+pub(crate) fn encode_query(params: &HashMap<&'static str, String>) -> Option<String> {
+    if params.is_empty() {
+        None
+    } else {
+        Some(
+            form_urlencoded::Serializer::new(String::new())
+                .extend_pairs(params)
+                .finish(),
+        )
+    }
+}
+
+/// Encodes body parameters as a JSON object
+pub(crate) fn encode_json<P>(params: &P) -> Result<String>
+where
+    P: Serialize,
+{
+    serde_json::to_string(params).map_err(Error::from)
+}
+
+/// Implements `builder()`, `serialize()` and the builder's `build()` for an options type whose
+/// only state is a `params` map of query parameters.
+macro_rules! impl_query_options {
+    ($options:ident, $builder:ident) => {
+        impl $options {
+            /// return a new instance of a builder for options
+            pub fn builder() -> $builder {
+                $builder::default()
+            }
+
+            /// serialize options as a string. returns None if no options are defined
+            pub fn serialize(&self) -> Option<String> {
+                crate::options::encode_query(&self.params)
+            }
+        }
+
+        impl $builder {
+            pub fn build(&self) -> $options {
+                $options {
+                    params: self.params.clone(),
+                }
+            }
+        }
+    };
+}
+
+/// Implements `serialize()` for an options type sent as a JSON body from its `params` map.
+macro_rules! impl_json_options {
+    ($options:ident) => {
+        impl $options {
+            /// serialize options as a JSON string
+            pub fn serialize(&self) -> crate::errors::Result<String> {
+                crate::options::encode_json(&self.params)
+            }
+        }
+    };
+}
+
+/// Generates builder setters storing a value in the query `params` map under the given key.
+macro_rules! query_setters {
+    ($($(#[$attr:meta])* $name:ident: $ty:ty => $key:literal),* $(,)?) => {
+        $(
+            $(#[$attr])*
+            pub fn $name(
+                &mut self,
+                value: $ty,
+            ) -> &mut Self {
+                self.params.insert($key, value.to_string());
+                self
+            }
+        )*
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct ExampleOptions {
+        params: HashMap<&'static str, String>,
+    }
+
+    #[derive(Default)]
+    struct ExampleOptionsBuilder {
+        params: HashMap<&'static str, String>,
+    }
+
+    impl ExampleOptionsBuilder {
+        query_setters! {
+            all: bool => "all",
+            limit: u64 => "limit",
+        }
+    }
+
+    impl_query_options!(ExampleOptions, ExampleOptionsBuilder);
+
+    #[test]
+    fn query_options_serialize() {
+        assert_eq!(ExampleOptions::default().serialize(), None);
+
+        let opts = ExampleOptions::builder().all(true).build();
+        assert_eq!(opts.serialize(), Some("all=true".to_string()));
+
+        let opts = ExampleOptions::builder().limit(10).build();
+        assert_eq!(opts.serialize(), Some("limit=10".to_string()));
+    }
+
+    #[test]
+    fn json_options_serialize() {
+        let mut params = HashMap::new();
+        params.insert("Name", serde_json::json!("example"));
+        assert_eq!(encode_json(&params).unwrap(), r#"{"Name":"example"}"#);
+    }
+}
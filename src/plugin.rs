@@ -0,0 +1,86 @@
+//! Inspect the plugins extending the docker daemon.
+//!
+//! API Reference: <https://docs.docker.com/engine/api/v1.41/#tag/Plugin>
+
+use serde::{Deserialize, Serialize};
+use url::form_urlencoded;
+
+use crate::{errors::Result, Docker};
+
+/// Interface for docker plugins
+///
+/// API Reference: <https://docs.docker.com/engine/api/v1.41/#tag/Plugin>
+pub struct Plugins<'docker> {
+    docker: &'docker Docker,
+}
+
+impl<'docker> Plugins<'docker> {
+    /// Exports an interface for interacting with docker plugins
+    pub fn new(docker: &'docker Docker) -> Self {
+        Plugins { docker }
+    }
+
+    /// Lists the plugins installed on the docker host
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/PluginList>
+    pub async fn list(&self) -> Result<Vec<PluginInfo>> {
+        self.docker.get_json("/plugins").await
+    }
+
+    /// Returns the privileges the plugin at `remote`, e.g. `vieux/sshfs:latest`, requests, so
+    /// they can be reviewed before it is installed. Installing grants exactly these privileges.
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/GetPluginPrivileges>
+    pub async fn privileges(
+        &self,
+        remote: &str,
+    ) -> Result<Vec<PluginPrivilege>> {
+        let query = form_urlencoded::Serializer::new(String::new())
+            .append_pair("remote", remote)
+            .finish();
+        self.docker
+            .get_json(&format!("/plugins/privileges?{}", query))
+            .await
+    }
+}
+
+/// A privilege requested by a plugin
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PluginPrivilege {
+    pub name: String,
+    pub description: String,
+    pub value: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PluginInfo {
+    #[serde(rename = "Id")]
+    pub id: Option<String>,
+    pub name: String,
+    pub enabled: bool,
+    pub plugin_reference: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plugin_privileges_deserialize() {
+        let privileges: Vec<PluginPrivilege> = serde_json::from_str(
+            r#"[{"Name":"network","Description":"permissions to access a network","Value":["host"]}]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            privileges,
+            vec![PluginPrivilege {
+                name: "network".into(),
+                description: "permissions to access a network".into(),
+                value: vec!["host".into()],
+            }]
+        );
+    }
+}
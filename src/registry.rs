@@ -0,0 +1,551 @@
+//! A client for registries implementing the OCI distribution API, to list repositories and
+//! tags, fetch manifests and delete tags without going through a docker daemon.
+//!
+//! Credentials are loaded the way the docker CLI stores them, see
+//! [ClientConfig::registry_auth](crate::ClientConfig::registry_auth).
+//!
+//! API Reference: <https://github.com/opencontainers/distribution-spec/blob/main/spec.md>
+
+use std::{collections::HashMap, io, sync::Mutex};
+
+use hyper::{
+    client::HttpConnector,
+    header::{self, HeaderValue},
+    Body, Client, Method, Request, Response, StatusCode,
+};
+use hyper_openssl::HttpsConnector;
+use serde::Deserialize;
+use serde_json::Value;
+use url::form_urlencoded;
+
+use crate::{
+    config::{registry_host, ClientConfig},
+    errors::{Error, Result},
+    image::{MediaType, RegistryAuth},
+};
+
+/// The manifest media types asked for, OCI and Docker schema 2 alike
+const MANIFEST_ACCEPT: &str = "application/vnd.oci.image.manifest.v1+json, \
+     application/vnd.oci.image.index.v1+json, \
+     application/vnd.docker.distribution.manifest.v2+json, \
+     application/vnd.docker.distribution.manifest.list.v2+json";
+
+/// Interface for a registry
+pub struct Registry {
+    client: Client<HttpsConnector<HttpConnector>>,
+    /// Scheme and host the API is served at, such as `https://registry-1.docker.io`
+    base: String,
+    docker_hub: bool,
+    auth: Option<RegistryAuth>,
+    /// Bearer tokens by the scope they were issued for
+    tokens: Mutex<HashMap<String, String>>,
+}
+
+impl Registry {
+    /// Exports an interface for the registry at `registry`, such as `docker.io` or
+    /// `registry.example.com:5000`, authenticating with the credentials `docker login` stored
+    /// for it, if any. The registry is reached over https unless `registry` starts with
+    /// `http://`.
+    pub fn new(registry: &str) -> Result<Registry> {
+        let auth = ClientConfig::default().registry_auth(registry)?;
+        Registry::with_auth(registry, auth)
+    }
+
+    /// Exports an interface for the registry at `registry`, authenticating with `auth`
+    pub fn with_auth(
+        registry: &str,
+        auth: Option<RegistryAuth>,
+    ) -> Result<Registry> {
+        let connector =
+            HttpsConnector::new().map_err(|e| Error::IO(io::Error::other(e.to_string())))?;
+        let docker_hub = registry_host(registry) == "docker.io";
+        let base = if docker_hub {
+            "https://registry-1.docker.io".to_owned()
+        } else if registry.starts_with("http://") || registry.starts_with("https://") {
+            registry.trim_end_matches('/').to_owned()
+        } else {
+            format!("https://{}", registry.trim_end_matches('/'))
+        };
+
+        Ok(Registry {
+            client: Client::builder().build(connector),
+            base,
+            docker_hub,
+            auth,
+            tokens: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Lists the repositories of the registry. Registries may restrict this to their
+    /// administrators, and Docker Hub does not offer it at all.
+    pub async fn catalog(&self) -> Result<Vec<String>> {
+        #[derive(Deserialize)]
+        struct Catalog {
+            #[serde(default)]
+            repositories: Vec<String>,
+        }
+
+        let mut repositories = Vec::new();
+        self.paginate("/v2/_catalog", "registry:catalog:*", |page: Catalog| {
+            repositories.extend(page.repositories)
+        })
+        .await?;
+        Ok(repositories)
+    }
+
+    /// Lists the tags of `repository`
+    pub async fn tags(
+        &self,
+        repository: &str,
+    ) -> Result<Vec<String>> {
+        #[derive(Deserialize)]
+        struct Tags {
+            #[serde(default)]
+            tags: Option<Vec<String>>,
+        }
+
+        let repository = self.repository(repository);
+        let mut tags = Vec::new();
+        self.paginate(
+            &format!("/v2/{}/tags/list", repository),
+            &pull_scope(&repository),
+            |page: Tags| tags.extend(page.tags.unwrap_or_default()),
+        )
+        .await?;
+        Ok(tags)
+    }
+
+    /// Fetches the manifest of `repository` a tag or digest refers to. An index is returned as
+    /// is, rather than resolved to the manifest of a platform.
+    pub async fn manifest(
+        &self,
+        repository: &str,
+        reference: &str,
+    ) -> Result<Manifest> {
+        let repository = self.repository(repository);
+        let response = self
+            .send(
+                Method::GET,
+                &format!("/v2/{}/manifests/{}", repository, reference),
+                &pull_scope(&repository),
+                Some(MANIFEST_ACCEPT),
+            )
+            .await?;
+        let media_type = header_str(&response, header::CONTENT_TYPE).map(MediaType::from);
+        let digest = header_str(&response, "Docker-Content-Digest").map(str::to_owned);
+        let body = hyper::body::to_bytes(response.into_body()).await?.to_vec();
+
+        let media_type = match media_type {
+            Some(media_type) => media_type,
+            None => {
+                let value: Value = serde_json::from_slice(&body)?;
+                MediaType::from(value["mediaType"].as_str().unwrap_or_default())
+            }
+        };
+        Ok(Manifest {
+            media_type,
+            digest,
+            body,
+        })
+    }
+
+    /// The digest of the manifest of `repository` a tag refers to, without fetching it
+    pub async fn manifest_digest(
+        &self,
+        repository: &str,
+        reference: &str,
+    ) -> Result<String> {
+        let repository = self.repository(repository);
+        let response = self
+            .send(
+                Method::HEAD,
+                &format!("/v2/{}/manifests/{}", repository, reference),
+                &pull_scope(&repository),
+                Some(MANIFEST_ACCEPT),
+            )
+            .await?;
+        header_str(&response, "Docker-Content-Digest")
+            .map(str::to_owned)
+            .ok_or_else(|| {
+                Error::InvalidResponse(format!(
+                    "the registry did not report the digest of {}:{}",
+                    repository, reference
+                ))
+            })
+    }
+
+    /// Deletes `tag` from `repository`, leaving the manifest it refers to and its other tags.
+    /// Not every registry supports deleting tags, see
+    /// [delete_manifest](Registry::delete_manifest) for those.
+    pub async fn delete_tag(
+        &self,
+        repository: &str,
+        tag: &str,
+    ) -> Result<()> {
+        self.delete_reference(repository, tag).await
+    }
+
+    /// Deletes the manifest with `digest` from `repository`, along with every tag referring to
+    /// it. The registry may need deletes enabled, as with `REGISTRY_STORAGE_DELETE_ENABLED` for
+    /// the reference registry.
+    pub async fn delete_manifest(
+        &self,
+        repository: &str,
+        digest: &str,
+    ) -> Result<()> {
+        self.delete_reference(repository, digest).await
+    }
+
+    async fn delete_reference(
+        &self,
+        repository: &str,
+        reference: &str,
+    ) -> Result<()> {
+        let repository = self.repository(repository);
+        self.send(
+            Method::DELETE,
+            &format!("/v2/{}/manifests/{}", repository, reference),
+            &format!("repository:{}:delete", repository),
+            None,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Docker Hub keeps official images under `library/`, which the docker CLI leaves out
+    fn repository(
+        &self,
+        repository: &str,
+    ) -> String {
+        if self.docker_hub && !repository.contains('/') {
+            format!("library/{}", repository)
+        } else {
+            repository.to_owned()
+        }
+    }
+
+    /// Fetches every page of a paginated list, following the `Link` headers
+    async fn paginate<T, F>(
+        &self,
+        path: &str,
+        scope: &str,
+        mut page: F,
+    ) -> Result<()>
+    where
+        T: serde::de::DeserializeOwned,
+        F: FnMut(T),
+    {
+        let mut next = Some(path.to_owned());
+        while let Some(path) = next.take() {
+            let response = self.send(Method::GET, &path, scope, None).await?;
+            next = header_str(&response, header::LINK).and_then(next_link);
+            let body = hyper::body::to_bytes(response.into_body()).await?;
+            page(serde_json::from_slice(&body)?);
+        }
+        Ok(())
+    }
+
+    /// Sends a request, authenticating as the registry asks on the first `401`
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        scope: &str,
+        accept: Option<&str>,
+    ) -> Result<Response<Body>> {
+        let cached = self.tokens.lock().unwrap().get(scope).cloned();
+        let authorization = cached.map(|token| format!("Bearer {}", token));
+        let response = self
+            .request(method.clone(), path, accept, authorization.as_deref())
+            .await?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return check(response).await;
+        }
+
+        let challenge = header_str(&response, header::WWW_AUTHENTICATE)
+            .map(Challenge::parse)
+            .unwrap_or(Challenge::Basic);
+        let authorization = match challenge {
+            Challenge::Basic => match self.auth {
+                Some(RegistryAuth::Password {
+                    ref username,
+                    ref password,
+                    ..
+                }) => basic(username, password),
+                _ => return check(response).await,
+            },
+            Challenge::Bearer {
+                realm,
+                service,
+                scope: challenge_scope,
+            } => {
+                let scope = challenge_scope.unwrap_or_else(|| scope.to_owned());
+                let token = self.token(&realm, service.as_deref(), &scope).await?;
+                self.tokens.lock().unwrap().insert(scope, token.clone());
+                format!("Bearer {}", token)
+            }
+        };
+        check(
+            self.request(method, path, accept, Some(&authorization))
+                .await?,
+        )
+        .await
+    }
+
+    async fn request(
+        &self,
+        method: Method,
+        path: &str,
+        accept: Option<&str>,
+        authorization: Option<&str>,
+    ) -> Result<Response<Body>> {
+        let uri = if path.starts_with("http://") || path.starts_with("https://") {
+            path.to_owned()
+        } else {
+            format!("{}{}", self.base, path)
+        };
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(accept) = accept {
+            request = request.header(header::ACCEPT, accept);
+        }
+        if let Some(authorization) = authorization {
+            request = request.header(header::AUTHORIZATION, authorization);
+        }
+        Ok(self.client.request(request.body(Body::empty())?).await?)
+    }
+
+    /// Fetches a bearer token from the registry's token server
+    async fn token(
+        &self,
+        realm: &str,
+        service: Option<&str>,
+        scope: &str,
+    ) -> Result<String> {
+        #[derive(Deserialize)]
+        struct TokenResponse {
+            token: Option<String>,
+            access_token: Option<String>,
+        }
+
+        let mut params = form_urlencoded::Serializer::new(String::new());
+        if let Some(service) = service {
+            params.append_pair("service", service);
+        }
+        params.append_pair("scope", scope);
+
+        let request = match self.auth {
+            // an identity token is an OAuth2 refresh token
+            Some(RegistryAuth::Token { ref identity_token }) => {
+                let body = params
+                    .append_pair("grant_type", "refresh_token")
+                    .append_pair("refresh_token", identity_token)
+                    .append_pair("client_id", "shiplift")
+                    .finish();
+                Request::post(realm)
+                    .header(
+                        header::CONTENT_TYPE,
+                        HeaderValue::from_static("application/x-www-form-urlencoded"),
+                    )
+                    .body(Body::from(body))?
+            }
+            ref auth => {
+                let mut request = Request::get(format!("{}?{}", realm, params.finish()));
+                if let Some(RegistryAuth::Password {
+                    username, password, ..
+                }) = auth
+                {
+                    request = request.header(header::AUTHORIZATION, basic(username, password));
+                }
+                request.body(Body::empty())?
+            }
+        };
+
+        let response = check(self.client.request(request).await?).await?;
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+        let token: TokenResponse = serde_json::from_slice(&body)?;
+        token
+            .token
+            .or(token.access_token)
+            .ok_or_else(|| Error::InvalidResponse("the token server did not return a token".into()))
+    }
+}
+
+/// A manifest fetched from a registry
+#[derive(Clone, Debug)]
+pub struct Manifest {
+    pub media_type: MediaType,
+    /// The digest the registry reported for the manifest
+    pub digest: Option<String>,
+    /// The manifest as the registry returned it, which its digest is computed over
+    pub body: Vec<u8>,
+}
+
+impl Manifest {
+    /// The manifest as JSON
+    pub fn json(&self) -> Result<Value> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+}
+
+/// How a registry asks to be authenticated with, from its `WWW-Authenticate` header
+#[derive(Debug, PartialEq)]
+enum Challenge {
+    Basic,
+    Bearer {
+        realm: String,
+        service: Option<String>,
+        scope: Option<String>,
+    },
+}
+
+impl Challenge {
+    fn parse(header: &str) -> Challenge {
+        let params = match header.split_once(' ') {
+            Some((scheme, params)) if scheme.eq_ignore_ascii_case("bearer") => params,
+            _ => return Challenge::Basic,
+        };
+
+        let mut values = HashMap::new();
+        let mut rest = params.trim();
+        while let Some((key, value)) = rest.split_once('=') {
+            let key = key
+                .trim()
+                .trim_start_matches(',')
+                .trim()
+                .to_ascii_lowercase();
+            let (value, remaining) = match value.strip_prefix('"') {
+                Some(quoted) => match quoted.split_once('"') {
+                    Some((value, remaining)) => (value, remaining),
+                    None => (quoted, ""),
+                },
+                None => match value.split_once(',') {
+                    Some((value, remaining)) => (value, remaining),
+                    None => (value, ""),
+                },
+            };
+            values.insert(key, value.to_owned());
+            rest = remaining;
+        }
+
+        match values.remove("realm") {
+            Some(realm) => Challenge::Bearer {
+                realm,
+                service: values.remove("service"),
+                scope: values.remove("scope"),
+            },
+            None => Challenge::Basic,
+        }
+    }
+}
+
+fn pull_scope(repository: &str) -> String {
+    format!("repository:{}:pull", repository)
+}
+
+fn basic(
+    username: &str,
+    password: &str,
+) -> String {
+    format!(
+        "Basic {}",
+        base64::encode(format!("{}:{}", username, password))
+    )
+}
+
+fn header_str<K>(
+    response: &Response<Body>,
+    name: K,
+) -> Option<&str>
+where
+    K: header::AsHeaderName,
+{
+    response.headers().get(name)?.to_str().ok()
+}
+
+/// The target of a `Link: </v2/_catalog?last=b&n=100>; rel="next"` header
+fn next_link(link: &str) -> Option<String> {
+    if !link.contains("rel=\"next\"") {
+        return None;
+    }
+    let start = link.find('<')? + 1;
+    let end = link[start..].find('>')? + start;
+    Some(link[start..end].to_owned())
+}
+
+/// Turns a response the registry failed with into an error, with the messages of the
+/// registry's error body
+async fn check(response: Response<Body>) -> Result<Response<Body>> {
+    #[derive(Deserialize)]
+    struct Errors {
+        errors: Vec<ErrorEntry>,
+    }
+    #[derive(Deserialize)]
+    struct ErrorEntry {
+        code: String,
+        #[serde(default)]
+        message: String,
+    }
+
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = hyper::body::to_bytes(response.into_body()).await?;
+    let message = match serde_json::from_slice::<Errors>(&body) {
+        Ok(errors) => errors
+            .errors
+            .iter()
+            .map(|e| format!("{}: {}", e.code, e.message))
+            .collect::<Vec<_>>()
+            .join(", "),
+        Err(_) => String::from_utf8_lossy(&body).into_owned(),
+    };
+    Err(Error::Fault {
+        code: status,
+        message,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn challenge_parse() {
+        assert_eq!(
+            Challenge::parse(
+                r#"Bearer realm="https://auth.docker.io/token",service="registry.docker.io",scope="repository:library/alpine:pull""#
+            ),
+            Challenge::Bearer {
+                realm: "https://auth.docker.io/token".into(),
+                service: Some("registry.docker.io".into()),
+                scope: Some("repository:library/alpine:pull".into()),
+            }
+        );
+        assert_eq!(
+            Challenge::parse(r#"Basic realm="Registry Realm""#),
+            Challenge::Basic
+        );
+    }
+
+    #[test]
+    fn link_pagination() {
+        assert_eq!(
+            next_link(r#"</v2/_catalog?last=b&n=100>; rel="next""#),
+            Some("/v2/_catalog?last=b&n=100".to_owned())
+        );
+        assert_eq!(next_link(r#"</v2/_catalog?n=1>; rel="prev""#), None);
+    }
+
+    #[test]
+    fn docker_hub_repositories() {
+        let hub = Registry::with_auth("docker.io", None).unwrap();
+        assert_eq!(hub.base, "https://registry-1.docker.io");
+        assert_eq!(hub.repository("alpine"), "library/alpine");
+        assert_eq!(hub.repository("grafana/grafana"), "grafana/grafana");
+
+        let local = Registry::with_auth("http://localhost:5000", None).unwrap();
+        assert_eq!(local.base, "http://localhost:5000");
+        assert_eq!(local.repository("alpine"), "alpine");
+    }
+}
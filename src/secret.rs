@@ -0,0 +1,388 @@
+//! Create and manage sensitive data for swarm services.
+//!
+//! Secrets are immutable once created. To change a secret's data a new secret has to be created
+//! and the services using it pointed at the new one, which
+//! [Secrets::replace](struct.Secrets.html#method.replace) automates.
+//!
+//! API Reference: <https://docs.docker.com/engine/api/v1.41/#tag/Secret>
+
+use std::{
+    collections::HashMap,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use hyper::Body;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    errors::{Error, Result},
+    service::ObjectVersion,
+    Docker,
+};
+
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, Utc};
+
+/// Label recording what created a secret
+pub const CREATED_BY_LABEL: &str = "created-by";
+/// Label recording when a secret was created, in seconds since the unix epoch
+pub const CREATED_AT_LABEL: &str = "created-at";
+
+/// Interface for docker secrets
+///
+/// API Reference: <https://docs.docker.com/engine/api/v1.41/#tag/Secret>
+pub struct Secrets<'docker> {
+    docker: &'docker Docker,
+}
+
+impl<'docker> Secrets<'docker> {
+    /// Exports an interface for interacting with docker secrets
+    pub fn new(docker: &'docker Docker) -> Self {
+        Secrets { docker }
+    }
+
+    /// Lists the secrets of the swarm
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/SecretList>
+    pub async fn list(&self) -> Result<Vec<SecretInfo>> {
+        self.docker.get_json("/secrets").await
+    }
+
+    /// Creates a new secret
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/SecretCreate>
+    pub async fn create(
+        &self,
+        opts: &SecretOptions,
+    ) -> Result<SecretCreateInfo> {
+        let body: Body = opts.serialize()?.into();
+        self.docker
+            .post_json("/secrets/create", Some((body, mime::APPLICATION_JSON)))
+            .await
+    }
+
+    /// Replaces the data of the secret named `name`. As secrets are immutable, this creates a
+    /// new secret with the next versioned name (`db-password` becomes `db-password.v2`,
+    /// `db-password.v2` becomes `db-password.v3`) carrying the old secret's labels, and updates
+    /// every service using the old secret to use the new one under the same target file.
+    ///
+    /// The old secret is left in place, as services keep using it until their rolling update
+    /// completes. Delete it once it is no longer needed.
+    pub async fn replace<D>(
+        &self,
+        name: &str,
+        data: D,
+    ) -> Result<SecretReplacement>
+    where
+        D: AsRef<[u8]>,
+    {
+        let current = self.get(name).inspect().await?;
+        let new_name = next_version(&current.spec.name);
+
+        let mut opts = SecretOptions::builder(&new_name);
+        opts.data(data);
+        if let Some(labels) = &current.spec.labels {
+            opts.labels(labels.iter().filter(|(key, _)| {
+                key.as_str() != CREATED_BY_LABEL && key.as_str() != CREATED_AT_LABEL
+            }));
+        }
+        let created = self.create(&opts.build()).await?;
+
+        let services: Vec<Value> = self.docker.get_json("/services").await?;
+        let mut updated_services = Vec::new();
+        for mut service in services {
+            let spec = &mut service["Spec"];
+            if !replace_secret_refs(spec, &current.id, &created.id, &new_name) {
+                continue;
+            }
+            let id = service["ID"].as_str().unwrap_or_default().to_owned();
+            let version = service["Version"]["Index"]
+                .as_u64()
+                .ok_or_else(|| Error::InvalidResponse(format!("service {} has no version", id)))?;
+            let body: Body = serde_json::to_string(&service["Spec"])?.into();
+            self.docker
+                .post(
+                    &format!("/services/{}/update?version={}", id, version),
+                    Some((body, mime::APPLICATION_JSON)),
+                )
+                .await?;
+            updated_services.push(id);
+        }
+
+        Ok(SecretReplacement {
+            id: created.id,
+            name: new_name,
+            updated_services,
+        })
+    }
+
+    /// Returns a reference to a set of operations available for a named secret
+    pub fn get(
+        &self,
+        name: &str,
+    ) -> Secret<'docker> {
+        Secret::new(self.docker, name)
+    }
+}
+
+/// Interface for accessing and manipulating a named docker secret
+///
+/// API Reference: <https://docs.docker.com/engine/api/v1.41/#tag/Secret>
+pub struct Secret<'docker> {
+    docker: &'docker Docker,
+    name: String,
+}
+
+impl<'docker> Secret<'docker> {
+    /// Exports an interface for operations that may be performed against a named secret
+    pub fn new<S>(
+        docker: &'docker Docker,
+        name: S,
+    ) -> Self
+    where
+        S: Into<String>,
+    {
+        Secret {
+            docker,
+            name: name.into(),
+        }
+    }
+
+    /// Inspects a secret. Its data is never returned.
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/SecretInspect>
+    pub async fn inspect(&self) -> Result<SecretInfo> {
+        self.docker
+            .get_json(&format!("/secrets/{}", self.name)[..])
+            .await
+    }
+
+    /// Deletes a secret
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/SecretDelete>
+    pub async fn delete(&self) -> Result<()> {
+        self.docker
+            .delete(&format!("/secrets/{}", self.name)[..])
+            .await?;
+        Ok(())
+    }
+}
+
+/// Options for creating a secret
+#[derive(Serialize, Debug)]
+pub struct SecretOptions {
+    params: HashMap<&'static str, Value>,
+}
+
+impl SecretOptions {
+    /// return a new instance of a builder for a secret named `name`
+    pub fn builder(name: &str) -> SecretOptionsBuilder {
+        SecretOptionsBuilder::new(name)
+    }
+}
+
+impl_json_options!(SecretOptions);
+
+/// Builder interface for `SecretOptions`
+///
+/// Secrets are labeled with `created-by` and `created-at` labels, recording that shiplift
+/// created them and when.
+pub struct SecretOptionsBuilder {
+    params: HashMap<&'static str, Value>,
+    labels: HashMap<String, String>,
+}
+
+impl SecretOptionsBuilder {
+    pub(crate) fn new(name: &str) -> Self {
+        let mut params = HashMap::new();
+        params.insert("Name", json!(name));
+
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_secs())
+            .unwrap_or_default();
+        let mut labels = HashMap::new();
+        labels.insert(CREATED_BY_LABEL.to_owned(), "shiplift".to_owned());
+        labels.insert(CREATED_AT_LABEL.to_owned(), created_at.to_string());
+
+        SecretOptionsBuilder { params, labels }
+    }
+
+    /// The secret's data, base64 encoded for the daemon
+    pub fn data<D>(
+        &mut self,
+        data: D,
+    ) -> &mut Self
+    where
+        D: AsRef<[u8]>,
+    {
+        self.params.insert("Data", json!(base64::encode(data)));
+        self
+    }
+
+    /// Reads the secret's data from a local file
+    pub fn file<P>(
+        &mut self,
+        path: P,
+    ) -> Result<&mut Self>
+    where
+        P: AsRef<Path>,
+    {
+        let data = std::fs::read(path)?;
+        Ok(self.data(data))
+    }
+
+    /// Adds labels, replacing any with the same key, including the standard ones
+    pub fn labels<I, K, V>(
+        &mut self,
+        labels: I,
+    ) -> &mut Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        for (key, value) in labels {
+            self.labels
+                .insert(key.as_ref().to_owned(), value.as_ref().to_owned());
+        }
+        self
+    }
+
+    pub fn build(&self) -> SecretOptions {
+        let mut params = self.params.clone();
+        params.insert("Labels", json!(self.labels));
+        SecretOptions { params }
+    }
+}
+
+/// The next versioned name of a secret: `name.v2` for `name`, `name.v3` for `name.v2`
+fn next_version(name: &str) -> String {
+    if let Some((base, version)) = name.rsplit_once(".v") {
+        if let Ok(version) = version.parse::<u64>() {
+            return format!("{}.v{}", base, version + 1);
+        }
+    }
+    format!("{}.v2", name)
+}
+
+/// Points the secret references of a raw service spec at another secret. Returns whether any
+/// reference was changed.
+fn replace_secret_refs(
+    spec: &mut Value,
+    old_id: &str,
+    new_id: &str,
+    new_name: &str,
+) -> bool {
+    let mut replaced = false;
+    if let Some(secrets) = spec["TaskTemplate"]["ContainerSpec"]["Secrets"].as_array_mut() {
+        for secret in secrets {
+            if secret["SecretID"] == old_id {
+                secret["SecretID"] = json!(new_id);
+                secret["SecretName"] = json!(new_name);
+                replaced = true;
+            }
+        }
+    }
+    replaced
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SecretCreateInfo {
+    #[serde(rename = "ID")]
+    pub id: String,
+}
+
+/// Outcome of [Secrets::replace](Secrets::replace)
+#[derive(Clone, Debug)]
+pub struct SecretReplacement {
+    /// Id of the new secret
+    pub id: String,
+    /// Name of the new secret
+    pub name: String,
+    /// Ids of the services updated to use the new secret
+    pub updated_services: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SecretInfo {
+    #[serde(rename = "ID")]
+    pub id: String,
+    pub version: ObjectVersion,
+    #[cfg(feature = "chrono")]
+    pub created_at: DateTime<Utc>,
+    #[cfg(not(feature = "chrono"))]
+    pub created_at: String,
+    #[cfg(feature = "chrono")]
+    pub updated_at: DateTime<Utc>,
+    #[cfg(not(feature = "chrono"))]
+    pub updated_at: String,
+    pub spec: SecretSpec,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SecretSpec {
+    pub name: String,
+    pub labels: Option<HashMap<String, String>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secret_options_serialize() {
+        let opts = SecretOptions::builder("db-password")
+            .data("hunter2")
+            .labels(vec![(CREATED_AT_LABEL, "0"), ("app", "db")])
+            .build();
+
+        assert_eq!(
+            serde_json::from_str::<Value>(&opts.serialize().unwrap()).unwrap(),
+            json!({
+                "Name": "db-password",
+                "Data": "aHVudGVyMg==",
+                "Labels": {"app": "db", "created-by": "shiplift", "created-at": "0"},
+            })
+        );
+    }
+
+    #[test]
+    fn secret_next_version() {
+        assert_eq!(next_version("db-password"), "db-password.v2");
+        assert_eq!(next_version("db-password.v2"), "db-password.v3");
+        assert_eq!(next_version("db.vault"), "db.vault.v2");
+    }
+
+    #[test]
+    fn secret_refs_replaced() {
+        let mut spec = json!({
+            "TaskTemplate": {"ContainerSpec": {"Secrets": [
+                {"SecretID": "old", "SecretName": "db-password", "File": {"Name": "db"}},
+                {"SecretID": "other", "SecretName": "api-key"},
+            ]}}
+        });
+
+        assert!(replace_secret_refs(
+            &mut spec,
+            "old",
+            "new",
+            "db-password.v2"
+        ));
+        assert_eq!(
+            spec["TaskTemplate"]["ContainerSpec"]["Secrets"][0],
+            json!({"SecretID": "new", "SecretName": "db-password.v2", "File": {"Name": "db"}})
+        );
+        assert!(!replace_secret_refs(
+            &mut spec,
+            "old",
+            "new",
+            "db-password.v2"
+        ));
+    }
+}
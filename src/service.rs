@@ -2,19 +2,25 @@
 //!
 //! API Reference: <https://docs.docker.com/engine/api/v1.41/#tag/Service>
 
-use std::{collections::HashMap, iter};
+use std::{
+    collections::{BTreeSet, HashMap},
+    pin::Pin,
+};
 
-use futures_util::stream::Stream;
+use futures_util::stream::{self, Stream, StreamExt};
 use hyper::Body;
 use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
-use url::form_urlencoded;
+use serde_json::{json, Map, Value};
 
 use crate::{
     container::LogsOptions,
-    docker::Docker,
+    docker::{Docker, Event, EventFilter, EventFilterType, EventsOptions},
     errors::{Error, Result},
-    image::RegistryAuth,
+    filter::{self, Filter, LabelSelector},
+    image::{set_header, with_auth_header, RegistryAuth},
+    node::{NodeFilter, NodeInfo, NodeListOptions, Nodes},
+    task::{TaskDesiredState, TaskFilter, TaskInfo, TaskListOptions, Tasks},
+    transport::Headers,
     tty,
 };
 
@@ -50,6 +56,18 @@ impl<'docker> Services<'docker> {
             .await
     }
 
+    /// Lists the services labeled `key` with `value`
+    pub async fn with_label(
+        &self,
+        key: &str,
+        value: &str,
+    ) -> Result<Vec<ServiceInfo>> {
+        let opts = ServiceListOptions::builder()
+            .filter(LabelSelector::new().equals(key, value).filters())
+            .build();
+        self.list(&opts).await
+    }
+
     /// Returns a reference to a set of operations available for a named service
     pub fn get(
         &self,
@@ -90,17 +108,12 @@ impl<'docker> Service<'docker> {
         opts: &ServiceOptions,
     ) -> Result<ServiceCreateInfo> {
         let body: Body = opts.serialize()?.into();
-        let path = vec!["/service/create".to_owned()];
-
-        let headers = opts
-            .auth_header()
-            .map(|a| iter::once(("X-Registry-Auth", a)));
 
         self.docker
             .post_json_headers(
-                &path.join("?"),
+                "/service/create",
                 Some((body, mime::APPLICATION_JSON)),
-                headers,
+                opts.headers(),
             )
             .await
     }
@@ -123,6 +136,119 @@ impl<'docker> Service<'docker> {
             .await
     }
 
+    /// Brings the service in line with `opts`, the spec it should have. The current spec is
+    /// compared with `opts` on image, environment, mounts, secrets and configs, and the service
+    /// is only updated, to exactly `opts`, when one of those differs. Returns what differed,
+    /// which is empty when nothing had to change.
+    ///
+    /// Changes to other parts of the spec alone, such as the number of replicas, do not
+    /// trigger an update.
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/ServiceUpdate>
+    pub async fn reconcile(
+        &self,
+        opts: &ServiceOptions,
+    ) -> Result<ServiceDiff> {
+        let current: Value = self
+            .docker
+            .get_json(&format!("/services/{}", self.name)[..])
+            .await?;
+        let desired: Value = serde_json::from_str(&opts.serialize()?)?;
+
+        let diff = ServiceDiff::between(&current["Spec"], &desired);
+        if diff.is_empty() {
+            return Ok(diff);
+        }
+
+        let version = current["Version"]["Index"].as_u64().ok_or_else(|| {
+            Error::InvalidResponse(format!("service {} has no version", self.name))
+        })?;
+        let id = current["ID"].as_str().unwrap_or(&self.name);
+        let body: Body = serde_json::to_string(&desired)?.into();
+        self.docker
+            .post_json_headers::<Value, _, _>(
+                format!("/services/{}/update?version={}", id, version),
+                Some((body, mime::APPLICATION_JSON)),
+                opts.headers(),
+            )
+            .await?;
+        Ok(diff)
+    }
+
+    /// Returns a stream of the service's [UpdateStatus](UpdateStatus) as a rolling update, or its
+    /// rollback, progresses. The current status is yielded first, followed by every change to its
+    /// state or message, and the stream ends once the update has
+    /// [finished](UpdateStatus::is_finished). While the service has no update status, as right
+    /// after its spec changed, the stream waits for the update to start.
+    ///
+    /// Changes are picked up from the daemon's service events rather than by polling.
+    pub fn watch_update(&self) -> impl Stream<Item = Result<UpdateStatus>> + Unpin + 'docker {
+        let events = EventsOptions::builder()
+            .filter(vec![
+                EventFilter::Type(EventFilterType::Service),
+                EventFilter::Service(self.name.clone()),
+            ])
+            .build();
+
+        let watch = UpdateWatch {
+            service: Service::new(self.docker, self.name.clone()),
+            events_options: events,
+            events: None,
+            last: None,
+            finished: false,
+        };
+
+        Box::pin(stream::unfold(watch, |mut watch| async move {
+            let item = watch.next().await?;
+            Some((item, watch))
+        }))
+    }
+
+    /// Addresses clients outside the swarm can reach the service at: for each port the service
+    /// publishes, the address of each node running one of its tasks.
+    ///
+    /// Ports published in `host` mode are only open on the nodes running a task, on the port
+    /// each task reports, which the swarm picks when the service doesn't set one. Ports
+    /// published through the `ingress` routing mesh are open on every node of the swarm; the
+    /// nodes running a task are returned for them too, as those serve connections without an
+    /// extra hop.
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/TaskList>
+    pub async fn published_endpoints(&self) -> Result<Vec<PublishedEndpoint>> {
+        let details = self.inspect().await?;
+        let tasks = Tasks::new(self.docker)
+            .list(
+                &TaskListOptions::builder()
+                    .filter(vec![
+                        TaskFilter::Service(details.id.clone()),
+                        TaskFilter::DesiredState(TaskDesiredState::Running),
+                    ])
+                    .build(),
+            )
+            .await?;
+        let node_ids: BTreeSet<String> = tasks
+            .iter()
+            .filter(|task| task.status.state == "running")
+            .filter_map(|task| task.node_id.clone())
+            .collect();
+        if node_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let nodes = Nodes::new(self.docker)
+            .list(
+                &NodeListOptions::builder()
+                    .filter(node_ids.into_iter().map(NodeFilter::Id))
+                    .build(),
+            )
+            .await?;
+
+        Ok(published_endpoints(
+            details.endpoint.ports.as_deref().unwrap_or_default(),
+            &tasks,
+            &nodes,
+        ))
+    }
+
     /// Returns a stream of logs from a service
     ///
     /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/ServiceLogs>
@@ -141,32 +267,248 @@ impl<'docker> Service<'docker> {
     }
 }
 
-/// Options for filtering services list results
-#[derive(Default, Debug)]
-pub struct ServiceListOptions {
-    params: HashMap<&'static str, String>,
+/// A published port of a service on a node, as found by
+/// [Service::published_endpoints](Service::published_endpoints)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PublishedEndpoint {
+    pub node_id: String,
+    /// The node's IP address
+    pub address: String,
+    pub published_port: u64,
+    /// The port in the service's containers the published port forwards to
+    pub target_port: u64,
+    /// `tcp`, `udp` or `sctp`
+    pub protocol: String,
+    /// `ingress` or `host`
+    pub publish_mode: String,
 }
 
-impl ServiceListOptions {
-    /// return a new instance of a builder for options
-    pub fn builder() -> ServiceListOptionsBuilder {
-        ServiceListOptionsBuilder::default()
+impl PublishedEndpoint {
+    /// The endpoint as an address to connect to, such as `10.0.0.5:8080` or `[fd00::5]:8080`
+    pub fn socket_address(&self) -> String {
+        if self.address.contains(':') {
+            format!("[{}]:{}", self.address, self.published_port)
+        } else {
+            format!("{}:{}", self.address, self.published_port)
+        }
+    }
+}
+
+/// The IP address clients reach a node at. Managers may report `0.0.0.0` in their status, in
+/// which case the address they advertise to the swarm is used.
+fn node_address(node: &NodeInfo) -> Option<String> {
+    let address = node
+        .status
+        .addr
+        .as_deref()
+        .filter(|addr| !addr.is_empty() && *addr != "0.0.0.0")
+        .map(str::to_owned);
+    address.or_else(|| {
+        let manager_addr = &node.manager_status.as_ref()?.addr;
+        let (host, _port) = manager_addr.rsplit_once(':')?;
+        Some(
+            host.trim_start_matches('[')
+                .trim_end_matches(']')
+                .to_owned(),
+        )
+    })
+}
+
+fn published_endpoints(
+    ports: &[EndpointPortConfig],
+    tasks: &[TaskInfo],
+    nodes: &[NodeInfo],
+) -> Vec<PublishedEndpoint> {
+    let addresses: HashMap<&str, String> = nodes
+        .iter()
+        .filter_map(|node| Some((node.id.as_str(), node_address(node)?)))
+        .collect();
+
+    let mut endpoints: Vec<PublishedEndpoint> = Vec::new();
+    let running = tasks.iter().filter(|task| task.status.state == "running");
+    for task in running {
+        let node_id = match task.node_id {
+            Some(ref node_id) => node_id,
+            None => continue,
+        };
+        let address = match addresses.get(node_id.as_str()) {
+            Some(address) => address,
+            None => continue,
+        };
+        let host_ports = task
+            .status
+            .port_status
+            .as_ref()
+            .and_then(|status| status.ports.as_deref())
+            .unwrap_or_default();
+        let task_ports = ports
+            .iter()
+            .filter(|port| port.publish_mode == "ingress")
+            .chain(host_ports);
+        for port in task_ports {
+            let published_port = match port.published_port {
+                Some(published_port) if published_port > 0 => published_port,
+                _ => continue,
+            };
+            let endpoint = PublishedEndpoint {
+                node_id: node_id.clone(),
+                address: address.clone(),
+                published_port,
+                target_port: port.target_port,
+                protocol: port.protocol.clone(),
+                publish_mode: port.publish_mode.clone(),
+            };
+            if !endpoints.contains(&endpoint) {
+                endpoints.push(endpoint);
+            }
+        }
+    }
+    endpoints
+}
+
+/// What differs between a service's current spec and the spec it should have, as found by
+/// [Service::reconcile](Service::reconcile)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ServiceDiff {
+    /// The current and the desired image, when they differ
+    pub image: Option<(String, String)>,
+    /// `NAME=value` environment entries to add
+    pub env_added: Vec<String>,
+    /// `NAME=value` environment entries to remove
+    pub env_removed: Vec<String>,
+    /// Targets of the mounts added, removed or changed
+    pub mounts: Vec<String>,
+    /// Names of the secrets added, removed or mounted differently
+    pub secrets: Vec<String>,
+    /// Names of the configs added, removed or mounted differently
+    pub configs: Vec<String>,
+}
+
+impl ServiceDiff {
+    /// Whether the specs match
+    pub fn is_empty(&self) -> bool {
+        self.image.is_none()
+            && self.env_added.is_empty()
+            && self.env_removed.is_empty()
+            && self.mounts.is_empty()
+            && self.secrets.is_empty()
+            && self.configs.is_empty()
     }
 
-    /// serialize options as a string. returns None if no options are defined
-    pub fn serialize(&self) -> Option<String> {
-        if self.params.is_empty() {
+    /// Compares two raw service specs
+    fn between(
+        current: &Value,
+        desired: &Value,
+    ) -> Self {
+        let current = &current["TaskTemplate"]["ContainerSpec"];
+        let desired = &desired["TaskTemplate"]["ContainerSpec"];
+
+        let current_image = current["Image"].as_str().unwrap_or_default();
+        let desired_image = desired["Image"].as_str().unwrap_or_default();
+        // the daemon pins the image it resolved by appending its digest, and tags it `latest`
+        // when no tag was given
+        let (current_name, current_digest) = image_reference(current_image);
+        let (desired_name, desired_digest) = image_reference(desired_image);
+        let image = if current_name == desired_name
+            && (desired_digest.is_none() || desired_digest == current_digest)
+        {
             None
         } else {
-            Some(
-                form_urlencoded::Serializer::new(String::new())
-                    .extend_pairs(&self.params)
-                    .finish(),
-            )
+            Some((current_image.to_owned(), desired_image.to_owned()))
+        };
+
+        let env = |spec: &Value| -> BTreeSet<String> {
+            spec["Env"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|entry| entry.as_str().map(str::to_owned))
+                .collect()
+        };
+        let (current_env, desired_env) = (env(current), env(desired));
+
+        ServiceDiff {
+            image,
+            env_added: desired_env.difference(&current_env).cloned().collect(),
+            env_removed: current_env.difference(&desired_env).cloned().collect(),
+            mounts: changed_entries(&current["Mounts"], &desired["Mounts"], |mount| {
+                mount["Target"].as_str()
+            }),
+            secrets: changed_references(&current["Secrets"], &desired["Secrets"], "SecretName"),
+            configs: changed_references(&current["Configs"], &desired["Configs"], "ConfigName"),
         }
     }
 }
 
+/// The name of an image reference, tagged `latest` if it has no tag, and its digest if it has
+/// one
+fn image_reference(image: &str) -> (String, Option<&str>) {
+    let (name, digest) = match image.split_once('@') {
+        Some((name, digest)) => (name, Some(digest)),
+        None => (image, None),
+    };
+    // a colon before the last slash separates the port of a registry
+    let tagged = name.rsplit('/').next().unwrap_or(name).contains(':');
+    if tagged || name.is_empty() {
+        (name.to_owned(), digest)
+    } else {
+        (format!("{}:latest", name), digest)
+    }
+}
+
+/// Keys of the entries of two arrays that were added, removed or changed
+fn changed_entries<F>(
+    current: &Value,
+    desired: &Value,
+    key: F,
+) -> Vec<String>
+where
+    F: Fn(&Value) -> Option<&str>,
+{
+    let entries = |value: &'_ Value| -> HashMap<String, Value> {
+        value
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| Some((key(entry)?.to_owned(), entry.clone())))
+            .collect()
+    };
+    let (current, desired) = (entries(current), entries(desired));
+    let keys: BTreeSet<&String> = current.keys().chain(desired.keys()).collect();
+    keys.into_iter()
+        .filter(|key| current.get(*key) != desired.get(*key))
+        .cloned()
+        .collect()
+}
+
+/// Names of the secret or config references that were added, removed or mounted differently.
+/// Ids are left out of the comparison, as specs usually refer to them by name.
+fn changed_references(
+    current: &Value,
+    desired: &Value,
+    name_key: &str,
+) -> Vec<String> {
+    let without_ids = |value: &Value| -> Value {
+        let mut value = value.clone();
+        for reference in value.as_array_mut().into_iter().flatten() {
+            if let Some(reference) = reference.as_object_mut() {
+                reference.remove("SecretID");
+                reference.remove("ConfigID");
+            }
+        }
+        value
+    };
+    changed_entries(&without_ids(current), &without_ids(desired), |reference| {
+        reference[name_key].as_str()
+    })
+}
+
+/// Options for filtering services list results
+#[derive(Default, Debug)]
+pub struct ServiceListOptions {
+    params: HashMap<&'static str, String>,
+}
+
 /// Filter options for services listings
 pub enum ServiceFilter {
     Id(String),
@@ -176,6 +518,18 @@ pub enum ServiceFilter {
     Name(String),
 }
 
+impl From<ServiceFilter> for Filter {
+    fn from(filter: ServiceFilter) -> Self {
+        match filter {
+            ServiceFilter::Id(i) => Filter::id(i),
+            ServiceFilter::Label(l) => Filter::label(l),
+            ServiceFilter::ReplicatedMode => Filter::new("mode", "replicated"),
+            ServiceFilter::GlobalMode => Filter::new("mode", "global"),
+            ServiceFilter::Name(n) => Filter::name(n),
+        }
+    }
+}
+
 /// Builder interface for `ServicesListOptions`
 #[derive(Default)]
 pub struct ServiceListOptionsBuilder {
@@ -183,26 +537,15 @@ pub struct ServiceListOptionsBuilder {
 }
 
 impl ServiceListOptionsBuilder {
-    pub fn filter(
+    pub fn filter<I, F>(
         &mut self,
-        filters: Vec<ServiceFilter>,
-    ) -> &mut Self {
-        let mut param = HashMap::new();
-        for f in filters {
-            match f {
-                ServiceFilter::Id(i) => param.insert("id", vec![i]),
-                ServiceFilter::Label(l) => param.insert("label", vec![l]),
-                ServiceFilter::ReplicatedMode => {
-                    param.insert("mode", vec!["replicated".to_string()])
-                }
-                ServiceFilter::GlobalMode => param.insert("mode", vec!["global".to_string()]),
-                ServiceFilter::Name(n) => param.insert("name", vec![n.to_string()]),
-            };
-        }
-        // structure is a a json encoded object mapping string keys to a list
-        // of string values
-        self.params
-            .insert("filters", serde_json::to_string(&param).unwrap());
+        filters: I,
+    ) -> &mut Self
+    where
+        I: IntoIterator<Item = F>,
+        F: Into<Filter>,
+    {
+        self.params.insert("filters", filter::serialize(filters));
         self
     }
 
@@ -210,18 +553,20 @@ impl ServiceListOptionsBuilder {
         self.params.insert("status", "true".to_owned());
         self
     }
-
-    pub fn build(&self) -> ServiceListOptions {
-        ServiceListOptions {
-            params: self.params.clone(),
-        }
-    }
 }
 
-#[derive(Default, Debug)]
+impl_query_options!(ServiceListOptions, ServiceListOptionsBuilder);
+
+/// Serializes to the spec sent as the body of the create or update request, and can be
+/// deserialized from one. Registry authentication is never serialized.
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct ServiceOptions {
+    #[serde(skip)]
     auth: Option<RegistryAuth>,
-    params: HashMap<&'static str, Value>,
+    params: Map<String, Value>,
+    #[serde(skip)]
+    headers: Vec<(&'static str, String)>,
 }
 
 impl ServiceOptions {
@@ -230,20 +575,32 @@ impl ServiceOptions {
         ServiceOptionsBuilder::default()
     }
 
-    /// serialize options as a string. returns None if no options are defined
-    pub fn serialize(&self) -> Result<String> {
-        serde_json::to_string(&self.params).map_err(Error::from)
+    /// Options sending the JSON `body` as is, such as one deserialized from a stored spec
+    pub fn from_body(body: Map<String, Value>) -> ServiceOptions {
+        ServiceOptions {
+            params: body,
+            ..Default::default()
+        }
+    }
+
+    /// The JSON body of the request
+    pub fn body(&self) -> &Map<String, Value> {
+        &self.params
     }
 
-    pub(crate) fn auth_header(&self) -> Option<String> {
-        self.auth.clone().map(|a| a.serialize())
+    /// headers to send along with the request, including registry authentication
+    pub(crate) fn headers(&self) -> Headers {
+        with_auth_header(&self.auth, &self.headers)
     }
 }
 
+impl_json_options!(ServiceOptions);
+
 #[derive(Default)]
 pub struct ServiceOptionsBuilder {
     auth: Option<RegistryAuth>,
     params: HashMap<&'static str, Result<Value>>,
+    headers: Vec<(&'static str, String)>,
 }
 
 impl ServiceOptionsBuilder {
@@ -340,15 +697,29 @@ impl ServiceOptionsBuilder {
         self
     }
 
+    /// Sets an extra header to send along with the request, replacing any previous value
+    pub fn header<V>(
+        &mut self,
+        name: &'static str,
+        value: V,
+    ) -> &mut Self
+    where
+        V: Into<String>,
+    {
+        set_header(&mut self.headers, name, value.into());
+        self
+    }
+
     pub fn build(&mut self) -> Result<ServiceOptions> {
         let params = std::mem::take(&mut self.params);
-        let mut new_params = HashMap::new();
+        let mut new_params = Map::new();
         for (k, v) in params.into_iter() {
-            new_params.insert(k, v?);
+            new_params.insert(k.to_string(), v?);
         }
         Ok(ServiceOptions {
             auth: self.auth.take(),
             params: new_params,
+            headers: std::mem::take(&mut self.headers),
         })
     }
 }
@@ -422,13 +793,80 @@ pub struct UpdateStatus {
     pub started_at: DateTime<Utc>,
     #[cfg(not(feature = "chrono"))]
     pub started_at: String,
+    /// Unset while the update is still in progress
     #[cfg(feature = "chrono")]
-    pub completed_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
     #[cfg(not(feature = "chrono"))]
-    pub completed_at: String,
+    pub completed_at: Option<String>,
     pub message: String,
 }
 
+impl UpdateStatus {
+    /// Whether the update, or its rollback, has stopped: `completed`, `paused`,
+    /// `rollback_completed` or `rollback_paused`
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self.state.as_str(),
+            "completed" | "paused" | "rollback_completed" | "rollback_paused"
+        )
+    }
+}
+
+/// State behind [Service::watch_update](Service::watch_update)
+struct UpdateWatch<'docker> {
+    service: Service<'docker>,
+    events_options: EventsOptions,
+    /// Subscribed to before the first inspect, so that no change after it is missed
+    events: Option<Pin<Box<dyn Stream<Item = Result<Event>> + Send + 'docker>>>,
+    last: Option<(String, String)>,
+    finished: bool,
+}
+
+impl<'docker> UpdateWatch<'docker> {
+    async fn next(&mut self) -> Option<Result<UpdateStatus>> {
+        loop {
+            if self.finished {
+                return None;
+            }
+            match self.events {
+                // wait for the service to change before inspecting it again
+                Some(ref mut events) => match events.next().await? {
+                    Ok(_) => (),
+                    Err(e) => return Some(Err(e)),
+                },
+                None => match self
+                    .service
+                    .docker
+                    .subscribe_events(&self.events_options)
+                    .await
+                {
+                    Ok(events) => self.events = Some(Box::pin(events)),
+                    Err(e) => {
+                        self.finished = true;
+                        return Some(Err(e));
+                    }
+                },
+            }
+
+            let status = match self.service.inspect().await {
+                Ok(details) => details.update_status,
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e));
+                }
+            };
+            if let Some(status) = status {
+                let key = (status.state.clone(), status.message.clone());
+                if self.last.as_ref() != Some(&key) {
+                    self.last = Some(key);
+                    self.finished = status.is_finished();
+                    return Some(Ok(status));
+                }
+            }
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct ServiceStatus {
@@ -468,6 +906,19 @@ pub struct ServiceDetails {
     pub job_status: Option<JobStatus>,
 }
 
+impl ServiceDetails {
+    /// Labels set on the service's spec
+    pub fn labels(&self) -> HashMap<String, String> {
+        self.spec
+            .labels
+            .iter()
+            .filter_map(Value::as_object)
+            .flatten()
+            .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_owned())))
+            .collect()
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct ServiceSpec {
@@ -515,7 +966,8 @@ pub struct UpdateConfig {
     pub delay: u64,
     pub failure_action: String,
     pub monitor: u64,
-    pub max_failure_ratio: usize,
+    /// Fraction of tasks that may fail during an update, between 0 and 1
+    pub max_failure_ratio: f64,
     pub order: String,
 }
 
@@ -533,6 +985,197 @@ pub struct NetworkAttachmentConfig {
 pub struct ServiceCreateInfo {
     #[serde(rename = "ID")]
     pub id: String,
-    #[serde(rename = "Warning")]
-    pub warning: Option<String>,
+    #[serde(
+        default,
+        rename = "Warnings",
+        alias = "Warning",
+        deserialize_with = "crate::de::warnings"
+    )]
+    pub warnings: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockDaemon, Route};
+
+    #[test]
+    fn update_status_in_progress() {
+        let status: UpdateStatus = serde_json::from_str(
+            r#"{"State":"updating","StartedAt":"2023-04-05T06:07:08Z","Message":"update in progress"}"#,
+        )
+        .unwrap();
+        assert!(status.completed_at.is_none());
+        assert!(!status.is_finished());
+
+        let status: UpdateStatus = serde_json::from_str(
+            r#"{"State":"rollback_completed","StartedAt":"2023-04-05T06:07:08Z","CompletedAt":"2023-04-05T06:08:08Z","Message":"rollback completed"}"#,
+        )
+        .unwrap();
+        assert!(status.is_finished());
+    }
+
+    #[test]
+    fn service_diff_between_specs() {
+        let current = json!({"TaskTemplate": {"ContainerSpec": {
+            "Image": "nginx:1.25@sha256:0123",
+            "Env": ["A=1", "B=2"],
+            "Mounts": [{"Type": "volume", "Source": "data", "Target": "/data"}],
+            "Secrets": [{"SecretID": "s1", "SecretName": "db-password", "File": {"Name": "db"}}],
+        }}});
+
+        let same = json!({"TaskTemplate": {"ContainerSpec": {
+            "Image": "nginx:1.25",
+            "Env": ["B=2", "A=1"],
+            "Mounts": [{"Type": "volume", "Source": "data", "Target": "/data"}],
+            "Secrets": [{"SecretName": "db-password", "File": {"Name": "db"}}],
+        }}});
+        assert!(ServiceDiff::between(&current, &same).is_empty());
+
+        let changed = json!({"TaskTemplate": {"ContainerSpec": {
+            "Image": "nginx:1.26",
+            "Env": ["A=1", "B=3"],
+            "Mounts": [{"Type": "volume", "Source": "data2", "Target": "/data"}],
+            "Configs": [{"ConfigName": "nginx.conf", "File": {"Name": "/etc/nginx/nginx.conf"}}],
+        }}});
+        assert_eq!(
+            ServiceDiff::between(&current, &changed),
+            ServiceDiff {
+                image: Some(("nginx:1.25@sha256:0123".into(), "nginx:1.26".into())),
+                env_added: vec!["B=3".into()],
+                env_removed: vec!["B=2".into()],
+                mounts: vec!["/data".into()],
+                secrets: vec!["db-password".into()],
+                configs: vec!["nginx.conf".into()],
+            }
+        );
+    }
+
+    #[test]
+    fn service_diff_normalizes_images() {
+        let spec = |image: &str| json!({"TaskTemplate": {"ContainerSpec": {"Image": image}}});
+        let image_changed = |current: &str, desired: &str| {
+            ServiceDiff::between(&spec(current), &spec(desired))
+                .image
+                .is_some()
+        };
+
+        assert!(!image_changed("nginx:latest@sha256:0123", "nginx"));
+        assert!(!image_changed(
+            "nginx:latest@sha256:0123",
+            "nginx@sha256:0123"
+        ));
+        assert!(!image_changed(
+            "registry:5000/nginx:latest@sha256:0123",
+            "registry:5000/nginx"
+        ));
+        assert!(image_changed(
+            "nginx:latest@sha256:0123",
+            "nginx@sha256:4567"
+        ));
+        assert!(image_changed("nginx:1.25@sha256:0123", "nginx"));
+    }
+
+    #[test]
+    fn update_config_failure_ratio() {
+        let config: UpdateConfig = serde_json::from_str(
+            r#"{"Parallelism":2,"Delay":10000000000,"FailureAction":"rollback","Monitor":5000000000,"MaxFailureRatio":0.25,"Order":"start-first"}"#,
+        )
+        .unwrap();
+        assert_eq!(config.max_failure_ratio, 0.25);
+        assert_eq!(config.delay, 10_000_000_000);
+    }
+
+    #[test]
+    fn service_options_spec_round_trip() {
+        let opts = ServiceOptions::builder()
+            .name("web")
+            .auth(RegistryAuth::token("secret"))
+            .build()
+            .unwrap();
+        let spec = serde_json::to_string(&opts).unwrap();
+        assert_eq!(spec, r#"{"Name":"web"}"#);
+
+        let parsed: ServiceOptions = serde_json::from_str(&spec).unwrap();
+        assert_eq!(parsed.body(), opts.body());
+        assert!(parsed.headers().is_none());
+    }
+
+    #[test]
+    fn published_endpoints_of_tasks() {
+        let ports: Vec<EndpointPortConfig> = serde_json::from_value(json!([
+            {"Protocol": "tcp", "PublishMode": "ingress", "PublishedPort": 8080, "TargetPort": 80}
+        ]))
+        .unwrap();
+        let task = |id: &str, node: &str, state: &str, host_port: Option<u64>| -> TaskInfo {
+            let ports = host_port.map(|port| {
+                json!({"Ports": [{"Protocol": "udp", "PublishMode": "host", "PublishedPort": port, "TargetPort": 53}]})
+            });
+            serde_json::from_value(json!({
+                "ID": id, "Version": {"Index": 1},
+                "CreatedAt": "2023-01-01T00:00:00Z", "UpdatedAt": "2023-01-01T00:00:00Z",
+                "ServiceID": "svc", "NodeID": node, "DesiredState": "running",
+                "Status": {"Timestamp": "2023-01-01T00:00:00Z", "State": state, "Message": "", "PortStatus": ports}
+            }))
+            .unwrap()
+        };
+        let node = |id: &str, addr: &str, manager_addr: Option<&str>| -> NodeInfo {
+            serde_json::from_value(json!({
+                "ID": id, "Version": {"Index": 1},
+                "CreatedAt": "2023-01-01T00:00:00Z", "UpdatedAt": "2023-01-01T00:00:00Z",
+                "Spec": {"Role": "worker", "Availability": "active"},
+                "Status": {"State": "ready", "Addr": addr},
+                "ManagerStatus": manager_addr.map(|addr| json!({"Reachability": "reachable", "Addr": addr}))
+            }))
+            .unwrap()
+        };
+
+        let tasks = vec![
+            task("t1", "n1", "running", Some(30053)),
+            task("t2", "n1", "running", None),
+            task("t3", "n2", "running", None),
+            task("t4", "n3", "shutdown", None),
+        ];
+        let nodes = vec![
+            node("n1", "10.0.0.1", None),
+            node("n2", "0.0.0.0", Some("10.0.0.2:2377")),
+            node("n3", "10.0.0.3", None),
+        ];
+
+        let addresses: Vec<String> = published_endpoints(&ports, &tasks, &nodes)
+            .iter()
+            .map(|endpoint| format!("{}/{}", endpoint.socket_address(), endpoint.protocol))
+            .collect();
+        assert_eq!(
+            addresses,
+            vec![
+                "10.0.0.1:8080/tcp",
+                "10.0.0.1:30053/udp",
+                "10.0.0.2:8080/tcp"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn watch_update_subscribes_before_inspecting() {
+        let daemon = MockDaemon::start(vec![
+            Route::new("GET /events", 200, ""),
+            Route::new(
+                "GET /services/web",
+                404,
+                r#"{"message":"service web not found"}"#,
+            ),
+        ]);
+
+        let statuses: Vec<_> = Service::new(daemon.docker(), "web")
+            .watch_update()
+            .collect()
+            .await;
+
+        assert_eq!(statuses.len(), 1);
+        assert!(statuses[0].is_err());
+        let requests = daemon.requests();
+        assert!(requests[0].starts_with("GET /events?"));
+        assert!(requests[1].starts_with("GET /services/web "));
+    }
 }
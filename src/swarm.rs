@@ -0,0 +1,263 @@
+//! Inspect and update the swarm the docker host is a manager of.
+//!
+//! API Reference: <https://docs.docker.com/engine/api/v1.41/#tag/Swarm>
+
+use std::collections::HashMap;
+
+use hyper::Body;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{docker::Docker, errors::Result, service::ObjectVersion};
+
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, Utc};
+
+/// Interface for the swarm of a docker host
+///
+/// [Api Reference](https://docs.docker.com/engine/api/v1.41/#tag/Swarm)
+pub struct Swarm<'docker> {
+    docker: &'docker Docker,
+}
+
+impl<'docker> Swarm<'docker> {
+    /// Exports an interface for interacting with the swarm
+    pub fn new(docker: &'docker Docker) -> Self {
+        Swarm { docker }
+    }
+
+    /// Inspects the swarm
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/SwarmInspect)
+    pub async fn inspect(&self) -> Result<SwarmInfo> {
+        self.docker.get_json("/swarm").await
+    }
+
+    /// Updates the swarm's spec. The spec replaces the current one entirely, so it is usually
+    /// the inspected spec with the desired changes applied.
+    ///
+    /// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/SwarmUpdate)
+    pub async fn update(
+        &self,
+        opts: &SwarmUpdateOptions,
+    ) -> Result<()> {
+        let body: Body = opts.serialize_spec()?.into();
+        self.docker
+            .post(
+                &format!("/swarm/update?{}", opts.serialize()),
+                Some((body, mime::APPLICATION_JSON)),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+/// Options for updating a swarm
+#[derive(Debug)]
+pub struct SwarmUpdateOptions {
+    spec: SwarmSpec,
+    params: HashMap<&'static str, String>,
+}
+
+impl SwarmUpdateOptions {
+    /// return a new instance of a builder for options. `version` is the version of the swarm
+    /// being updated, as returned by [Swarm::inspect](Swarm::inspect), and `spec` its new spec
+    pub fn builder(
+        version: u64,
+        spec: SwarmSpec,
+    ) -> SwarmUpdateOptionsBuilder {
+        SwarmUpdateOptionsBuilder::new(version, spec)
+    }
+
+    /// serialize options as a string
+    pub fn serialize(&self) -> String {
+        crate::options::encode_query(&self.params).unwrap_or_default()
+    }
+
+    fn serialize_spec(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self.spec)?)
+    }
+}
+
+/// Builder interface for `SwarmUpdateOptions`
+pub struct SwarmUpdateOptionsBuilder {
+    spec: SwarmSpec,
+    params: HashMap<&'static str, String>,
+}
+
+impl SwarmUpdateOptionsBuilder {
+    pub(crate) fn new(
+        version: u64,
+        spec: SwarmSpec,
+    ) -> Self {
+        let mut params = HashMap::new();
+        params.insert("version", version.to_string());
+        SwarmUpdateOptionsBuilder { spec, params }
+    }
+
+    /// Replaces the CA configuration of the spec, e.g. to rotate the root CA or switch to an
+    /// external CA
+    pub fn ca_config(
+        &mut self,
+        ca_config: CaConfig,
+    ) -> &mut Self {
+        self.spec.ca_config = Some(ca_config);
+        self
+    }
+
+    query_setters! {
+        /// Rotate the worker join token
+        rotate_worker_token: bool => "rotateWorkerToken",
+        /// Rotate the manager join token
+        rotate_manager_token: bool => "rotateManagerToken",
+        /// Rotate the manager unlock key
+        rotate_manager_unlock_key: bool => "rotateManagerUnlockKey",
+    }
+
+    pub fn build(&self) -> SwarmUpdateOptions {
+        SwarmUpdateOptions {
+            spec: self.spec.clone(),
+            params: self.params.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SwarmInfo {
+    #[serde(rename = "ID")]
+    pub id: String,
+    pub version: ObjectVersion,
+    #[cfg(feature = "chrono")]
+    pub created_at: DateTime<Utc>,
+    #[cfg(not(feature = "chrono"))]
+    pub created_at: String,
+    #[cfg(feature = "chrono")]
+    pub updated_at: DateTime<Utc>,
+    #[cfg(not(feature = "chrono"))]
+    pub updated_at: String,
+    pub spec: SwarmSpec,
+    #[serde(rename = "TLSInfo")]
+    pub tls_info: Option<TlsInfo>,
+    pub root_rotation_in_progress: bool,
+    pub join_tokens: Option<JoinTokens>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SwarmSpec {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<HashMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub orchestration: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raft: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dispatcher: Option<Value>,
+    #[serde(rename = "CAConfig", skip_serializing_if = "Option::is_none")]
+    pub ca_config: Option<CaConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encryption_config: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task_defaults: Option<Value>,
+}
+
+/// Certificate authority configuration of a swarm
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CaConfig {
+    /// Validity period of node certificates, in nanoseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node_cert_expiry: Option<i64>,
+    /// External CAs to forward certificate signing requests to
+    #[serde(rename = "ExternalCAs", skip_serializing_if = "Option::is_none")]
+    pub external_cas: Option<Vec<ExternalCa>>,
+    /// PEM encoded certificate of a new root CA to rotate to
+    #[serde(rename = "SigningCACert", skip_serializing_if = "Option::is_none")]
+    pub signing_ca_cert: Option<String>,
+    /// PEM encoded key of a new root CA to rotate to
+    #[serde(rename = "SigningCAKey", skip_serializing_if = "Option::is_none")]
+    pub signing_ca_key: Option<String>,
+    /// Bumping this counter forces a root CA rotation even if nothing else changed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub force_rotate: Option<u64>,
+}
+
+impl CaConfig {
+    /// Forces a rotation to a new, swarm generated, root CA on the next update
+    pub fn force_rotation(&mut self) -> &mut Self {
+        self.force_rotate = Some(self.force_rotate.unwrap_or_default() + 1);
+        self
+    }
+}
+
+/// An external certificate authority
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ExternalCa {
+    /// Protocol used to talk to the CA, currently always `cfssl`
+    pub protocol: String,
+    #[serde(rename = "URL")]
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<HashMap<String, String>>,
+    /// PEM encoded root CA certificate the external CA uses to issue certificates
+    #[serde(rename = "CACert", skip_serializing_if = "Option::is_none")]
+    pub ca_cert: Option<String>,
+}
+
+impl ExternalCa {
+    /// An external cfssl CA reachable at `url`
+    pub fn cfssl<U>(url: U) -> Self
+    where
+        U: Into<String>,
+    {
+        ExternalCa {
+            protocol: "cfssl".to_owned(),
+            url: url.into(),
+            options: None,
+            ca_cert: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TlsInfo {
+    pub trust_root: Option<String>,
+    pub cert_issuer_subject: Option<String>,
+    pub cert_issuer_public_key: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct JoinTokens {
+    pub worker: String,
+    pub manager: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swarm_update_options_serialize() {
+        let mut ca_config = CaConfig {
+            external_cas: Some(vec![ExternalCa::cfssl("https://ca.example.com")]),
+            ..Default::default()
+        };
+        ca_config.force_rotation();
+
+        let opts = SwarmUpdateOptions::builder(42, SwarmSpec::default())
+            .ca_config(ca_config)
+            .build();
+
+        assert_eq!(opts.serialize(), "version=42");
+        assert_eq!(
+            opts.serialize_spec().unwrap(),
+            r#"{"CAConfig":{"ExternalCAs":[{"Protocol":"cfssl","URL":"https://ca.example.com"}],"ForceRotate":1}}"#
+        );
+    }
+}
@@ -1,10 +1,19 @@
+use bytes::Bytes;
 use flate2::{write::GzEncoder, Compression};
+use futures_util::stream::{self, Stream, StreamExt};
 use std::{
     fs::{self, File},
-    io::{self, Write},
-    path::{Path, MAIN_SEPARATOR},
+    io::{self, Read, Write},
+    path::{Path, PathBuf, MAIN_SEPARATOR},
+    thread,
 };
-use tar::Builder;
+use tar::{Archive, Builder, EntryType, Header, HeaderMode};
+use tokio::sync::{mpsc, oneshot};
+
+/// Size of the chunks a streamed archive is cut into
+const CHUNK_SIZE: usize = 64 * 1024;
+/// Number of chunks buffered between the archiving thread and the request
+const CHUNK_BUFFER: usize = 8;
 
 // todo: this is pretty involved. (re)factor this into its own crate
 pub fn dir<W>(
@@ -25,14 +34,14 @@ where
     {
         if fs::metadata(dir)?.is_dir() {
             if bundle_dir {
-                f(&dir)?;
+                f(dir)?;
             }
             for entry in fs::read_dir(dir)? {
                 let entry = entry?;
                 if fs::metadata(entry.path())?.is_dir() {
                     bundle(&entry.path(), f, true)?;
                 } else {
-                    f(&entry.path().as_path())?;
+                    f(entry.path().as_path())?;
                 }
             }
         }
@@ -69,3 +78,233 @@ where
 
     Ok(())
 }
+
+/// Writes a gzipped tarball of in-memory files, given as `(path, contents, mode)`
+pub fn entries<'a, W, I>(
+    buf: W,
+    entries: I,
+) -> io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = (&'a str, &'a [u8], u32)>,
+{
+    let mut archive = Builder::new(GzEncoder::new(buf, Compression::best()));
+    for (path, bytes, mode) in entries {
+        let mut header = Header::new_gnu();
+        header.set_size(bytes.len() as u64);
+        header.set_mode(mode);
+        header.set_entry_type(EntryType::Regular);
+        archive.append_data(&mut header, path.trim_start_matches('/'), bytes)?;
+    }
+    archive.into_inner()?.finish()?;
+
+    Ok(())
+}
+
+/// Streams an uncompressed tarball of the contents of the directory at `path`.
+///
+/// The tree is archived on a separate thread as the stream is consumed, so large trees are never
+/// held in memory at once. Permissions and modification times are kept and symlinks are archived
+/// as links rather than followed.
+pub fn dir_stream(path: PathBuf) -> impl Stream<Item = io::Result<Bytes>> + Send + 'static {
+    writer_stream(move |writer| {
+        let mut archive = Builder::new(writer);
+        archive.mode(HeaderMode::Complete);
+        archive.follow_symlinks(false);
+        archive.append_dir_all(".", &path)?;
+        archive.finish()
+    })
+}
+
+/// Streams what `write` writes, in chunks.
+///
+/// `write` runs on a separate thread as the stream is consumed, so what it writes is never
+/// held in memory at once. An error it returns ends the stream.
+pub fn writer_stream<F>(write: F) -> impl Stream<Item = io::Result<Bytes>> + Send + 'static
+where
+    F: FnOnce(&mut dyn Write) -> io::Result<()> + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel(CHUNK_BUFFER);
+
+    thread::spawn(move || {
+        let mut writer = ChunkWriter {
+            sender: sender.clone(),
+            buf: Vec::with_capacity(CHUNK_SIZE),
+        };
+        let result = write(&mut writer).and_then(|_| writer.flush());
+
+        if let Err(e) = result {
+            let _ = sender.blocking_send(Err(e));
+        }
+    });
+
+    stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|chunk| (chunk, receiver))
+    })
+}
+
+/// Unpacks an uncompressed tarball streamed as `chunks` into the directory at `dest`, creating
+/// it if needed. Permissions and symlinks stored in the archive are restored.
+pub async fn unpack_stream<S, E>(
+    chunks: S,
+    dest: PathBuf,
+) -> Result<(), E>
+where
+    S: Stream<Item = Result<Bytes, E>>,
+    E: From<io::Error>,
+{
+    let (sender, receiver) = mpsc::channel::<Bytes>(CHUNK_BUFFER);
+    let (done_sender, done) = oneshot::channel();
+
+    thread::spawn(move || {
+        let reader = ChunkReader {
+            receiver,
+            chunk: Bytes::new(),
+        };
+        let result = fs::create_dir_all(&dest).and_then(|_| {
+            let mut archive = Archive::new(reader);
+            archive.set_preserve_permissions(true);
+            archive.set_preserve_mtime(true);
+            archive.unpack(&dest)
+        });
+        let _ = done_sender.send(result);
+    });
+
+    futures_util::pin_mut!(chunks);
+    while let Some(chunk) = chunks.next().await {
+        // a closed channel means unpacking already stopped, and its error is reported below
+        if sender.send(chunk?).await.is_err() {
+            break;
+        }
+    }
+    drop(sender);
+
+    done.await
+        .unwrap_or_else(|_| Err(io::Error::other("archive unpacking thread panicked")))
+        .map_err(E::from)
+}
+
+/// Writes into fixed size chunks sent over a channel to an async consumer
+struct ChunkWriter {
+    sender: mpsc::Sender<io::Result<Bytes>>,
+    buf: Vec<u8>,
+}
+
+impl Write for ChunkWriter {
+    fn write(
+        &mut self,
+        data: &[u8],
+    ) -> io::Result<usize> {
+        let len = data.len().min(CHUNK_SIZE - self.buf.len());
+        self.buf.extend_from_slice(&data[..len]);
+        if self.buf.len() == CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let chunk = std::mem::replace(&mut self.buf, Vec::with_capacity(CHUNK_SIZE));
+        self.sender
+            .blocking_send(Ok(chunk.into()))
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))
+    }
+}
+
+/// Reads chunks received over a channel from an async producer
+struct ChunkReader {
+    receiver: mpsc::Receiver<Bytes>,
+    chunk: Bytes,
+}
+
+impl Read for ChunkReader {
+    fn read(
+        &mut self,
+        buf: &mut [u8],
+    ) -> io::Result<usize> {
+        while self.chunk.is_empty() {
+            match self.receiver.blocking_recv() {
+                Some(chunk) => self.chunk = chunk,
+                None => return Ok(0),
+            }
+        }
+        let len = buf.len().min(self.chunk.len());
+        buf[..len].copy_from_slice(&self.chunk.split_to(len));
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("shiplift-tarball-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn entries_archive() {
+        let mut buf = Vec::new();
+        entries(
+            &mut buf,
+            vec![
+                ("Dockerfile", &b"FROM scratch"[..], 0o644),
+                ("/bin/run.sh", &b"#!/bin/sh"[..], 0o755),
+            ],
+        )
+        .unwrap();
+
+        let mut archive = Archive::new(flate2::read::GzDecoder::new(&buf[..]));
+        let found = archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let entry = entry.unwrap();
+                (
+                    entry.path().unwrap().to_string_lossy().into_owned(),
+                    entry.header().mode().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![
+                ("Dockerfile".to_string(), 0o644),
+                ("bin/run.sh".to_string(), 0o755)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn dir_stream_round_trips() {
+        let src = scratch_dir("src");
+        fs::create_dir_all(src.join("nested")).unwrap();
+        fs::write(src.join("nested/file.txt"), vec![7u8; CHUNK_SIZE * 3]).unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("nested/file.txt", src.join("link")).unwrap();
+
+        let dest = scratch_dir("dest");
+        unpack_stream::<_, io::Error>(dir_stream(src.clone()), dest.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            fs::read(dest.join("nested/file.txt")).unwrap(),
+            vec![7u8; CHUNK_SIZE * 3]
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            fs::read_link(dest.join("link")).unwrap(),
+            Path::new("nested/file.txt")
+        );
+
+        fs::remove_dir_all(src).unwrap();
+        fs::remove_dir_all(dest).unwrap();
+    }
+}
@@ -0,0 +1,186 @@
+//! Inspect the tasks swarm services are run as.
+//!
+//! API Reference: <https://docs.docker.com/engine/api/v1.41/#tag/Task>
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::Result,
+    filter::{self, Filter},
+    service::{EndpointPortConfig, ObjectVersion},
+    Docker,
+};
+
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, Utc};
+
+/// Interface for the tasks of a swarm
+///
+/// API Reference: <https://docs.docker.com/engine/api/v1.41/#tag/Task>
+pub struct Tasks<'docker> {
+    docker: &'docker Docker,
+}
+
+impl<'docker> Tasks<'docker> {
+    /// Exports an interface for interacting with swarm tasks
+    pub fn new(docker: &'docker Docker) -> Self {
+        Tasks { docker }
+    }
+
+    /// Lists the tasks of the swarm the docker host is a manager of
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/TaskList>
+    pub async fn list(
+        &self,
+        opts: &TaskListOptions,
+    ) -> Result<Vec<TaskInfo>> {
+        let mut path = vec!["/tasks".to_owned()];
+        if let Some(query) = opts.serialize() {
+            path.push(query);
+        }
+        self.docker.get_json(&path.join("?")).await
+    }
+}
+
+/// Filter options for task list results
+pub enum TaskFilter {
+    DesiredState(TaskDesiredState),
+    Id(String),
+    /// Tasks carrying a label with the given name, or `name=value`
+    Label(String),
+    Name(String),
+    /// Tasks scheduled on the node with the given id or name
+    Node(String),
+    /// Tasks of the service with the given id or name
+    Service(String),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TaskDesiredState {
+    Running,
+    Shutdown,
+    Accepted,
+}
+
+impl From<TaskFilter> for Filter {
+    fn from(filter: TaskFilter) -> Self {
+        match filter {
+            TaskFilter::DesiredState(s) => Filter::new(
+                "desired-state",
+                match s {
+                    TaskDesiredState::Running => "running",
+                    TaskDesiredState::Shutdown => "shutdown",
+                    TaskDesiredState::Accepted => "accepted",
+                },
+            ),
+            TaskFilter::Id(i) => Filter::id(i),
+            TaskFilter::Label(l) => Filter::label(l),
+            TaskFilter::Name(n) => Filter::name(n),
+            TaskFilter::Node(n) => Filter::new("node", n),
+            TaskFilter::Service(s) => Filter::new("service", s),
+        }
+    }
+}
+
+/// Options for filtering task list results
+#[derive(Default, Debug)]
+pub struct TaskListOptions {
+    params: HashMap<&'static str, String>,
+}
+
+/// Builder interface for `TaskListOptions`
+#[derive(Default)]
+pub struct TaskListOptionsBuilder {
+    params: HashMap<&'static str, String>,
+}
+
+impl TaskListOptionsBuilder {
+    pub fn filter<I, F>(
+        &mut self,
+        filters: I,
+    ) -> &mut Self
+    where
+        I: IntoIterator<Item = F>,
+        F: Into<Filter>,
+    {
+        self.params.insert("filters", filter::serialize(filters));
+        self
+    }
+}
+
+impl_query_options!(TaskListOptions, TaskListOptionsBuilder);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TaskInfo {
+    #[serde(rename = "ID")]
+    pub id: String,
+    pub version: ObjectVersion,
+    #[cfg(feature = "chrono")]
+    pub created_at: DateTime<Utc>,
+    #[cfg(not(feature = "chrono"))]
+    pub created_at: String,
+    #[cfg(feature = "chrono")]
+    pub updated_at: DateTime<Utc>,
+    #[cfg(not(feature = "chrono"))]
+    pub updated_at: String,
+    pub name: Option<String>,
+    pub labels: Option<HashMap<String, String>>,
+    #[serde(rename = "ServiceID")]
+    pub service_id: String,
+    pub slot: Option<u64>,
+    #[serde(rename = "NodeID")]
+    pub node_id: Option<String>,
+    pub status: TaskStatus,
+    pub desired_state: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TaskStatus {
+    #[cfg(feature = "chrono")]
+    pub timestamp: DateTime<Utc>,
+    #[cfg(not(feature = "chrono"))]
+    pub timestamp: String,
+    pub state: String,
+    pub message: String,
+    pub err: Option<String>,
+    pub container_status: Option<TaskContainerStatus>,
+    /// Ports the task publishes in `host` mode
+    pub port_status: Option<TaskPortStatus>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TaskContainerStatus {
+    #[serde(rename = "ContainerID")]
+    pub container_id: Option<String>,
+    #[serde(rename = "PID")]
+    pub pid: Option<u64>,
+    pub exit_code: Option<i64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TaskPortStatus {
+    pub ports: Option<Vec<EndpointPortConfig>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn task_filters_serialize() {
+        assert_eq!(
+            filter::serialize(vec![
+                TaskFilter::DesiredState(TaskDesiredState::Running),
+                TaskFilter::Service("web".into()),
+                TaskFilter::Node("node-1".into()),
+            ]),
+            r#"{"desired-state":["running"],"node":["node-1"],"service":["web"]}"#
+        );
+    }
+}
@@ -0,0 +1,276 @@
+//! Helpers for integration tests that need a docker daemon of their own, so they can create,
+//! break and tear down state without touching the daemon they run against.
+//!
+//! Enabled by the `testing` feature.
+//!
+//! ```no_run
+//! # async {
+//! use shiplift::{testing::Dind, Docker};
+//!
+//! let dind = Dind::start(&Docker::new()).await?;
+//! println!("{}", dind.client().ping().await?);
+//! dind.stop().await?;
+//! # Ok::<(), shiplift::Error>(())
+//! # };
+//! ```
+
+use std::{
+    env, fs,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use futures_util::TryStreamExt;
+use hyper::{StatusCode, Uri};
+use tar::Archive;
+
+use crate::{
+    config::{ClientConfig, TlsConfig},
+    container::{ContainerOptions, RmContainerOptions},
+    docker::Docker,
+    errors::{Error, Result},
+    image::PullOptions,
+};
+
+/// The image [Dind::start] runs
+pub const DIND_IMAGE: &str = "docker:dind";
+
+/// The port the inner daemon serves its TLS-protected API on
+const TLS_PORT: u16 = 2376;
+
+/// Where the `docker:dind` entrypoint generates certificates for clients
+const CLIENT_CERTS: &str = "/certs/client";
+
+/// How often the inner daemon is polled while it starts
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A docker daemon running in a container of another, `outer`, daemon.
+///
+/// The container isn't removed when a `Dind` is dropped, call [stop](Dind::stop) once done
+/// with it.
+pub struct Dind {
+    outer: Docker,
+    id: String,
+    docker: Docker,
+    cert_dir: PathBuf,
+}
+
+impl Dind {
+    /// Starts a [DIND_IMAGE] container on `outer`, pulling the image if it's missing, and
+    /// waits up to a minute for the inner daemon to answer.
+    pub async fn start(outer: &Docker) -> Result<Dind> {
+        Dind::start_with(outer, DIND_IMAGE, Duration::from_secs(60)).await
+    }
+
+    /// Like [start](Dind::start), but runs `image`, which must behave like `docker:dind`, and
+    /// waits up to `timeout` for the inner daemon.
+    ///
+    /// The container is removed again if the daemon doesn't come up in time.
+    pub async fn start_with(
+        outer: &Docker,
+        image: &str,
+        timeout: Duration,
+    ) -> Result<Dind> {
+        let deadline = Instant::now() + timeout;
+        ensure_image(outer, image).await?;
+
+        let host = outer_host(outer.tcp_host());
+        let opts = ContainerOptions::builder(image)
+            .privileged(true)
+            .env(vec![
+                "DOCKER_TLS_CERTDIR=/certs".to_owned(),
+                format!("DOCKER_TLS_SAN={}", tls_san(&host)),
+            ])
+            .publish(TLS_PORT as u32, "tcp")
+            .publish_all_ports()
+            .build();
+        let id = outer.containers().create(&opts).await?.id;
+
+        match Dind::connect(outer, &id, &host, deadline).await {
+            Ok(dind) => Ok(dind),
+            Err(err) => {
+                let _ = remove(outer, &id).await;
+                Err(err)
+            }
+        }
+    }
+
+    async fn connect(
+        outer: &Docker,
+        id: &str,
+        host: &str,
+        deadline: Instant,
+    ) -> Result<Dind> {
+        let container = outer.containers().get(id);
+        container.start().await?;
+
+        let cert_dir = env::temp_dir().join(format!("shiplift-dind-{}", &id[..12.min(id.len())]));
+        let cert_path = loop {
+            match fetch_certs(outer, id, &cert_dir).await {
+                Ok(cert_path) => break cert_path,
+                Err(err) if Instant::now() >= deadline => return Err(err),
+                Err(_) => tokio::time::sleep(POLL_INTERVAL).await,
+            }
+        };
+
+        let port = container
+            .mapped_port(TLS_PORT, "tcp")
+            .await?
+            .ok_or_else(|| Error::InvalidResponse(format!("port {} is not published", TLS_PORT)))?
+            .host_port;
+        let docker = Docker::from_env_and_config(&ClientConfig {
+            host: Some(format!("tcp://{}:{}", host, port)),
+            tls: Some(TlsConfig {
+                cert_path,
+                verify: true,
+            }),
+            ..Default::default()
+        })?;
+        docker
+            .wait_ready(deadline.saturating_duration_since(Instant::now()))
+            .await?;
+
+        Ok(Dind {
+            outer: outer.clone(),
+            id: id.to_owned(),
+            docker,
+            cert_dir,
+        })
+    }
+
+    /// A client for the inner daemon
+    pub fn client(&self) -> &Docker {
+        &self.docker
+    }
+
+    /// The id of the container the inner daemon runs in, on the outer daemon
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The directory holding `ca.pem`, `cert.pem` and `key.pem` for the inner daemon, to point
+    /// other tools, such as the docker CLI, at it
+    pub fn cert_path(&self) -> PathBuf {
+        self.cert_dir.join("client")
+    }
+
+    /// Removes the container, along with the inner daemon's images and containers, and the
+    /// certificates copied out of it
+    pub async fn stop(self) -> Result<()> {
+        self.docker.shutdown();
+        remove(&self.outer, &self.id).await?;
+        match fs::remove_dir_all(&self.cert_dir) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+async fn ensure_image(
+    docker: &Docker,
+    image: &str,
+) -> Result<()> {
+    match docker.images().get(image).inspect().await {
+        Ok(_) => Ok(()),
+        Err(Error::Fault {
+            code: StatusCode::NOT_FOUND,
+            ..
+        }) => {
+            docker
+                .images()
+                .pull(&PullOptions::builder().image(image).build())
+                .try_for_each(|_| async { Ok(()) })
+                .await
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Copies the client certificates out of the container once the entrypoint has generated all
+/// of them, returning the directory they are in
+async fn fetch_certs(
+    docker: &Docker,
+    id: &str,
+    dir: &Path,
+) -> Result<PathBuf> {
+    let tarball = docker
+        .containers()
+        .get(id)
+        .copy_from(Path::new(CLIENT_CERTS))
+        .map_ok(Vec::from)
+        .try_concat()
+        .await?;
+    Archive::new(&tarball[..]).unpack(dir)?;
+
+    let cert_path = dir.join("client");
+    match ["ca.pem", "cert.pem", "key.pem"]
+        .iter()
+        .find(|file| !cert_path.join(file).is_file())
+    {
+        Some(missing) => Err(Error::InvalidResponse(format!(
+            "{} has not been generated yet",
+            missing
+        ))),
+        None => Ok(cert_path),
+    }
+}
+
+async fn remove(
+    docker: &Docker,
+    id: &str,
+) -> Result<()> {
+    docker
+        .containers()
+        .get(id)
+        .remove(
+            RmContainerOptions::builder()
+                .force(true)
+                .volumes(true)
+                .build(),
+        )
+        .await
+}
+
+/// The host published ports of the outer daemon are reachable at: its own host when it's
+/// dialed over tcp, and the loopback address when it's local
+fn outer_host(tcp_host: Option<&str>) -> String {
+    tcp_host
+        .and_then(|url| url.parse::<Uri>().ok())
+        .and_then(|uri| uri.host().map(str::to_owned))
+        .unwrap_or_else(|| "127.0.0.1".to_owned())
+}
+
+/// The subject alternative name the inner daemon's certificate needs to be valid for `host`
+fn tls_san(host: &str) -> String {
+    match host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+    {
+        Ok(ip) => format!("IP:{}", ip),
+        Err(_) => format!("DNS:{}", host),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outer_host_of_local_and_remote_daemons() {
+        assert_eq!(outer_host(None), "127.0.0.1");
+        assert_eq!(outer_host(Some("http://10.0.0.5:2375")), "10.0.0.5");
+        assert_eq!(
+            outer_host(Some("https://docker.example.com:2376")),
+            "docker.example.com"
+        );
+    }
+
+    #[test]
+    fn tls_san_of_addresses_and_names() {
+        assert_eq!(tls_san("127.0.0.1"), "IP:127.0.0.1");
+        assert_eq!(tls_san("[::1]"), "IP:::1");
+        assert_eq!(tls_san("docker.example.com"), "DNS:docker.example.com");
+    }
+}
@@ -1,15 +1,15 @@
 //! Transports for communicating with the docker daemon
 
-use crate::{Error, Result};
+use crate::{tarball, Error, Result};
 use futures_util::{
     io::{AsyncRead, AsyncWrite},
     stream::Stream,
-    StreamExt, TryFutureExt,
+    StreamExt, TryFutureExt, TryStreamExt,
 };
 use hyper::{
     body::Bytes,
     client::{Client, HttpConnector},
-    header, Body, Method, Request, StatusCode,
+    header, Body, HeaderMap, Method, Request, Response, StatusCode,
 };
 #[cfg(feature = "tls")]
 use hyper_openssl::HttpsConnector;
@@ -21,10 +21,18 @@ use mime::Mime;
 use pin_project::pin_project;
 use serde::{Deserialize, Serialize};
 use std::{
-    fmt, io, iter,
+    fmt,
+    future::Future,
+    io::{self, Read},
+    iter,
     pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
     task::{Context, Poll},
 };
+use tokio::sync::{watch, OwnedSemaphorePermit, Semaphore};
 
 pub fn tar() -> Mime {
     "application/tar".parse().unwrap()
@@ -33,6 +41,311 @@ pub fn tar() -> Mime {
 pub(crate) type Headers = Option<Vec<(&'static str, String)>>;
 pub(crate) type Payload = Option<(Body, Mime)>;
 
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A request body sent as it is read from a stream, rather than buffered in memory first
+pub struct StreamedBody {
+    stream: Pin<Box<dyn Stream<Item = std::result::Result<Bytes, BoxError>> + Send>>,
+    length: Option<u64>,
+}
+
+impl StreamedBody {
+    /// A body of unknown length, sent with chunked transfer encoding
+    pub fn chunked<S, O, E>(stream: S) -> Self
+    where
+        S: Stream<Item = std::result::Result<O, E>> + Send + 'static,
+        O: Into<Bytes> + 'static,
+        E: Into<BoxError> + 'static,
+    {
+        StreamedBody {
+            stream: Box::pin(stream.map_ok(Into::into).map_err(Into::into)),
+            length: None,
+        }
+    }
+
+    /// A body of `length` bytes, sent with a `Content-Length` header. The stream must yield
+    /// exactly `length` bytes, or the request fails.
+    pub fn sized<S, O, E>(
+        stream: S,
+        length: u64,
+    ) -> Self
+    where
+        S: Stream<Item = std::result::Result<O, E>> + Send + 'static,
+        O: Into<Bytes> + 'static,
+        E: Into<BoxError> + 'static,
+    {
+        StreamedBody {
+            length: Some(length),
+            ..StreamedBody::chunked(stream)
+        }
+    }
+
+    /// A body read from `reader` on a separate thread as it is sent, `length` bytes long if
+    /// known
+    pub fn from_reader<R>(
+        mut reader: R,
+        length: Option<u64>,
+    ) -> Self
+    where
+        R: Read + Send + 'static,
+    {
+        StreamedBody {
+            length,
+            ..StreamedBody::chunked(tarball::writer_stream(move |writer| {
+                io::copy(&mut reader, writer).map(|_| ())
+            }))
+        }
+    }
+
+    /// Calls `progress` with the bytes sent so far each time a chunk of the body is sent, so
+    /// the progress of large uploads can be shown
+    pub fn on_progress<F>(
+        self,
+        mut progress: F,
+    ) -> Self
+    where
+        F: FnMut(UploadProgress) + Send + 'static,
+    {
+        let total = self.length;
+        let mut sent = 0;
+        StreamedBody {
+            stream: Box::pin(self.stream.inspect_ok(move |chunk| {
+                sent += chunk.len() as u64;
+                progress(UploadProgress { sent, total });
+            })),
+            length: total,
+        }
+    }
+
+    /// The length of the body, if known up front
+    pub fn length(&self) -> Option<u64> {
+        self.length
+    }
+}
+
+/// How much of a [StreamedBody] was sent
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct UploadProgress {
+    /// Bytes sent so far
+    pub sent: u64,
+    /// The length of the body, if known up front
+    pub total: Option<u64>,
+}
+
+impl fmt::Debug for StreamedBody {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        f.debug_struct("StreamedBody")
+            .field("length", &self.length)
+            .finish()
+    }
+}
+
+/// Something a request body can be made of: anything convertible [Into] a [Body], or a
+/// [StreamedBody]
+pub trait IntoRequestBody {
+    /// The body, along with its length when it has to be announced in a `Content-Length` header
+    fn into_request_body(self) -> (Body, Option<u64>);
+}
+
+impl<B: Into<Body>> IntoRequestBody for B {
+    fn into_request_body(self) -> (Body, Option<u64>) {
+        // hyper announces the length of bodies it holds in full on its own
+        (self.into(), None)
+    }
+}
+
+impl IntoRequestBody for StreamedBody {
+    fn into_request_body(self) -> (Body, Option<u64>) {
+        (Body::wrap_stream(self.stream), self.length)
+    }
+}
+
+/// Caps the number of requests a client has in flight against the docker daemon.
+///
+/// Requests over the limit wait in line for a free slot. When `max_queued` is set, requests
+/// that would make the line any longer fail right away with
+/// [RequestQueueFull](crate::Error::RequestQueueFull).
+#[derive(Debug)]
+pub(crate) struct RequestLimiter {
+    semaphore: Arc<Semaphore>,
+    max_queued: Option<usize>,
+    queued: AtomicUsize,
+}
+
+/// Keeps a request slot taken until dropped
+pub(crate) struct RequestPermit {
+    _permit: OwnedSemaphorePermit,
+}
+
+/// Keeps a place in the request queue until dropped, so cancelled waiters give it back
+struct QueuePlace<'a>(&'a AtomicUsize);
+
+impl<'a> Drop for QueuePlace<'a> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl RequestLimiter {
+    pub(crate) fn new(
+        max_in_flight: usize,
+        max_queued: Option<usize>,
+    ) -> Self {
+        RequestLimiter {
+            // a limit of zero would never let a request through
+            semaphore: Arc::new(Semaphore::new(max_in_flight.max(1))),
+            max_queued,
+            queued: AtomicUsize::new(0),
+        }
+    }
+
+    /// Waits for a free request slot
+    pub(crate) async fn acquire(&self) -> Result<RequestPermit> {
+        if let Ok(permit) = self.semaphore.clone().try_acquire_owned() {
+            return Ok(RequestPermit { _permit: permit });
+        }
+
+        let place = QueuePlace(&self.queued);
+        let queued = self.queued.fetch_add(1, Ordering::SeqCst);
+        if let Some(max_queued) = self.max_queued {
+            if queued >= max_queued {
+                return Err(Error::RequestQueueFull);
+            }
+        }
+
+        let permit = self
+            .semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("request semaphore is never closed");
+        drop(place);
+
+        Ok(RequestPermit { _permit: permit })
+    }
+}
+
+/// Shared flag telling every clone of a client that it has been shut down.
+#[derive(Debug)]
+pub(crate) struct ShutdownSignal {
+    sender: watch::Sender<bool>,
+}
+
+impl ShutdownSignal {
+    pub(crate) fn new() -> Self {
+        ShutdownSignal {
+            sender: watch::channel(false).0,
+        }
+    }
+
+    pub(crate) fn trigger(&self) {
+        self.sender.send_replace(true);
+    }
+
+    pub(crate) fn is_triggered(&self) -> bool {
+        *self.sender.borrow()
+    }
+
+    /// Resolves once the signal has been triggered
+    pub(crate) fn triggered(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut receiver = self.sender.subscribe();
+        async move {
+            // the sender lives as long as any client clone, so an error only means nobody is left
+            let _ = receiver.wait_for(|triggered| *triggered).await;
+        }
+    }
+
+    /// Wraps an upgraded connection so it is closed when the signal is triggered
+    pub(crate) fn guard<T>(
+        &self,
+        connection: T,
+    ) -> UntilShutdown<T>
+    where
+        T: AsyncRead + AsyncWrite,
+    {
+        UntilShutdown {
+            connection: Some(Box::pin(connection)),
+            triggered: Box::pin(self.triggered()),
+        }
+    }
+}
+
+/// An upgraded connection that is dropped once its client shuts down. Reads then report end of
+/// stream and writes fail with `BrokenPipe`.
+pub(crate) struct UntilShutdown<T> {
+    connection: Option<Pin<Box<T>>>,
+    triggered: Pin<Box<dyn Future<Output = ()> + Send>>,
+}
+
+impl<T> UntilShutdown<T> {
+    fn connection(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Option<Pin<&mut T>> {
+        if self.connection.is_some() && self.triggered.as_mut().poll(cx).is_ready() {
+            self.connection = None;
+        }
+        self.connection
+            .as_mut()
+            .map(|connection| connection.as_mut())
+    }
+}
+
+impl<T> AsyncRead for UntilShutdown<T>
+where
+    T: AsyncRead,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.connection(cx) {
+            Some(connection) => connection.poll_read(cx, buf),
+            None => Poll::Ready(Ok(0)),
+        }
+    }
+}
+
+impl<T> AsyncWrite for UntilShutdown<T>
+where
+    T: AsyncWrite,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.connection(cx) {
+            Some(connection) => connection.poll_write(cx, buf),
+            None => Poll::Ready(Err(io::ErrorKind::BrokenPipe.into())),
+        }
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.connection(cx) {
+            Some(connection) => connection.poll_flush(cx),
+            None => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn poll_close(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.connection(cx) {
+            Some(connection) => connection.poll_close(cx),
+            None => Poll::Ready(Ok(())),
+        }
+    }
+}
+
 /// Transports are types which define the means of communication
 /// with the docker daemon
 #[derive(Clone)]
@@ -81,16 +394,61 @@ impl Transport {
         headers: Option<H>,
     ) -> Result<String>
     where
-        B: Into<Body>,
+        B: IntoRequestBody,
         H: IntoIterator<Item = (&'static str, String)>,
     {
-        let body = self.get_body(method, endpoint, body, headers).await?;
-        let bytes = hyper::body::to_bytes(body).await?;
-        let string = String::from_utf8(bytes.to_vec())?;
+        let (string, _) = self
+            .request_with_headers(method, endpoint, body, headers)
+            .await?;
 
         Ok(string)
     }
 
+    /// Make a request and return the whole response in a `String` along with the response
+    /// headers
+    pub async fn request_with_headers<B, H>(
+        &self,
+        method: Method,
+        endpoint: impl AsRef<str>,
+        body: Option<(B, Mime)>,
+        headers: Option<H>,
+    ) -> Result<(String, HeaderMap)>
+    where
+        B: IntoRequestBody,
+        H: IntoIterator<Item = (&'static str, String)>,
+    {
+        self.request_with_limit(method, endpoint, body, headers, None)
+            .await
+    }
+
+    /// Like `request_with_headers`, failing with
+    /// [Error::ResponseTooLarge](crate::Error::ResponseTooLarge) instead of reading a response
+    /// body larger than `limit` bytes
+    pub(crate) async fn request_with_limit<B, H>(
+        &self,
+        method: Method,
+        endpoint: impl AsRef<str>,
+        body: Option<(B, Mime)>,
+        headers: Option<H>,
+        limit: Option<usize>,
+    ) -> Result<(String, HeaderMap)>
+    where
+        B: IntoRequestBody,
+        H: IntoIterator<Item = (&'static str, String)>,
+    {
+        let (parts, body) = self
+            .get_response(method, endpoint, body, headers)
+            .await?
+            .into_parts();
+        let bytes = match limit {
+            Some(limit) => read_limited(body, &parts.headers, limit).await?,
+            None => hyper::body::to_bytes(body).await?.to_vec(),
+        };
+        let string = String::from_utf8(bytes)?;
+
+        Ok((string, parts.headers))
+    }
+
     async fn get_body<B, H>(
         &self,
         method: Method,
@@ -99,7 +457,23 @@ impl Transport {
         headers: Option<H>,
     ) -> Result<Body>
     where
-        B: Into<Body>,
+        B: IntoRequestBody,
+        H: IntoIterator<Item = (&'static str, String)>,
+    {
+        let response = self.get_response(method, endpoint, body, headers).await?;
+
+        Ok(response.into_body())
+    }
+
+    async fn get_response<B, H>(
+        &self,
+        method: Method,
+        endpoint: impl AsRef<str>,
+        body: Option<(B, Mime)>,
+        headers: Option<H>,
+    ) -> Result<Response<Body>>
+    where
+        B: IntoRequestBody,
         H: IntoIterator<Item = (&'static str, String)>,
     {
         let req = self
@@ -115,7 +489,7 @@ impl Transport {
             StatusCode::OK
             | StatusCode::CREATED
             | StatusCode::SWITCHING_PROTOCOLS
-            | StatusCode::NO_CONTENT => Ok(response.into_body()),
+            | StatusCode::NO_CONTENT => Ok(response),
             _ => {
                 let bytes = hyper::body::to_bytes(response.into_body()).await?;
                 let message_body = String::from_utf8(bytes.to_vec())?;
@@ -133,7 +507,7 @@ impl Transport {
         }
     }
 
-    async fn get_chunk_stream<B, H>(
+    pub(crate) async fn get_chunk_stream<B, H>(
         &self,
         method: Method,
         endpoint: impl AsRef<str>,
@@ -141,7 +515,7 @@ impl Transport {
         headers: Option<H>,
     ) -> Result<impl Stream<Item = Result<Bytes>>>
     where
-        B: Into<Body>,
+        B: IntoRequestBody,
         H: IntoIterator<Item = (&'static str, String)>,
     {
         let body = self.get_body(method, endpoint, body, headers).await?;
@@ -157,7 +531,7 @@ impl Transport {
         headers: Option<H>,
     ) -> impl Stream<Item = Result<Bytes>> + 'stream
     where
-        B: Into<Body> + 'stream,
+        B: IntoRequestBody + 'stream,
         H: IntoIterator<Item = (&'static str, String)> + 'stream,
     {
         self.get_chunk_stream(method, endpoint, body, headers)
@@ -174,7 +548,7 @@ impl Transport {
         builder: hyper::http::request::Builder,
     ) -> Result<Request<Body>>
     where
-        B: Into<Body>,
+        B: IntoRequestBody,
         H: IntoIterator<Item = (&'static str, String)>,
     {
         let req = match *self {
@@ -191,7 +565,7 @@ impl Transport {
             }
             #[cfg(feature = "unix-socket")]
             Transport::Unix { ref path, .. } => {
-                let uri = DomainUri::new(&path, endpoint.as_ref());
+                let uri = DomainUri::new(path, endpoint.as_ref());
                 builder.method(method).uri(uri)
             }
         };
@@ -204,9 +578,15 @@ impl Transport {
         }
 
         match body {
-            Some((b, c)) => Ok(req
-                .header(header::CONTENT_TYPE, &c.to_string()[..])
-                .body(b.into())?),
+            Some((b, c)) => {
+                let (b, length) = b.into_request_body();
+                if let Some(length) = length {
+                    req = req.header(header::CONTENT_LENGTH, length);
+                }
+                Ok(req
+                    .header(header::CONTENT_TYPE, &c.to_string()[..])
+                    .body(b)?)
+            }
             _ => Ok(req.body(Body::empty())?),
         }
     }
@@ -237,7 +617,7 @@ impl Transport {
         body: Option<(B, Mime)>,
     ) -> Result<hyper::upgrade::Upgraded>
     where
-        B: Into<Body>,
+        B: IntoRequestBody,
     {
         let req = self
             .build_request(
@@ -266,7 +646,7 @@ impl Transport {
         body: Option<(B, Mime)>,
     ) -> Result<impl AsyncRead + AsyncWrite>
     where
-        B: Into<Body>,
+        B: IntoRequestBody,
     {
         let tokio_multiplexer = self.stream_upgrade_tokio(method, endpoint, body).await?;
 
@@ -336,6 +716,32 @@ struct ErrorResponse {
     message: String,
 }
 
+/// Reads a whole response body, giving up as soon as it is known to exceed `limit` bytes
+async fn read_limited(
+    mut body: Body,
+    headers: &HeaderMap,
+    limit: usize,
+) -> Result<Vec<u8>> {
+    let too_large = || Error::ResponseTooLarge { limit };
+    let length = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|length| length.to_str().ok())
+        .and_then(|length| length.parse::<u64>().ok());
+    if length.is_some_and(|length| length > limit as u64) {
+        return Err(too_large());
+    }
+
+    let mut bytes = Vec::with_capacity(length.unwrap_or(0) as usize);
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk?;
+        if bytes.len() + chunk.len() > limit {
+            return Err(too_large());
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
+}
+
 fn stream_body(body: Body) -> impl Stream<Item = Result<Bytes>> {
     async fn unfold(mut body: Body) -> Option<(Result<Bytes>, Body)> {
         let chunk_result = body.next().await?.map_err(Error::from);
@@ -345,3 +751,156 @@ fn stream_body(body: Body) -> impl Stream<Item = Result<Bytes>> {
 
     futures_util::stream::unfold(body, unfold)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn read_limited_bodies() {
+        let mut headers = HeaderMap::new();
+        let body = || {
+            Body::wrap_stream(futures_util::stream::iter(vec![
+                Ok::<_, io::Error>("abc"),
+                Ok("def"),
+            ]))
+        };
+
+        assert_eq!(read_limited(body(), &headers, 6).await.unwrap(), b"abcdef");
+        assert!(matches!(
+            read_limited(body(), &headers, 5).await,
+            Err(Error::ResponseTooLarge { limit: 5 })
+        ));
+
+        headers.insert(header::CONTENT_LENGTH, "1000000".parse().unwrap());
+        assert!(matches!(
+            read_limited(Body::empty(), &headers, 1024).await,
+            Err(Error::ResponseTooLarge { limit: 1024 })
+        ));
+    }
+
+    #[tokio::test]
+    async fn until_shutdown_closes_connection() {
+        use futures_util::io::{AsyncReadExt, AsyncWriteExt, Cursor};
+
+        let signal = ShutdownSignal::new();
+        let mut connection = signal.guard(Cursor::new(b"hello".to_vec()));
+
+        let mut buf = [0u8; 2];
+        assert_eq!(connection.read(&mut buf).await.unwrap(), 2);
+
+        signal.trigger();
+        assert!(signal.is_triggered());
+        assert_eq!(connection.read(&mut buf).await.unwrap(), 0);
+        assert_eq!(
+            connection.write(b"x").await.unwrap_err().kind(),
+            io::ErrorKind::BrokenPipe
+        );
+        signal.triggered().await;
+    }
+
+    #[tokio::test]
+    async fn request_limiter_rejects_when_queue_is_full() {
+        let limiter = RequestLimiter::new(1, Some(0));
+
+        let permit = limiter.acquire().await.unwrap();
+        assert!(matches!(
+            limiter.acquire().await,
+            Err(Error::RequestQueueFull)
+        ));
+
+        drop(permit);
+        assert!(limiter.acquire().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn request_limiter_lets_one_request_through_at_zero() {
+        let limiter = RequestLimiter::new(0, Some(0));
+
+        let permit = limiter.acquire().await.unwrap();
+        assert!(matches!(
+            limiter.acquire().await,
+            Err(Error::RequestQueueFull)
+        ));
+        drop(permit);
+    }
+
+    #[tokio::test]
+    async fn request_limiter_queues_waiters() {
+        let limiter = Arc::new(RequestLimiter::new(1, Some(1)));
+
+        let permit = limiter.acquire().await.unwrap();
+        let waiter = {
+            let limiter = limiter.clone();
+            tokio::spawn(async move { limiter.acquire().await.map(|_| ()) })
+        };
+        while limiter.queued.load(Ordering::SeqCst) == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert!(matches!(
+            limiter.acquire().await,
+            Err(Error::RequestQueueFull)
+        ));
+
+        drop(permit);
+        assert!(waiter.await.unwrap().is_ok());
+        assert_eq!(limiter.queued.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn streamed_body_announces_known_length() {
+        let transport = Transport::Tcp {
+            client: Client::new(),
+            host: "http://localhost:2375".to_owned(),
+        };
+        let chunks = || {
+            futures_util::stream::iter(vec![
+                Ok::<_, io::Error>(Bytes::from_static(b"ab")),
+                Ok(Bytes::from_static(b"c")),
+            ])
+        };
+
+        let req = transport
+            .build_request(
+                Method::PUT,
+                "/containers/web/archive",
+                Some((StreamedBody::sized(chunks(), 3), tar())),
+                None::<iter::Empty<_>>,
+                Request::builder(),
+            )
+            .unwrap();
+        assert_eq!(req.headers()[header::CONTENT_LENGTH], "3");
+        let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+        assert_eq!(&body[..], b"abc");
+
+        let req = transport
+            .build_request(
+                Method::PUT,
+                "/containers/web/archive",
+                Some((StreamedBody::chunked(chunks()), tar())),
+                None::<iter::Empty<_>>,
+                Request::builder(),
+            )
+            .unwrap();
+        assert!(!req.headers().contains_key(header::CONTENT_LENGTH));
+    }
+
+    #[tokio::test]
+    async fn streamed_body_reports_progress() {
+        let progress = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let reported = progress.clone();
+        let body = StreamedBody::from_reader(io::Cursor::new(b"abc".to_vec()), Some(3))
+            .on_progress(move |sent| reported.lock().unwrap().push(sent));
+
+        let (body, length) = body.into_request_body();
+        assert_eq!(length, Some(3));
+        assert_eq!(&hyper::body::to_bytes(body).await.unwrap()[..], b"abc");
+        assert_eq!(
+            *progress.lock().unwrap(),
+            vec![UploadProgress {
+                sent: 3,
+                total: Some(3)
+            }]
+        );
+    }
+}
@@ -1,13 +1,18 @@
 //! Types for working with docker TTY streams
 
 use crate::{Error, Result};
+use bytes::Bytes;
 use futures_util::{
+    future::Either,
     io::{AsyncRead, AsyncReadExt, AsyncWrite},
-    stream::{Stream, TryStreamExt},
+    stream::{Stream, StreamExt, TryStreamExt},
 };
 use pin_project::pin_project;
 use std::{convert::TryInto, io};
 
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, Utc};
+
 /// An enum representing a chunk of TTY text streamed from a Docker container.
 ///
 /// For convenience, this type can deref to the contained `Vec<u8>`.
@@ -18,6 +23,88 @@ pub enum TtyChunk {
     StdErr(Vec<u8>),
 }
 
+impl TtyChunk {
+    /// Splits off the timestamp the daemon prefixes log lines with when they are requested with
+    /// `timestamps=true`, returning it along with the rest of the line. Returns `None` if the
+    /// chunk does not start with a timestamp.
+    #[cfg(feature = "chrono")]
+    pub fn timestamp(&self) -> Option<(DateTime<Utc>, &[u8])> {
+        let (timestamp, line) = split_timestamp(self)?;
+        let timestamp = DateTime::parse_from_rfc3339(timestamp).ok()?;
+        Some((timestamp.with_timezone(&Utc), line))
+    }
+
+    /// Splits off the timestamp the daemon prefixes log lines with when they are requested with
+    /// `timestamps=true`, returning it along with the rest of the line. Returns `None` if the
+    /// chunk does not start with a timestamp.
+    #[cfg(not(feature = "chrono"))]
+    pub fn timestamp(&self) -> Option<(String, &[u8])> {
+        split_timestamp(self).map(|(timestamp, line)| (timestamp.to_owned(), line))
+    }
+}
+
+impl TtyChunk {
+    /// The chunk with the daemon's timestamp prefix removed, if it has one
+    pub(crate) fn without_timestamp(self) -> TtyChunk {
+        let strip = |bytes: Vec<u8>| match split_timestamp(&bytes) {
+            Some((_, line)) => line.to_vec(),
+            None => bytes,
+        };
+        match self {
+            TtyChunk::StdIn(bytes) => TtyChunk::StdIn(strip(bytes)),
+            TtyChunk::StdOut(bytes) => TtyChunk::StdOut(strip(bytes)),
+            TtyChunk::StdErr(bytes) => TtyChunk::StdErr(strip(bytes)),
+        }
+    }
+
+    /// The chunk's timestamp prefix in nanoseconds since the unix epoch
+    pub(crate) fn timestamp_nanos(&self) -> Option<i128> {
+        split_timestamp(self).and_then(|(timestamp, _)| unix_nanos(timestamp))
+    }
+}
+
+/// Parses a UTC RFC3339 timestamp such as `2023-04-05T06:07:08.123456789Z`, as the daemon
+/// prefixes log lines with, into nanoseconds since the unix epoch
+fn unix_nanos(timestamp: &str) -> Option<i128> {
+    let timestamp = timestamp.strip_suffix('Z')?;
+    let (date, time) = timestamp.split_once('T')?;
+    let mut date = date.splitn(3, '-').map(str::parse::<i64>);
+    let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+    let (time, fraction) = time.split_once('.').unwrap_or((time, ""));
+    let mut time = time.splitn(3, ':').map(str::parse::<i64>);
+    let (hour, minute, second) = (time.next()?.ok()?, time.next()?.ok()?, time.next()?.ok()?);
+    if fraction.len() > 9 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let nanos: i64 = format!("{:0<9}", fraction).parse().ok()?;
+
+    // days since the epoch of a proleptic gregorian date
+    let (y, m) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * m + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    let seconds = days * 86_400 + hour * 3_600 + minute * 60 + second;
+    Some(seconds as i128 * 1_000_000_000 + nanos as i128)
+}
+
+/// Splits `<RFC3339 timestamp> <line>` at the first space
+fn split_timestamp(bytes: &[u8]) -> Option<(&str, &[u8])> {
+    let space = bytes.iter().position(|b| *b == b' ')?;
+    let timestamp = std::str::from_utf8(&bytes[..space]).ok()?;
+    // RFC3339 timestamps start with a four digit year
+    if timestamp.len() < 20 || !timestamp[..4].bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some((timestamp, &bytes[space + 1..]))
+}
+
 impl From<TtyChunk> for Vec<u8> {
     fn from(tty_chunk: TtyChunk) -> Self {
         match tty_chunk {
@@ -85,18 +172,63 @@ where
     S: Stream<Item = Result<hyper::body::Bytes>> + Unpin,
 {
     let stream = hyper_chunk_stream
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+        .map_err(io::Error::other)
         .into_async_read();
 
     futures_util::stream::unfold(stream, decode_chunk)
 }
 
+/// Reads the output of a process running with a tty, which the daemon streams as is instead of
+/// multiplexing it, as stdout chunks
+pub(crate) fn decode_raw<S>(hyper_chunk_stream: S) -> impl Stream<Item = Result<TtyChunk>>
+where
+    S: Stream<Item = Result<hyper::body::Bytes>> + Unpin,
+{
+    hyper_chunk_stream.map_ok(|bytes| TtyChunk::StdOut(bytes.to_vec()))
+}
+
+/// Reads the output of a process with the framing the daemon uses for it, depending on whether
+/// the process runs with a tty
+pub(crate) fn decode_output<S>(
+    hyper_chunk_stream: S,
+    tty: bool,
+) -> impl Stream<Item = Result<TtyChunk>>
+where
+    S: Stream<Item = Result<hyper::body::Bytes>> + Unpin,
+{
+    if tty {
+        Either::Left(decode_raw(hyper_chunk_stream))
+    } else {
+        Either::Right(decode(hyper_chunk_stream))
+    }
+}
+
+async fn read_raw_chunk<S>(mut stream: S) -> Option<(Result<TtyChunk>, S)>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut data = vec![0u8; 8192];
+    match stream.read(&mut data).await {
+        Ok(0) => None,
+        Ok(n) => {
+            data.truncate(n);
+            Some((Ok(TtyChunk::StdOut(data)), stream))
+        }
+        Err(e) => Some((Err(Error::IO(e)), stream)),
+    }
+}
+
 type TtyReader<'a> = Pin<Box<dyn Stream<Item = Result<TtyChunk>> + Send + 'a>>;
 type TtyWriter<'a> = Pin<Box<dyn AsyncWrite + Send + 'a>>;
 
 /// TTY multiplexer returned by the `attach` method.
 ///
 /// This object can emit a stream of `TtyChunk`s and also implements `AsyncWrite` for streaming bytes to Stdin.
+///
+/// Closing the writer, with [close_stdin](Multiplexer::close_stdin) or `AsyncWriteExt::close`,
+/// only shuts down the write half of the connection. The daemon sees it as the end of stdin
+/// while output keeps streaming, so commands that read stdin until EOF can be driven to
+/// completion.
 #[pin_project]
 pub struct Multiplexer<'a> {
     #[pin]
@@ -119,11 +251,27 @@ impl<'a> Multiplexer<'a> {
             writer: Box::pin(writer),
         }
     }
+
+    /// A multiplexer over the connection of a process running with a tty, whose output is not
+    /// multiplexed. All of it is read as stdout.
+    pub(crate) fn new_raw<T>(tcp_connection: T) -> Self
+    where
+        T: AsyncRead + AsyncWrite + Send + 'a,
+    {
+        let (reader, writer) = tcp_connection.split();
+
+        Self {
+            reader: Box::pin(futures_util::stream::unfold(reader, read_raw_chunk)),
+            writer: Box::pin(writer),
+        }
+    }
 }
 
 use std::{
+    collections::VecDeque,
     pin::Pin,
-    task::{Context, Poll},
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Wake, Waker},
 };
 
 impl<'a> Stream for Multiplexer<'a> {
@@ -159,6 +307,12 @@ impl<'a> AsyncWrite for Multiplexer<'a> {
 }
 
 impl<'a> Multiplexer<'a> {
+    /// Flushes pending input and signals the end of stdin by shutting down the write half of the
+    /// connection. Output can still be read afterwards.
+    pub async fn close_stdin(&mut self) -> io::Result<()> {
+        futures_util::io::AsyncWriteExt::close(&mut self.writer).await
+    }
+
     /// Split the `Multiplexer` into the component `Stream` and `AsyncWrite` parts
     pub fn split(
         self
@@ -169,3 +323,511 @@ impl<'a> Multiplexer<'a> {
         (self.reader, self.writer)
     }
 }
+
+type TtySource<'a> = Pin<Box<dyn Stream<Item = Result<TtyChunk>> + Send + 'a>>;
+
+/// Splits a demultiplexed stream of `TtyChunk`s, such as the one returned by `Container::logs`,
+/// into independent stdout and stderr streams.
+///
+/// Either half may be polled from its own task. Whichever half is polled drives the underlying
+/// stream and chunks destined for the other half are buffered until it is polled, so a half
+/// that is kept around but never polled will accumulate its output in memory. Dropping a half
+/// discards its output. `StdIn` chunks are discarded, and an error from the underlying stream
+/// is yielded by the half that encountered it.
+pub fn split_output<'a, S>(stream: S) -> (TtyOutput<'a>, TtyOutput<'a>)
+where
+    S: Stream<Item = Result<TtyChunk>> + Send + 'a,
+{
+    let shared = Arc::new(Mutex::new(SplitState {
+        source: Box::pin(stream),
+        done: false,
+        halves: Default::default(),
+        wakers: Default::default(),
+    }));
+
+    (
+        TtyOutput {
+            shared: shared.clone(),
+            kind: OutputKind::StdOut,
+        },
+        TtyOutput {
+            shared,
+            kind: OutputKind::StdErr,
+        },
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OutputKind {
+    StdOut = 0,
+    StdErr = 1,
+}
+
+#[derive(Default)]
+struct HalfState {
+    buffer: VecDeque<Bytes>,
+    dropped: bool,
+}
+
+/// The wakers of both halves, woken together by the source so that whichever half is waiting
+/// gets to drive it. Kept out of the shared state's lock, as the source may wake it while that
+/// is held.
+#[derive(Default)]
+struct SplitWakers([Mutex<Option<Waker>>; 2]);
+
+impl SplitWakers {
+    fn register(
+        &self,
+        kind: OutputKind,
+        waker: &Waker,
+    ) {
+        let mut slot = self.0[kind as usize]
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if !slot.as_ref().is_some_and(|w| w.will_wake(waker)) {
+            *slot = Some(waker.clone());
+        }
+    }
+
+    fn wake_all(&self) {
+        for slot in &self.0 {
+            if let Some(waker) = slot.lock().unwrap_or_else(|e| e.into_inner()).take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl Wake for SplitWakers {
+    fn wake(self: Arc<Self>) {
+        self.wake_all()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.wake_all()
+    }
+}
+
+struct SplitState<'a> {
+    source: TtySource<'a>,
+    done: bool,
+    halves: [HalfState; 2],
+    wakers: Arc<SplitWakers>,
+}
+
+/// One half of a TTY stream split by [`split_output`](fn.split_output.html).
+pub struct TtyOutput<'a> {
+    shared: Arc<Mutex<SplitState<'a>>>,
+    kind: OutputKind,
+}
+
+impl<'a> Stream for TtyOutput<'a> {
+    type Item = Result<Bytes>;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let mut state = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+        let own = self.kind as usize;
+
+        // registered before polling the source, so progress it makes from here on wakes this half
+        state.wakers.register(self.kind, cx.waker());
+        let waker = Waker::from(state.wakers.clone());
+        let mut source_cx = Context::from_waker(&waker);
+
+        loop {
+            if let Some(bytes) = state.halves[own].buffer.pop_front() {
+                return Poll::Ready(Some(Ok(bytes)));
+            }
+            if state.done {
+                return Poll::Ready(None);
+            }
+
+            let polled = state.source.as_mut().poll_next(&mut source_cx);
+            if polled.is_ready() {
+                // the other half may be waiting on this one to drive the source, or on the
+                // chunk it just got
+                state.wakers.wake_all();
+            }
+            match polled {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(None) => state.done = true,
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+                Poll::Ready(Some(Ok(chunk))) => {
+                    let target = match chunk {
+                        TtyChunk::StdOut(_) => OutputKind::StdOut as usize,
+                        TtyChunk::StdErr(_) => OutputKind::StdErr as usize,
+                        TtyChunk::StdIn(_) => continue,
+                    };
+                    let half = &mut state.halves[target];
+                    if !half.dropped {
+                        half.buffer.push_back(Bytes::from(Vec::from(chunk)));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<'a> Drop for TtyOutput<'a> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+        let half = &mut state.halves[self.kind as usize];
+        half.dropped = true;
+        half.buffer.clear();
+        // the remaining half may be parked waiting on this one to drive the source
+        state.wakers.wake_all();
+    }
+}
+
+/// A complete line of TTY output, without its line ending, as yielded by
+/// [`lines`](fn.lines.html)
+#[derive(Debug, Clone, PartialEq)]
+pub enum TtyLine {
+    StdIn(String),
+    StdOut(String),
+    StdErr(String),
+}
+
+/// Turns a demultiplexed stream of `TtyChunk`s, such as the one returned by `Container::logs`,
+/// into a stream of complete lines.
+///
+/// Lines are reassembled per output stream, so chunks of stdout and stderr may interleave.
+/// Invalid UTF-8 is replaced with `U+FFFD`, and a line longer than `max_line_length` bytes is
+/// broken up into several lines of at most that length. An unterminated last line is yielded
+/// when the stream ends. Errors from the underlying stream are passed through.
+pub fn lines<'a, S>(
+    stream: S,
+    max_line_length: usize,
+) -> impl Stream<Item = Result<TtyLine>> + Send + 'a
+where
+    S: Stream<Item = Result<TtyChunk>> + Send + 'a,
+{
+    let splitter = LineSplitter {
+        source: Box::pin(stream),
+        max_line_length: max_line_length.max(4),
+        buffers: Default::default(),
+        lines: VecDeque::new(),
+        done: false,
+    };
+
+    futures_util::stream::unfold(splitter, |mut splitter| async move {
+        loop {
+            if let Some(line) = splitter.lines.pop_front() {
+                return Some((Ok(line), splitter));
+            }
+            if splitter.done {
+                return None;
+            }
+
+            match splitter.source.next().await {
+                Some(Ok(chunk)) => splitter.push(chunk),
+                Some(Err(e)) => return Some((Err(e), splitter)),
+                None => {
+                    splitter.done = true;
+                    splitter.flush();
+                }
+            }
+        }
+    })
+}
+
+struct LineSplitter<'a> {
+    source: TtySource<'a>,
+    max_line_length: usize,
+    /// Incomplete lines of stdin, stdout and stderr
+    buffers: [Vec<u8>; 3],
+    lines: VecDeque<TtyLine>,
+    done: bool,
+}
+
+impl<'a> LineSplitter<'a> {
+    fn push(
+        &mut self,
+        chunk: TtyChunk,
+    ) {
+        let (index, bytes) = match chunk {
+            TtyChunk::StdIn(bytes) => (0, bytes),
+            TtyChunk::StdOut(bytes) => (1, bytes),
+            TtyChunk::StdErr(bytes) => (2, bytes),
+        };
+        let buffer = &mut self.buffers[index];
+        buffer.extend_from_slice(&bytes);
+
+        loop {
+            let buffer = &mut self.buffers[index];
+            let newline = buffer.iter().position(|b| *b == b'\n');
+            // the length of the line's content, which a line ending doesn't count towards
+            let content_length = newline.unwrap_or(buffer.len());
+            let content_length = match buffer[..content_length].last() {
+                Some(b'\r') => content_length - 1,
+                _ => content_length,
+            };
+            let line = match newline {
+                Some(end) if content_length <= self.max_line_length => {
+                    let mut line: Vec<u8> = buffer.drain(..=end).collect();
+                    line.truncate(content_length);
+                    line
+                }
+                _ if content_length > self.max_line_length => {
+                    // don't cut through a multi-byte character
+                    let mut end = self.max_line_length;
+                    while end > self.max_line_length - 3 && buffer[end] & 0xC0 == 0x80 {
+                        end -= 1;
+                    }
+                    buffer.drain(..end).collect()
+                }
+                _ => break,
+            };
+            self.lines.push_back(to_line(index, &line));
+        }
+    }
+
+    fn flush(&mut self) {
+        for index in 0..self.buffers.len() {
+            let rest = std::mem::take(&mut self.buffers[index]);
+            if !rest.is_empty() {
+                self.lines.push_back(to_line(index, &rest));
+            }
+        }
+    }
+}
+
+fn to_line(
+    index: usize,
+    bytes: &[u8],
+) -> TtyLine {
+    let line = String::from_utf8_lossy(bytes).into_owned();
+    match index {
+        0 => TtyLine::StdIn(line),
+        1 => TtyLine::StdOut(line),
+        _ => TtyLine::StdErr(line),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::{stream, StreamExt};
+
+    fn chunks() -> Vec<Result<TtyChunk>> {
+        vec![
+            Ok(TtyChunk::StdOut(b"out 1".to_vec())),
+            Ok(TtyChunk::StdErr(b"err 1".to_vec())),
+            Ok(TtyChunk::StdIn(b"in".to_vec())),
+            Ok(TtyChunk::StdOut(b"out 2".to_vec())),
+        ]
+    }
+
+    #[test]
+    fn timestamp_nanos_and_strip() {
+        let chunk = TtyChunk::StdErr(b"2023-04-05T06:07:08.1234Z oops\n".to_vec());
+        assert_eq!(chunk.timestamp_nanos(), Some(1_680_674_828_123_400_000));
+        assert_eq!(unix_nanos("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(
+            unix_nanos("2024-02-29T23:59:59.5Z"),
+            Some(1_709_251_199_500_000_000)
+        );
+        assert!(matches!(chunk.without_timestamp(), TtyChunk::StdErr(bytes) if bytes == b"oops\n"));
+    }
+
+    #[test]
+    fn timestamp_splits_log_prefix() {
+        let chunk = TtyChunk::StdOut(b"2023-04-05T06:07:08.123456789Z hello world\n".to_vec());
+        let (timestamp, line) = chunk.timestamp().unwrap();
+        #[cfg(feature = "chrono")]
+        assert_eq!(
+            timestamp.to_rfc3339(),
+            "2023-04-05T06:07:08.123456789+00:00"
+        );
+        #[cfg(not(feature = "chrono"))]
+        assert_eq!(timestamp, "2023-04-05T06:07:08.123456789Z");
+        assert_eq!(line, b"hello world\n");
+
+        assert!(TtyChunk::StdOut(b"hello world\n".to_vec())
+            .timestamp()
+            .is_none());
+    }
+
+    #[test]
+    fn lines_reassemble_chunks() {
+        let chunks = vec![
+            Ok(TtyChunk::StdOut(b"first li".to_vec())),
+            Ok(TtyChunk::StdErr(b"oops\r\n".to_vec())),
+            Ok(TtyChunk::StdOut(b"ne\nsecond \xE2\x82".to_vec())),
+            Ok(TtyChunk::StdOut(b"\xAC \xFF\nabcdefghij".to_vec())),
+        ];
+        let lines: Vec<TtyLine> = futures::executor::block_on(
+            lines(stream::iter(chunks), 4)
+                .map(|line| line.unwrap())
+                .collect(),
+        );
+
+        assert_eq!(
+            lines,
+            vec![
+                TtyLine::StdOut("firs".into()),
+                TtyLine::StdErr("oops".into()),
+                TtyLine::StdOut("t li".into()),
+                TtyLine::StdOut("ne".into()),
+                TtyLine::StdOut("seco".into()),
+                TtyLine::StdOut("nd ".into()),
+                TtyLine::StdOut("\u{20ac} ".into()),
+                TtyLine::StdOut("\u{fffd}".into()),
+                TtyLine::StdOut("abcd".into()),
+                TtyLine::StdOut("efgh".into()),
+                TtyLine::StdOut("ij".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn split_output_separates_streams() {
+        let (stdout, stderr) = split_output(stream::iter(chunks()));
+
+        let stderr: Vec<Bytes> = futures::executor::block_on(stderr.map(|b| b.unwrap()).collect());
+        let stdout: Vec<Bytes> = futures::executor::block_on(stdout.map(|b| b.unwrap()).collect());
+
+        assert_eq!(stdout, vec![Bytes::from("out 1"), Bytes::from("out 2")]);
+        assert_eq!(stderr, vec![Bytes::from("err 1")]);
+    }
+
+    /// A connection replaying canned output which records whether its write half was closed
+    struct HalfClosable {
+        output: futures_util::io::Cursor<Vec<u8>>,
+        closed: Arc<Mutex<bool>>,
+    }
+
+    impl AsyncRead for HalfClosable {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.output).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for HalfClosable {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            if *self.closed.lock().unwrap() {
+                Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()))
+            } else {
+                Poll::Ready(Ok(buf.len()))
+            }
+        }
+
+        fn poll_flush(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+        ) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+        ) -> Poll<io::Result<()>> {
+            *self.closed.lock().unwrap() = true;
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn multiplexer_reads_after_close_stdin() {
+        use futures_util::io::AsyncWriteExt;
+
+        let mut output = vec![1, 0, 0, 0, 0, 0, 0, 5];
+        output.extend_from_slice(b"hello");
+        let closed = Arc::new(Mutex::new(false));
+        let mut multiplexer = Multiplexer::new(HalfClosable {
+            output: futures_util::io::Cursor::new(output),
+            closed: closed.clone(),
+        });
+
+        futures::executor::block_on(async {
+            multiplexer.write_all(b"hello").await.unwrap();
+            multiplexer.close_stdin().await.unwrap();
+            assert!(*closed.lock().unwrap());
+
+            match multiplexer.next().await {
+                Some(Ok(TtyChunk::StdOut(bytes))) => assert_eq!(bytes, b"hello"),
+                other => panic!("expected stdout, got {:?}", other),
+            }
+            assert!(multiplexer.next().await.is_none());
+        });
+    }
+
+    #[test]
+    fn decode_output_by_tty() {
+        let mut framed = vec![2, 0, 0, 0, 0, 0, 0, 3];
+        framed.extend_from_slice(b"err");
+        let body = |bytes: Vec<u8>| stream::iter(vec![Ok(hyper::body::Bytes::from(bytes))]);
+
+        let chunks: Vec<TtyChunk> = futures::executor::block_on(
+            decode_output(body(framed.clone()), false)
+                .map(|chunk| chunk.unwrap())
+                .collect(),
+        );
+        assert!(matches!(&chunks[..], [TtyChunk::StdErr(bytes)] if bytes == b"err"));
+
+        let chunks: Vec<TtyChunk> = futures::executor::block_on(
+            decode_output(body(framed.clone()), true)
+                .map(|chunk| chunk.unwrap())
+                .collect(),
+        );
+        assert!(matches!(&chunks[..], [TtyChunk::StdOut(bytes)] if *bytes == framed));
+    }
+
+    #[test]
+    fn split_output_survives_dropped_half() {
+        let (stdout, stderr) = split_output(stream::iter(chunks()));
+        drop(stdout);
+
+        let stderr: Vec<Bytes> = futures::executor::block_on(stderr.map(|b| b.unwrap()).collect());
+        assert_eq!(stderr, vec![Bytes::from("err 1")]);
+    }
+
+    #[derive(Default)]
+    struct WakeFlag(std::sync::atomic::AtomicBool);
+
+    impl Wake for WakeFlag {
+        fn wake(self: Arc<Self>) {
+            self.0.store(true, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
+    impl WakeFlag {
+        fn woken(&self) -> bool {
+            self.0.load(std::sync::atomic::Ordering::SeqCst)
+        }
+    }
+
+    #[test]
+    fn split_output_wakes_both_halves() {
+        let (tx, rx) = futures::channel::mpsc::unbounded();
+        let (mut stdout, mut stderr) = split_output(rx);
+        let (out_woken, err_woken) = (Arc::new(WakeFlag::default()), Arc::new(WakeFlag::default()));
+        let (out_waker, err_waker) = (
+            Waker::from(out_woken.clone()),
+            Waker::from(err_woken.clone()),
+        );
+
+        assert!(stdout
+            .poll_next_unpin(&mut Context::from_waker(&out_waker))
+            .is_pending());
+        assert!(stderr
+            .poll_next_unpin(&mut Context::from_waker(&err_waker))
+            .is_pending());
+
+        tx.unbounded_send(Err(Error::ConnectionNotUpgraded))
+            .unwrap();
+        assert!(out_woken.woken());
+        assert!(err_woken.woken());
+    }
+}
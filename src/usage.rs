@@ -0,0 +1,202 @@
+//! Resource usage of the docker host, as reported by
+//! [Docker::host_usage](../struct.Docker.html#method.host_usage).
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Disk usage of images, containers and volumes
+///
+/// [Api Reference](https://docs.docker.com/engine/api/v1.41/#operation/SystemDataUsage)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DataUsage {
+    pub layers_size: i64,
+    pub images: Option<Vec<ImageUsage>>,
+    pub containers: Option<Vec<ContainerDiskUsage>>,
+    pub volumes: Option<Vec<VolumeUsage>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ImageUsage {
+    pub id: String,
+    pub repo_tags: Option<Vec<String>>,
+    pub size: i64,
+    /// Size shared with other images, -1 if not calculated
+    pub shared_size: i64,
+    /// Number of containers using the image, -1 if not calculated
+    pub containers: i64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContainerDiskUsage {
+    pub id: String,
+    pub names: Vec<String>,
+    pub image: String,
+    pub state: String,
+    /// Size of the files the container created or changed
+    pub size_rw: Option<i64>,
+    /// Total size of the container's files, including its image
+    pub size_root_fs: Option<i64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct VolumeUsage {
+    pub name: String,
+    pub driver: String,
+    pub usage_data: Option<VolumeUsageData>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct VolumeUsageData {
+    /// Disk space used by the volume, -1 if not available
+    pub size: i64,
+    /// Number of containers referencing the volume, -1 if not available
+    pub ref_count: i64,
+}
+
+/// A snapshot of the resource usage of the docker host
+#[derive(Clone, Debug)]
+pub struct HostUsage {
+    pub name: String,
+    pub n_cpu: u64,
+    pub mem_total: u64,
+    /// CPU and memory usage of the running containers
+    pub containers: Vec<ContainerUsage>,
+    pub disk: DataUsage,
+}
+
+/// CPU and memory usage of a running container
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContainerUsage {
+    pub id: String,
+    pub names: Vec<String>,
+    /// CPU usage since the previous sample, in percent of a single CPU
+    pub cpu_percent: f64,
+    /// Memory in use, excluding the page cache the kernel can reclaim
+    pub memory_usage: u64,
+    pub memory_limit: u64,
+}
+
+/// The subset of a container stats sample needed for usage, tolerant of the fields cgroup v1
+/// and v2 hosts leave out
+#[derive(Deserialize)]
+pub(crate) struct StatsSample {
+    cpu_stats: CpuSample,
+    precpu_stats: CpuSample,
+    memory_stats: MemorySample,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct CpuSample {
+    cpu_usage: CpuUsageSample,
+    system_cpu_usage: u64,
+    online_cpus: Option<u64>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct CpuUsageSample {
+    total_usage: u64,
+    percpu_usage: Option<Vec<u64>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct MemorySample {
+    usage: u64,
+    limit: u64,
+    stats: HashMap<String, u64>,
+}
+
+impl StatsSample {
+    /// Computes usage the way `docker stats` does
+    pub(crate) fn usage(
+        &self,
+        id: String,
+        names: Vec<String>,
+    ) -> ContainerUsage {
+        let cpu_delta = self
+            .cpu_stats
+            .cpu_usage
+            .total_usage
+            .saturating_sub(self.precpu_stats.cpu_usage.total_usage);
+        let system_delta = self
+            .cpu_stats
+            .system_cpu_usage
+            .saturating_sub(self.precpu_stats.system_cpu_usage);
+        let online_cpus = self.cpu_stats.online_cpus.unwrap_or_else(|| {
+            self.cpu_stats
+                .cpu_usage
+                .percpu_usage
+                .as_ref()
+                .map(|percpu| percpu.len() as u64)
+                .unwrap_or(1)
+        });
+        let cpu_percent = if cpu_delta > 0 && system_delta > 0 {
+            cpu_delta as f64 / system_delta as f64 * online_cpus as f64 * 100.0
+        } else {
+            0.0
+        };
+
+        // cgroup v1 reports the reclaimable cache as total_inactive_file, v2 as inactive_file
+        let memory = &self.memory_stats;
+        let cache = memory
+            .stats
+            .get("total_inactive_file")
+            .or_else(|| memory.stats.get("inactive_file"))
+            .copied()
+            .unwrap_or_default();
+
+        ContainerUsage {
+            id,
+            names,
+            cpu_percent,
+            memory_usage: memory.usage.saturating_sub(cache),
+            memory_limit: memory.limit,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_sample_usage() {
+        let sample: StatsSample = serde_json::from_str(
+            r#"{
+                "cpu_stats": {"cpu_usage": {"total_usage": 400}, "system_cpu_usage": 2000, "online_cpus": 4},
+                "precpu_stats": {"cpu_usage": {"total_usage": 200}, "system_cpu_usage": 1000},
+                "memory_stats": {"usage": 1000, "limit": 4000, "stats": {"inactive_file": 200}}
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            sample.usage("0123".into(), vec!["/web".into()]),
+            ContainerUsage {
+                id: "0123".into(),
+                names: vec!["/web".into()],
+                cpu_percent: 80.0,
+                memory_usage: 800,
+                memory_limit: 4000,
+            }
+        );
+    }
+
+    #[test]
+    fn stats_sample_of_stopped_container() {
+        let sample: StatsSample =
+            serde_json::from_str(r#"{"cpu_stats": {}, "precpu_stats": {}, "memory_stats": {}}"#)
+                .unwrap();
+        let usage = sample.usage("0123".into(), Vec::new());
+        assert_eq!(usage.cpu_percent, 0.0);
+        assert_eq!(usage.memory_usage, 0);
+    }
+}
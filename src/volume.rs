@@ -5,17 +5,35 @@
 use std::{
     collections::{BTreeMap, HashMap},
     hash::Hash,
+    path::Path,
 };
 
-use hyper::Body;
+use futures_util::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    stream, Future, StreamExt,
+};
+use hyper::{Body, StatusCode};
 use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
+use serde_json::{json, Map, Value};
 
 use crate::{
+    capabilities::Feature,
+    container::{Container, ContainerOptions, RmContainerOptions},
     errors::{Error, Result},
+    filter::{self, Filter, LabelSelector},
+    idempotency::{self, IDEMPOTENCY_KEY_LABEL},
+    image::PullOptions,
+    service::ObjectVersion,
     Docker,
 };
 
+/// Image of the throwaway container volumes are backed up and restored through. It is pulled
+/// if missing, and never started.
+pub const BACKUP_HELPER_IMAGE: &str = "busybox:latest";
+
+/// Where the volume is mounted in the backup helper container
+const BACKUP_MOUNT: &str = "/volume";
+
 #[cfg(feature = "chrono")]
 use chrono::{DateTime, Utc};
 
@@ -34,16 +52,43 @@ impl<'docker> Volumes<'docker> {
 
     /// Creates a new docker volume.
     ///
+    /// When `opts` has an
+    /// [idempotency key](VolumeCreateOptionsBuilder::idempotency_key), a volume created with
+    /// the same key is returned instead of creating another.
+    ///
     /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/VolumeCreate>
     pub async fn create(
         &self,
         opts: &VolumeCreateOptions,
     ) -> Result<VolumeCreateInfo> {
-        let body: Body = opts.serialize()?.into();
-        let path = vec!["/volumes/create".to_owned()];
+        idempotency::create(
+            &opts.params,
+            || self.create_new(opts),
+            |key| self.created_with_key(key),
+        )
+        .await
+    }
 
+    /// The volume created with the idempotency `key`, if any
+    async fn created_with_key(
+        &self,
+        key: &str,
+    ) -> Result<Option<VolumeCreateInfo>> {
+        let volumes = self.with_label(IDEMPOTENCY_KEY_LABEL, key).await?;
+        Ok(volumes
+            .into_iter()
+            .next()
+            .map(|volume| VolumeCreateInfo { name: volume.name }))
+    }
+
+    async fn create_new(
+        &self,
+        opts: &VolumeCreateOptions,
+    ) -> Result<VolumeCreateInfo> {
+        self.docker.require(&opts.features()).await?;
+        let body: Body = opts.serialize()?.into();
         self.docker
-            .post_json(&path.join("?"), Some((body, mime::APPLICATION_JSON)))
+            .post_json("/volumes/create", Some((body, mime::APPLICATION_JSON)))
             .await
     }
 
@@ -51,13 +96,110 @@ impl<'docker> Volumes<'docker> {
     ///
     /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/VolumeList>
     pub async fn list(&self) -> Result<Vec<VolumeInfo>> {
-        let path = vec!["/volumes".to_owned()];
+        self.list_with(&VolumeListOptions::default()).await
+    }
 
+    /// Lists the docker volumes on the current docker host matching `opts`
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/VolumeList>
+    pub async fn list_with(
+        &self,
+        opts: &VolumeListOptions,
+    ) -> Result<Vec<VolumeInfo>> {
+        let mut path = vec!["/volumes".to_owned()];
+        if let Some(query) = opts.serialize() {
+            path.push(query);
+        }
         let volumes_rep = self.docker.get_json::<VolumesInfo>(&path.join("?")).await?;
-        Ok(match volumes_rep.volumes {
-            Some(volumes) => volumes,
-            None => vec![],
+        Ok(volumes_rep.volumes.unwrap_or_default())
+    }
+
+    /// Lists the volumes labeled `key` with `value`
+    pub async fn with_label(
+        &self,
+        key: &str,
+        value: &str,
+    ) -> Result<Vec<VolumeInfo>> {
+        let opts = VolumeListOptions::builder()
+            .filter(LabelSelector::new().equals(key, value).filters())
+            .build();
+        self.list_with(&opts).await
+    }
+
+    /// Restores a tarball, such as one written by [Volume::backup_to](Volume::backup_to), into
+    /// the named volume, creating the volume if it does not exist. Files already in the volume
+    /// are kept unless the tarball overwrites them.
+    ///
+    /// The tarball is copied in through a temporary container of
+    /// [BACKUP_HELPER_IMAGE](BACKUP_HELPER_IMAGE) which mounts the volume.
+    pub async fn restore_from<R>(
+        &self,
+        name: &str,
+        reader: R,
+    ) -> Result<()>
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+    {
+        self.create(&VolumeCreateOptions::builder().name(name).build())
+            .await?;
+
+        let chunks = stream::unfold(reader, |mut reader| async move {
+            let mut buf = vec![0; 64 * 1024];
+            match reader.read(&mut buf).await {
+                Ok(0) => None,
+                Ok(n) => {
+                    buf.truncate(n);
+                    Some((Ok(buf), reader))
+                }
+                Err(e) => Some((Err(e), reader)),
+            }
+        });
+        let body = Body::wrap_stream(chunks);
+
+        with_helper_container(self.docker, name, |helper| async move {
+            helper.copy_to(Path::new(BACKUP_MOUNT), body).await
         })
+        .await
+    }
+
+    /// Returns the volume `name`, creating it with `opts` if it does not exist yet.
+    ///
+    /// An existing volume is compatible when it has the driver and all labels `opts` ask for.
+    /// With `strict` an incompatible volume is an
+    /// [Error::SpecMismatch](crate::Error::SpecMismatch), otherwise it is returned as is.
+    pub async fn ensure(
+        &self,
+        name: &str,
+        opts: &VolumeCreateOptions,
+        strict: bool,
+    ) -> Result<VolumeInfo> {
+        let volume = self.get(name);
+        let existing = match volume.inspect().await {
+            Ok(existing) => existing,
+            Err(Error::Fault { code, .. }) if code == StatusCode::NOT_FOUND => {
+                let mut body = opts.params.clone();
+                body.insert("Name".to_owned(), json!(name));
+                match self.create(&VolumeCreateOptions::from_body(body)).await {
+                    // a conflict means someone else created it concurrently, with another driver
+                    Ok(_)
+                    | Err(Error::Fault {
+                        code: StatusCode::CONFLICT,
+                        ..
+                    }) => volume.inspect().await?,
+                    Err(e) => return Err(e),
+                }
+            }
+            Err(e) => return Err(e),
+        };
+
+        let differences = opts.differences(&existing);
+        if strict && !differences.is_empty() {
+            return Err(Error::SpecMismatch {
+                name: existing.name,
+                differences,
+            });
+        }
+        Ok(existing)
     }
 
     /// Returns a reference to a set of operations available for a named volume
@@ -92,6 +234,37 @@ impl<'docker> Volume<'docker> {
         }
     }
 
+    /// Inspects the volume
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.42/#operation/VolumeInspect>
+    pub async fn inspect(&self) -> Result<VolumeInfo> {
+        self.docker
+            .get_json(&format!("/volumes/{}", self.name)[..])
+            .await
+    }
+
+    /// Updates the spec of a cluster volume. `version` is the
+    /// [version](ClusterVolume::version) of the cluster volume the spec was based on, so
+    /// concurrent updates don't overwrite each other. Only the availability of a cluster volume
+    /// can be changed.
+    ///
+    /// API Reference: <https://docs.docker.com/engine/api/v1.42/#operation/VolumeUpdate>
+    pub async fn update(
+        &self,
+        version: u64,
+        spec: &ClusterVolumeSpec,
+    ) -> Result<()> {
+        self.docker.require(&[Feature::ClusterVolume]).await?;
+        let body: Body = serde_json::to_string(&json!({ "Spec": spec }))?.into();
+        self.docker
+            .put(
+                &format!("/volumes/{}?version={}", self.name, version),
+                Some((body, mime::APPLICATION_JSON)),
+            )
+            .await?;
+        Ok(())
+    }
+
     /// Deletes a volume
     ///
     /// API Reference: <https://docs.docker.com/engine/api/v1.41/#operation/VolumeDelete>
@@ -101,18 +274,117 @@ impl<'docker> Volume<'docker> {
             .await?;
         Ok(())
     }
+
+    /// Writes the contents of the volume to `writer` as an uncompressed tarball, which
+    /// [Volumes::restore_from](Volumes::restore_from) can restore.
+    ///
+    /// The tarball is copied out through a temporary container of
+    /// [BACKUP_HELPER_IMAGE](BACKUP_HELPER_IMAGE) which mounts the volume. Stop containers
+    /// writing to the volume first for a consistent backup.
+    pub async fn backup_to<W>(
+        &self,
+        mut writer: W,
+    ) -> Result<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let writer = &mut writer;
+        with_helper_container(self.docker, &self.name, |helper| async move {
+            // a trailing `/.` asks for the directory's contents rather than the directory itself
+            let mut tarball = helper.copy_from(&Path::new(BACKUP_MOUNT).join("."));
+            while let Some(chunk) = tarball.next().await {
+                writer.write_all(&chunk?).await?;
+            }
+            writer.flush().await?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+/// Runs `f` against a created, but never started, container mounting the named volume, and
+/// removes the container afterwards
+async fn with_helper_container<'docker, F, Fut>(
+    docker: &'docker Docker,
+    volume: &str,
+    f: F,
+) -> Result<()>
+where
+    F: FnOnce(Container<'docker>) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let bind = format!("{}:{}", volume, BACKUP_MOUNT);
+    let opts = ContainerOptions::builder(BACKUP_HELPER_IMAGE)
+        .volumes(vec![&bind])
+        .cmd(vec!["true"])
+        .build();
+
+    let created = match docker.containers().create(&opts).await {
+        Err(Error::Fault { code, .. }) if code == StatusCode::NOT_FOUND => {
+            let mut pull = docker
+                .images()
+                .pull(&PullOptions::builder().image(BACKUP_HELPER_IMAGE).build());
+            while let Some(chunk) = pull.next().await {
+                chunk?;
+            }
+            docker.containers().create(&opts).await?
+        }
+        created => created?,
+    };
+
+    let result = f(Container::new(docker, created.id.clone())).await;
+    let removed = Container::new(docker, created.id)
+        .remove(RmContainerOptions::builder().force(true).build())
+        .await;
+    result.and(removed)
+}
+
+/// Options for filtering volume list results
+#[derive(Default, Debug)]
+pub struct VolumeListOptions {
+    params: HashMap<&'static str, String>,
+}
+
+/// Builder interface for `VolumeListOptions`
+#[derive(Default)]
+pub struct VolumeListOptionsBuilder {
+    params: HashMap<&'static str, String>,
+}
+
+impl VolumeListOptionsBuilder {
+    pub fn filter<I, F>(
+        &mut self,
+        filters: I,
+    ) -> &mut Self
+    where
+        I: IntoIterator<Item = F>,
+        F: Into<Filter>,
+    {
+        self.params.insert("filters", filter::serialize(filters));
+        self
+    }
 }
 
+impl_query_options!(VolumeListOptions, VolumeListOptionsBuilder);
+
 /// Interface for creating volumes
-#[derive(Serialize, Debug)]
+///
+/// Serializes to the body of the create request, and can be deserialized from one.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct VolumeCreateOptions {
-    params: HashMap<&'static str, Value>,
+    params: Map<String, Value>,
 }
 
 impl VolumeCreateOptions {
-    /// serialize options as a string. returns None if no options are defined
-    pub fn serialize(&self) -> Result<String> {
-        serde_json::to_string(&self.params).map_err(Error::from)
+    /// Options sending the JSON `body` as is, such as one deserialized from a stored spec
+    pub fn from_body(body: Map<String, Value>) -> VolumeCreateOptions {
+        VolumeCreateOptions { params: body }
+    }
+
+    /// The JSON body of the request
+    pub fn body(&self) -> &Map<String, Value> {
+        &self.params
     }
 
     pub fn parse_from<'a, K, V>(
@@ -136,17 +408,50 @@ impl VolumeCreateOptions {
     pub fn builder() -> VolumeCreateOptionsBuilder {
         VolumeCreateOptionsBuilder::new()
     }
+
+    /// Options in use that not every daemon supports
+    pub(crate) fn features(&self) -> Vec<Feature> {
+        if self.params.contains_key("ClusterVolumeSpec") {
+            vec![Feature::ClusterVolume]
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Describes how an existing volume differs from the driver and labels these options ask
+    /// for. Anything not asked for is not compared.
+    pub(crate) fn differences(
+        &self,
+        volume: &VolumeInfo,
+    ) -> Vec<String> {
+        let mut differences = Vec::new();
+        if let Some(driver) = self.params.get("Driver").and_then(Value::as_str) {
+            if volume.driver != driver {
+                differences.push(format!("driver is {} instead of {}", volume.driver, driver));
+            }
+        }
+        if let Some(Value::Object(labels)) = self.params.get("Labels") {
+            differences.extend(filter::label_differences(labels, &volume.labels()));
+        }
+        differences
+    }
 }
 
+impl_json_options!(VolumeCreateOptions);
+
 #[derive(Default)]
 pub struct VolumeCreateOptionsBuilder {
     params: HashMap<&'static str, Value>,
+    idempotency_key: Option<String>,
 }
 
 impl VolumeCreateOptionsBuilder {
     pub(crate) fn new() -> Self {
         let params = HashMap::new();
-        VolumeCreateOptionsBuilder { params }
+        VolumeCreateOptionsBuilder {
+            params,
+            idempotency_key: None,
+        }
     }
 
     pub fn driver(
@@ -169,6 +474,16 @@ impl VolumeCreateOptionsBuilder {
         self
     }
 
+    /// Creates a swarm cluster volume with the given spec, backed by the CSI plugin set as the
+    /// [driver](VolumeCreateOptionsBuilder::driver). Requires a swarm manager.
+    pub fn cluster_volume_spec(
+        &mut self,
+        spec: &ClusterVolumeSpec,
+    ) -> &mut Self {
+        self.params.insert("ClusterVolumeSpec", json!(spec));
+        self
+    }
+
     pub fn labels(
         &mut self,
         labels: &HashMap<&str, &str>,
@@ -177,10 +492,29 @@ impl VolumeCreateOptionsBuilder {
         self
     }
 
+    /// Labels the volume with `key` as its [idempotency key](crate::idempotency), so that
+    /// [create](crate::Volumes::create) can be retried safely
+    pub fn idempotency_key<K>(
+        &mut self,
+        key: K,
+    ) -> &mut Self
+    where
+        K: Into<String>,
+    {
+        self.idempotency_key = Some(key.into());
+        self
+    }
+
     pub fn build(&self) -> VolumeCreateOptions {
-        VolumeCreateOptions {
-            params: self.params.clone(),
+        let mut params = self
+            .params
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect();
+        if let Some(ref key) = self.idempotency_key {
+            idempotency::insert_key(&mut params, key);
         }
+        VolumeCreateOptions { params }
     }
 }
 
@@ -210,6 +544,165 @@ pub struct VolumeInfo {
     pub mountpoint: String,
     pub options: Option<HashMap<String, String>>,
     pub scope: String,
+    /// Set for swarm cluster volumes
+    pub cluster_volume: Option<ClusterVolume>,
+}
+
+impl VolumeInfo {
+    /// Labels set on the volume
+    pub fn labels(&self) -> HashMap<String, String> {
+        self.labels.clone().unwrap_or_default()
+    }
+}
+
+/// The cluster side of a swarm cluster volume
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ClusterVolume {
+    #[serde(rename = "ID")]
+    pub id: String,
+    pub version: ObjectVersion,
+    #[cfg(feature = "chrono")]
+    pub created_at: DateTime<Utc>,
+    #[cfg(not(feature = "chrono"))]
+    pub created_at: String,
+    #[cfg(feature = "chrono")]
+    pub updated_at: DateTime<Utc>,
+    #[cfg(not(feature = "chrono"))]
+    pub updated_at: String,
+    pub spec: ClusterVolumeSpec,
+    /// What the CSI plugin reported about the volume, once it was created
+    pub info: Option<ClusterVolumeInfo>,
+    /// Which nodes the volume is published to
+    pub publish_status: Option<Vec<PublishStatus>>,
+}
+
+/// The desired state of a swarm cluster volume
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ClusterVolumeSpec {
+    /// Volumes of the same group can be requested by services by the group name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_mode: Option<AccessMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secrets: Option<Vec<ClusterVolumeSecret>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accessibility_requirements: Option<AccessibilityRequirements>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub capacity_range: Option<CapacityRange>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub availability: Option<Availability>,
+}
+
+/// How a cluster volume may be used by the tasks it is published to
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct AccessMode {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<AccessScope>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sharing: Option<AccessSharing>,
+    /// Options for a volume mounted as a filesystem, the default
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mount_volume: Option<Value>,
+    /// Options for a volume used as a block device
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_volume: Option<Value>,
+}
+
+/// Whether a cluster volume can be published to one node at a time or to several
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessScope {
+    Single,
+    Multi,
+}
+
+/// How the tasks a cluster volume is published to may share it
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessSharing {
+    /// Only one task at a time may use the volume
+    None,
+    /// Every task may read, none may write
+    Readonly,
+    /// Every task may read, one may write
+    Onewriter,
+    /// Every task may read and write
+    All,
+}
+
+/// A swarm secret passed to the CSI plugin under `key`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ClusterVolumeSecret {
+    pub key: String,
+    /// Name or id of the secret
+    pub secret: String,
+}
+
+/// Where in the cluster a volume must, or should preferably, be accessible from
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct AccessibilityRequirements {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requisite: Option<Vec<Topology>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preferred: Option<Vec<Topology>>,
+}
+
+/// A location in the cluster, as segments such as a region or zone understood by the CSI plugin
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Topology {
+    pub segments: HashMap<String, String>,
+}
+
+/// Bounds on the size of a cluster volume, in bytes
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CapacityRange {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required_bytes: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit_bytes: Option<i64>,
+}
+
+/// Whether a cluster volume may be used by new tasks
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Availability {
+    /// Usable by tasks
+    Active,
+    /// Not scheduled for new tasks, existing ones keep using it
+    Pause,
+    /// Not scheduled for new tasks, and removed from existing ones
+    Drain,
+}
+
+/// What the CSI plugin reported about a created cluster volume
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ClusterVolumeInfo {
+    pub capacity_bytes: Option<i64>,
+    pub volume_context: Option<HashMap<String, String>>,
+    #[serde(rename = "VolumeID")]
+    pub volume_id: Option<String>,
+    pub accessible_topology: Option<Vec<Topology>>,
+}
+
+/// The publishing of a cluster volume to a node
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PublishStatus {
+    #[serde(rename = "NodeID")]
+    pub node_id: String,
+    /// `pending-publish`, `published`, `pending-node-unpublish` or
+    /// `pending-controller-unpublish`
+    pub state: String,
+    pub publish_context: Option<HashMap<String, String>>,
 }
 
 #[cfg(test)]
@@ -254,4 +747,104 @@ mod test {
         assert_eq!(volume_info.name, None);
         assert_eq!(volume_info.driver_opts, Some(driver_options))
     }
+
+    #[test]
+    fn test_volumecreateoptionsbuilder_cluster_volume_spec() {
+        let spec = ClusterVolumeSpec {
+            group: Some("db".into()),
+            access_mode: Some(AccessMode {
+                scope: Some(AccessScope::Single),
+                sharing: Some(AccessSharing::Onewriter),
+                ..Default::default()
+            }),
+            capacity_range: Some(CapacityRange {
+                required_bytes: Some(1 << 30),
+                limit_bytes: None,
+            }),
+            availability: Some(Availability::Active),
+            ..Default::default()
+        };
+        let volume = VolumeCreateOptions::builder()
+            .driver("csi-plugin", None)
+            .cluster_volume_spec(&spec)
+            .build();
+
+        assert_eq!(volume.features(), vec![Feature::ClusterVolume]);
+        assert_eq!(
+            serde_json::from_str::<Value>(&volume.serialize().unwrap()).unwrap()
+                ["ClusterVolumeSpec"],
+            json!({
+                "Group": "db",
+                "AccessMode": { "Scope": "single", "Sharing": "onewriter" },
+                "CapacityRange": { "RequiredBytes": 1073741824 },
+                "Availability": "active"
+            })
+        );
+    }
+
+    #[test]
+    fn test_volumeinfo_cluster_volume() {
+        let info: VolumeInfo = serde_json::from_value(json!({
+            "CreatedAt": "2022-06-01T10:00:00Z",
+            "Driver": "csi-plugin",
+            "Labels": null,
+            "Name": "data",
+            "Mountpoint": "",
+            "Options": null,
+            "Scope": "global",
+            "ClusterVolume": {
+                "ID": "fczxnkwpvqj9",
+                "Version": { "Index": 42 },
+                "CreatedAt": "2022-06-01T10:00:00Z",
+                "UpdatedAt": "2022-06-01T10:00:05Z",
+                "Spec": { "AccessMode": { "Scope": "multi", "Sharing": "readonly" }, "Availability": "pause" },
+                "Info": { "CapacityBytes": 1073741824, "VolumeID": "vol-1" },
+                "PublishStatus": [{ "NodeID": "node-1", "State": "published" }]
+            }
+        }))
+        .unwrap();
+
+        let cluster = info.cluster_volume.unwrap();
+        assert_eq!(cluster.version.index, 42);
+        assert_eq!(cluster.spec.availability, Some(Availability::Pause));
+        assert_eq!(cluster.info.unwrap().volume_id, Some("vol-1".to_string()));
+        assert_eq!(cluster.publish_status.unwrap()[0].node_id, "node-1");
+    }
+
+    #[test]
+    fn volume_create_options_differences() {
+        let volume: VolumeInfo = serde_json::from_value(json!({
+            "CreatedAt": "2024-01-01T00:00:00Z",
+            "Driver": "local",
+            "Labels": {"app": "shop"},
+            "Name": "data",
+            "Mountpoint": "/var/lib/docker/volumes/data/_data",
+            "Options": null,
+            "Scope": "local"
+        }))
+        .unwrap();
+
+        let mut labels = HashMap::new();
+        labels.insert("app", "shop");
+        let opts = VolumeCreateOptions::builder()
+            .driver("local", None)
+            .labels(&labels)
+            .build();
+        assert!(opts.differences(&volume).is_empty());
+
+        labels.insert("app", "blog");
+        labels.insert("tier", "db");
+        let opts = VolumeCreateOptions::builder()
+            .driver("nfs", None)
+            .labels(&labels)
+            .build();
+        assert_eq!(
+            opts.differences(&volume),
+            vec![
+                "driver is local instead of nfs",
+                "label app is shop instead of blog",
+                "label tier is missing",
+            ]
+        );
+    }
 }